@@ -0,0 +1,320 @@
+use crate::engine::margin::MarginCalculator;
+use crate::engine::models::{Account, Order, OrderType, Trade, TradingPair};
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AccountError {
+    UnknownClient(String),
+    InsufficientBalance {
+        asset: String,
+        required: f64,
+        available: f64,
+    },
+}
+
+impl fmt::Display for AccountError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AccountError::UnknownClient(client_id) => write!(f, "no account for {}", client_id),
+            AccountError::InsufficientBalance {
+                asset,
+                required,
+                available,
+            } => write!(
+                f,
+                "{} {} required but only {} available",
+                required, asset, available
+            ),
+        }
+    }
+}
+
+impl std::error::Error for AccountError {}
+
+/// Funds held back for a resting order until it fills or is cancelled.
+#[derive(Debug, Clone)]
+struct OrderLock {
+    client_id: String,
+    asset: String,
+    amount: f64,
+    remaining_quantity: f64,
+}
+
+/// Validates orders against client balances and settles fills. Orders
+/// without a `client_id` bypass accounting. Pairs registered with
+/// `enable_isolated_margin` are treated as derivatives: orders lock initial
+/// margin in the quote currency and fills open or close positions instead of
+/// exchanging assets.
+#[derive(Debug, Default)]
+pub struct AccountManager {
+    accounts: HashMap<String, Account>,
+    locks: HashMap<u64, OrderLock>,
+    margin_pairs: HashMap<TradingPair, MarginCalculator>,
+}
+
+impl AccountManager {
+    pub fn new() -> Self {
+        AccountManager::default()
+    }
+
+    pub fn deposit(&mut self, client_id: &str, asset: &str, amount: f64) {
+        let account = self
+            .accounts
+            .entry(client_id.to_string())
+            .or_insert_with(|| Account::new(client_id));
+        *account.balances.entry(asset.to_string()).or_insert(0.0) += amount;
+    }
+
+    pub fn account(&self, client_id: &str) -> Option<&Account> {
+        self.accounts.get(client_id)
+    }
+
+    /// Trades `trading_pair` as an isolated-margin derivative from now on.
+    pub fn enable_isolated_margin(
+        &mut self,
+        trading_pair: TradingPair,
+        calculator: MarginCalculator,
+    ) {
+        self.margin_pairs.insert(trading_pair, calculator);
+    }
+
+    pub fn margin_calculator(&self, trading_pair: &TradingPair) -> Option<&MarginCalculator> {
+        self.margin_pairs.get(trading_pair)
+    }
+
+    /// Mark price at which the client's position would be liquidated.
+    pub fn liquidation_price(&self, client_id: &str, trading_pair: &TradingPair) -> Option<f64> {
+        let calculator = self.margin_pairs.get(trading_pair)?;
+        let position = self.accounts.get(client_id)?.positions.get(trading_pair)?;
+        calculator.liquidation_price(position)
+    }
+
+    /// Clients whose position in `trading_pair` is below maintenance margin
+    /// at `mark_price`.
+    pub fn liquidation_candidates(
+        &self,
+        trading_pair: &TradingPair,
+        mark_price: f64,
+    ) -> Vec<String> {
+        let Some(calculator) = self.margin_pairs.get(trading_pair) else {
+            return vec![];
+        };
+        self.accounts
+            .values()
+            .filter(|account| calculator.check_liquidation(trading_pair, mark_price, account))
+            .map(|account| account.client_id.clone())
+            .collect()
+    }
+
+    /// Total held by the client's resting orders in `asset`.
+    pub fn locked_balance(&self, client_id: &str, asset: &str) -> f64 {
+        self.locks
+            .values()
+            .filter(|lock| lock.client_id == client_id && lock.asset == asset)
+            .map(|lock| lock.amount)
+            .sum()
+    }
+
+    /// Moves the funds the order could spend out of the available balance:
+    /// quote currency at the limit price for buys, base currency for sells,
+    /// and initial margin in the quote currency on margin pairs.
+    pub fn lock_for_order(&mut self, order: &Order) -> Result<(), AccountError> {
+        let Some(client_id) = &order.client_id else {
+            return Ok(());
+        };
+        let (asset, required) = self.requirement(order);
+
+        let account = self
+            .accounts
+            .get_mut(client_id)
+            .ok_or_else(|| AccountError::UnknownClient(client_id.clone()))?;
+        let available = account.balance(asset);
+        if available < required {
+            return Err(AccountError::InsufficientBalance {
+                asset: asset.clone(),
+                required,
+                available,
+            });
+        }
+
+        account.balances.insert(asset.clone(), available - required);
+        self.locks.insert(
+            order.id,
+            OrderLock {
+                client_id: client_id.clone(),
+                asset: asset.clone(),
+                amount: required,
+                remaining_quantity: order.quantity.value(),
+            },
+        );
+        Ok(())
+    }
+
+    /// Fails as `lock_for_order` would if `orders` were locked in turn after
+    /// the locks of `released` were returned, but changes nothing. Lets a
+    /// quote be replaced without cancelling the old legs first.
+    pub fn check_replacement(
+        &self,
+        released: &[u64],
+        orders: &[&Order],
+    ) -> Result<(), AccountError> {
+        let mut freed: HashMap<(&str, &str), f64> = HashMap::new();
+        for lock in released
+            .iter()
+            .filter_map(|order_id| self.locks.get(order_id))
+        {
+            *freed.entry((&lock.client_id, &lock.asset)).or_default() += lock.amount;
+        }
+        for order in orders {
+            let Some(client_id) = &order.client_id else {
+                continue;
+            };
+            let (asset, required) = self.requirement(order);
+            let account = self
+                .accounts
+                .get(client_id)
+                .ok_or_else(|| AccountError::UnknownClient(client_id.clone()))?;
+            let freed = freed.entry((client_id, asset)).or_default();
+            let available = account.balance(asset) + *freed;
+            if available < required {
+                return Err(AccountError::InsufficientBalance {
+                    asset: asset.clone(),
+                    required,
+                    available,
+                });
+            }
+            *freed -= required;
+        }
+        Ok(())
+    }
+
+    /// The asset and amount `lock_for_order` takes for `order`.
+    fn requirement<'a>(&self, order: &'a Order) -> (&'a String, f64) {
+        match (
+            self.margin_pairs.get(&order.trading_pair),
+            &order.order_type,
+        ) {
+            (Some(calculator), _) => (
+                &order.trading_pair.quote,
+                calculator.initial_margin(order.price.value(), order.quantity.value()),
+            ),
+            (None, OrderType::Buy) => (&order.trading_pair.quote, order.notional_value()),
+            (None, OrderType::Sell) => (&order.trading_pair.base, order.quantity.value()),
+            (
+                None,
+                OrderType::Convert {
+                    quantity,
+                    from_asset,
+                    ..
+                },
+            ) => (from_asset, *quantity),
+        }
+    }
+
+    /// Returns whatever the order still has locked to its owner.
+    pub fn release_order(&mut self, order_id: u64) {
+        if let Some(lock) = self.locks.remove(&order_id) {
+            self.deposit(&lock.client_id, &lock.asset, lock.amount);
+        }
+    }
+
+    /// Debits the seller's base and the buyer's quote from their locks and
+    /// credits each side with what it bought. A buy that fills below its
+    /// limit gets the unspent quote back once it is fully filled.
+    pub fn settle_trade(&mut self, trade: &Trade) {
+        if self.margin_pairs.contains_key(&trade.trading_pair) {
+            self.settle_margin_trade(trade);
+            return;
+        }
+        let notional = trade.notional_value();
+        let quantity = trade.quantity.value();
+        let pair = &trade.trading_pair;
+
+        if let Some(buyer) = &trade.buy_client_id {
+            self.consume_lock(trade.buy_order_id, notional, quantity);
+            self.deposit(buyer, &pair.base, quantity);
+        }
+        if let Some(seller) = &trade.sell_client_id {
+            self.consume_lock(trade.sell_order_id, quantity, quantity);
+            self.deposit(seller, &pair.quote, notional);
+        }
+    }
+
+    fn consume_lock(&mut self, order_id: u64, amount: f64, quantity: f64) {
+        let Some(lock) = self.locks.get_mut(&order_id) else {
+            return;
+        };
+        lock.amount = (lock.amount - amount).max(0.0);
+        lock.remaining_quantity -= quantity;
+        if lock.remaining_quantity <= 0.0 {
+            self.release_order(order_id);
+        }
+    }
+
+    /// Moves the filled share of each side's locked margin into its position.
+    fn settle_margin_trade(&mut self, trade: &Trade) {
+        let quantity = trade.quantity.value();
+        let sides = [
+            (&trade.buy_client_id, trade.buy_order_id, quantity),
+            (&trade.sell_client_id, trade.sell_order_id, -quantity),
+        ];
+        for (client_id, order_id, signed_quantity) in sides {
+            let Some(client_id) = client_id else {
+                continue;
+            };
+            let margin = self
+                .locks
+                .get(&order_id)
+                .map(|lock| lock.amount * (quantity / lock.remaining_quantity).min(1.0))
+                .unwrap_or(0.0);
+            self.consume_lock(order_id, margin, quantity);
+            self.apply_fill(client_id, trade, signed_quantity, margin);
+        }
+    }
+
+    /// Adds a fill to the client's position. Reducing fills realize PnL and
+    /// return the released margin to the quote balance; any excess opens a
+    /// position on the other side at the trade price.
+    fn apply_fill(&mut self, client_id: &str, trade: &Trade, signed_quantity: f64, margin: f64) {
+        let quote = &trade.trading_pair.quote;
+        let price = trade.price.value();
+        let account = self
+            .accounts
+            .entry(client_id.to_string())
+            .or_insert_with(|| Account::new(client_id));
+        let position = account
+            .positions
+            .entry(trade.trading_pair.clone())
+            .or_default();
+
+        if position.quantity == 0.0 || position.quantity.signum() == signed_quantity.signum() {
+            let quantity = position.quantity + signed_quantity;
+            position.entry_price = (position.entry_price * position.quantity.abs()
+                + price * signed_quantity.abs())
+                / quantity.abs();
+            position.quantity = quantity;
+            position.margin += margin;
+            return;
+        }
+
+        let closing = signed_quantity.abs().min(position.quantity.abs());
+        let fraction = closing / position.quantity.abs();
+        let pnl = closing * (price - position.entry_price) * position.quantity.signum();
+        let released = position.margin * fraction;
+        let unused = margin * closing / signed_quantity.abs();
+        position.margin -= released;
+        position.quantity += closing * signed_quantity.signum();
+
+        let opening = signed_quantity.abs() - closing;
+        if opening > 0.0 {
+            position.quantity = opening * signed_quantity.signum();
+            position.entry_price = price;
+            position.margin = margin - unused;
+        }
+        if position.quantity == 0.0 {
+            account.positions.remove(&trade.trading_pair);
+        }
+        *account.balances.entry(quote.clone()).or_insert(0.0) += released + pnl + unused;
+    }
+}
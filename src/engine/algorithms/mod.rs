@@ -0,0 +1,140 @@
+pub mod participation;
+pub mod twap;
+pub mod vwap;
+
+use crate::engine::core::Message;
+use crate::engine::models::{Order, OrderType, Trade, TradingPair};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+
+pub use participation::{ParticipationParams, ParticipationRateExecutor};
+pub use twap::{TwapExecutor, TwapParams};
+pub use vwap::{VwapExecutor, VwapParams};
+
+// Child orders, and the legs of market-maker quotes, draw their IDs from a
+// range well above client-assigned IDs so their fills can be picked out of a
+// shared match cycle.
+static NEXT_CHILD_ORDER_ID: AtomicU64 = AtomicU64::new(1 << 48);
+
+pub(crate) fn next_child_order_id() -> u64 {
+    NEXT_CHILD_ORDER_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+#[cfg(feature = "serde")]
+pub(crate) fn peek_child_order_id() -> u64 {
+    NEXT_CHILD_ORDER_ID.load(Ordering::Relaxed)
+}
+
+/// Never moves the counter backwards, so IDs already handed out in this
+/// process are not reused.
+#[cfg(feature = "serde")]
+pub(crate) fn restore_child_order_id(next_id: u64) {
+    NEXT_CHILD_ORDER_ID.fetch_max(next_id, Ordering::Relaxed);
+}
+
+/// Submits a marketable child order, matches the book and cancels whatever
+/// did not fill, returning the child's trades.
+pub(crate) async fn execute_market_order(
+    engine_tx: &mpsc::Sender<Message>,
+    trading_pair: &TradingPair,
+    side: &OrderType,
+    quantity: f64,
+) -> Vec<Trade> {
+    let order_id = next_child_order_id();
+    let price = match side {
+        OrderType::Buy => f64::MAX,
+        OrderType::Sell => f64::MIN_POSITIVE,
+        // Not a side of the book; there is nothing to take.
+        OrderType::Convert { .. } => return vec![],
+    };
+
+    let order = Order {
+        id: order_id,
+        trading_pair: trading_pair.clone(),
+        order_type: side.clone(),
+        price: price.into(),
+        quantity: quantity.into(),
+        timestamp: chrono::Utc::now(),
+        client_id: None,
+        session_id: None,
+        average_fill_price: None,
+        cumulative_filled_quantity: 0.0,
+        stop_price: None,
+        received_at: None,
+        arrival_seq: 0,
+    };
+
+    if engine_tx.send(Message::NewOrder(order)).await.is_err() {
+        return Vec::new();
+    }
+
+    let (match_tx, mut match_rx) = mpsc::channel(1);
+    let trades = match engine_tx
+        .send(Message::MatchOrders(trading_pair.clone(), match_tx))
+        .await
+    {
+        Ok(_) => match_rx.recv().await.unwrap_or_default(),
+        Err(_) => Vec::new(),
+    };
+
+    let (cancel_tx, mut cancel_rx) = mpsc::channel(1);
+    if engine_tx
+        .send(Message::CancelOrder(
+            trading_pair.clone(),
+            order_id,
+            cancel_tx,
+        ))
+        .await
+        .is_ok()
+    {
+        let _ = cancel_rx.recv().await;
+    }
+
+    trades
+        .into_iter()
+        .filter(|trade| match side {
+            OrderType::Buy => trade.buy_order_id == order_id,
+            OrderType::Sell => trade.sell_order_id == order_id,
+            OrderType::Convert { .. } => false,
+        })
+        .collect()
+}
+
+/// Submits each quantity in `schedule` as a market order, `interval` apart,
+/// until the schedule is exhausted or `cancel_token` fires. Zero-quantity
+/// slices hold their time bucket without trading.
+pub(crate) async fn execute_schedule(
+    engine_tx: &mpsc::Sender<Message>,
+    trading_pair: &TradingPair,
+    side: &OrderType,
+    schedule: &[f64],
+    interval: Duration,
+    cancel_token: &CancellationToken,
+) -> Vec<Trade> {
+    let mut trades = Vec::new();
+
+    for (slice, &quantity) in schedule.iter().enumerate() {
+        if slice > 0 {
+            tokio::select! {
+                _ = tokio::time::sleep(interval) => {}
+                _ = cancel_token.cancelled() => {}
+            }
+        }
+        if cancel_token.is_cancelled() {
+            info!(slice, "Execution cancelled");
+            break;
+        }
+        if quantity <= 0.0 {
+            continue;
+        }
+
+        let fills = execute_market_order(engine_tx, trading_pair, side, quantity).await;
+        info!(slice, quantity, fills = fills.len(), "Slice executed");
+        trades.extend(fills);
+    }
+
+    trades
+}
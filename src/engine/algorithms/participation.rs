@@ -0,0 +1,156 @@
+use crate::engine::algorithms::execute_market_order;
+use crate::engine::core::Message;
+use crate::engine::models::{MarketEvent, OrderType, TradingPair};
+use std::collections::HashSet;
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc};
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+
+#[derive(Debug, Clone)]
+pub struct ParticipationParams {
+    pub target_pct: f64,
+    pub max_total_qty: f64,
+    pub poll_interval: Duration,
+    pub trading_pair: TradingPair,
+    pub side: OrderType,
+    pub cancel_token: CancellationToken,
+}
+
+/// Trades `target_pct` of the volume observed on the trade feed each
+/// `poll_interval` until `max_total_qty` has been filled.
+pub struct ParticipationRateExecutor {
+    target_pct: f64,
+    max_total_qty: f64,
+    poll_interval: Duration,
+    trading_pair: TradingPair,
+    side: OrderType,
+    engine_tx: mpsc::Sender<Message>,
+    cancel_token: CancellationToken,
+}
+
+impl ParticipationRateExecutor {
+    pub fn new(
+        target_pct: f64,
+        max_total_qty: f64,
+        poll_interval: Duration,
+        trading_pair: TradingPair,
+        side: OrderType,
+        engine_tx: mpsc::Sender<Message>,
+    ) -> Self {
+        ParticipationRateExecutor {
+            target_pct,
+            max_total_qty,
+            poll_interval,
+            trading_pair,
+            side,
+            engine_tx,
+            cancel_token: CancellationToken::new(),
+        }
+    }
+
+    pub fn from_params(params: ParticipationParams, engine_tx: mpsc::Sender<Message>) -> Self {
+        ParticipationRateExecutor {
+            target_pct: params.target_pct,
+            max_total_qty: params.max_total_qty,
+            poll_interval: params.poll_interval,
+            trading_pair: params.trading_pair,
+            side: params.side,
+            engine_tx,
+            cancel_token: params.cancel_token,
+        }
+    }
+
+    /// Cancelling the returned token stops execution at the next poll.
+    pub fn cancel_token(&self) -> CancellationToken {
+        self.cancel_token.clone()
+    }
+
+    /// Returns the cumulative quantity filled.
+    pub async fn execute(&self) -> f64 {
+        let Some(mut trade_rx) = self.subscribe().await else {
+            return 0.0;
+        };
+
+        let mut own_order_ids = HashSet::new();
+        let mut filled_so_far = 0.0;
+        // Quantity a thin book could not fill, carried into the next interval.
+        let mut shortfall = 0.0;
+
+        while filled_so_far < self.max_total_qty {
+            tokio::select! {
+                _ = tokio::time::sleep(self.poll_interval) => {}
+                _ = self.cancel_token.cancelled() => {}
+            }
+            if self.cancel_token.is_cancelled() {
+                info!(filled_so_far, "Participation execution cancelled");
+                break;
+            }
+            if self.engine_tx.is_closed() {
+                break;
+            }
+
+            let observed_volume = drain_market_volume(&mut trade_rx, &own_order_ids);
+            let target = (self.target_pct * observed_volume + shortfall)
+                .min(self.max_total_qty - filled_so_far);
+            if target <= 0.0 {
+                continue;
+            }
+
+            let fills =
+                execute_market_order(&self.engine_tx, &self.trading_pair, &self.side, target).await;
+            let filled: f64 = fills.iter().map(|trade| trade.quantity.value()).sum();
+            own_order_ids.extend(fills.iter().map(|trade| {
+                if self.side == OrderType::Buy {
+                    trade.buy_order_id
+                } else {
+                    trade.sell_order_id
+                }
+            }));
+
+            filled_so_far += filled;
+            shortfall = target - filled;
+            info!(
+                observed_volume,
+                target, filled, filled_so_far, "Participation interval executed"
+            );
+        }
+
+        filled_so_far
+    }
+
+    async fn subscribe(&self) -> Option<broadcast::Receiver<MarketEvent>> {
+        let (subscribe_tx, mut subscribe_rx) = mpsc::channel(1);
+        self.engine_tx
+            .send(Message::SubscribeToPair(
+                self.trading_pair.clone(),
+                subscribe_tx,
+            ))
+            .await
+            .ok()?;
+        subscribe_rx.recv().await
+    }
+}
+
+/// Sums the volume received since the last poll, leaving out our own fills.
+fn drain_market_volume(
+    trade_rx: &mut broadcast::Receiver<MarketEvent>,
+    own_order_ids: &HashSet<u64>,
+) -> f64 {
+    let mut volume = 0.0;
+    loop {
+        match trade_rx.try_recv() {
+            Ok(MarketEvent::Trade(trade)) => {
+                if !own_order_ids.contains(&trade.buy_order_id)
+                    && !own_order_ids.contains(&trade.sell_order_id)
+                {
+                    volume += trade.quantity.value();
+                }
+            }
+            Ok(_) => continue,
+            Err(broadcast::error::TryRecvError::Lagged(_)) => continue,
+            Err(_) => break,
+        }
+    }
+    volume
+}
@@ -0,0 +1,83 @@
+use crate::engine::algorithms::execute_schedule;
+use crate::engine::core::Message;
+use crate::engine::models::{OrderType, Trade, TradingPair};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+#[derive(Debug, Clone)]
+pub struct TwapParams {
+    pub total_qty: f64,
+    pub num_slices: usize,
+    pub interval: Duration,
+    pub trading_pair: TradingPair,
+    pub side: OrderType,
+    pub cancel_token: CancellationToken,
+}
+
+/// Splits `total_qty` into equal market orders submitted `interval` apart.
+pub struct TwapExecutor {
+    total_qty: f64,
+    num_slices: usize,
+    interval: Duration,
+    trading_pair: TradingPair,
+    side: OrderType,
+    engine_tx: mpsc::Sender<Message>,
+    cancel_token: CancellationToken,
+}
+
+impl TwapExecutor {
+    pub fn new(
+        total_qty: f64,
+        num_slices: usize,
+        interval: Duration,
+        trading_pair: TradingPair,
+        side: OrderType,
+        engine_tx: mpsc::Sender<Message>,
+    ) -> Self {
+        TwapExecutor {
+            total_qty,
+            num_slices,
+            interval,
+            trading_pair,
+            side,
+            engine_tx,
+            cancel_token: CancellationToken::new(),
+        }
+    }
+
+    pub fn from_params(params: TwapParams, engine_tx: mpsc::Sender<Message>) -> Self {
+        TwapExecutor {
+            total_qty: params.total_qty,
+            num_slices: params.num_slices,
+            interval: params.interval,
+            trading_pair: params.trading_pair,
+            side: params.side,
+            engine_tx,
+            cancel_token: params.cancel_token,
+        }
+    }
+
+    /// Cancelling the returned token stops execution before the next slice.
+    pub fn cancel_token(&self) -> CancellationToken {
+        self.cancel_token.clone()
+    }
+
+    pub async fn execute(&self) -> Vec<Trade> {
+        if self.num_slices == 0 {
+            return Vec::new();
+        }
+
+        let slice_qty = self.total_qty / self.num_slices as f64;
+        let schedule = vec![slice_qty; self.num_slices];
+        execute_schedule(
+            &self.engine_tx,
+            &self.trading_pair,
+            &self.side,
+            &schedule,
+            self.interval,
+            &self.cancel_token,
+        )
+        .await
+    }
+}
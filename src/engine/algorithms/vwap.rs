@@ -0,0 +1,141 @@
+use crate::engine::algorithms::execute_schedule;
+use crate::engine::core::Message;
+use crate::engine::models::{OhlcvBar, OrderType, Trade, TradingPair};
+use chrono::{DateTime, DurationRound, Utc};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+/// Width of the historical bars the volume profile is built from.
+pub const PROFILE_BAR_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Clone)]
+pub struct VwapParams {
+    pub total_qty: f64,
+    pub num_buckets: usize,
+    pub interval: Duration,
+    pub trading_pair: TradingPair,
+    pub side: OrderType,
+    pub cancel_token: CancellationToken,
+}
+
+/// Splits `total_qty` across `num_buckets` market orders, `interval` apart,
+/// sized by the volume traded in each of the last `num_buckets` hours.
+pub struct VwapExecutor {
+    total_qty: f64,
+    num_buckets: usize,
+    interval: Duration,
+    trading_pair: TradingPair,
+    side: OrderType,
+    engine_tx: mpsc::Sender<Message>,
+    cancel_token: CancellationToken,
+}
+
+impl VwapExecutor {
+    pub fn new(
+        total_qty: f64,
+        num_buckets: usize,
+        interval: Duration,
+        trading_pair: TradingPair,
+        side: OrderType,
+        engine_tx: mpsc::Sender<Message>,
+    ) -> Self {
+        VwapExecutor {
+            total_qty,
+            num_buckets,
+            interval,
+            trading_pair,
+            side,
+            engine_tx,
+            cancel_token: CancellationToken::new(),
+        }
+    }
+
+    pub fn from_params(params: VwapParams, engine_tx: mpsc::Sender<Message>) -> Self {
+        VwapExecutor {
+            total_qty: params.total_qty,
+            num_buckets: params.num_buckets,
+            interval: params.interval,
+            trading_pair: params.trading_pair,
+            side: params.side,
+            engine_tx,
+            cancel_token: params.cancel_token,
+        }
+    }
+
+    /// Cancelling the returned token stops execution before the next bucket.
+    pub fn cancel_token(&self) -> CancellationToken {
+        self.cancel_token.clone()
+    }
+
+    pub async fn execute(&self) -> Vec<Trade> {
+        if self.num_buckets == 0 {
+            return Vec::new();
+        }
+
+        let start = profile_start(Utc::now(), self.num_buckets);
+        let bars = self.fetch_ohlcv(start).await;
+        let schedule: Vec<f64> = volume_profile(&bars, start, self.num_buckets)
+            .into_iter()
+            .map(|fraction| fraction * self.total_qty)
+            .collect();
+
+        execute_schedule(
+            &self.engine_tx,
+            &self.trading_pair,
+            &self.side,
+            &schedule,
+            self.interval,
+            &self.cancel_token,
+        )
+        .await
+    }
+
+    async fn fetch_ohlcv(&self, since: DateTime<Utc>) -> Vec<OhlcvBar> {
+        let (ohlcv_tx, mut ohlcv_rx) = mpsc::channel(1);
+        match self
+            .engine_tx
+            .send(Message::GetOhlcv(
+                self.trading_pair.clone(),
+                PROFILE_BAR_INTERVAL,
+                since,
+                ohlcv_tx,
+            ))
+            .await
+        {
+            Ok(_) => ohlcv_rx.recv().await.unwrap_or_default(),
+            Err(_) => Vec::new(),
+        }
+    }
+}
+
+/// Start of the oldest of `num_buckets` profile bars, the newest being the
+/// bar that contains `now`.
+pub fn profile_start(now: DateTime<Utc>, num_buckets: usize) -> DateTime<Utc> {
+    let bar = chrono::Duration::from_std(PROFILE_BAR_INTERVAL).unwrap();
+    let current_bar = now.duration_trunc(bar).unwrap_or(now);
+    current_bar - bar * (num_buckets.saturating_sub(1) as i32)
+}
+
+/// Fraction of the total volume traded in each profile bucket starting at
+/// `start`. Falls back to an even split when there is no volume history.
+pub fn volume_profile(bars: &[OhlcvBar], start: DateTime<Utc>, num_buckets: usize) -> Vec<f64> {
+    let mut volumes = vec![0.0; num_buckets];
+    let width = PROFILE_BAR_INTERVAL.as_secs() as i64;
+
+    for bar in bars {
+        let offset = (bar.open_time - start).num_seconds();
+        if offset < 0 {
+            continue;
+        }
+        if let Some(volume) = volumes.get_mut((offset / width) as usize) {
+            *volume += bar.volume;
+        }
+    }
+
+    let total: f64 = volumes.iter().sum();
+    if total <= 0.0 {
+        return vec![1.0 / num_buckets as f64; num_buckets];
+    }
+    volumes.into_iter().map(|volume| volume / total).collect()
+}
@@ -0,0 +1,129 @@
+use crate::engine::models::{Trade, TradingPair};
+use chrono::{DateTime, NaiveDate, Utc};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// One pair's trading over a UTC day so far.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DailyStats {
+    pub trading_pair: TradingPair,
+    pub date: NaiveDate,
+    pub volume: f64,
+    pub trade_count: u64,
+    /// `None` until the pair trades that day.
+    pub high: Option<f64>,
+    pub low: Option<f64>,
+}
+
+/// One pair's figures for a dashboard; see `Message::GetAllPairStats`.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PairStats {
+    /// Price of the pair's most recent trade still in its history.
+    pub last_price: Option<f64>,
+    /// `None` unless both sides are quoted.
+    pub spread: Option<f64>,
+    /// As in `DailyStats::volume`.
+    pub daily_volume: f64,
+    pub active_orders: usize,
+    pub bid_levels: usize,
+    pub ask_levels: usize,
+}
+
+/// Running per-pair volume, trade count and price range for the current UTC
+/// day. Everything resets with the first trade or query after midnight.
+#[derive(Debug)]
+pub struct TradeAggregator {
+    day: NaiveDate,
+    daily_volume: HashMap<TradingPair, f64>,
+    daily_trade_count: HashMap<TradingPair, u64>,
+    daily_high: HashMap<TradingPair, f64>,
+    daily_low: HashMap<TradingPair, f64>,
+}
+
+impl Default for TradeAggregator {
+    fn default() -> Self {
+        TradeAggregator {
+            day: Utc::now().date_naive(),
+            daily_volume: HashMap::new(),
+            daily_trade_count: HashMap::new(),
+            daily_high: HashMap::new(),
+            daily_low: HashMap::new(),
+        }
+    }
+}
+
+impl TradeAggregator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `trade` to its day's figures. Trades from a day already rolled
+    /// past are ignored.
+    pub fn add_trade(&mut self, trade: &Trade) {
+        let date = trade.timestamp.date_naive();
+        self.roll_over(date);
+        if date < self.day {
+            return;
+        }
+
+        let pair = &trade.trading_pair;
+        let price = trade.price.value();
+        *self.daily_volume.entry(pair.clone()).or_insert(0.0) += trade.quantity.value();
+        *self.daily_trade_count.entry(pair.clone()).or_insert(0) += 1;
+        let high = self.daily_high.entry(pair.clone()).or_insert(price);
+        *high = high.max(price);
+        let low = self.daily_low.entry(pair.clone()).or_insert(price);
+        *low = low.min(price);
+    }
+
+    pub fn add_trades(&mut self, trades: &[Trade]) {
+        for trade in trades {
+            self.add_trade(trade);
+        }
+    }
+
+    /// Takes a busted trade back out of its day's volume and trade count.
+    /// The day's high and low may still be its price.
+    pub fn remove_trade(&mut self, trade: &Trade) {
+        if trade.timestamp.date_naive() != self.day {
+            return;
+        }
+        let pair = &trade.trading_pair;
+        if let Some(volume) = self.daily_volume.get_mut(pair) {
+            *volume -= trade.quantity.value();
+        }
+        if let Some(count) = self.daily_trade_count.get_mut(pair) {
+            *count = count.saturating_sub(1);
+        }
+    }
+
+    /// Figures for the UTC day containing `now`.
+    pub fn daily_stats(&mut self, trading_pair: &TradingPair, now: DateTime<Utc>) -> DailyStats {
+        self.roll_over(now.date_naive());
+        DailyStats {
+            trading_pair: trading_pair.clone(),
+            date: self.day,
+            volume: self.daily_volume.get(trading_pair).copied().unwrap_or(0.0),
+            trade_count: self
+                .daily_trade_count
+                .get(trading_pair)
+                .copied()
+                .unwrap_or(0),
+            high: self.daily_high.get(trading_pair).copied(),
+            low: self.daily_low.get(trading_pair).copied(),
+        }
+    }
+
+    fn roll_over(&mut self, date: NaiveDate) {
+        if date > self.day {
+            self.day = date;
+            self.daily_volume.clear();
+            self.daily_trade_count.clear();
+            self.daily_high.clear();
+            self.daily_low.clear();
+        }
+    }
+}
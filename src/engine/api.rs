@@ -1,15 +1,23 @@
+#[cfg(feature = "rest-api")]
 use crate::engine::core::Message;
-use crate::engine::models::{Order, OrderType, TradingPair};
+use crate::engine::models::Order;
+#[cfg(feature = "rest-api")]
+use crate::engine::models::{OrderType, TradingPair};
+#[cfg(feature = "rest-api")]
 use axum::{
     extract::{Path, State},
     routing::{get, post},
     Json, Router,
 };
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "rest-api")]
 use tokio::sync::mpsc;
+#[cfg(feature = "rest-api")]
 use tracing::info;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct PlaceOrderRequest {
     trading_pair: String,
     order_type: String,
@@ -17,34 +25,133 @@ pub struct PlaceOrderRequest {
     quantity: f64,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct PlaceOrderResponse {
     order_id: u64,
     status: String,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct PriceResponse {
     trading_pair: String,
     price: Option<f64>,
     timestamp: String,
 }
 
-#[derive(Debug, Serialize)]
-pub struct OrderBookEntry {
+/// One aggregated level of an order book.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PriceLevel {
     pub price: f64,
-    pub quantity: f64,
+    /// Also read from `quantity`, the field's name before `PriceLevel`.
+    #[cfg_attr(feature = "serde", serde(alias = "quantity"))]
+    pub total_quantity: f64,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub order_count: usize,
+}
+
+#[deprecated(note = "use `PriceLevel`; `quantity` is now `total_quantity`")]
+pub type OrderBookEntry = PriceLevel;
+
+impl PriceLevel {
+    /// The level holding both levels' orders, e.g. the same price on two
+    /// venues.
+    ///
+    /// Panics if the prices differ.
+    pub fn merge(&self, other: &PriceLevel) -> PriceLevel {
+        assert_eq!(
+            self.price, other.price,
+            "cannot merge levels at different prices"
+        );
+        PriceLevel {
+            price: self.price,
+            total_quantity: self.total_quantity + other.total_quantity,
+            order_count: self.order_count + other.order_count,
+        }
+    }
+}
+
+/// Merges two sides sorted the same way, descending for bids and ascending
+/// for asks, into one, merging levels at the same price.
+pub fn merge_order_books(
+    a: Vec<PriceLevel>,
+    b: Vec<PriceLevel>,
+    descending: bool,
+) -> Vec<PriceLevel> {
+    let mut merged = Vec::with_capacity(a.len() + b.len());
+    let mut a = a.into_iter().peekable();
+    let mut b = b.into_iter().peekable();
+    loop {
+        let next = match (a.peek(), b.peek()) {
+            (Some(x), Some(y)) if x.price == y.price => {
+                let level = x.merge(y);
+                a.next();
+                b.next();
+                level
+            }
+            (Some(x), Some(y)) if (x.price > y.price) == descending => a.next().unwrap(),
+            (Some(_), Some(_)) | (None, Some(_)) => b.next().unwrap(),
+            (Some(_), None) => a.next().unwrap(),
+            (None, None) => break,
+        };
+        merged.push(next);
+    }
+    merged
+}
+
+/// A level holding just `order`, at its remaining quantity.
+impl From<&Order> for PriceLevel {
+    fn from(order: &Order) -> Self {
+        PriceLevel {
+            price: order.price.value(),
+            total_quantity: order.quantity.value(),
+            order_count: 1,
+        }
+    }
+}
+
+impl From<Order> for PriceLevel {
+    fn from(order: Order) -> Self {
+        PriceLevel::from(&order)
+    }
+}
+
+/// Aggregates orders resting at one price, taken from the first order. An
+/// empty `Vec` gives an empty level at price zero.
+impl From<Vec<Order>> for PriceLevel {
+    fn from(orders: Vec<Order>) -> Self {
+        PriceLevel {
+            price: orders.first().map_or(0.0, |order| order.price.value()),
+            total_quantity: orders.iter().map(|order| order.quantity.value()).sum(),
+            order_count: orders.len(),
+        }
+    }
+}
+
+/// One rung of a fixed-spacing price ladder; see `OrderBook::price_ladder`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PriceLadderEntry {
+    pub price: f64,
+    pub bid_qty: f64,
+    pub ask_qty: f64,
+    pub bid_order_count: usize,
+    pub ask_order_count: usize,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct OrderBookResponse {
     trading_pair: String,
-    bids: Vec<OrderBookEntry>,
-    asks: Vec<OrderBookEntry>,
+    bids: Vec<PriceLevel>,
+    asks: Vec<PriceLevel>,
     timestamp: String,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct TradeResponse {
     pub id: u64,
     pub trading_pair: String,
@@ -53,17 +160,20 @@ pub struct TradeResponse {
     pub timestamp: String,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct TradeHistoryResponse {
     pub trading_pair: String,
     pub trades: Vec<TradeResponse>,
 }
 
+#[cfg(feature = "rest-api")]
 #[derive(Clone)]
 pub struct AppState {
     pub engine_tx: mpsc::Sender<Message>,
 }
 
+#[cfg(feature = "rest-api")]
 impl AppState {
     #[allow(dead_code)]
     pub fn new(engine_tx: mpsc::Sender<Message>) -> Self {
@@ -71,6 +181,7 @@ impl AppState {
     }
 }
 
+#[cfg(feature = "rest-api")]
 pub async fn run_api_server(engine_tx: mpsc::Sender<Message>) {
     let state = AppState {
         engine_tx: engine_tx.clone(),
@@ -109,6 +220,7 @@ pub async fn run_api_server(engine_tx: mpsc::Sender<Message>) {
     }
 }
 
+#[cfg(feature = "rest-api")]
 async fn place_order(
     State(state): State<AppState>,
     Json(request): Json<PlaceOrderRequest>,
@@ -132,9 +244,16 @@ async fn place_order(
         id: 0,
         trading_pair,
         order_type,
-        price: request.price,
-        quantity: request.quantity,
+        price: request.price.into(),
+        quantity: request.quantity.into(),
         timestamp: chrono::Utc::now(),
+        client_id: None,
+        session_id: None,
+        average_fill_price: None,
+        cumulative_filled_quantity: 0.0,
+        stop_price: None,
+        received_at: None,
+        arrival_seq: 0,
     };
 
     if state.engine_tx.send(Message::NewOrder(order)).await.is_ok() {
@@ -150,6 +269,7 @@ async fn place_order(
     }
 }
 
+#[cfg(feature = "rest-api")]
 async fn get_price(
     State(state): State<AppState>,
     Path((base, quote)): Path<(String, String)>,
@@ -209,6 +329,7 @@ async fn get_price(
     }
 }
 
+#[cfg(feature = "rest-api")]
 async fn get_order_book(
     State(state): State<AppState>,
     Path((base, quote)): Path<(String, String)>,
@@ -258,6 +379,7 @@ async fn get_order_book(
     }
 }
 
+#[cfg(feature = "rest-api")]
 async fn get_trade_history(
     State(state): State<AppState>,
     Path((base, quote)): Path<(String, String)>,
@@ -292,8 +414,8 @@ async fn get_trade_history(
                             "{}/{}",
                             trade.trading_pair.base, trade.trading_pair.quote
                         ),
-                        price: trade.price,
-                        quantity: trade.quantity,
+                        price: trade.price.value(),
+                        quantity: trade.quantity.value(),
                         timestamp: trade.timestamp.to_rfc3339(),
                     })
                     .collect();
@@ -315,11 +437,24 @@ async fn get_trade_history(
     }
 }
 
+#[cfg(feature = "rest-api")]
 async fn health_check() -> &'static str {
     "OK"
 }
 
-#[cfg(test)]
+#[cfg(feature = "rest-api")]
+#[allow(dead_code)]
+pub fn create_test_app(state: AppState) -> Router {
+    Router::new()
+        .route("/order", post(place_order))
+        .route("/price/:base/:quote", get(get_price))
+        .route("/orderbook/:base/:quote", get(get_order_book))
+        .route("/trades/:base/:quote", get(get_trade_history))
+        .route("/health", get(health_check))
+        .with_state(state)
+}
+
+#[cfg(all(test, feature = "serde"))]
 mod tests {
     use super::*;
 
@@ -337,14 +472,3 @@ mod tests {
         assert!(serialized.contains("price"));
     }
 }
-
-#[allow(dead_code)]
-pub fn create_test_app(state: AppState) -> Router {
-    Router::new()
-        .route("/order", post(place_order))
-        .route("/price/:base/:quote", get(get_price))
-        .route("/orderbook/:base/:quote", get(get_order_book))
-        .route("/trades/:base/:quote", get(get_trade_history))
-        .route("/health", get(health_check))
-        .with_state(state)
-}
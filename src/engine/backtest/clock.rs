@@ -0,0 +1,48 @@
+use crate::engine::clock::Clock;
+use chrono::{DateTime, Utc};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::time::Duration;
+
+/// Shared clock for backtests, moved explicitly instead of following wall
+/// time. Resolution is one nanosecond.
+#[derive(Debug)]
+pub struct SimulatedClock {
+    nanos: AtomicI64,
+}
+
+impl SimulatedClock {
+    pub fn new(start: DateTime<Utc>) -> Self {
+        SimulatedClock {
+            nanos: AtomicI64::new(to_nanos(start)),
+        }
+    }
+
+    pub fn now(&self) -> DateTime<Utc> {
+        DateTime::from_timestamp_nanos(self.nanos.load(Ordering::Acquire))
+    }
+
+    pub fn set(&self, time: DateTime<Utc>) {
+        self.nanos.store(to_nanos(time), Ordering::Release);
+    }
+
+    pub fn advance(&self, by: Duration) {
+        let by = i64::try_from(by.as_nanos()).unwrap_or(i64::MAX);
+        self.nanos.fetch_add(by, Ordering::AcqRel);
+    }
+}
+
+impl Clock for SimulatedClock {
+    fn now(&self) -> DateTime<Utc> {
+        SimulatedClock::now(self)
+    }
+}
+
+/// Times outside the representable range (roughly 1677 to 2262) saturate.
+fn to_nanos(time: DateTime<Utc>) -> i64 {
+    time.timestamp_nanos_opt()
+        .unwrap_or(if time.timestamp() < 0 {
+            i64::MIN
+        } else {
+            i64::MAX
+        })
+}
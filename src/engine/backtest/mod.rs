@@ -0,0 +1,7 @@
+pub mod clock;
+#[cfg(feature = "serde")]
+pub mod replay;
+
+pub use clock::SimulatedClock;
+#[cfg(feature = "serde")]
+pub use replay::{ReplayEngine, ReplaySummary};
@@ -0,0 +1,146 @@
+use crate::engine::backtest::SimulatedClock;
+use crate::engine::core::Message;
+use crate::engine::models::{MarketEvent, Order, TradingPair};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, mpsc};
+use tracing::{info, warn};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplaySummary {
+    pub total_orders: usize,
+    pub total_trades: usize,
+    pub total_volume: f64,
+    /// Wall-clock time the replay took.
+    pub elapsed: Duration,
+}
+
+/// Feeds recorded orders through a running engine in timestamp order,
+/// matching the book after each one. The clock is set to each order's
+/// timestamp before it is sent, so an engine built `with_clock` on the same
+/// clock stamps and times everything in recorded time.
+pub struct ReplayEngine {
+    path: PathBuf,
+    engine_tx: mpsc::Sender<Message>,
+    clock: Arc<SimulatedClock>,
+}
+
+impl ReplayEngine {
+    pub fn new(
+        path: PathBuf,
+        engine_tx: mpsc::Sender<Message>,
+        clock: Arc<SimulatedClock>,
+    ) -> Self {
+        ReplayEngine {
+            path,
+            engine_tx,
+            clock,
+        }
+    }
+
+    /// Reads the file as newline-delimited JSON `Order`s; blank lines are
+    /// skipped. Fails before submitting anything if a line does not parse.
+    pub fn load_orders(&self) -> io::Result<Vec<Order>> {
+        let contents = fs::read_to_string(&self.path)?;
+        let mut orders = Vec::new();
+        for (index, line) in contents.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let order: Order = serde_json::from_str(line).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {}: {}", index + 1, e),
+                )
+            })?;
+            orders.push(order);
+        }
+        // Stable, so orders sharing a timestamp keep their file order.
+        orders.sort_by_key(|order| order.timestamp);
+        Ok(orders)
+    }
+
+    pub async fn run(&self) -> io::Result<ReplaySummary> {
+        let start = Instant::now();
+        let orders = self.load_orders()?;
+        let mut summary = ReplaySummary {
+            total_orders: 0,
+            total_trades: 0,
+            total_volume: 0.0,
+            elapsed: Duration::ZERO,
+        };
+
+        let mut subscriptions: HashMap<TradingPair, broadcast::Receiver<MarketEvent>> =
+            HashMap::new();
+        for order in orders {
+            let trading_pair = order.trading_pair.clone();
+            if !subscriptions.contains_key(&trading_pair) {
+                let events = self
+                    .subscribe(&trading_pair)
+                    .await
+                    .ok_or_else(engine_gone)?;
+                subscriptions.insert(trading_pair.clone(), events);
+            }
+
+            self.clock.set(order.timestamp);
+            self.engine_tx
+                .send(Message::NewOrder(order))
+                .await
+                .map_err(|_| engine_gone())?;
+            summary.total_orders += 1;
+
+            // The match response tells us every trade event for this cycle
+            // has been published.
+            let (match_tx, mut match_rx) = mpsc::channel(1);
+            self.engine_tx
+                .send(Message::MatchOrders(trading_pair.clone(), match_tx))
+                .await
+                .map_err(|_| engine_gone())?;
+            match_rx.recv().await.ok_or_else(engine_gone)?;
+
+            let events = subscriptions.get_mut(&trading_pair).unwrap();
+            loop {
+                match events.try_recv() {
+                    Ok(MarketEvent::Trade(trade)) => {
+                        summary.total_trades += 1;
+                        summary.total_volume += trade.quantity.value();
+                    }
+                    Ok(_) => continue,
+                    Err(broadcast::error::TryRecvError::Lagged(skipped)) => {
+                        warn!(skipped, "Replay fell behind the {:?} stream", trading_pair);
+                    }
+                    Err(_) => break,
+                }
+            }
+        }
+
+        summary.elapsed = start.elapsed();
+        info!(
+            orders = summary.total_orders,
+            trades = summary.total_trades,
+            "Replayed {:?}",
+            self.path
+        );
+        Ok(summary)
+    }
+
+    async fn subscribe(
+        &self,
+        trading_pair: &TradingPair,
+    ) -> Option<broadcast::Receiver<MarketEvent>> {
+        let (subscribe_tx, mut subscribe_rx) = mpsc::channel(1);
+        self.engine_tx
+            .send(Message::SubscribeToPair(trading_pair.clone(), subscribe_tx))
+            .await
+            .ok()?;
+        subscribe_rx.recv().await
+    }
+}
+
+fn engine_gone() -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, "engine stopped during replay")
+}
@@ -0,0 +1,288 @@
+use crate::engine::api::PriceLevel;
+use crate::engine::core::Message;
+use crate::engine::models::{Order, PriceUpdate, Trade, TradingPair};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::mpsc;
+use tracing::warn;
+use uuid::Uuid;
+
+/// The part of a `Message` that can cross a process boundary: everything but
+/// the response sender. Messages carrying trait objects or local channels
+/// have no remote form.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RemotePayload {
+    Ping,
+    NewOrder(Order),
+    GetPrice(TradingPair),
+    GetOrderBook(TradingPair),
+    GetTradeHistory(TradingPair),
+    MatchOrders(TradingPair),
+    CancelOrder(TradingPair, u64),
+    PriceUpdate(PriceUpdate),
+    ResumeTrading(TradingPair),
+    Shutdown,
+}
+
+/// What the remote engine sent back on the message's response channel.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RemoteResponse {
+    Pong,
+    Price(Option<f64>),
+    OrderBook(Vec<PriceLevel>, Vec<PriceLevel>),
+    Trades(Vec<Trade>),
+    Cancelled(Option<Order>),
+}
+
+/// A forwarded message. `correlation_id` ties the eventual `RemoteReply` to
+/// the sender registered by the forwarding bridge.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RemoteMessage {
+    pub correlation_id: Uuid,
+    pub payload: RemotePayload,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RemoteReply {
+    pub correlation_id: Uuid,
+    pub response: RemoteResponse,
+}
+
+enum ReplySender {
+    Pong(mpsc::Sender<()>),
+    Price(mpsc::Sender<Option<f64>>),
+    OrderBook(mpsc::Sender<(Vec<PriceLevel>, Vec<PriceLevel>)>),
+    Trades(mpsc::Sender<Vec<Trade>>),
+    Cancelled(mpsc::Sender<Option<Order>>),
+}
+
+impl ReplySender {
+    async fn send(self, response: RemoteResponse) -> bool {
+        match (self, response) {
+            (ReplySender::Pong(tx), RemoteResponse::Pong) => tx.send(()).await.is_ok(),
+            (ReplySender::Price(tx), RemoteResponse::Price(price)) => tx.send(price).await.is_ok(),
+            (ReplySender::OrderBook(tx), RemoteResponse::OrderBook(bids, asks)) => {
+                tx.send((bids, asks)).await.is_ok()
+            }
+            (ReplySender::Trades(tx), RemoteResponse::Trades(trades)) => {
+                tx.send(trades).await.is_ok()
+            }
+            (ReplySender::Cancelled(tx), RemoteResponse::Cancelled(order)) => {
+                tx.send(order).await.is_ok()
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Separates a message into its payload and response sender. `None` if it
+/// has no remote form.
+fn split(message: Message) -> Option<(RemotePayload, Option<ReplySender>)> {
+    let split = match message {
+        Message::Ping(tx) => (RemotePayload::Ping, Some(ReplySender::Pong(tx))),
+        Message::NewOrder(order) => (RemotePayload::NewOrder(order), None),
+        Message::GetPrice(pair, tx) => {
+            (RemotePayload::GetPrice(pair), Some(ReplySender::Price(tx)))
+        }
+        Message::GetOrderBook(pair, tx) => (
+            RemotePayload::GetOrderBook(pair),
+            Some(ReplySender::OrderBook(tx)),
+        ),
+        Message::GetTradeHistory(pair, tx) => (
+            RemotePayload::GetTradeHistory(pair),
+            Some(ReplySender::Trades(tx)),
+        ),
+        Message::MatchOrders(pair, tx) => (
+            RemotePayload::MatchOrders(pair),
+            Some(ReplySender::Trades(tx)),
+        ),
+        Message::CancelOrder(pair, order_id, tx) => (
+            RemotePayload::CancelOrder(pair, order_id),
+            Some(ReplySender::Cancelled(tx)),
+        ),
+        Message::PriceUpdate(update) => (RemotePayload::PriceUpdate(update), None),
+        Message::ResumeTrading(pair) => (RemotePayload::ResumeTrading(pair), None),
+        Message::Shutdown => (RemotePayload::Shutdown, None),
+        _ => return None,
+    };
+    Some(split)
+}
+
+/// Runs `payload` on the local engine and waits for its response, if it has
+/// one.
+async fn execute(
+    payload: RemotePayload,
+    engine_tx: &mpsc::Sender<Message>,
+) -> io::Result<Option<RemoteResponse>> {
+    let closed = || io::Error::new(io::ErrorKind::BrokenPipe, "engine has stopped");
+    macro_rules! request {
+        ($variant:ident ( $($arg:expr),* ), $response:expr) => {{
+            let (tx, mut rx) = mpsc::channel(1);
+            engine_tx
+                .send(Message::$variant($($arg,)* tx))
+                .await
+                .map_err(|_| closed())?;
+            rx.recv().await.map($response).ok_or_else(closed)?
+        }};
+    }
+
+    let response = match payload {
+        RemotePayload::Ping => request!(Ping(), |()| RemoteResponse::Pong),
+        RemotePayload::GetPrice(pair) => request!(GetPrice(pair), RemoteResponse::Price),
+        RemotePayload::GetOrderBook(pair) => request!(GetOrderBook(pair), |(bids, asks)| {
+            RemoteResponse::OrderBook(bids, asks)
+        }),
+        RemotePayload::GetTradeHistory(pair) => {
+            request!(GetTradeHistory(pair), RemoteResponse::Trades)
+        }
+        RemotePayload::MatchOrders(pair) => request!(MatchOrders(pair), RemoteResponse::Trades),
+        RemotePayload::CancelOrder(pair, order_id) => {
+            request!(CancelOrder(pair, order_id), RemoteResponse::Cancelled)
+        }
+        RemotePayload::NewOrder(order) => return notify(engine_tx, Message::NewOrder(order)).await,
+        RemotePayload::PriceUpdate(update) => {
+            return notify(engine_tx, Message::PriceUpdate(update)).await
+        }
+        RemotePayload::ResumeTrading(pair) => {
+            return notify(engine_tx, Message::ResumeTrading(pair)).await
+        }
+        RemotePayload::Shutdown => return notify(engine_tx, Message::Shutdown).await,
+    };
+    Ok(Some(response))
+}
+
+async fn notify(
+    engine_tx: &mpsc::Sender<Message>,
+    message: Message,
+) -> io::Result<Option<RemoteResponse>> {
+    engine_tx
+        .send(message)
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "engine has stopped"))?;
+    Ok(None)
+}
+
+fn invalid_data(e: serde_json::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+async fn write_line<W, T>(writer: &mut W, value: &T) -> io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+    T: Serialize,
+{
+    let mut line = serde_json::to_vec(value).map_err(invalid_data)?;
+    line.push(b'\n');
+    writer.write_all(&line).await?;
+    writer.flush().await
+}
+
+type Writer = Box<dyn AsyncWrite + Send + Unpin>;
+
+/// Forwards engine messages to an engine in another process. Messages are
+/// sent as newline-delimited JSON `RemoteMessage`s over any byte stream, such
+/// as a `TcpStream` or `UnixStream`; the far end runs `MessageBridge::serve`.
+pub struct MessageBridge {
+    writer: tokio::sync::Mutex<Writer>,
+    /// Response senders of forwarded messages still awaiting a reply.
+    pending: Arc<Mutex<HashMap<Uuid, ReplySender>>>,
+}
+
+impl MessageBridge {
+    /// Spawns a task that routes replies arriving on `stream` to the senders
+    /// of the messages they answer.
+    pub fn connect<S>(stream: S) -> Self
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let (reader, writer) = tokio::io::split(stream);
+        let pending: Arc<Mutex<HashMap<Uuid, ReplySender>>> = Arc::default();
+
+        let routes = pending.clone();
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(reader).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let reply: RemoteReply = match serde_json::from_str(&line) {
+                    Ok(reply) => reply,
+                    Err(e) => {
+                        warn!("Dropping malformed reply: {}", e);
+                        continue;
+                    }
+                };
+                let sender = routes.lock().remove(&reply.correlation_id);
+                match sender {
+                    Some(sender) => {
+                        if !sender.send(reply.response).await {
+                            warn!("Reply {} could not be delivered", reply.correlation_id);
+                        }
+                    }
+                    None => warn!("No pending message for reply {}", reply.correlation_id),
+                }
+            }
+            // The connection is gone; dropping the senders wakes their
+            // receivers.
+            routes.lock().clear();
+        });
+
+        MessageBridge {
+            writer: tokio::sync::Mutex::new(Box::new(writer)),
+            pending,
+        }
+    }
+
+    /// Sends `message` to the remote engine. Its response, if any, arrives
+    /// on the message's own sender. Messages without a remote form are
+    /// rejected with `ErrorKind::Unsupported`.
+    pub async fn forward(&self, message: Message) -> io::Result<()> {
+        let (payload, reply) = split(message).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::Unsupported,
+                "message cannot be sent to a remote engine",
+            )
+        })?;
+        let remote = RemoteMessage {
+            correlation_id: Uuid::new_v4(),
+            payload,
+        };
+        if let Some(reply) = reply {
+            self.pending.lock().insert(remote.correlation_id, reply);
+        }
+
+        let result = write_line(&mut *self.writer.lock().await, &remote).await;
+        if result.is_err() {
+            self.pending.lock().remove(&remote.correlation_id);
+        }
+        result
+    }
+
+    /// Forwarded messages still waiting for their reply.
+    pub fn pending_replies(&self) -> usize {
+        self.pending.lock().len()
+    }
+
+    /// Runs messages read from `stream` on the engine behind `engine_tx`,
+    /// one at a time, writing back a reply for those that expect one.
+    /// Returns once the stream closes or the engine stops.
+    pub async fn serve<S>(stream: S, engine_tx: mpsc::Sender<Message>) -> io::Result<()>
+    where
+        S: AsyncRead + AsyncWrite,
+    {
+        let (reader, mut writer) = tokio::io::split(stream);
+        let mut lines = BufReader::new(reader).lines();
+        while let Some(line) = lines.next_line().await? {
+            let message: RemoteMessage = serde_json::from_str(&line).map_err(invalid_data)?;
+            if let Some(response) = execute(message.payload, &engine_tx).await? {
+                let reply = RemoteReply {
+                    correlation_id: message.correlation_id,
+                    response,
+                };
+                write_line(&mut writer, &reply).await?;
+            }
+        }
+        Ok(())
+    }
+}
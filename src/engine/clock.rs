@@ -0,0 +1,17 @@
+use chrono::{DateTime, Utc};
+
+/// Where the engine and its books read the time: order receipt, trade
+/// timestamps, trading hours, retention and idle-book eviction.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// Wall time; the default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
@@ -1,6 +1,6 @@
-use crate::engine::api::OrderBookEntry;
-use crate::engine::models::{Order, OrderType, Trade, TradingPair};
-use crate::engine::order_book::OrderBook;
+use crate::engine::api::PriceLevel;
+use crate::engine::models::{MatchResult, Order, OrderType, Quantity, Trade, TradingPair};
+use crate::engine::order_book::{OrderBook, OrderBookError};
 use async_trait::async_trait;
 use parking_lot::RwLock;
 use std::cmp::Ordering;
@@ -26,12 +26,12 @@ impl PartialOrd for OrderPrice {
 }
 
 #[derive(Debug)]
-struct PriceLevel {
+struct LockedPriceLevel {
     orders: VecDeque<Order>,
     total_quantity: f64,
 }
 
-impl PriceLevel {
+impl LockedPriceLevel {
     fn new() -> Self {
         Self {
             orders: VecDeque::new(),
@@ -40,7 +40,7 @@ impl PriceLevel {
     }
 
     fn add_order(&mut self, order: Order) {
-        self.total_quantity += order.quantity;
+        self.total_quantity += order.quantity.value();
         self.orders.push_back(order);
     }
 
@@ -50,38 +50,51 @@ impl PriceLevel {
         }
 
         let resting_order = self.orders.front_mut()?;
-        let match_quantity = f64::min(incoming_order.quantity, resting_order.quantity);
+        let match_quantity = incoming_order.quantity.min(resting_order.quantity);
 
-        if match_quantity <= 0.0 {
+        if match_quantity <= Quantity::ZERO {
             return None;
         }
 
-        resting_order.quantity -= match_quantity;
-        self.total_quantity -= match_quantity;
+        resting_order.fill(match_quantity, resting_order.price);
+        self.total_quantity -= match_quantity.value();
 
         let resting_order = self.orders.front()?.clone();
-        let match_quantity = f64::min(incoming_order.quantity, resting_order.quantity);
+        let match_quantity = incoming_order.quantity.min(resting_order.quantity);
 
-        if match_quantity <= 0.0 {
+        if match_quantity <= Quantity::ZERO {
             return None;
         }
 
         let trade = Trade {
             id: next_trade_id,
             trading_pair: incoming_order.trading_pair.clone(),
-            buy_order_id: if incoming_order.order_type == OrderType::Buy {
+            buy_order_id: if incoming_order.is_buy() {
                 incoming_order.id
             } else {
                 resting_order.id
             },
-            sell_order_id: if incoming_order.order_type == OrderType::Buy {
+            sell_order_id: if incoming_order.is_buy() {
                 resting_order.id
             } else {
                 incoming_order.id
             },
             price: resting_order.price,
             quantity: match_quantity,
+            aggressor_side: incoming_order.order_type.clone(),
             timestamp: chrono::Utc::now(),
+            buy_client_id: if incoming_order.is_buy() {
+                incoming_order.client_id.clone()
+            } else {
+                resting_order.client_id.clone()
+            },
+            sell_client_id: if incoming_order.is_buy() {
+                resting_order.client_id.clone()
+            } else {
+                incoming_order.client_id.clone()
+            },
+            buy_fee: 0.0,
+            sell_fee: 0.0,
         };
 
         Some(trade)
@@ -90,8 +103,8 @@ impl PriceLevel {
 
 pub struct ConcurrentOrderBook {
     _trading_pair: TradingPair,
-    buy_levels: Arc<RwLock<BTreeMap<OrderPrice, Arc<RwLock<PriceLevel>>>>>,
-    sell_levels: Arc<RwLock<BTreeMap<OrderPrice, Arc<RwLock<PriceLevel>>>>>,
+    buy_levels: Arc<RwLock<BTreeMap<OrderPrice, Arc<RwLock<LockedPriceLevel>>>>>,
+    sell_levels: Arc<RwLock<BTreeMap<OrderPrice, Arc<RwLock<LockedPriceLevel>>>>>,
     trade_tx: mpsc::UnboundedSender<Trade>,
     next_trade_id: Arc<std::sync::atomic::AtomicU64>,
 }
@@ -118,23 +131,25 @@ impl ConcurrentOrderBook {
         let (matching_levels, resting_levels) = match incoming_order.order_type {
             OrderType::Buy => (&self.sell_levels, &self.buy_levels),
             OrderType::Sell => (&self.buy_levels, &self.sell_levels),
+            // Refused by `add_order`.
+            OrderType::Convert { .. } => return trades,
         };
 
         {
             let levels = matching_levels.read();
             for level in levels.values() {
-                if incoming_order.quantity <= 0.0 {
+                if incoming_order.quantity <= Quantity::ZERO {
                     break;
                 }
 
                 let mut price_level = level.write();
-                while incoming_order.quantity > 0.0 {
+                while incoming_order.quantity > Quantity::ZERO {
                     let trade_id = self
                         .next_trade_id
                         .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
                     match price_level.try_match(&incoming_order, trade_id) {
                         Some(trade) => {
-                            incoming_order.quantity -= trade.quantity;
+                            incoming_order.fill(trade.quantity, trade.price);
                             trades.push(trade);
                         }
                         None => break,
@@ -143,11 +158,11 @@ impl ConcurrentOrderBook {
             }
         }
 
-        if incoming_order.quantity > 0.0 {
+        if incoming_order.quantity > Quantity::ZERO {
             let mut levels = resting_levels.write();
             let price_level = levels
-                .entry(OrderPrice(incoming_order.price))
-                .or_insert_with(|| Arc::new(RwLock::new(PriceLevel::new())));
+                .entry(OrderPrice(incoming_order.price.value()))
+                .or_insert_with(|| Arc::new(RwLock::new(LockedPriceLevel::new())));
             price_level.write().add_order(incoming_order);
         }
 
@@ -157,15 +172,23 @@ impl ConcurrentOrderBook {
 
 #[async_trait]
 impl OrderBook for ConcurrentOrderBook {
-    async fn add_order(&self, order: Order) {
+    async fn add_order(&self, order: Order) -> Result<(), OrderBookError> {
+        if let OrderType::Convert { .. } = order.order_type {
+            return Err(OrderBookError::CannotRest);
+        }
         let trades = self.process_order(order).await;
         for trade in trades {
             let _ = self.trade_tx.send(trade);
         }
+        Ok(())
     }
 
-    async fn match_orders(&self) -> Vec<Trade> {
-        Vec::new()
+    async fn match_orders(&self) -> MatchResult {
+        // Real-time matching is done in process_order
+        MatchResult {
+            no_match_reason: Some("orders are matched on arrival".to_string()),
+            ..MatchResult::default()
+        }
     }
 
     async fn get_current_price(&self) -> Option<f64> {
@@ -180,24 +203,32 @@ impl OrderBook for ConcurrentOrderBook {
         }
     }
 
-    async fn get_order_book(&self) -> (Vec<OrderBookEntry>, Vec<OrderBookEntry>) {
+    async fn get_order_book(&self) -> (Vec<PriceLevel>, Vec<PriceLevel>) {
         let buy_levels = self.buy_levels.read();
         let sell_levels = self.sell_levels.read();
 
-        let bids: Vec<OrderBookEntry> = buy_levels
+        let bids: Vec<PriceLevel> = buy_levels
             .iter()
             .rev()
-            .map(|(&OrderPrice(price), level)| OrderBookEntry {
-                price,
-                quantity: level.read().total_quantity,
+            .map(|(&OrderPrice(price), level)| {
+                let level = level.read();
+                PriceLevel {
+                    price,
+                    total_quantity: level.total_quantity,
+                    order_count: level.orders.len(),
+                }
             })
             .collect();
 
-        let asks: Vec<OrderBookEntry> = sell_levels
+        let asks: Vec<PriceLevel> = sell_levels
             .iter()
-            .map(|(&OrderPrice(price), level)| OrderBookEntry {
-                price,
-                quantity: level.read().total_quantity,
+            .map(|(&OrderPrice(price), level)| {
+                let level = level.read();
+                PriceLevel {
+                    price,
+                    total_quantity: level.total_quantity,
+                    order_count: level.orders.len(),
+                }
             })
             .collect();
 
@@ -225,4 +256,40 @@ impl OrderBook for ConcurrentOrderBook {
 
         buy_count + sell_count
     }
+    async fn cancel_order(&self, order_id: u64) -> Option<Order> {
+        for side in [&self.buy_levels, &self.sell_levels] {
+            let mut levels = side.write();
+            let mut cancelled = None;
+
+            for (&price, level) in levels.iter() {
+                let mut price_level = level.write();
+                if let Some(index) = price_level.orders.iter().position(|o| o.id == order_id) {
+                    let order = price_level.orders.remove(index)?;
+                    price_level.total_quantity -= order.quantity.value();
+                    cancelled = Some((price, order, price_level.orders.is_empty()));
+                    break;
+                }
+            }
+
+            if let Some((price, order, is_empty)) = cancelled {
+                if is_empty {
+                    levels.remove(&price);
+                }
+                return Some(order);
+            }
+        }
+        None
+    }
+
+    async fn get_active_orders(&self) -> Vec<Order> {
+        let buy_levels = self.buy_levels.read();
+        let sell_levels = self.sell_levels.read();
+
+        buy_levels
+            .values()
+            .rev()
+            .chain(sell_levels.values())
+            .flat_map(|level| level.read().orders.iter().cloned().collect::<Vec<_>>())
+            .collect()
+    }
 }
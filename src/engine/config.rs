@@ -0,0 +1,190 @@
+use crate::engine::core::MessageType;
+use crate::engine::diff::DEFAULT_DELTA_RETENTION;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use tracing::level_filters::LevelFilter;
+
+/// Fields marked "live" can be changed with `Message::ReloadConfig`; the
+/// rest are fixed once the engine has started.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct EngineConfig {
+    /// Capacity of the engine's message channel.
+    pub channel_capacity: usize,
+    /// Buffer of the `SubscribeToAllTrades` channel. Larger than the
+    /// per-pair channels, since a subscriber sees every pair's trades.
+    pub all_trades_channel_capacity: usize,
+    /// Serve `GetPrice`, `GetOrderBook` and `GetTradeHistory` on their own
+    /// tasks under a read lock instead of inline in the main loop. Live.
+    pub concurrent_books: bool,
+    /// `tracing` filter directive, e.g. `"info"` or `"engine=debug"`. Live.
+    pub log_level: String,
+    /// Level at which each type of message is logged as the engine handles
+    /// it, e.g. `{"GetPrice": "off"}`. Types not listed use
+    /// `MessageType::default_log_level`. Live.
+    #[cfg_attr(feature = "serde", serde(with = "level_filters"))]
+    pub log_filter: HashMap<MessageType, LevelFilter>,
+    /// Drop order books with no resting orders after this long without
+    /// activity. Live.
+    pub idle_book_ttl_seconds: Option<u64>,
+    /// Trim trade history older than this after each match. Live.
+    pub trade_history_retention_seconds: Option<u64>,
+    /// Drop fill reports of filled or cancelled orders this long after their
+    /// last update. Checked after each match. Defaults to a day; `None` keeps
+    /// every report, so a long-running book's records grow without bound.
+    /// Live.
+    pub fill_report_retention_seconds: Option<u64>,
+    /// Reject new orders for a book whose estimated footprint is above this
+    /// many bytes. Live.
+    pub max_memory_per_book: Option<usize>,
+    /// Reject new orders for a side of a book that already holds this many
+    /// resting orders, unless the pair's reference data sets its own limit.
+    /// Live.
+    pub default_max_orders_per_side: Option<usize>,
+    /// Reject an order whose fingerprint matches one accepted on the same
+    /// pair within this many seconds, e.g. a retried submission. Live.
+    pub dedup_window_seconds: Option<u64>,
+    /// Fingerprints remembered per pair for deduplication; the oldest are
+    /// forgotten first. Live.
+    pub dedup_capacity: usize,
+    /// Halt a pair when a `PriceUpdate` moves its mark price by more than
+    /// this many percent. Live.
+    pub circuit_breaker_pct: Option<f64>,
+    /// Scan all books for triangular arbitrage after each match and publish
+    /// what is found to the market event stream. Live.
+    pub detect_arbitrage: bool,
+    /// Encoding used when writing state files. Live.
+    pub serialization_format: SerializationFormat,
+    /// Diffs each book keeps for `SimpleOrderBook::take_snapshot_delta`;
+    /// older ones are compacted into a base snapshot.
+    pub delta_retention_count: usize,
+    /// Match a pair after every order accepted through `NewOrder` or
+    /// `NewOrderWithCallback`, publishing trades as `MatchOrders` would.
+    /// Off by default, matching only on `MatchOrders` as batch auctions
+    /// and the execution algorithms expect. Live.
+    pub auto_match: bool,
+    /// Run a final match on every book at shutdown, then cancel the orders
+    /// still resting. Off by default, leaving the books as they were, e.g.
+    /// for `Engine::save_state`. Live.
+    pub close_books_on_shutdown: bool,
+    /// Turn off logging and record no metrics, so benchmarks measure the
+    /// matching rather than the instrumentation. Only this engine goes
+    /// quiet: it logs through a no-op dispatcher, leaving the shared
+    /// subscriber and the other engines alone, and reloading `log_level`
+    /// has no effect while it is set.
+    pub benchmark_mode: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SerializationFormat {
+    #[default]
+    Json,
+    /// Compact binary encoding; needs the `bincode-serde` feature.
+    Bincode,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        EngineConfig {
+            channel_capacity: 100,
+            all_trades_channel_capacity: 16_384,
+            concurrent_books: false,
+            log_level: "info".to_string(),
+            log_filter: HashMap::new(),
+            idle_book_ttl_seconds: None,
+            trade_history_retention_seconds: None,
+            fill_report_retention_seconds: Some(86_400),
+            max_memory_per_book: None,
+            default_max_orders_per_side: None,
+            dedup_window_seconds: None,
+            dedup_capacity: 10_000,
+            circuit_breaker_pct: None,
+            detect_arbitrage: false,
+            serialization_format: SerializationFormat::Json,
+            delta_retention_count: DEFAULT_DELTA_RETENTION,
+            auto_match: false,
+            close_books_on_shutdown: false,
+            benchmark_mode: false,
+        }
+    }
+}
+
+impl EngineConfig {
+    /// Checks that moving from `self` to `new` only touches live fields.
+    pub fn check_reload(&self, new: &EngineConfig) -> Result<(), ConfigError> {
+        if self.channel_capacity != new.channel_capacity {
+            return Err(ConfigError::ImmutableField("channel_capacity".to_string()));
+        }
+        if self.all_trades_channel_capacity != new.all_trades_channel_capacity {
+            return Err(ConfigError::ImmutableField(
+                "all_trades_channel_capacity".to_string(),
+            ));
+        }
+        if self.delta_retention_count != new.delta_retention_count {
+            return Err(ConfigError::ImmutableField(
+                "delta_retention_count".to_string(),
+            ));
+        }
+        if self.benchmark_mode != new.benchmark_mode {
+            return Err(ConfigError::ImmutableField("benchmark_mode".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// `LevelFilter` has no serde support; it is written as its `Display` form.
+#[cfg(feature = "serde")]
+mod level_filters {
+    use super::*;
+    use serde::de::Error;
+    use serde::{Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        filter: &HashMap<MessageType, LevelFilter>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(
+            filter
+                .iter()
+                .map(|(message_type, level)| (message_type, level.to_string())),
+        )
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<HashMap<MessageType, LevelFilter>, D::Error> {
+        HashMap::<MessageType, String>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(message_type, level)| {
+                level
+                    .parse()
+                    .map(|level| (message_type, level))
+                    .map_err(|_| D::Error::custom(ConfigError::InvalidLogLevel(level)))
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigError {
+    /// The named field cannot be changed without restarting the engine.
+    ImmutableField(String),
+    InvalidLogLevel(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::ImmutableField(field) => {
+                write!(f, "{} cannot be changed while the engine is running", field)
+            }
+            ConfigError::InvalidLogLevel(level) => write!(f, "invalid log level {:?}", level),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
@@ -0,0 +1,152 @@
+use crate::engine::algorithms;
+use crate::engine::api::PriceLevel;
+use crate::engine::models::{Order, OrderType, TradingPair};
+use crate::engine::order_book::OrderBookError;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConversionError {
+    /// No book, or pair of books through one other asset, links the assets.
+    NoRoute {
+        from_asset: String,
+        to_asset: String,
+    },
+    /// Every route's books are too thin to take the whole quantity, or a
+    /// placed leg could not fill in full.
+    InsufficientLiquidity,
+    /// A leg failed the engine's order checks. No leg was matched.
+    Rejected(OrderBookError),
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConversionError::NoRoute {
+                from_asset,
+                to_asset,
+            } => write!(f, "no route from {} to {}", from_asset, to_asset),
+            ConversionError::InsufficientLiquidity => {
+                write!(f, "not enough liquidity to convert")
+            }
+            ConversionError::Rejected(e) => write!(f, "conversion rejected: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ConversionError {}
+
+/// Relative slack in `ConversionLeg::is_covered_by`.
+const FILL_TOLERANCE: f64 = 1e-9;
+
+/// One order of a conversion, sized against the book as it stands.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConversionLeg {
+    pub trading_pair: TradingPair,
+    pub side: OrderType,
+    /// Base quantity of the order.
+    pub quantity: f64,
+    /// Price of the last level the leg takes from.
+    pub limit_price: f64,
+    /// Amount of the asset the leg converts into.
+    pub output: f64,
+}
+
+impl ConversionLeg {
+    /// Plans spending `amount` of `from_asset` on `trading_pair`: selling
+    /// into the bids if it is the base, buying from the asks if it is the
+    /// quote. `None` if the asset is not in the pair or the book cannot
+    /// take all of `amount`.
+    pub fn plan(
+        trading_pair: &TradingPair,
+        from_asset: &str,
+        amount: f64,
+        bids: &[PriceLevel],
+        asks: &[PriceLevel],
+    ) -> Option<Self> {
+        if amount.is_nan() || amount <= 0.0 {
+            return None;
+        }
+        let leg = |side, quantity, limit_price, output| ConversionLeg {
+            trading_pair: trading_pair.clone(),
+            side,
+            quantity,
+            limit_price,
+            output,
+        };
+
+        let mut remaining = amount;
+        if trading_pair.base == from_asset {
+            let mut proceeds = 0.0;
+            for level in bids {
+                let filled = level.total_quantity.min(remaining);
+                proceeds += filled * level.price;
+                remaining -= filled;
+                if remaining <= 0.0 {
+                    return Some(leg(OrderType::Sell, amount, level.price, proceeds));
+                }
+            }
+        } else if trading_pair.quote == from_asset {
+            let mut bought = 0.0;
+            for level in asks {
+                let cost = level.total_quantity * level.price;
+                if cost >= remaining {
+                    bought += remaining / level.price;
+                    return Some(leg(OrderType::Buy, bought, level.price, bought));
+                }
+                bought += level.total_quantity;
+                remaining -= cost;
+            }
+        }
+        None
+    }
+
+    /// Whether `fillable` of the opposing side covers the whole leg. The
+    /// plan and the book sum the same levels in a different order, so a
+    /// shortfall within rounding does not count.
+    pub fn is_covered_by(&self, fillable: f64) -> bool {
+        fillable >= self.quantity * (1.0 - FILL_TOLERANCE)
+    }
+
+    pub(crate) fn order(&self) -> Order {
+        Order {
+            id: algorithms::next_child_order_id(),
+            trading_pair: self.trading_pair.clone(),
+            order_type: self.side.clone(),
+            price: self.limit_price.into(),
+            quantity: self.quantity.into(),
+            timestamp: chrono::Utc::now(),
+            ..Order::default()
+        }
+    }
+}
+
+/// Ways to get from `from_asset` to `to_asset` over `pairs`: the direct
+/// pair in either orientation, then every two-pair route through another
+/// asset.
+pub fn routes(pairs: &[TradingPair], from_asset: &str, to_asset: &str) -> Vec<Vec<TradingPair>> {
+    let links = |pair: &TradingPair, a: &str, b: &str| {
+        (pair.base == a && pair.quote == b) || (pair.base == b && pair.quote == a)
+    };
+    let mut routes: Vec<Vec<TradingPair>> = pairs
+        .iter()
+        .filter(|pair| links(pair, from_asset, to_asset))
+        .map(|pair| vec![pair.clone()])
+        .collect();
+
+    for first in pairs {
+        let via = if first.base == from_asset {
+            &first.quote
+        } else if first.quote == from_asset {
+            &first.base
+        } else {
+            continue;
+        };
+        if via == to_asset {
+            continue;
+        }
+        for second in pairs.iter().filter(|pair| links(pair, via, to_asset)) {
+            routes.push(vec![first.clone(), second.clone()]);
+        }
+    }
+    routes
+}
@@ -1,24 +1,648 @@
-use crate::engine::api::OrderBookEntry;
-use crate::engine::models::{Order, Trade, TradingPair};
-use crate::engine::order_book::OrderBook;
-use std::collections::HashMap;
-use tokio::sync::mpsc;
-use tracing::info;
+use crate::engine::accounts::AccountManager;
+use crate::engine::algorithms::{
+    ParticipationParams, ParticipationRateExecutor, TwapExecutor, TwapParams, VwapExecutor,
+    VwapParams,
+};
+use crate::engine::analytics::{DailyStats, PairStats, TradeAggregator};
+use crate::engine::api::{PriceLadderEntry, PriceLevel};
+use crate::engine::clock::{Clock, SystemClock};
+use crate::engine::config::{ConfigError, EngineConfig};
+use crate::engine::convert::{self, ConversionError, ConversionLeg};
+use crate::engine::fees::{FeeModel, ZeroFeeModel};
+use crate::engine::link::{self, EngineLink};
+use crate::engine::metrics::{
+    EngineMetrics, EngineMetricsInterface, EngineMetricsSnapshot, NoopEngineMetrics,
+};
+use crate::engine::microstructure::{self, PriceImpact, VpinCalculator};
+use crate::engine::models::{
+    Account, BboUpdate, FillReport, MarketEvent, MatchResult, OhlcvBar, Order, OrderType,
+    PriceUpdate, Trade, TradePage, TradingPair, TradingPairInfo,
+};
+use crate::engine::order_book::{AllocationStats, OrderBook, OrderBookError, PriceUpdateCallback};
+#[cfg(feature = "serde")]
+use crate::engine::persistence::EngineState;
+use crate::engine::persistence::{OrderBookSnapshot, PersistenceBackend};
+use crate::engine::position::{PositionChangeHook, PositionTracker};
+use crate::engine::quotes::{MarketMakerQuoteManager, QuoteAck, QuoteError, QuoteRequest};
+use crate::engine::reference_data::SharedReferenceData;
+use crate::engine::risk::{RiskError, RiskManager, TradingHoursManager, TradingSession};
+use crate::engine::surveillance::ArbitrageDetector;
+use crate::engine::version::{EngineVersion, ENGINE_VERSION};
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+#[cfg(any(feature = "serde", feature = "export"))]
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock, Weak};
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, mpsc, OwnedRwLockWriteGuard, RwLock};
+use tracing::dispatcher::{self, Dispatch};
+use tracing::instrument::WithSubscriber;
+use tracing::level_filters::LevelFilter;
+use tracing::{debug, event, info, info_span, warn, Instrument, Level, Span};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{reload, EnvFilter, Registry};
+
+const PAIR_CHANNEL_CAPACITY: usize = 1024;
+const MARKET_EVENT_CHANNEL_CAPACITY: usize = 1024;
+const VPIN_BUCKET_VOLUME: f64 = 10.0;
+const VPIN_BUCKETS: usize = 50;
+
+// Set by whichever engine installs the global subscriber first; every engine
+// in the process shares it.
+static LOG_FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+fn init_tracing(log_level: &str) {
+    let filter = EnvFilter::try_new(log_level).unwrap_or_else(|_| EnvFilter::new("info"));
+    let (filter, handle) = reload::Layer::new(filter);
+    let installed = tracing_subscriber::registry()
+        .with(filter)
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_target(false)
+                .with_thread_ids(true)
+                .with_level(true)
+                .with_file(true)
+                .with_line_number(true),
+        )
+        .try_init();
+
+    if installed.is_ok() {
+        let _ = LOG_FILTER.set(handle);
+    }
+}
+
+pub type SharedOrderBook = Arc<RwLock<Box<dyn OrderBook>>>;
+
+/// Exclusive access to one order book, from `Engine::lock_order_book`. The
+/// guard borrows the engine mutably, so nothing else is processed while it
+/// is held: messages sent meanwhile, for any pair, wait in the channel and
+/// are handled in order once the guard is dropped and the engine runs again.
+/// Changes made through the guard skip the engine's checks and events.
+pub struct OrderBookGuard<'a> {
+    order_book: OwnedRwLockWriteGuard<Box<dyn OrderBook>>,
+    _engine: PhantomData<&'a mut Engine>,
+}
+
+impl Deref for OrderBookGuard<'_> {
+    type Target = dyn OrderBook;
+
+    fn deref(&self) -> &Self::Target {
+        self.order_book.as_ref()
+    }
+}
+
+impl DerefMut for OrderBookGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.order_book.as_mut()
+    }
+}
 
 pub enum Message {
+    /// Health probe; the engine answers with `()` without touching any book.
+    Ping(mpsc::Sender<()>),
     NewOrder(Order),
+    /// Like `NewOrder`, but reports whether the order made it onto the book.
+    NewOrderWithCallback(Order, mpsc::Sender<OrderAck>),
     GetPrice(TradingPair, mpsc::Sender<Option<f64>>),
     GetOrderBook(
         TradingPair,
-        mpsc::Sender<(Vec<OrderBookEntry>, Vec<OrderBookEntry>)>,
+        mpsc::Sender<(Vec<PriceLevel>, Vec<PriceLevel>)>,
     ),
     GetTradeHistory(TradingPair, mpsc::Sender<Vec<Trade>>),
+    /// Pair, offset and limit; see `OrderBook::get_trade_history_paged`.
+    /// An empty page if the pair has no book.
+    GetTradeHistoryPaginated(TradingPair, u64, usize, mpsc::Sender<TradePage>),
+    /// Quantity traded on the pair at or after the timestamp.
+    GetVolumeSince(TradingPair, DateTime<Utc>, mpsc::Sender<f64>),
+    GetOhlcv(
+        TradingPair,
+        Duration,
+        DateTime<Utc>,
+        mpsc::Sender<Vec<OhlcvBar>>,
+    ),
+    /// Center price, range in percent and side.
+    GetLiquidityWithinRange(TradingPair, f64, f64, OrderType, mpsc::Sender<f64>),
+    GetKyleLambda(TradingPair, usize, mpsc::Sender<Option<f64>>),
+    GetVpin(TradingPair, mpsc::Sender<Option<f64>>),
+    /// The pair's figures for the current UTC day; see `TradeAggregator`.
+    GetDailyStats(TradingPair, mpsc::Sender<DailyStats>),
+    /// Pair and price level, 0 being the best.
+    GetDepthImbalance(TradingPair, usize, mpsc::Sender<Option<f64>>),
+    /// Pair and trade size; see `OrderBook::get_effective_spread`.
+    GetEffectiveSpread(TradingPair, f64, mpsc::Sender<Option<f64>>),
+    /// Pair, side and quantity; see `OrderBook::get_price_impact`. A pair
+    /// without a book is treated as empty.
+    GetPriceImpact(TradingPair, OrderType, f64, mpsc::Sender<PriceImpact>),
+    /// Pair, center price, step and rungs either side; see
+    /// `OrderBook::price_ladder`. Empty if the pair has no book.
+    GetPriceLadder(
+        TradingPair,
+        f64,
+        f64,
+        usize,
+        mpsc::Sender<Vec<PriceLadderEntry>>,
+    ),
+    MatchOrders(TradingPair, mpsc::Sender<Vec<Trade>>),
+    /// Loads resting orders into the pair's book, creating it if needed,
+    /// without the order checks or matching; see `OrderBook::warm_up`.
+    WarmUpOrderBook(TradingPair, Vec<Order>, mpsc::Sender<()>),
+    /// Resting orders of a client on one pair.
+    GetClientOrders(String, TradingPair, mpsc::Sender<Vec<Order>>),
+    /// Resting orders of a client, by pair; pairs without any are left out.
+    GetClientOrdersAllPairs(String, mpsc::Sender<HashMap<TradingPair, Vec<Order>>>),
+    /// Resting orders in every book, oldest first.
+    GetAllActiveOrders(mpsc::Sender<Vec<Order>>),
+    /// Like `GetAllActiveOrders`, for one client.
+    GetAllActiveOrdersByClient(String, mpsc::Sender<Vec<Order>>),
+    /// Number of resting orders on one pair; 0 if it has no book.
+    GetActiveOrderCount(TradingPair, mpsc::Sender<usize>),
+    /// Resting orders on the pair that have waited longer than the
+    /// threshold, by `Order::time_in_book`, oldest first.
+    GetStaleOrders(TradingPair, chrono::Duration, mpsc::Sender<Vec<Order>>),
+    /// Number of resting orders in every book, including empty ones.
+    GetActiveOrderCountAllPairs(mpsc::Sender<HashMap<TradingPair, usize>>),
+    /// Dashboard figures for every pair with a book, in one round trip.
+    GetAllPairStats(mpsc::Sender<HashMap<TradingPair, PairStats>>),
+    /// `None` if the pair has no book or its book does not report
+    /// allocations; see `OrderBook::get_allocation_stats`.
+    GetAllocationStats(TradingPair, mpsc::Sender<Option<AllocationStats>>),
+    /// Drops the pair's empty price levels now; answers with how many.
+    RebalanceOrderBook(TradingPair, mpsc::Sender<usize>),
+    /// Source and target pair: moves every resting and stop order from the
+    /// source book into the target's, as after a pair rename, then removes
+    /// the source book and everything the engine keeps for the pair. Answers
+    /// with how many orders moved; zero, with nothing changed, while the
+    /// source is halted. The source's trade history is not carried over.
+    MergeTradingPairs(TradingPair, TradingPair, mpsc::Sender<usize>),
+    CancelOrder(TradingPair, u64, mpsc::Sender<Option<Order>>),
+    /// Cancels an erroneous trade on the pair by ID, returning its quantity
+    /// to both orders and taking it out of positions, daily stats and VPIN,
+    /// and publishes `MarketEvent::TradeBusted`. Refused with
+    /// `OrderBookError::TradeSettled` while an account or risk manager is
+    /// set, as neither can give a trade back.
+    BustTrade(TradingPair, u64, mpsc::Sender<Result<(), OrderBookError>>),
+    /// Converts a quantity of the first asset into the second through the
+    /// best-priced direct or two-pair route, as limit orders that either
+    /// all fill or are never matched. Answers with the conversion's trades.
+    /// A `NewOrder` of type `OrderType::Convert` is carried out the same way.
+    ConvertCurrency(
+        String,
+        f64,
+        String,
+        mpsc::Sender<Result<Vec<Trade>, EngineError>>,
+    ),
+    /// Replaces a market maker's two-sided quote; see `QuoteRequest`.
+    UpdateQuote(
+        TradingPair,
+        QuoteRequest,
+        mpsc::Sender<Result<QuoteAck, QuoteError>>,
+    ),
+    StartTwapExecution(TwapParams, mpsc::Sender<Vec<Trade>>),
+    StartVwapExecution(VwapParams, mpsc::Sender<Vec<Trade>>),
+    StartParticipation(ParticipationParams, mpsc::Sender<f64>),
+    /// Trades and book diffs for one pair.
+    SubscribeToPair(TradingPair, mpsc::Sender<broadcast::Receiver<MarketEvent>>),
+    /// Top-of-book updates only, published whenever the best bid or ask
+    /// price or quantity changes.
+    SubscribeToBbo(TradingPair, mpsc::Sender<broadcast::Receiver<BboUpdate>>),
+    /// One-shot top of book. `seq` is that of the last streamed update, or
+    /// zero if none has been published.
+    GetTopOfBook(TradingPair, mpsc::Sender<BboUpdate>),
+    SubscribeToMarketEvents(mpsc::Sender<broadcast::Receiver<MarketEvent>>),
+    /// Every trade on every pair, as it is matched. A receiver that falls
+    /// more than `EngineConfig::all_trades_channel_capacity` trades behind
+    /// gets `RecvError::Lagged` and skips ahead.
+    SubscribeToAllTrades(mpsc::Sender<broadcast::Receiver<Trade>>),
+    PriceUpdate(PriceUpdate),
+    /// Lifts a circuit breaker halt on the pair.
+    ResumeTrading(TradingPair),
+    /// Replaces the pre-trade checks applied to every new order.
+    SetRiskManager(Box<dyn RiskManager>),
+    /// Replaces the pair's trading sessions. Pairs never registered are
+    /// always open; see `TradingHoursManager`.
+    RegisterTradingHours(TradingPair, Vec<TradingSession>, mpsc::Sender<()>),
+    /// Enables balance checks, margin and settlement for orders with a
+    /// `client_id`.
+    SetAccountManager(AccountManager),
+    /// Client, asset and amount. Ignored until an account manager is set.
+    Deposit(String, String, f64),
+    GetAccount(String, mpsc::Sender<Option<Account>>),
+    /// Client and margin pair; `None` without an open position.
+    GetLiquidationPrice(String, TradingPair, mpsc::Sender<Option<f64>>),
+    /// See `Engine::save_state`.
+    #[cfg(feature = "serde")]
+    SaveState(PathBuf, mpsc::Sender<io::Result<()>>),
+    /// See `Engine::save_snapshots`.
+    SaveSnapshots(Arc<dyn PersistenceBackend>, mpsc::Sender<io::Result<()>>),
+    /// See `Engine::restore_snapshot`.
+    RestoreSnapshot(
+        TradingPair,
+        Arc<dyn PersistenceBackend>,
+        mpsc::Sender<io::Result<bool>>,
+    ),
+    /// See `Engine::export_orderbook_csv`.
+    #[cfg(feature = "export")]
+    ExportOrderBookCsv(TradingPair, PathBuf, mpsc::Sender<io::Result<()>>),
+    /// Applies the live fields of a new config; see `EngineConfig`.
+    ReloadConfig(EngineConfig, mpsc::Sender<Result<(), ConfigError>>),
+    GetEngineVersion(mpsc::Sender<EngineVersion>),
+    GetMetrics(mpsc::Sender<EngineMetricsSnapshot>),
+    /// Zeroes the engine's local metrics, e.g. between tests sharing an
+    /// engine; see `EngineMetrics::reset`.
+    ResetMetrics(mpsc::Sender<()>),
+    /// Closes the channel to new messages, processes everything already
+    /// queued, shuts down as `Shutdown` does, replies with how many messages
+    /// that was and stops.
+    Drain(mpsc::Sender<usize>),
+    /// Registers instrument specs; orders on a registered pair must then
+    /// respect its tick and lot sizes.
+    LoadReferenceData(Vec<TradingPairInfo>, mpsc::Sender<()>),
+    /// Pair and order id; `None` for orders that have neither traded nor
+    /// been cancelled, or whose report has been pruned.
+    GetFillReport(TradingPair, u64, mpsc::Sender<Option<FillReport>>),
+    /// Checks every book's integrity; see `Engine::diagnose`. Debug builds
+    /// also run this every `DIAGNOSTIC_INTERVAL` and log what it finds.
+    DiagnoseEngine(mpsc::Sender<DiagnosticReport>),
+    /// Replaces the engine-wide fee model, or overrides it for one pair.
+    /// Applies from each book's next match.
+    SetFeeModel(
+        Option<TradingPair>,
+        Arc<dyn FeeModel + Send + Sync>,
+        mpsc::Sender<()>,
+    ),
+    /// Applies the payload to every pair with a book in one step; answers
+    /// with how many pairs it changed.
+    Broadcast(BroadcastPayload, mpsc::Sender<usize>),
+    /// Sets the level at which messages of this type are logged as they are
+    /// handled. `OFF` silences them.
+    SetLogFilter(MessageType, LevelFilter, mpsc::Sender<()>),
+    /// Handles the inner message inside a span carrying the caller's trace
+    /// ID; see `Message::with_correlation_id`.
+    WithCorrelationId(CorrelationId, Box<Message>),
+    /// Handles the inner message, copied from another engine by an
+    /// `EngineLink`, which marks it so no link forwards it again.
+    Forwarded(Box<Message>),
+    Shutdown,
+}
+
+/// The variant of a `Message`, without its payload. Keys the per-message log
+/// levels in `EngineConfig::log_filter`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum MessageType {
+    Ping,
+    NewOrder,
+    NewOrderWithCallback,
+    GetPrice,
+    GetOrderBook,
+    GetTradeHistory,
+    GetTradeHistoryPaginated,
+    GetVolumeSince,
+    GetOhlcv,
+    GetLiquidityWithinRange,
+    GetKyleLambda,
+    GetVpin,
+    GetDailyStats,
+    GetDepthImbalance,
+    GetEffectiveSpread,
+    GetPriceImpact,
+    GetPriceLadder,
+    MatchOrders,
+    WarmUpOrderBook,
+    GetClientOrders,
+    GetClientOrdersAllPairs,
+    GetAllActiveOrders,
+    GetAllActiveOrdersByClient,
+    GetActiveOrderCount,
+    GetStaleOrders,
+    GetActiveOrderCountAllPairs,
+    GetAllPairStats,
+    GetAllocationStats,
+    RebalanceOrderBook,
+    MergeTradingPairs,
+    CancelOrder,
+    BustTrade,
+    ConvertCurrency,
+    UpdateQuote,
+    StartTwapExecution,
+    StartVwapExecution,
+    StartParticipation,
+    SubscribeToPair,
+    SubscribeToBbo,
+    GetTopOfBook,
+    SubscribeToMarketEvents,
+    SubscribeToAllTrades,
+    PriceUpdate,
+    ResumeTrading,
+    SetRiskManager,
+    RegisterTradingHours,
+    SetAccountManager,
+    Deposit,
+    GetAccount,
+    GetLiquidationPrice,
+    SaveState,
+    SaveSnapshots,
+    RestoreSnapshot,
+    ExportOrderBookCsv,
+    ReloadConfig,
+    GetEngineVersion,
+    GetMetrics,
+    ResetMetrics,
+    Drain,
+    LoadReferenceData,
+    GetFillReport,
+    DiagnoseEngine,
+    SetFeeModel,
+    Broadcast,
+    SetLogFilter,
     Shutdown,
 }
 
+impl MessageType {
+    /// Queries and subscriptions, which leave the engine unchanged.
+    pub fn is_read_only(self) -> bool {
+        matches!(
+            self,
+            MessageType::Ping
+                | MessageType::GetPrice
+                | MessageType::GetOrderBook
+                | MessageType::GetTradeHistory
+                | MessageType::GetTradeHistoryPaginated
+                | MessageType::GetVolumeSince
+                | MessageType::GetOhlcv
+                | MessageType::GetLiquidityWithinRange
+                | MessageType::GetKyleLambda
+                | MessageType::GetVpin
+                | MessageType::GetDailyStats
+                | MessageType::GetDepthImbalance
+                | MessageType::GetEffectiveSpread
+                | MessageType::GetPriceImpact
+                | MessageType::GetPriceLadder
+                | MessageType::GetClientOrders
+                | MessageType::GetClientOrdersAllPairs
+                | MessageType::GetAllActiveOrders
+                | MessageType::GetAllActiveOrdersByClient
+                | MessageType::GetActiveOrderCount
+                | MessageType::GetStaleOrders
+                | MessageType::GetActiveOrderCountAllPairs
+                | MessageType::GetAllPairStats
+                | MessageType::GetAllocationStats
+                | MessageType::SubscribeToPair
+                | MessageType::SubscribeToBbo
+                | MessageType::GetTopOfBook
+                | MessageType::SubscribeToMarketEvents
+                | MessageType::SubscribeToAllTrades
+                | MessageType::GetAccount
+                | MessageType::GetLiquidationPrice
+                | MessageType::GetEngineVersion
+                | MessageType::GetMetrics
+                | MessageType::GetFillReport
+                | MessageType::DiagnoseEngine
+        )
+    }
+
+    /// Logged at `DEBUG` if read-only and `INFO` otherwise, unless
+    /// `EngineConfig::log_filter` says differently.
+    pub fn default_log_level(self) -> LevelFilter {
+        if self.is_read_only() {
+            LevelFilter::DEBUG
+        } else {
+            LevelFilter::INFO
+        }
+    }
+}
+
+/// A change `Message::Broadcast` makes to every pair with a book.
+pub enum BroadcastPayload {
+    /// Overrides each pair's fee model, as `SetFeeModel` does for one pair.
+    UpdateFeeModel(Arc<dyn FeeModel + Send + Sync>),
+    SetTradingHours(Vec<TradingSession>),
+    /// Halts each pair as a tripped circuit breaker would.
+    HaltAllTrading,
+    /// Lifts every halt, including those on pairs without a book.
+    ResumeAllTrading,
+}
+
+/// Trace ID of the request a message belongs to, such as one forwarded
+/// from a REST or gRPC caller. Shown in hex, as tracing backends show
+/// 128-bit trace IDs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CorrelationId(pub u128);
+
+impl fmt::Display for CorrelationId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:032x}", self.0)
+    }
+}
+
+impl Message {
+    /// Wraps the message so the engine handles it in a `message` span with
+    /// a `correlation_id` field.
+    pub fn with_correlation_id(self, correlation_id: CorrelationId) -> Message {
+        Message::WithCorrelationId(correlation_id, Box::new(self))
+    }
+
+    /// The wrapped message's type for `WithCorrelationId` and `Forwarded`.
+    pub fn message_type(&self) -> MessageType {
+        match self {
+            Message::Ping(..) => MessageType::Ping,
+            Message::NewOrder(..) => MessageType::NewOrder,
+            Message::NewOrderWithCallback(..) => MessageType::NewOrderWithCallback,
+            Message::GetPrice(..) => MessageType::GetPrice,
+            Message::GetOrderBook(..) => MessageType::GetOrderBook,
+            Message::GetTradeHistory(..) => MessageType::GetTradeHistory,
+            Message::GetTradeHistoryPaginated(..) => MessageType::GetTradeHistoryPaginated,
+            Message::GetVolumeSince(..) => MessageType::GetVolumeSince,
+            Message::GetOhlcv(..) => MessageType::GetOhlcv,
+            Message::GetLiquidityWithinRange(..) => MessageType::GetLiquidityWithinRange,
+            Message::GetKyleLambda(..) => MessageType::GetKyleLambda,
+            Message::GetVpin(..) => MessageType::GetVpin,
+            Message::GetDailyStats(..) => MessageType::GetDailyStats,
+            Message::GetDepthImbalance(..) => MessageType::GetDepthImbalance,
+            Message::GetEffectiveSpread(..) => MessageType::GetEffectiveSpread,
+            Message::GetPriceImpact(..) => MessageType::GetPriceImpact,
+            Message::GetPriceLadder(..) => MessageType::GetPriceLadder,
+            Message::MatchOrders(..) => MessageType::MatchOrders,
+            Message::WarmUpOrderBook(..) => MessageType::WarmUpOrderBook,
+            Message::GetClientOrders(..) => MessageType::GetClientOrders,
+            Message::GetClientOrdersAllPairs(..) => MessageType::GetClientOrdersAllPairs,
+            Message::GetAllActiveOrders(..) => MessageType::GetAllActiveOrders,
+            Message::GetAllActiveOrdersByClient(..) => MessageType::GetAllActiveOrdersByClient,
+            Message::GetActiveOrderCount(..) => MessageType::GetActiveOrderCount,
+            Message::GetStaleOrders(..) => MessageType::GetStaleOrders,
+            Message::GetActiveOrderCountAllPairs(..) => MessageType::GetActiveOrderCountAllPairs,
+            Message::GetAllPairStats(..) => MessageType::GetAllPairStats,
+            Message::GetAllocationStats(..) => MessageType::GetAllocationStats,
+            Message::RebalanceOrderBook(..) => MessageType::RebalanceOrderBook,
+            Message::MergeTradingPairs(..) => MessageType::MergeTradingPairs,
+            Message::CancelOrder(..) => MessageType::CancelOrder,
+            Message::BustTrade(..) => MessageType::BustTrade,
+            Message::ConvertCurrency(..) => MessageType::ConvertCurrency,
+            Message::UpdateQuote(..) => MessageType::UpdateQuote,
+            Message::StartTwapExecution(..) => MessageType::StartTwapExecution,
+            Message::StartVwapExecution(..) => MessageType::StartVwapExecution,
+            Message::StartParticipation(..) => MessageType::StartParticipation,
+            Message::SubscribeToPair(..) => MessageType::SubscribeToPair,
+            Message::SubscribeToBbo(..) => MessageType::SubscribeToBbo,
+            Message::GetTopOfBook(..) => MessageType::GetTopOfBook,
+            Message::SubscribeToMarketEvents(..) => MessageType::SubscribeToMarketEvents,
+            Message::SubscribeToAllTrades(..) => MessageType::SubscribeToAllTrades,
+            Message::PriceUpdate(..) => MessageType::PriceUpdate,
+            Message::ResumeTrading(..) => MessageType::ResumeTrading,
+            Message::SetRiskManager(..) => MessageType::SetRiskManager,
+            Message::RegisterTradingHours(..) => MessageType::RegisterTradingHours,
+            Message::SetAccountManager(..) => MessageType::SetAccountManager,
+            Message::Deposit(..) => MessageType::Deposit,
+            Message::GetAccount(..) => MessageType::GetAccount,
+            Message::GetLiquidationPrice(..) => MessageType::GetLiquidationPrice,
+            #[cfg(feature = "serde")]
+            Message::SaveState(..) => MessageType::SaveState,
+            Message::SaveSnapshots(..) => MessageType::SaveSnapshots,
+            Message::RestoreSnapshot(..) => MessageType::RestoreSnapshot,
+            #[cfg(feature = "export")]
+            Message::ExportOrderBookCsv(..) => MessageType::ExportOrderBookCsv,
+            Message::ReloadConfig(..) => MessageType::ReloadConfig,
+            Message::GetEngineVersion(..) => MessageType::GetEngineVersion,
+            Message::GetMetrics(..) => MessageType::GetMetrics,
+            Message::ResetMetrics(..) => MessageType::ResetMetrics,
+            Message::Drain(..) => MessageType::Drain,
+            Message::LoadReferenceData(..) => MessageType::LoadReferenceData,
+            Message::GetFillReport(..) => MessageType::GetFillReport,
+            Message::DiagnoseEngine(..) => MessageType::DiagnoseEngine,
+            Message::SetFeeModel(..) => MessageType::SetFeeModel,
+            Message::Broadcast(..) => MessageType::Broadcast,
+            Message::SetLogFilter(..) => MessageType::SetLogFilter,
+            Message::WithCorrelationId(_, message) | Message::Forwarded(message) => {
+                message.message_type()
+            }
+            Message::Shutdown => MessageType::Shutdown,
+        }
+    }
+}
+
+struct BboChannel {
+    sender: broadcast::Sender<BboUpdate>,
+    last: Option<BboUpdate>,
+}
+
+/// Current top of book; an empty book, or no book, has neither side.
+async fn bbo_snapshot(
+    trading_pair: &TradingPair,
+    order_book: Option<&dyn OrderBook>,
+    seq: u64,
+    now: DateTime<Utc>,
+) -> BboUpdate {
+    let (bid, ask) = match order_book {
+        Some(order_book) => order_book.best_bid_offer().await,
+        None => (None, None),
+    };
+    BboUpdate {
+        pair: trading_pair.clone(),
+        bid: bid.as_ref().map(|level| level.price),
+        bid_qty: bid.map_or(0.0, |level| level.total_quantity),
+        ask: ask.as_ref().map(|level| level.price),
+        ask_qty: ask.map_or(0.0, |level| level.total_quantity),
+        seq,
+        timestamp: now,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OrderAck {
+    /// `book_seq` is the book's diff sequence once the order rests.
+    Accepted { order_id: u64, book_seq: u64 },
+    Rejected {
+        order_id: u64,
+        reason: OrderBookError,
+    },
+}
+
+/// Why a request that spans several books failed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EngineError {
+    Conversion(ConversionError),
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EngineError::Conversion(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for EngineError {}
+
+impl From<ConversionError> for EngineError {
+    fn from(e: ConversionError) -> Self {
+        EngineError::Conversion(e)
+    }
+}
+
+/// How often every book's allocations are checked for a leaking index.
+const ALLOCATION_CHECK_INTERVAL: Duration = Duration::from_secs(300);
+
+/// How often debug builds diagnose the engine of their own accord.
+#[cfg(debug_assertions)]
+const DIAGNOSTIC_INTERVAL: Duration = Duration::from_secs(60);
+
+/// What `Engine::diagnose` found across every book.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DiagnosticReport {
+    /// Each prefixed with the pair it was found on.
+    pub inconsistencies: Vec<String>,
+    /// Books whose best bid is at or above their best ask. Expected between
+    /// matches when `auto_match` is off.
+    pub crossed_books: Vec<TradingPair>,
+    pub orphaned_order_ids: Vec<u64>,
+    pub total_pairs: usize,
+    pub total_orders: usize,
+}
+
+impl DiagnosticReport {
+    /// No inconsistencies or orphaned orders. Crossed books do not count, as
+    /// they are not corruption.
+    pub fn is_healthy(&self) -> bool {
+        self.inconsistencies.is_empty() && self.orphaned_order_ids.is_empty()
+    }
+}
+
 pub struct Engine {
-    order_books: HashMap<TradingPair, Box<dyn OrderBook>>,
+    config: EngineConfig,
+    order_books: Arc<DashMap<TradingPair, SharedOrderBook>>,
     order_book_factory: Box<dyn Fn(TradingPair) -> Box<dyn OrderBook> + Send + Sync>,
+    pair_channels: HashMap<TradingPair, broadcast::Sender<MarketEvent>>,
+    bbo_channels: HashMap<TradingPair, BboChannel>,
+    vpin_calculators: HashMap<TradingPair, VpinCalculator>,
+    trade_aggregator: TradeAggregator,
+    last_activity: HashMap<TradingPair, DateTime<Utc>>,
+    mark_prices: HashMap<TradingPair, f64>,
+    halted_pairs: HashSet<TradingPair>,
+    market_events: broadcast::Sender<MarketEvent>,
+    all_trades: broadcast::Sender<Trade>,
+    risk_manager: Option<Box<dyn RiskManager>>,
+    // Checked ahead of, and independently of, `risk_manager`.
+    trading_hours: TradingHoursManager,
+    account_manager: Option<AccountManager>,
+    default_fee_model: Arc<dyn FeeModel>,
+    reference_data: SharedReferenceData,
+    pair_fee_overrides: HashMap<TradingPair, Arc<dyn FeeModel>>,
+    metrics: Arc<dyn EngineMetricsInterface>,
+    quote_manager: MarketMakerQuoteManager,
+    position_tracker: PositionTracker,
+    clock: Arc<dyn Clock>,
+    // Weak so that execution algorithms can feed orders back in without
+    // keeping the channel open after every external sender is gone.
+    engine_tx: Option<mpsc::WeakSender<Message>>,
 }
 
 impl Engine {
@@ -26,112 +650,2379 @@ impl Engine {
     where
         F: Fn(TradingPair) -> Box<dyn OrderBook> + Send + Sync + 'static,
     {
-        tracing_subscriber::fmt()
-            .with_target(false)
-            .with_thread_ids(true)
-            .with_level(true)
-            .with_file(true)
-            .with_line_number(true)
-            .with_env_filter("info")
-            .init();
+        Engine::with_config(EngineConfig::default(), order_book_factory)
+    }
+
+    pub fn with_config<F>(config: EngineConfig, order_book_factory: F) -> Self
+    where
+        F: Fn(TradingPair) -> Box<dyn OrderBook> + Send + Sync + 'static,
+    {
+        init_tracing(&config.log_level);
+        let all_trades = broadcast::channel(config.all_trades_channel_capacity.max(1)).0;
+        let metrics: Arc<dyn EngineMetricsInterface> = if config.benchmark_mode {
+            Arc::new(NoopEngineMetrics)
+        } else {
+            let metrics = EngineMetrics::new();
+            // Engines built outside a runtime, e.g. for offline replay, go
+            // without the uptime gauge.
+            if tokio::runtime::Handle::try_current().is_ok() {
+                metrics.spawn_uptime_updates(Instant::now());
+            }
+            Arc::new(metrics)
+        };
 
         Engine {
-            order_books: HashMap::new(),
+            config,
+            order_books: Arc::new(DashMap::new()),
             order_book_factory: Box::new(order_book_factory),
+            pair_channels: HashMap::new(),
+            bbo_channels: HashMap::new(),
+            vpin_calculators: HashMap::new(),
+            trade_aggregator: TradeAggregator::new(),
+            last_activity: HashMap::new(),
+            mark_prices: HashMap::new(),
+            halted_pairs: HashSet::new(),
+            market_events: broadcast::channel(MARKET_EVENT_CHANNEL_CAPACITY).0,
+            all_trades,
+            risk_manager: None,
+            trading_hours: TradingHoursManager::new(),
+            account_manager: None,
+            default_fee_model: Arc::new(ZeroFeeModel),
+            reference_data: SharedReferenceData::default(),
+            pair_fee_overrides: HashMap::new(),
+            metrics,
+            quote_manager: MarketMakerQuoteManager::new(),
+            position_tracker: PositionTracker::new(),
+            clock: Arc::new(SystemClock),
+            engine_tx: None,
         }
     }
 
-    async fn process_get_price(
-        &mut self,
-        trading_pair: TradingPair,
-        response_tx: mpsc::Sender<Option<f64>>,
-    ) {
-        info!("Processing get_price request for {:?}", trading_pair);
+    /// Rebuilds an engine from a file written by `save_state`. Order books are
+    /// created through `order_book_factory` and repopulated before returning.
+    #[cfg(feature = "serde")]
+    pub async fn load_state<F>(path: PathBuf, order_book_factory: F) -> io::Result<Self>
+    where
+        F: Fn(TradingPair) -> Box<dyn OrderBook> + Send + Sync + 'static,
+    {
+        let state = EngineState::read_from(&path)?;
+        let engine = Engine::with_config(state.config, order_book_factory);
 
-        let price = if let Some(order_book) = self.order_books.get(&trading_pair) {
-            info!("Found existing order book");
-            let price = order_book.get_current_price().await;
-            info!("Got price from existing order book: {:?}", price);
-            price
-        } else {
-            info!("Creating new order book");
-            let order_book = (self.order_book_factory)(trading_pair.clone());
-            let price = order_book.get_current_price().await;
-            info!("Got price from new order book: {:?}", price);
-            self.order_books.insert(trading_pair, order_book);
-            price
+        for snapshot in state.order_books {
+            let trading_pair = snapshot.trading_pair.clone();
+            let order_book = engine.get_or_create_order_book(&trading_pair);
+            let order_book = order_book.write().await;
+            order_book.restore(snapshot).await;
+            engine
+                .metrics
+                .increment_active_orders(&trading_pair, order_book.get_active_orders_count().await);
+        }
+        crate::engine::algorithms::restore_child_order_id(state.next_child_order_id);
+
+        info!("Loaded engine state from {:?}", path);
+        Ok(engine)
+    }
+
+    /// Writes every order book and the engine configuration to `path`.
+    #[cfg(feature = "serde")]
+    pub async fn save_state(&self, path: &Path) -> io::Result<()> {
+        EngineState::new(
+            self.config.clone(),
+            self.snapshots().await,
+            crate::engine::algorithms::peek_child_order_id(),
+        )
+        .write_to(path, self.config.serialization_format)
+    }
+
+    /// Saves a snapshot of every order book to `backend`.
+    pub async fn save_snapshots(&self, backend: &dyn PersistenceBackend) -> io::Result<()> {
+        for snapshot in self.snapshots().await {
+            backend.save_snapshot(snapshot)?;
+        }
+        Ok(())
+    }
+
+    /// Restores the pair's book from `backend`, adding to whatever it holds
+    /// already. `false` if the backend has no snapshot for the pair.
+    pub async fn restore_snapshot(
+        &self,
+        backend: &dyn PersistenceBackend,
+        trading_pair: &TradingPair,
+    ) -> io::Result<bool> {
+        let Some(snapshot) = backend.load_snapshot(trading_pair)? else {
+            return Ok(false);
         };
+        let order_book = self.get_or_create_order_book(trading_pair);
+        let order_book = order_book.write().await;
+        let active_before = order_book.get_active_orders_count().await;
+        order_book.restore(snapshot).await;
+        let restored = order_book.get_active_orders_count().await - active_before;
+        self.metrics.increment_active_orders(trading_pair, restored);
+        Ok(true)
+    }
+
+    async fn snapshots(&self) -> Vec<OrderBookSnapshot> {
+        // Collect the handles first so no DashMap shard lock is held across
+        // an await.
+        let order_books: Vec<(TradingPair, SharedOrderBook)> = self
+            .order_books
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect();
 
-        if let Err(e) = response_tx.send(price).await {
-            eprintln!("Failed to send price response: {}", e);
+        let mut snapshots = Vec::with_capacity(order_books.len());
+        for (trading_pair, order_book) in order_books {
+            let order_book = order_book.read().await;
+            snapshots.push(OrderBookSnapshot {
+                trading_pair,
+                orders: order_book.get_active_orders().await,
+                trades: order_book.get_trade_history().await,
+            });
         }
+        snapshots
     }
 
-    async fn process_get_order_book(
+    /// Writes the pair's book to `path` as CSV, one row per price level; see
+    /// `export::write_order_book_csv`. A pair without a book gets a file
+    /// with only the header and separator rows.
+    #[cfg(feature = "export")]
+    pub async fn export_orderbook_csv(&self, pair: &TradingPair, path: &Path) -> io::Result<()> {
+        let orders = match self.get_order_book(pair) {
+            Some(order_book) => order_book.read().await.get_active_orders().await,
+            None => vec![],
+        };
+        let file = std::fs::File::create(path)?;
+        crate::engine::export::write_order_book_csv(&orders, io::BufWriter::new(file))?;
+        Ok(())
+    }
+
+    /// Replaces the engine's metrics, e.g. with a `MockEngineMetrics` that
+    /// records calls for a test to check.
+    pub fn with_metrics(mut self, metrics: Arc<dyn EngineMetricsInterface>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Reads the time from `clock` instead of the system clock, e.g. a
+    /// `SimulatedClock` driven by a `ReplayEngine`.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        for entry in self.order_books.iter() {
+            // Nothing else holds the books while the engine is being built.
+            if let Ok(order_book) = entry.value().try_read() {
+                order_book.set_clock(clock.clone());
+            }
+        }
+        self.clock = clock;
+        self
+    }
+
+    /// Registers `hook` to run after every trade that changes a client's
+    /// position; see `PositionTracker`.
+    pub fn with_position_tracker_hook(mut self, hook: Arc<dyn PositionChangeHook>) -> Self {
+        self.position_tracker.add_hook(hook);
+        self
+    }
+
+    /// The engine's instrument specs, which later loads update in place.
+    pub fn reference_data(&self) -> SharedReferenceData {
+        self.reference_data.clone()
+    }
+
+    pub fn metrics(&self) -> &dyn EngineMetricsInterface {
+        self.metrics.as_ref()
+    }
+
+    /// The pair's order book, for callers embedding the engine that want to
+    /// read it under a read lock instead of through a message round trip.
+    /// Writes must still go through the message channel so they stay
+    /// ordered with everything else. `None` until the book is created; a
+    /// handle to a book later dropped as idle no longer sees new orders.
+    pub fn get_order_book_handle(&self, trading_pair: &TradingPair) -> Option<SharedOrderBook> {
+        self.get_order_book(trading_pair)
+    }
+
+    /// Adds `order` with the same checks as `Message::NewOrder`, then
+    /// matches the pair if `auto_match` is set, returning the trades. For
+    /// embedding the engine in synchronous code: each call blocks the
+    /// thread until it is done, with no message channel involved. Without
+    /// a channel, post-match price updates are not fed back as marks.
+    pub fn process_order_synchronously(
         &mut self,
-        trading_pair: TradingPair,
-        response_tx: mpsc::Sender<(Vec<OrderBookEntry>, Vec<OrderBookEntry>)>,
-    ) {
-        if let Some(order_book) = self.order_books.get(&trading_pair) {
-            let (bids, asks) = order_book.get_order_book().await;
-            let _ = response_tx.send((bids, asks)).await;
+        order: Order,
+    ) -> Result<Vec<Trade>, OrderBookError> {
+        let trading_pair = order.trading_pair.clone();
+        futures::executor::block_on(async {
+            self.process_new_order(order).await?;
+            if !self.config.auto_match {
+                return Ok(Vec::new());
+            }
+            let (match_tx, mut match_rx) = mpsc::channel(1);
+            self.process_match_orders(trading_pair, match_tx).await;
+            Ok(match_rx.try_recv().unwrap_or_default())
+        })
+    }
+
+    /// `Message::CancelOrder` without the channel; see
+    /// `process_order_synchronously`.
+    pub fn cancel_order_synchronously(
+        &mut self,
+        trading_pair: &TradingPair,
+        order_id: u64,
+    ) -> Option<Order> {
+        futures::executor::block_on(self.cancel_order(trading_pair, order_id))
+    }
+
+    /// `Message::GetPrice` without the channel; see
+    /// `process_order_synchronously`.
+    pub fn get_price_synchronously(&mut self, trading_pair: &TradingPair) -> Option<f64> {
+        let order_book = self.get_or_create_order_book(trading_pair);
+        futures::executor::block_on(async { order_book.read().await.get_current_price().await })
+    }
+
+    /// Write-locks the pair's order book for a multi-step change that no
+    /// other message may interleave with; see `OrderBookGuard`. Waits for
+    /// readers holding a handle from `get_order_book_handle`. `None` if the
+    /// pair has no book.
+    pub async fn lock_order_book<'a>(
+        &'a mut self,
+        trading_pair: &TradingPair,
+    ) -> Option<OrderBookGuard<'a>> {
+        let order_book = self.get_order_book(trading_pair)?.write_owned().await;
+        Some(OrderBookGuard {
+            order_book,
+            _engine: PhantomData,
+        })
+    }
+
+    /// Joins two running engines so orders, cancels and matches for `pairs`
+    /// sent through either front door of the returned link also reach the
+    /// other engine; see `EngineLink`.
+    pub fn connect_engines(
+        engine_a_tx: mpsc::Sender<Message>,
+        engine_b_tx: mpsc::Sender<Message>,
+        pairs: Vec<TradingPair>,
+    ) -> EngineLink {
+        link::connect(engine_a_tx, engine_b_tx, pairs)
+    }
+
+    pub fn quote_manager(&self) -> &MarketMakerQuoteManager {
+        &self.quote_manager
+    }
+
+    /// Records the shutdown metrics. With `close_books_on_shutdown`, first
+    /// runs a final match on every order book and cancels the orders still
+    /// resting, since nothing will match them once the engine stops.
+    /// Called when the engine receives `Message::Shutdown` or finishes a
+    /// `Message::Drain`.
+    pub async fn shutdown(&mut self) {
+        let start = Instant::now();
+        let trading_pairs: Vec<TradingPair> = if self.config.close_books_on_shutdown {
+            self.order_books
+                .iter()
+                .map(|entry| entry.key().clone())
+                .collect()
         } else {
-            let _ = response_tx.send((vec![], vec![])).await;
+            Vec::new()
+        };
+
+        let mut trades = 0;
+        let mut cancelled_orders = 0;
+        for trading_pair in trading_pairs {
+            let (match_tx, mut match_rx) = mpsc::channel(1);
+            self.process_match_orders(trading_pair.clone(), match_tx)
+                .await;
+            trades += match_rx.recv().await.map_or(0, |trades| trades.len());
+
+            let Some(order_book) = self.get_order_book(&trading_pair) else {
+                continue;
+            };
+            let order_book = order_book.write().await;
+            for order in order_book.get_active_orders().await {
+                if order_book.cancel_order(order.id).await.is_none() {
+                    continue;
+                }
+                cancelled_orders += 1;
+                self.metrics.record_order_cancelled();
+                self.metrics.decrement_active_orders(&trading_pair, 1);
+                if let Some(account_manager) = &mut self.account_manager {
+                    account_manager.release_order(order.id);
+                }
+            }
+            self.publish_book_diff(&trading_pair, order_book.as_ref())
+                .await;
+            self.publish_bbo(&trading_pair, order_book.as_ref()).await;
         }
+
+        let duration = start.elapsed();
+        self.metrics.record_shutdown_duration(duration);
+        self.metrics.record_shutdown_trades(trades);
+        self.metrics
+            .record_shutdown_cancelled_orders(cancelled_orders);
+        info!(
+            duration_ms = duration.as_millis() as u64,
+            trades, cancelled_orders, "Engine shut down"
+        );
+        info!(metrics = ?self.metrics.snapshot(), "Final engine metrics");
     }
 
-    async fn process_get_trade_history(
+    fn get_order_book(&self, trading_pair: &TradingPair) -> Option<SharedOrderBook> {
+        self.order_books
+            .get(trading_pair)
+            .map(|entry| entry.value().clone())
+    }
+
+    fn get_or_create_order_book(&self, trading_pair: &TradingPair) -> SharedOrderBook {
+        let order_book = self
+            .order_books
+            .entry(trading_pair.clone())
+            .or_insert_with(|| {
+                info!("Creating new order book for {:?}", trading_pair);
+                let order_book = (self.order_book_factory)(trading_pair.clone());
+                order_book.set_fee_model(self.fee_model_for(trading_pair));
+                order_book.set_clock(self.clock.clone());
+                order_book.set_reference_data(self.reference_data.clone());
+                order_book.set_delta_retention(self.config.delta_retention_count);
+                if let Some(engine_tx) = self.engine_tx.clone() {
+                    order_book.set_price_update_callback(price_update_callback(engine_tx));
+                }
+                Arc::new(RwLock::new(order_book))
+            })
+            .value()
+            .clone();
+        self.metrics.set_order_books(self.order_books.len() as u64);
+        order_book
+    }
+
+    fn fee_model_for(&self, trading_pair: &TradingPair) -> Arc<dyn FeeModel> {
+        self.pair_fee_overrides
+            .get(trading_pair)
+            .unwrap_or(&self.default_fee_model)
+            .clone()
+    }
+
+    async fn process_set_fee_model(
         &mut self,
-        trading_pair: TradingPair,
-        response_tx: mpsc::Sender<Vec<Trade>>,
+        trading_pair: Option<TradingPair>,
+        fee_model: Arc<dyn FeeModel>,
     ) {
-        if let Some(order_book) = self.order_books.get(&trading_pair) {
-            let trades = order_book.get_trade_history().await;
-            let _ = response_tx.send(trades).await;
+        match trading_pair {
+            Some(trading_pair) => {
+                info!("Installed fee model override for {:?}", trading_pair);
+                self.pair_fee_overrides.insert(trading_pair, fee_model);
+            }
+            None => {
+                info!("Installed default fee model");
+                self.default_fee_model = fee_model;
+            }
+        }
+
+        let order_books: Vec<(TradingPair, SharedOrderBook)> = self
+            .order_books
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect();
+        for (trading_pair, order_book) in order_books {
+            order_book
+                .read()
+                .await
+                .set_fee_model(self.fee_model_for(&trading_pair));
+        }
+    }
+
+    async fn process_broadcast(&mut self, payload: BroadcastPayload) -> usize {
+        let trading_pairs: Vec<TradingPair> = self
+            .order_books
+            .iter()
+            .map(|entry| entry.key().clone())
+            .collect();
+        match payload {
+            BroadcastPayload::UpdateFeeModel(fee_model) => {
+                for trading_pair in &trading_pairs {
+                    self.pair_fee_overrides
+                        .insert(trading_pair.clone(), fee_model.clone());
+                    if let Some(order_book) = self.get_order_book(trading_pair) {
+                        order_book.read().await.set_fee_model(fee_model.clone());
+                    }
+                }
+                info!("Installed fee model for {} pairs", trading_pairs.len());
+                trading_pairs.len()
+            }
+            BroadcastPayload::SetTradingHours(sessions) => {
+                for trading_pair in &trading_pairs {
+                    self.trading_hours
+                        .register(trading_pair.clone(), sessions.clone());
+                }
+                info!("Registered trading hours for {} pairs", trading_pairs.len());
+                trading_pairs.len()
+            }
+            BroadcastPayload::HaltAllTrading => {
+                let halted = trading_pairs
+                    .into_iter()
+                    .filter(|trading_pair| self.halted_pairs.insert(trading_pair.clone()))
+                    .count();
+                warn!("Halted trading for {} pairs", halted);
+                halted
+            }
+            BroadcastPayload::ResumeAllTrading => {
+                let resumed = self.halted_pairs.drain().count();
+                info!("Resumed trading for {} pairs", resumed);
+                resumed
+            }
+        }
+    }
+
+    /// Runs `OrderBook::check_book_integrity` on every book, one at a time,
+    /// and notes which are crossed.
+    pub async fn diagnose(&self) -> DiagnosticReport {
+        let mut order_books: Vec<(TradingPair, SharedOrderBook)> = self
+            .order_books
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect();
+        order_books.sort_by(|a, b| (&a.0.base, &a.0.quote).cmp(&(&b.0.base, &b.0.quote)));
+
+        let mut report = DiagnosticReport {
+            total_pairs: order_books.len(),
+            ..DiagnosticReport::default()
+        };
+        for (trading_pair, order_book) in order_books {
+            let order_book = order_book.read().await;
+            let integrity = order_book.check_book_integrity().await;
+            report.inconsistencies.extend(
+                integrity
+                    .inconsistencies
+                    .into_iter()
+                    .map(|problem| format!("{}: {}", trading_pair, problem)),
+            );
+            report
+                .orphaned_order_ids
+                .extend(integrity.orphaned_order_ids);
+            if let (Some(bid), Some(ask)) = order_book.best_bid_offer().await {
+                if bid.price >= ask.price {
+                    report.crossed_books.push(trading_pair.clone());
+                }
+            }
+            report.total_orders += order_book.get_active_orders_count().await;
+        }
+        report
+    }
+
+    /// Read-only queries run on their own task when `concurrent_books` is
+    /// set, so a slow reader only holds its book's read lock.
+    async fn dispatch_read<F>(&self, read: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        if self.config.concurrent_books {
+            tokio::spawn(read);
         } else {
-            let _ = response_tx.send(vec![]).await;
+            read.await;
         }
     }
 
-    pub async fn run(&mut self, mut rx: mpsc::Receiver<Message>) {
-        info!("Starting engine.");
-        while let Some(message) = rx.recv().await {
-            match message {
-                Message::NewOrder(order) => {
-                    let order_book = self
-                        .order_books
-                        .entry(order.trading_pair.clone())
-                        .or_insert_with(|| (self.order_book_factory)(order.trading_pair.clone()));
-                    order_book.add_order(order).await;
-                }
-                Message::GetPrice(trading_pair, response_tx) => {
-                    self.process_get_price(trading_pair, response_tx).await;
-                }
-                Message::GetOrderBook(trading_pair, response_tx) => {
-                    self.process_get_order_book(trading_pair, response_tx).await;
-                }
-                Message::GetTradeHistory(trading_pair, response_tx) => {
-                    self.process_get_trade_history(trading_pair, response_tx)
-                        .await;
-                }
-                Message::Shutdown => {
-                    info!("Received shutdown signal.");
-                    break;
+    /// Runs the pre-trade checks and adds the order, returning the book's
+    /// diff sequence after the add, or zero for books without diffs.
+    async fn process_new_order(&mut self, order: Order) -> Result<u64, OrderBookError> {
+        if let OrderType::Convert {
+            quantity,
+            from_asset,
+            to_asset,
+        } = order.order_type
+        {
+            // Boxed, as the conversion places its legs through this method.
+            return Box::pin(self.process_convert_currency(from_asset, quantity, to_asset))
+                .await
+                .map(|_| 0)
+                .map_err(|e| OrderBookError::Conversion(Box::new(e)));
+        }
+        let order_id = order.id;
+        let trading_pair = order.trading_pair.clone();
+        // Held stop orders count as active once they trigger and rest.
+        let rests = order.stop_price.is_none();
+        self.metrics.increment_orders_submitted(&trading_pair);
+        let result = self.try_add_order(order).await;
+        match &result {
+            Ok(_) => {
+                self.metrics.record_order_accepted();
+                if rests {
+                    self.metrics.increment_active_orders(&trading_pair, 1);
                 }
             }
+            Err(e) => {
+                warn!("Rejecting order {}: {}", order_id, e);
+                self.metrics.record_order_rejected();
+            }
         }
-        info!("Engine stopped.");
+        result
     }
-}
 
-pub fn start_engine<F>(order_book_factory: F) -> mpsc::Sender<Message>
-where
-    F: Fn(TradingPair) -> Box<dyn OrderBook> + Send + Sync + 'static,
-{
-    let (tx, rx) = mpsc::channel(100);
+    async fn try_add_order(&mut self, mut order: Order) -> Result<u64, OrderBookError> {
+        self.check_order_fields(&order)?;
+        self.evict_idle_books().await;
+        self.last_activity
+            .insert(order.trading_pair.clone(), self.clock.now());
+        let order_book = self.get_or_create_order_book(&order.trading_pair);
+        let mut order_book = order_book.write().await;
+        self.check_order_against_book(&order, order_book.as_ref())
+            .await?;
+        if let Some(window) = self.config.dedup_window_seconds {
+            order_book
+                .register_submission(
+                    &order,
+                    Duration::from_secs(window),
+                    self.config.dedup_capacity,
+                )
+                .await?;
+        }
+        if let Some(account_manager) = &mut self.account_manager {
+            if let Err(e) = account_manager.lock_for_order(&order) {
+                // Rejected, so a corrected retry must not count as a duplicate.
+                if self.config.dedup_window_seconds.is_some() {
+                    order_book.forget_submission(&order).await;
+                }
+                return Err(OrderBookError::Account(e));
+            }
+        }
+        let trading_pair = order.trading_pair.clone();
+        order.received_at = Some(self.clock.now());
+        if let Err(e) = order_book.add_order(order.clone()).await {
+            if let Some(account_manager) = &mut self.account_manager {
+                account_manager.release_order(order.id);
+            }
+            if self.config.dedup_window_seconds.is_some() {
+                order_book.forget_submission(&order).await;
+            }
+            return Err(e);
+        }
+        let rebalance_threshold = self
+            .reference_data
+            .read()
+            .get(&trading_pair)
+            .and_then(|info| info.rebalance_threshold);
+        if let Some(threshold) = rebalance_threshold {
+            if order_book.empty_level_ratio().await > threshold {
+                let removed = order_book.rebalance_price_levels();
+                info!(
+                    "Removed {} empty price levels from {:?}",
+                    removed, trading_pair
+                );
+            }
+        }
+        self.record_book_depth(&trading_pair, order_book.as_ref())
+            .await;
+        let book_seq = self
+            .publish_book_diff(&trading_pair, order_book.as_ref())
+            .await;
+        self.publish_bbo(&trading_pair, order_book.as_ref()).await;
+        Ok(book_seq.unwrap_or(0))
+    }
+
+    /// The checks of `try_add_order` that need no order book.
+    fn check_order_fields(&self, order: &Order) -> Result<(), OrderBookError> {
+        if self.halted_pairs.contains(&order.trading_pair) {
+            return Err(OrderBookError::TradingHalted(order.trading_pair.clone()));
+        }
+        if let Some(e) = order.validate().into_iter().next() {
+            return Err(OrderBookError::Invalid(e));
+        }
+        if let Some(info) = self.reference_data.read().get(&order.trading_pair) {
+            if let Some(e) = TradingPair::validate_order(order, info).into_iter().next() {
+                return Err(OrderBookError::Invalid(e));
+            }
+        }
+        if !self
+            .trading_hours
+            .is_open(&order.trading_pair, self.clock.now())
+        {
+            return Err(OrderBookError::Risk(RiskError::MarketClosed(
+                order.trading_pair.clone(),
+            )));
+        }
+        if let Some(risk_manager) = &self.risk_manager {
+            risk_manager
+                .check_order(order)
+                .map_err(OrderBookError::Risk)?;
+        }
+        Ok(())
+    }
+
+    /// The checks of `try_add_order` against the book the order would rest
+    /// in. Like `check_order_fields`, they change nothing, so several orders
+    /// can be vetted before any is added.
+    async fn check_order_against_book(
+        &self,
+        order: &Order,
+        order_book: &dyn OrderBook,
+    ) -> Result<(), OrderBookError> {
+        if order.stop_price.is_some() && !order_book.supports_stop_orders() {
+            return Err(OrderBookError::StopOrdersUnsupported);
+        }
+        if order_book.is_price_level_locked(order.price.value()) {
+            return Err(OrderBookError::PriceLevelLocked(order.price.value()));
+        }
+
+        if let Some(limit) = self.config.max_memory_per_book {
+            let used = order_book.estimated_memory_bytes().await;
+            if used >= limit {
+                return Err(OrderBookError::BookFull { used, limit });
+            }
+        }
+        let side_limit = self
+            .reference_data
+            .read()
+            .get(&order.trading_pair)
+            .and_then(|info| info.max_orders_per_side)
+            .or(self.config.default_max_orders_per_side);
+        if let Some(limit) = side_limit {
+            let side = order.order_type.clone();
+            if order_book.get_side_orders_count(side.clone()).await >= limit {
+                return Err(OrderBookError::SideFull { side, limit });
+            }
+        }
+        Ok(())
+    }
+
+    fn publish_to_pair(&self, trading_pair: &TradingPair, event: MarketEvent) {
+        if let Some(pair_tx) = self.pair_channels.get(trading_pair) {
+            let _ = pair_tx.send(event);
+        }
+    }
+
+    async fn record_book_depth(&self, trading_pair: &TradingPair, order_book: &dyn OrderBook) {
+        self.metrics
+            .set_book_depth(trading_pair, "bid", order_book.bid_level_count().await);
+        self.metrics
+            .set_book_depth(trading_pair, "ask", order_book.ask_level_count().await);
+    }
+
+    /// Drains the book's pending diff even when nobody is subscribed, so a
+    /// later subscriber only sees changes made after it joined.
+    async fn publish_book_diff(
+        &self,
+        trading_pair: &TradingPair,
+        order_book: &dyn OrderBook,
+    ) -> Option<u64> {
+        let diff = order_book.take_diff().await?;
+        let seq = diff.seq;
+        self.publish_to_pair(trading_pair, MarketEvent::BookDiff(diff));
+        Some(seq)
+    }
+
+    /// Publishes the book's top of book if it moved since the last update.
+    /// Skipped for pairs nobody has subscribed to, though the spread gauge
+    /// is updated either way.
+    async fn publish_bbo(&mut self, trading_pair: &TradingPair, order_book: &dyn OrderBook) {
+        if let (Some(bid), Some(ask)) = order_book.best_bid_offer().await {
+            self.metrics.set_spread(trading_pair, ask.price - bid.price);
+        }
+        let Some(channel) = self.bbo_channels.get_mut(trading_pair) else {
+            return;
+        };
+        if channel.sender.receiver_count() == 0 {
+            return;
+        }
+
+        let seq = channel.last.as_ref().map_or(1, |last| last.seq + 1);
+        let update = bbo_snapshot(trading_pair, Some(order_book), seq, self.clock.now()).await;
+        if channel
+            .last
+            .as_ref()
+            .is_some_and(|last| last.same_quote(&update))
+        {
+            return;
+        }
+        let _ = channel.sender.send(update.clone());
+        channel.last = Some(update);
+    }
+
+    async fn process_price_update(&mut self, update: PriceUpdate) {
+        info!(
+            price = update.price,
+            source = %update.source,
+            "Price update for {:?}", update.trading_pair
+        );
+        // Not a usable mark, and as a reference it would make every move
+        // infinite or NaN.
+        if !(update.price.is_finite() && update.price > 0.0) {
+            warn!(
+                price = update.price,
+                "Ignoring price update for {:?}", update.trading_pair
+            );
+            return;
+        }
+        let previous = self
+            .mark_prices
+            .insert(update.trading_pair.clone(), update.price);
+
+        if let (Some(threshold), Some(reference_price)) = (
+            self.config.circuit_breaker_pct,
+            previous.filter(|price| *price > 0.0),
+        ) {
+            let move_pct = (update.price - reference_price).abs() / reference_price * 100.0;
+            if move_pct > threshold && self.halted_pairs.insert(update.trading_pair.clone()) {
+                warn!(
+                    move_pct,
+                    threshold, "Circuit breaker tripped for {:?}", update.trading_pair
+                );
+                let _ = self.market_events.send(MarketEvent::CircuitBreakerTripped {
+                    trading_pair: update.trading_pair.clone(),
+                    reference_price,
+                    price: update.price,
+                });
+            }
+        }
+
+        if let Some(account_manager) = &self.account_manager {
+            for client_id in
+                account_manager.liquidation_candidates(&update.trading_pair, update.price)
+            {
+                warn!(
+                    mark_price = update.price,
+                    "Position of {} in {:?} is below maintenance margin",
+                    client_id,
+                    update.trading_pair
+                );
+                let _ = self.market_events.send(MarketEvent::LiquidationTriggered {
+                    client_id,
+                    trading_pair: update.trading_pair.clone(),
+                    mark_price: update.price,
+                });
+            }
+        }
+
+        if let Some(order_book) = self.get_order_book(&update.trading_pair) {
+            let order_book = order_book.read().await;
+            let triggered = order_book.trigger_stop_orders(update.price).await;
+            if triggered > 0 {
+                self.metrics
+                    .increment_active_orders(&update.trading_pair, triggered);
+                info!(
+                    "Triggered {} stop orders in {:?}",
+                    triggered, update.trading_pair
+                );
+                self.publish_book_diff(&update.trading_pair, order_book.as_ref())
+                    .await;
+                self.publish_bbo(&update.trading_pair, order_book.as_ref())
+                    .await;
+                drop(order_book);
+                // Triggered stops rest like new orders, so match them as one.
+                self.auto_match(update.trading_pair.clone()).await;
+            }
+        }
+
+        let _ = self
+            .market_events
+            .send(MarketEvent::MarkPriceUpdate(update));
+    }
+
+    /// Orders move in time priority within each side of the source, and
+    /// queue behind the target's orders at the same price; any at a price
+    /// locked in the target are dropped. Subscribers to
+    /// the source see their channels close. A halted source is left alone,
+    /// so the halt cannot be sidestepped by merging.
+    async fn merge_trading_pairs(&mut self, source: TradingPair, target: TradingPair) -> usize {
+        if source == target {
+            return 0;
+        }
+        if self.halted_pairs.contains(&source) {
+            warn!("Not merging {:?} while trading on it is halted", source);
+            return 0;
+        }
+        let Some((_, source_book)) = self.order_books.remove(&source) else {
+            return 0;
+        };
+        self.vpin_calculators.remove(&source);
+        self.last_activity.remove(&source);
+        self.pair_channels.remove(&source);
+        self.bbo_channels.remove(&source);
+        self.pair_fee_overrides.remove(&source);
+        self.mark_prices.remove(&source);
+
+        let (orders, resting) = {
+            let source_book = source_book.read().await;
+            let mut orders = source_book.get_active_orders().await;
+            let resting = orders.len();
+            orders.extend(source_book.get_stop_orders().await);
+            (orders, resting)
+        };
+        let mut migrated = orders.len();
+        let mut moved_resting = resting;
+        let order_book = self.get_or_create_order_book(&target);
+        {
+            let order_book = order_book.write().await;
+            for order in orders {
+                let (order_id, is_stop) = (order.id, order.stop_price.is_some());
+                let order = Order {
+                    trading_pair: target.clone(),
+                    ..order
+                };
+                if let Err(e) = order_book.add_order(order).await {
+                    warn!("Dropping order {} from the merge: {}", order_id, e);
+                    migrated -= 1;
+                    if !is_stop {
+                        moved_resting -= 1;
+                    }
+                }
+            }
+            self.publish_book_diff(&target, order_book.as_ref()).await;
+            self.publish_bbo(&target, order_book.as_ref()).await;
+            self.record_book_depth(&target, order_book.as_ref()).await;
+        }
+        self.metrics.decrement_active_orders(&source, resting);
+        self.metrics.increment_active_orders(&target, moved_resting);
+        self.metrics.set_order_books(self.order_books.len() as u64);
+        info!(migrated, "Merged {:?} into {:?}", source, target);
+
+        if self.config.auto_match {
+            let (match_tx, _) = mpsc::channel(1);
+            self.process_match_orders(target, match_tx).await;
+        }
+        migrated
+    }
+
+    /// Drops books that have been idle past `idle_book_ttl_seconds` and hold
+    /// no resting orders, with their per-pair state. Channels that still
+    /// have subscribers stay, so those keep receiving once the pair trades
+    /// again, and so do fee overrides, which are configuration rather than
+    /// book state.
+    async fn evict_idle_books(&mut self) {
+        let Some(ttl) = self.config.idle_book_ttl_seconds else {
+            return;
+        };
+        let cutoff = self.clock.now() - chrono::Duration::seconds(ttl as i64);
+        let idle: Vec<TradingPair> = self
+            .last_activity
+            .iter()
+            .filter(|(_, last_active)| **last_active <= cutoff)
+            .map(|(trading_pair, _)| trading_pair.clone())
+            .collect();
+
+        for trading_pair in idle {
+            if let Some(order_book) = self.get_order_book(&trading_pair) {
+                let order_book = order_book.read().await;
+                if order_book.get_active_orders_count().await > 0
+                    || !order_book.get_stop_orders().await.is_empty()
+                {
+                    continue;
+                }
+            }
+            info!("Evicting idle order book for {:?}", trading_pair);
+            self.order_books.remove(&trading_pair);
+            self.vpin_calculators.remove(&trading_pair);
+            self.last_activity.remove(&trading_pair);
+            self.mark_prices.remove(&trading_pair);
+            if self
+                .pair_channels
+                .get(&trading_pair)
+                .is_some_and(|pair_tx| pair_tx.receiver_count() == 0)
+            {
+                self.pair_channels.remove(&trading_pair);
+            }
+            if self
+                .bbo_channels
+                .get(&trading_pair)
+                .is_some_and(|channel| channel.sender.receiver_count() == 0)
+            {
+                self.bbo_channels.remove(&trading_pair);
+            }
+        }
+        self.metrics.set_order_books(self.order_books.len() as u64);
+    }
+
+    async fn process_reload_config(
+        &mut self,
+        config: EngineConfig,
+        response_tx: mpsc::Sender<Result<(), ConfigError>>,
+    ) {
+        let result = self.reload_config(config);
+        if let Err(e) = &result {
+            warn!("Rejected config reload: {}", e);
+        }
+        let _ = response_tx.send(result).await;
+    }
+
+    fn reload_config(&mut self, config: EngineConfig) -> Result<(), ConfigError> {
+        self.config.check_reload(&config)?;
+
+        if config.log_level != self.config.log_level && !config.benchmark_mode {
+            let filter = EnvFilter::try_new(&config.log_level)
+                .map_err(|_| ConfigError::InvalidLogLevel(config.log_level.clone()))?;
+            match LOG_FILTER.get() {
+                Some(handle) => {
+                    let _ = handle.reload(filter);
+                }
+                None => warn!("Log level is managed by another subscriber"),
+            }
+        }
+
+        info!("Reloaded engine config: {:?}", config);
+        self.config = config;
+        Ok(())
+    }
+
+    async fn process_get_price(
+        &mut self,
+        trading_pair: TradingPair,
+        response_tx: mpsc::Sender<Option<f64>>,
+    ) {
+        info!("Processing get_price request for {:?}", trading_pair);
+        let order_book = self.get_or_create_order_book(&trading_pair);
+
+        self.dispatch_read(async move {
+            let price = order_book.read().await.get_current_price().await;
+            info!("Got price from order book: {:?}", price);
+
+            if let Err(e) = response_tx.send(price).await {
+                eprintln!("Failed to send price response: {}", e);
+            }
+        })
+        .await;
+    }
+
+    async fn process_get_order_book(
+        &mut self,
+        trading_pair: TradingPair,
+        response_tx: mpsc::Sender<(Vec<PriceLevel>, Vec<PriceLevel>)>,
+    ) {
+        let order_book = self.get_order_book(&trading_pair);
+
+        self.dispatch_read(async move {
+            let book = match order_book {
+                Some(order_book) => order_book.read().await.get_order_book().await,
+                None => (vec![], vec![]),
+            };
+            let _ = response_tx.send(book).await;
+        })
+        .await;
+    }
+
+    async fn process_get_trade_history(
+        &mut self,
+        trading_pair: TradingPair,
+        response_tx: mpsc::Sender<Vec<Trade>>,
+    ) {
+        let order_book = self.get_order_book(&trading_pair);
+
+        self.dispatch_read(async move {
+            let trades = match order_book {
+                Some(order_book) => order_book.read().await.get_trade_history().await,
+                None => vec![],
+            };
+            let _ = response_tx.send(trades).await;
+        })
+        .await;
+    }
+
+    async fn process_get_volume_since(
+        &mut self,
+        trading_pair: TradingPair,
+        since: DateTime<Utc>,
+        response_tx: mpsc::Sender<f64>,
+    ) {
+        let order_book = self.get_order_book(&trading_pair);
+
+        self.dispatch_read(async move {
+            let volume = match order_book {
+                Some(order_book) => order_book.read().await.get_volume_traded_since(since).await,
+                None => 0.0,
+            };
+            let _ = response_tx.send(volume).await;
+        })
+        .await;
+    }
+
+    async fn process_get_liquidity_within_range(
+        &mut self,
+        trading_pair: TradingPair,
+        center_price: f64,
+        range_pct: f64,
+        side: OrderType,
+        response_tx: mpsc::Sender<f64>,
+    ) {
+        let order_book = self.get_order_book(&trading_pair);
+
+        self.dispatch_read(async move {
+            let liquidity = match order_book {
+                Some(order_book) => {
+                    order_book
+                        .read()
+                        .await
+                        .get_liquidity_within_range(center_price, range_pct, side)
+                        .await
+                }
+                None => 0.0,
+            };
+            let _ = response_tx.send(liquidity).await;
+        })
+        .await;
+    }
+
+    async fn process_get_kyle_lambda(
+        &mut self,
+        trading_pair: TradingPair,
+        window: usize,
+        response_tx: mpsc::Sender<Option<f64>>,
+    ) {
+        let lambda = match self.get_order_book(&trading_pair) {
+            Some(order_book) => order_book.read().await.kyle_lambda(window).await,
+            None => None,
+        };
+        let _ = response_tx.send(lambda).await;
+    }
+
+    async fn process_get_depth_imbalance(
+        &mut self,
+        trading_pair: TradingPair,
+        level: usize,
+        response_tx: mpsc::Sender<Option<f64>>,
+    ) {
+        let imbalance = match self.get_order_book(&trading_pair) {
+            Some(order_book) => {
+                order_book
+                    .read()
+                    .await
+                    .get_depth_imbalance_at_level(level)
+                    .await
+            }
+            None => None,
+        };
+        let _ = response_tx.send(imbalance).await;
+    }
+
+    async fn process_get_effective_spread(
+        &mut self,
+        trading_pair: TradingPair,
+        trade_size: f64,
+        response_tx: mpsc::Sender<Option<f64>>,
+    ) {
+        let spread = match self.get_order_book(&trading_pair) {
+            Some(order_book) => {
+                order_book
+                    .read()
+                    .await
+                    .get_effective_spread(trade_size)
+                    .await
+            }
+            None => None,
+        };
+        let _ = response_tx.send(spread).await;
+    }
+
+    async fn process_warm_up(
+        &mut self,
+        trading_pair: TradingPair,
+        orders: Vec<Order>,
+        response_tx: mpsc::Sender<()>,
+    ) {
+        info!("Warming up {:?} with {} orders", trading_pair, orders.len());
+        let order_book = self.get_or_create_order_book(&trading_pair);
+        let order_book = order_book.write().await;
+        order_book.warm_up(orders).await;
+        self.publish_book_diff(&trading_pair, order_book.as_ref())
+            .await;
+        self.publish_bbo(&trading_pair, order_book.as_ref()).await;
+        let _ = response_tx.send(()).await;
+    }
+
+    async fn process_get_client_orders(
+        &mut self,
+        client_id: String,
+        trading_pair: TradingPair,
+        response_tx: mpsc::Sender<Vec<Order>>,
+    ) {
+        let orders = match self.get_order_book(&trading_pair) {
+            Some(order_book) => {
+                order_book
+                    .read()
+                    .await
+                    .get_active_orders_by_client(&client_id)
+                    .await
+            }
+            None => vec![],
+        };
+        let _ = response_tx.send(orders).await;
+    }
+
+    async fn process_get_client_orders_all_pairs(
+        &mut self,
+        client_id: String,
+        response_tx: mpsc::Sender<HashMap<TradingPair, Vec<Order>>>,
+    ) {
+        let order_books: Vec<(TradingPair, SharedOrderBook)> = self
+            .order_books
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect();
+
+        let mut orders_by_pair = HashMap::new();
+        for (trading_pair, order_book) in order_books {
+            let orders = order_book
+                .read()
+                .await
+                .get_active_orders_by_client(&client_id)
+                .await;
+            if !orders.is_empty() {
+                orders_by_pair.insert(trading_pair, orders);
+            }
+        }
+        let _ = response_tx.send(orders_by_pair).await;
+    }
+
+    async fn process_get_all_active_orders(
+        &mut self,
+        client_id: Option<String>,
+        response_tx: mpsc::Sender<Vec<Order>>,
+    ) {
+        let order_books: Vec<SharedOrderBook> = self
+            .order_books
+            .iter()
+            .map(|entry| entry.value().clone())
+            .collect();
+
+        let mut orders = Vec::new();
+        for order_book in order_books {
+            let order_book = order_book.read().await;
+            match &client_id {
+                Some(client_id) => {
+                    orders.extend(order_book.get_active_orders_by_client(client_id).await)
+                }
+                None => orders.extend(order_book.get_active_orders().await),
+            }
+        }
+        orders.sort_by_key(|order| order.timestamp);
+        let _ = response_tx.send(orders).await;
+    }
+
+    async fn process_get_active_order_count(
+        &mut self,
+        trading_pair: TradingPair,
+        response_tx: mpsc::Sender<usize>,
+    ) {
+        let count = match self.get_order_book(&trading_pair) {
+            Some(order_book) => order_book.read().await.get_active_orders_count().await,
+            None => 0,
+        };
+        let _ = response_tx.send(count).await;
+    }
+
+    async fn process_get_stale_orders(
+        &mut self,
+        trading_pair: TradingPair,
+        threshold: chrono::Duration,
+        response_tx: mpsc::Sender<Vec<Order>>,
+    ) {
+        let orders = match self.get_order_book(&trading_pair) {
+            Some(order_book) => order_book.read().await.get_active_orders().await,
+            None => Vec::new(),
+        };
+        let now = self.clock.now();
+        let mut stale: Vec<Order> = orders
+            .into_iter()
+            .filter(|order| order.time_in_book(now) > threshold)
+            .collect();
+        stale.sort_by_key(|order| std::cmp::Reverse(order.time_in_book(now)));
+        let _ = response_tx.send(stale).await;
+    }
+
+    /// Daily volumes come from the engine's aggregator up front; the books
+    /// are then read one at a time, off the engine task if `concurrent_books`
+    /// is set.
+    async fn process_get_all_pair_stats(
+        &mut self,
+        response_tx: mpsc::Sender<HashMap<TradingPair, PairStats>>,
+    ) {
+        let now = self.clock.now();
+        let order_books: Vec<(TradingPair, SharedOrderBook)> = self
+            .order_books
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect();
+        let order_books: Vec<(TradingPair, SharedOrderBook, f64)> = order_books
+            .into_iter()
+            .map(|(trading_pair, order_book)| {
+                let daily_volume = self.trade_aggregator.daily_stats(&trading_pair, now).volume;
+                (trading_pair, order_book, daily_volume)
+            })
+            .collect();
+
+        self.dispatch_read(async move {
+            let mut stats = HashMap::with_capacity(order_books.len());
+            for (trading_pair, order_book, daily_volume) in order_books {
+                let order_book = order_book.read().await;
+                let (bid, ask) = order_book.best_bid_offer().await;
+                let trade_count = order_book.get_trade_history_paged(0, 0).await.total_count;
+                let last_price = order_book
+                    .get_trade_history_paged(trade_count.saturating_sub(1), 1)
+                    .await
+                    .trades
+                    .last()
+                    .map(|trade| trade.price.value());
+                let pair_stats = PairStats {
+                    last_price,
+                    spread: bid.zip(ask).map(|(bid, ask)| ask.price - bid.price),
+                    daily_volume,
+                    active_orders: order_book.get_active_orders_count().await,
+                    bid_levels: order_book.bid_level_count().await,
+                    ask_levels: order_book.ask_level_count().await,
+                };
+                stats.insert(trading_pair, pair_stats);
+            }
+            let _ = response_tx.send(stats).await;
+        })
+        .await;
+    }
+
+    async fn process_get_active_order_count_all_pairs(
+        &mut self,
+        response_tx: mpsc::Sender<HashMap<TradingPair, usize>>,
+    ) {
+        let order_books: Vec<(TradingPair, SharedOrderBook)> = self
+            .order_books
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect();
+
+        let mut counts = HashMap::with_capacity(order_books.len());
+        for (trading_pair, order_book) in order_books {
+            let count = order_book.read().await.get_active_orders_count().await;
+            counts.insert(trading_pair, count);
+        }
+        let _ = response_tx.send(counts).await;
+    }
+
+    async fn process_get_vpin(
+        &mut self,
+        trading_pair: TradingPair,
+        response_tx: mpsc::Sender<Option<f64>>,
+    ) {
+        let vpin = self
+            .vpin_calculators
+            .get(&trading_pair)
+            .and_then(|calculator| calculator.vpin());
+        let _ = response_tx.send(vpin).await;
+    }
+
+    /// Matches the pair as `MatchOrders` would when `auto_match` is on.
+    async fn auto_match(&mut self, trading_pair: TradingPair) {
+        if self.config.auto_match {
+            let (match_tx, _) = mpsc::channel(1);
+            self.process_match_orders(trading_pair, match_tx).await;
+        }
+    }
+
+    async fn process_match_orders(
+        &mut self,
+        trading_pair: TradingPair,
+        response_tx: mpsc::Sender<Vec<Trade>>,
+    ) {
+        let (result, diff) = match self.get_order_book(&trading_pair) {
+            Some(order_book) => {
+                self.last_activity
+                    .insert(trading_pair.clone(), self.clock.now());
+                let order_book = order_book.write().await;
+                let result = order_book.match_orders().await;
+                if let Some(retention) = self.config.trade_history_retention_seconds {
+                    let cutoff = self.clock.now() - chrono::Duration::seconds(retention as i64);
+                    order_book.prune_trade_history(cutoff).await;
+                }
+                if let Some(retention) = self.config.fill_report_retention_seconds {
+                    let cutoff = self.clock.now() - chrono::Duration::seconds(retention as i64);
+                    order_book.prune_fill_reports(cutoff).await;
+                }
+                let diff = order_book.take_diff().await;
+                self.record_book_depth(&trading_pair, order_book.as_ref())
+                    .await;
+                self.publish_bbo(&trading_pair, order_book.as_ref()).await;
+                (result, diff)
+            }
+            None => (
+                MatchResult {
+                    no_match_reason: Some("no order book for pair".to_string()),
+                    ..MatchResult::default()
+                },
+                None,
+            ),
+        };
+        let trades = result.trades.clone();
+        if !trades.is_empty() {
+            info!("Matched {} trades for {:?}", trades.len(), trading_pair);
+        }
+        self.metrics.record_trades(trades.len() as u64);
+        if result.triggered_stops > 0 {
+            self.metrics
+                .increment_active_orders(&trading_pair, result.triggered_stops);
+        }
+        self.metrics
+            .decrement_active_orders(&trading_pair, result.fully_filled.len());
+        if let Some(reason) = &result.no_match_reason {
+            debug!("No match for {:?}: {}", trading_pair, reason);
+        }
+        if !trades.is_empty() {
+            self.vpin_calculators
+                .entry(trading_pair.clone())
+                .or_insert_with(|| VpinCalculator::new(VPIN_BUCKET_VOLUME, VPIN_BUCKETS))
+                .add_trades(&trades);
+        }
+        self.trade_aggregator.add_trades(&trades);
+        if let Some(risk_manager) = &self.risk_manager {
+            for trade in &trades {
+                risk_manager.apply_trade(trade);
+            }
+        }
+        if let Some(account_manager) = &mut self.account_manager {
+            for trade in &trades {
+                account_manager.settle_trade(trade);
+            }
+        }
+        for trade in &trades {
+            self.position_tracker.apply_trade(trade);
+        }
+        for trade in &trades {
+            debug!("Executed {}", trade);
+            self.publish_to_pair(&trading_pair, MarketEvent::Trade(trade.clone()));
+            let _ = self.all_trades.send(trade.clone());
+        }
+        if !trades.is_empty() {
+            self.publish_to_pair(&trading_pair, MarketEvent::MatchCompleted(result));
+        }
+        if let Some(diff) = diff {
+            self.publish_to_pair(&trading_pair, MarketEvent::BookDiff(diff));
+        }
+        if self.config.detect_arbitrage {
+            self.detect_arbitrage().await;
+        }
+        let _ = response_tx.send(trades).await;
+    }
+
+    async fn detect_arbitrage(&self) {
+        let order_books: Vec<(TradingPair, SharedOrderBook)> = self
+            .order_books
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect();
+        let mut guards = Vec::with_capacity(order_books.len());
+        for (trading_pair, order_book) in &order_books {
+            guards.push((trading_pair.clone(), order_book.read().await));
+        }
+        let books = guards
+            .iter()
+            .map(|(trading_pair, guard)| (trading_pair.clone(), guard.as_ref()))
+            .collect();
+
+        for opportunity in ArbitrageDetector::default().check_triangle(&books).await {
+            info!(
+                implied_profit_bps = opportunity.implied_profit_bps,
+                "Arbitrage across {:?}", opportunity.legs
+            );
+            let _ = self
+                .market_events
+                .send(MarketEvent::ArbitrageDetected(opportunity));
+        }
+    }
+
+    async fn process_subscribe_to_pair(
+        &mut self,
+        trading_pair: TradingPair,
+        response_tx: mpsc::Sender<broadcast::Receiver<MarketEvent>>,
+    ) {
+        let pair_rx = self
+            .pair_channels
+            .entry(trading_pair)
+            .or_insert_with(|| broadcast::channel(PAIR_CHANNEL_CAPACITY).0)
+            .subscribe();
+        let _ = response_tx.send(pair_rx).await;
+    }
+
+    async fn process_subscribe_to_bbo(
+        &mut self,
+        trading_pair: TradingPair,
+        response_tx: mpsc::Sender<broadcast::Receiver<BboUpdate>>,
+    ) {
+        let bbo_rx = self
+            .bbo_channels
+            .entry(trading_pair)
+            .or_insert_with(|| BboChannel {
+                sender: broadcast::channel(PAIR_CHANNEL_CAPACITY).0,
+                last: None,
+            })
+            .sender
+            .subscribe();
+        let _ = response_tx.send(bbo_rx).await;
+    }
+
+    async fn process_cancel_order(
+        &mut self,
+        trading_pair: TradingPair,
+        order_id: u64,
+        response_tx: mpsc::Sender<Option<Order>>,
+    ) {
+        let cancelled = self.cancel_order(&trading_pair, order_id).await;
+        let _ = response_tx.send(cancelled).await;
+    }
+
+    async fn cancel_order(&mut self, trading_pair: &TradingPair, order_id: u64) -> Option<Order> {
+        match self.get_order_book(trading_pair) {
+            Some(order_book) => {
+                let order_book = order_book.write().await;
+                let cancelled = order_book.cancel_order(order_id).await;
+                if let Some(order) = &cancelled {
+                    self.metrics.record_order_cancelled();
+                    if order.stop_price.is_none() {
+                        self.metrics.decrement_active_orders(trading_pair, 1);
+                    }
+                }
+                if let (Some(account_manager), Some(_)) = (&mut self.account_manager, &cancelled) {
+                    account_manager.release_order(order_id);
+                }
+                self.publish_book_diff(trading_pair, order_book.as_ref())
+                    .await;
+                self.publish_bbo(trading_pair, order_book.as_ref()).await;
+                cancelled
+            }
+            None => None,
+        }
+    }
+
+    async fn process_bust_trade(
+        &mut self,
+        trading_pair: TradingPair,
+        trade_id: u64,
+    ) -> Result<(), OrderBookError> {
+        // Settlement, locks and risk limits cannot be unwound.
+        if self.account_manager.is_some() || self.risk_manager.is_some() {
+            return Err(OrderBookError::TradeSettled(trade_id));
+        }
+        let order_book = self
+            .get_order_book(&trading_pair)
+            .ok_or(OrderBookError::TradeNotFound(trade_id))?;
+        let order_book = order_book.write().await;
+        let active_before = order_book.get_active_orders_count().await;
+        let trade = order_book.bust_trade(trade_id).await?;
+        info!(trade_id, "Trade busted.");
+        self.position_tracker.reverse_trade(&trade);
+        self.trade_aggregator.remove_trade(&trade);
+        // Buckets cannot give a trade back, so start again from the history.
+        if let Some(vpin) = self.vpin_calculators.get_mut(&trading_pair) {
+            *vpin = VpinCalculator::new(VPIN_BUCKET_VOLUME, VPIN_BUCKETS);
+            vpin.add_trades(&order_book.get_trade_history().await);
+        }
+        // Busting puts traded-out orders back on the book.
+        let reinstated = order_book.get_active_orders_count().await - active_before;
+        self.metrics
+            .increment_active_orders(&trading_pair, reinstated);
+
+        let event = MarketEvent::TradeBusted {
+            trading_pair: trading_pair.clone(),
+            trade_id,
+        };
+        self.publish_to_pair(&trading_pair, event.clone());
+        let _ = self.market_events.send(event);
+        self.publish_book_diff(&trading_pair, order_book.as_ref())
+            .await;
+        self.publish_bbo(&trading_pair, order_book.as_ref()).await;
+        self.record_book_depth(&trading_pair, order_book.as_ref())
+            .await;
+        Ok(())
+    }
+
+    /// Cancels the previous quote and places the new one within a single
+    /// message, so no other message sees one side replaced and the other
+    /// not. Both new legs are checked before the previous ones are
+    /// cancelled, so a rejected quote leaves the old one resting.
+    async fn process_update_quote(
+        &mut self,
+        trading_pair: TradingPair,
+        request: QuoteRequest,
+    ) -> Result<QuoteAck, QuoteError> {
+        let resting = match self.get_order_book(&trading_pair) {
+            Some(order_book) => {
+                order_book
+                    .read()
+                    .await
+                    .get_active_orders_by_client(&request.client_id)
+                    .await
+            }
+            None => vec![],
+        };
+        let (bid, ask) = self
+            .quote_manager
+            .prepare(&trading_pair, &request, &resting)?;
+        let previous_ids: Vec<u64> = [request.previous_bid_id, request.previous_ask_id]
+            .into_iter()
+            .flatten()
+            .collect();
+        self.check_quote_legs(&trading_pair, &previous_ids, [&bid, &ask])
+            .await
+            .map_err(QuoteError::Rejected)?;
+
+        for &order_id in &previous_ids {
+            if self.cancel_order(&trading_pair, order_id).await.is_none() {
+                return Err(QuoteError::UnknownOrder(order_id));
+            }
+        }
+        let ack = QuoteAck {
+            new_bid_id: bid.id,
+            new_ask_id: ask.id,
+        };
+        self.process_new_order(bid)
+            .await
+            .map_err(QuoteError::Rejected)?;
+        if let Err(e) = self.process_new_order(ask).await {
+            self.cancel_order(&trading_pair, ack.new_bid_id).await;
+            return Err(QuoteError::Rejected(e));
+        }
+        self.quote_manager
+            .record(request.client_id, trading_pair, ack);
+        Ok(ack)
+    }
+
+    /// Runs the checks `try_add_order` would run on each leg of a quote,
+    /// without changing anything. The side limit still counts the previous
+    /// legs, so a book at its limit rejects a replacement it would have
+    /// room for once they are cancelled.
+    async fn check_quote_legs(
+        &self,
+        trading_pair: &TradingPair,
+        previous_ids: &[u64],
+        legs: [&Order; 2],
+    ) -> Result<(), OrderBookError> {
+        for leg in legs {
+            self.check_order_fields(leg)?;
+        }
+        if let Some(order_book) = self.get_order_book(trading_pair) {
+            let order_book = order_book.read().await;
+            for leg in legs {
+                self.check_order_against_book(leg, order_book.as_ref())
+                    .await?;
+                if let Some(window) = self.config.dedup_window_seconds {
+                    if order_book
+                        .is_duplicate_submission(leg, Duration::from_secs(window))
+                        .await
+                    {
+                        return Err(OrderBookError::DuplicateSubmission(leg.fingerprint()));
+                    }
+                }
+            }
+        }
+        if let Some(account_manager) = &self.account_manager {
+            account_manager
+                .check_replacement(previous_ids, &legs)
+                .map_err(OrderBookError::Account)?;
+        }
+        Ok(())
+    }
+
+    async fn process_convert_currency(
+        &mut self,
+        from_asset: String,
+        quantity: f64,
+        to_asset: String,
+    ) -> Result<Vec<Trade>, ConversionError> {
+        let pairs: Vec<TradingPair> = self
+            .order_books
+            .iter()
+            .map(|entry| entry.key().clone())
+            .collect();
+        let routes = convert::routes(&pairs, &from_asset, &to_asset);
+        if routes.is_empty() {
+            return Err(ConversionError::NoRoute {
+                from_asset,
+                to_asset,
+            });
+        }
+
+        let mut best: Option<Vec<ConversionLeg>> = None;
+        for route in routes {
+            let Some(legs) = self.plan_conversion(&route, &from_asset, quantity).await else {
+                continue;
+            };
+            let output = |legs: &[ConversionLeg]| legs.last().map_or(0.0, |leg| leg.output);
+            if best
+                .as_ref()
+                .is_none_or(|best| output(&legs) > output(best))
+            {
+                best = Some(legs);
+            }
+        }
+        let legs = best.ok_or(ConversionError::InsufficientLiquidity)?;
+
+        // Two phases: every leg rests and is checked against its book before
+        // any is matched, so a rejected or short leg leaves nothing to unwind
+        // but the orders already placed.
+        let mut placed: Vec<Order> = Vec::with_capacity(legs.len());
+        let mut failure = None;
+        for leg in &legs {
+            let order = leg.order();
+            if let Err(e) = self.process_new_order(order.clone()).await {
+                failure = Some(ConversionError::Rejected(e));
+                break;
+            }
+            placed.push(order);
+        }
+        if failure.is_none() {
+            for (leg, order) in legs.iter().zip(&placed) {
+                if !self.leg_fills_in_full(leg, order).await {
+                    failure = Some(ConversionError::InsufficientLiquidity);
+                    break;
+                }
+            }
+        }
+        if let Some(e) = failure {
+            for order in &placed {
+                self.cancel_order(&order.trading_pair, order.id).await;
+            }
+            return Err(e);
+        }
+
+        let mut trades = Vec::new();
+        for Order {
+            id: order_id,
+            trading_pair,
+            ..
+        } in placed
+        {
+            let (match_tx, mut match_rx) = mpsc::channel(1);
+            self.process_match_orders(trading_pair.clone(), match_tx)
+                .await;
+            trades.extend(
+                match_rx
+                    .recv()
+                    .await
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(|trade| {
+                        trade.buy_order_id == order_id || trade.sell_order_id == order_id
+                    }),
+            );
+            // Rounding can leave a sliver of the leg unfilled; it must not
+            // rest.
+            self.cancel_order(&trading_pair, order_id).await;
+        }
+        Ok(trades)
+    }
+
+    /// Whether `order`, resting for `leg`, would fill in full if its book
+    /// were matched now.
+    async fn leg_fills_in_full(&self, leg: &ConversionLeg, order: &Order) -> bool {
+        match self.get_order_book(&order.trading_pair) {
+            Some(order_book) => {
+                let fillable = order_book.read().await.fillable_quantity(order).await;
+                leg.is_covered_by(fillable)
+            }
+            None => false,
+        }
+    }
+
+    /// Sizes each leg of `route` from the output of the one before,
+    /// against the books as they stand.
+    async fn plan_conversion(
+        &self,
+        route: &[TradingPair],
+        from_asset: &str,
+        quantity: f64,
+    ) -> Option<Vec<ConversionLeg>> {
+        let mut asset = from_asset.to_string();
+        let mut amount = quantity;
+        let mut legs = Vec::with_capacity(route.len());
+        for trading_pair in route {
+            let order_book = self.get_order_book(trading_pair)?;
+            let (bids, asks) = order_book.read().await.get_order_book().await;
+            let leg = ConversionLeg::plan(trading_pair, &asset, amount, &bids, &asks)?;
+            asset = if trading_pair.base == asset {
+                trading_pair.quote.clone()
+            } else {
+                trading_pair.base.clone()
+            };
+            amount = leg.output;
+            legs.push(leg);
+        }
+        Some(legs)
+    }
+
+    async fn process_get_ohlcv(
+        &mut self,
+        trading_pair: TradingPair,
+        interval: Duration,
+        since: DateTime<Utc>,
+        response_tx: mpsc::Sender<Vec<OhlcvBar>>,
+    ) {
+        let bars = match self.get_order_book(&trading_pair) {
+            Some(order_book) => {
+                let trades = order_book.read().await.get_trade_history().await;
+                OhlcvBar::from_trades(&trades, interval, since)
+            }
+            None => vec![],
+        };
+        let _ = response_tx.send(bars).await;
+    }
+
+    /// Runs an execution algorithm on its own task, feeding its child orders
+    /// back through this engine's channel.
+    fn spawn_algorithm<T, F, Fut>(&self, response_tx: mpsc::Sender<T>, algorithm: F)
+    where
+        T: Default + Send + 'static,
+        F: FnOnce(mpsc::Sender<Message>) -> Fut + Send + 'static,
+        Fut: Future<Output = T> + Send,
+    {
+        let engine_tx = self.engine_tx.as_ref().and_then(|tx| tx.upgrade());
+        if engine_tx.is_none() {
+            warn!("Engine has no sender to route child orders through");
+        }
+
+        tokio::spawn(async move {
+            let result = match engine_tx {
+                Some(engine_tx) => algorithm(engine_tx).await,
+                None => T::default(),
+            };
+            let _ = response_tx.send(result).await;
+        });
+    }
+
+    pub async fn run(&mut self, mut rx: mpsc::Receiver<Message>) {
+        let dispatch = self.dispatch();
+        async {
+            info!("Starting engine.");
+            let mut drain = None;
+            while let Some(message) = rx.recv().await {
+                if !self.handle_message(message, &mut rx, &mut drain).await {
+                    break;
+                }
+            }
+            self.finish_drain(drain).await;
+            info!("Engine stopped.");
+        }
+        .with_subscriber(dispatch)
+        .await
+    }
+
+    /// Like `run`, but also returns once no message has arrived for
+    /// `idle_timeout`. Returns how many messages were processed.
+    #[cfg(any(test, feature = "testing"))]
+    pub async fn run_until_idle(
+        &mut self,
+        mut rx: mpsc::Receiver<Message>,
+        idle_timeout: Duration,
+    ) -> usize {
+        let dispatch = self.dispatch();
+        async {
+            let mut drain = None;
+            let mut processed = 0;
+            while let Ok(Some(message)) = tokio::time::timeout(idle_timeout, rx.recv()).await {
+                processed += 1;
+                if !self.handle_message(message, &mut rx, &mut drain).await {
+                    break;
+                }
+            }
+            self.finish_drain(drain).await;
+            processed
+        }
+        .with_subscriber(dispatch)
+        .await
+    }
+
+    /// The dispatcher this engine logs through. Benchmark engines get a
+    /// no-op one, so they stay silent without touching the process-wide
+    /// subscriber the other engines share.
+    fn dispatch(&self) -> Dispatch {
+        if self.config.benchmark_mode {
+            Dispatch::none()
+        } else {
+            dispatcher::get_default(Dispatch::clone)
+        }
+    }
+
+    fn log_message(&self, message_type: MessageType) {
+        if self.config.benchmark_mode {
+            return;
+        }
+        let level = self
+            .config
+            .log_filter
+            .get(&message_type)
+            .copied()
+            .unwrap_or_else(|| message_type.default_log_level());
+        // `event!` needs its level at compile time.
+        match level.into_level() {
+            Some(Level::ERROR) => event!(Level::ERROR, ?message_type, "Handling message"),
+            Some(Level::WARN) => event!(Level::WARN, ?message_type, "Handling message"),
+            Some(Level::INFO) => event!(Level::INFO, ?message_type, "Handling message"),
+            Some(Level::DEBUG) => event!(Level::DEBUG, ?message_type, "Handling message"),
+            Some(_) => event!(Level::TRACE, ?message_type, "Handling message"),
+            None => {}
+        }
+    }
+
+    /// Processes one message; `false` once the engine should stop.
+    async fn handle_message(
+        &mut self,
+        message: Message,
+        rx: &mut mpsc::Receiver<Message>,
+        drain: &mut Option<DrainState>,
+    ) -> bool {
+        let mut message = message;
+        let mut correlation_id = None;
+        let message = loop {
+            message = match message {
+                Message::WithCorrelationId(id, inner) => {
+                    correlation_id = Some(id);
+                    *inner
+                }
+                Message::Forwarded(inner) => *inner,
+                message => break message,
+            };
+        };
+        let span = match correlation_id {
+            Some(correlation_id) => info_span!("message", %correlation_id),
+            None => Span::none(),
+        };
+        self.process_message(message, rx, drain)
+            .instrument(span)
+            .await
+    }
+
+    async fn process_message(
+        &mut self,
+        message: Message,
+        rx: &mut mpsc::Receiver<Message>,
+        drain: &mut Option<DrainState>,
+    ) -> bool {
+        if let Some((_, processed)) = drain {
+            *processed += 1;
+        }
+        self.log_message(message.message_type());
+        match message {
+            Message::Ping(response_tx) => {
+                self.metrics.record_queue_depth(rx.len());
+                let _ = response_tx.try_send(());
+            }
+            Message::Drain(response_tx) => {
+                if drain.is_none() {
+                    info!("Draining engine.");
+                    rx.close();
+                    *drain = Some((response_tx, 0));
+                }
+            }
+            Message::NewOrder(order) => {
+                let trading_pair = order.trading_pair.clone();
+                if self.process_new_order(order).await.is_ok() {
+                    self.auto_match(trading_pair).await;
+                }
+            }
+            Message::NewOrderWithCallback(order, response_tx) => {
+                let order_id = order.id;
+                let trading_pair = order.trading_pair.clone();
+                let ack = match self.process_new_order(order).await {
+                    Ok(book_seq) => OrderAck::Accepted { order_id, book_seq },
+                    Err(reason) => OrderAck::Rejected { order_id, reason },
+                };
+                let accepted = matches!(ack, OrderAck::Accepted { .. });
+                let _ = response_tx.send(ack).await;
+                if accepted {
+                    self.auto_match(trading_pair).await;
+                }
+            }
+            Message::GetPrice(trading_pair, response_tx) => {
+                self.process_get_price(trading_pair, response_tx).await;
+            }
+            Message::GetOrderBook(trading_pair, response_tx) => {
+                self.process_get_order_book(trading_pair, response_tx).await;
+            }
+            Message::GetTradeHistory(trading_pair, response_tx) => {
+                self.process_get_trade_history(trading_pair, response_tx)
+                    .await;
+            }
+            Message::GetTradeHistoryPaginated(trading_pair, offset, limit, response_tx) => {
+                let order_book = self.get_order_book(&trading_pair);
+                self.dispatch_read(async move {
+                    let page = match order_book {
+                        Some(order_book) => {
+                            order_book
+                                .read()
+                                .await
+                                .get_trade_history_paged(offset, limit)
+                                .await
+                        }
+                        None => TradePage::default(),
+                    };
+                    let _ = response_tx.send(page).await;
+                })
+                .await;
+            }
+            Message::GetVolumeSince(trading_pair, since, response_tx) => {
+                self.process_get_volume_since(trading_pair, since, response_tx)
+                    .await;
+            }
+            Message::GetLiquidityWithinRange(
+                trading_pair,
+                center_price,
+                range_pct,
+                side,
+                response_tx,
+            ) => {
+                self.process_get_liquidity_within_range(
+                    trading_pair,
+                    center_price,
+                    range_pct,
+                    side,
+                    response_tx,
+                )
+                .await;
+            }
+            Message::GetKyleLambda(trading_pair, window, response_tx) => {
+                self.process_get_kyle_lambda(trading_pair, window, response_tx)
+                    .await;
+            }
+            Message::GetDepthImbalance(trading_pair, level, response_tx) => {
+                self.process_get_depth_imbalance(trading_pair, level, response_tx)
+                    .await;
+            }
+            Message::GetEffectiveSpread(trading_pair, trade_size, response_tx) => {
+                self.process_get_effective_spread(trading_pair, trade_size, response_tx)
+                    .await;
+            }
+            Message::GetPriceImpact(trading_pair, side, quantity, response_tx) => {
+                let impact = match self.get_order_book(&trading_pair) {
+                    Some(order_book) => {
+                        order_book
+                            .read()
+                            .await
+                            .get_price_impact(side, quantity)
+                            .await
+                    }
+                    None => microstructure::price_impact(&side, [], [], quantity),
+                };
+                let _ = response_tx.send(impact).await;
+            }
+            Message::GetPriceLadder(trading_pair, center, step, levels, response_tx) => {
+                let ladder = match self.get_order_book(&trading_pair) {
+                    Some(order_book) => {
+                        order_book
+                            .read()
+                            .await
+                            .price_ladder(center, step, levels)
+                            .await
+                    }
+                    None => vec![],
+                };
+                let _ = response_tx.send(ladder).await;
+            }
+            Message::GetVpin(trading_pair, response_tx) => {
+                self.process_get_vpin(trading_pair, response_tx).await;
+            }
+            Message::GetDailyStats(trading_pair, response_tx) => {
+                let stats = self
+                    .trade_aggregator
+                    .daily_stats(&trading_pair, self.clock.now());
+                let _ = response_tx.send(stats).await;
+            }
+            Message::MatchOrders(trading_pair, response_tx) => {
+                self.process_match_orders(trading_pair, response_tx).await;
+            }
+            Message::WarmUpOrderBook(trading_pair, orders, response_tx) => {
+                self.process_warm_up(trading_pair, orders, response_tx)
+                    .await;
+            }
+            Message::GetClientOrders(client_id, trading_pair, response_tx) => {
+                self.process_get_client_orders(client_id, trading_pair, response_tx)
+                    .await;
+            }
+            Message::GetClientOrdersAllPairs(client_id, response_tx) => {
+                self.process_get_client_orders_all_pairs(client_id, response_tx)
+                    .await;
+            }
+            Message::GetAllActiveOrders(response_tx) => {
+                self.process_get_all_active_orders(None, response_tx).await;
+            }
+            Message::GetAllActiveOrdersByClient(client_id, response_tx) => {
+                self.process_get_all_active_orders(Some(client_id), response_tx)
+                    .await;
+            }
+            Message::GetActiveOrderCount(trading_pair, response_tx) => {
+                self.process_get_active_order_count(trading_pair, response_tx)
+                    .await;
+            }
+            Message::GetStaleOrders(trading_pair, threshold, response_tx) => {
+                self.process_get_stale_orders(trading_pair, threshold, response_tx)
+                    .await;
+            }
+            Message::GetAllPairStats(response_tx) => {
+                self.process_get_all_pair_stats(response_tx).await;
+            }
+            Message::GetAllocationStats(trading_pair, response_tx) => {
+                let order_book = self.get_order_book(&trading_pair);
+                self.dispatch_read(async move {
+                    let stats = match order_book {
+                        Some(order_book) => order_book.read().await.get_allocation_stats().await,
+                        None => None,
+                    };
+                    let _ = response_tx.send(stats).await;
+                })
+                .await;
+            }
+            Message::GetActiveOrderCountAllPairs(response_tx) => {
+                self.process_get_active_order_count_all_pairs(response_tx)
+                    .await;
+            }
+            Message::RebalanceOrderBook(trading_pair, response_tx) => {
+                let removed = match self.get_order_book(&trading_pair) {
+                    Some(order_book) => order_book.write().await.rebalance_price_levels(),
+                    None => 0,
+                };
+                let _ = response_tx.send(removed).await;
+            }
+            Message::MergeTradingPairs(source, target, response_tx) => {
+                let migrated = self.merge_trading_pairs(source, target).await;
+                let _ = response_tx.send(migrated).await;
+            }
+            Message::CancelOrder(trading_pair, order_id, response_tx) => {
+                self.process_cancel_order(trading_pair, order_id, response_tx)
+                    .await;
+            }
+            Message::BustTrade(trading_pair, trade_id, response_tx) => {
+                let result = self.process_bust_trade(trading_pair, trade_id).await;
+                if let Err(e) = &result {
+                    warn!("Could not bust trade: {}", e);
+                }
+                let _ = response_tx.send(result).await;
+            }
+            Message::ConvertCurrency(from_asset, quantity, to_asset, response_tx) => {
+                let result = self
+                    .process_convert_currency(from_asset, quantity, to_asset)
+                    .await
+                    .map_err(EngineError::from);
+                if let Err(e) = &result {
+                    warn!("Rejecting conversion: {}", e);
+                }
+                let _ = response_tx.send(result).await;
+            }
+            Message::UpdateQuote(trading_pair, request, response_tx) => {
+                let result = self.process_update_quote(trading_pair, request).await;
+                if let Err(e) = &result {
+                    warn!("Rejecting quote update: {}", e);
+                }
+                let _ = response_tx.send(result).await;
+            }
+            Message::GetOhlcv(trading_pair, interval, since, response_tx) => {
+                self.process_get_ohlcv(trading_pair, interval, since, response_tx)
+                    .await;
+            }
+            Message::StartTwapExecution(params, response_tx) => {
+                self.spawn_algorithm(response_tx, |engine_tx| async move {
+                    TwapExecutor::from_params(params, engine_tx).execute().await
+                });
+            }
+            Message::StartVwapExecution(params, response_tx) => {
+                self.spawn_algorithm(response_tx, |engine_tx| async move {
+                    VwapExecutor::from_params(params, engine_tx).execute().await
+                });
+            }
+            Message::StartParticipation(params, response_tx) => {
+                self.spawn_algorithm(response_tx, |engine_tx| async move {
+                    ParticipationRateExecutor::from_params(params, engine_tx)
+                        .execute()
+                        .await
+                });
+            }
+            Message::SubscribeToPair(trading_pair, response_tx) => {
+                self.process_subscribe_to_pair(trading_pair, response_tx)
+                    .await;
+            }
+            Message::SubscribeToBbo(trading_pair, response_tx) => {
+                self.process_subscribe_to_bbo(trading_pair, response_tx)
+                    .await;
+            }
+            Message::GetTopOfBook(trading_pair, response_tx) => {
+                let seq = self
+                    .bbo_channels
+                    .get(&trading_pair)
+                    .and_then(|channel| channel.last.as_ref())
+                    .map_or(0, |last| last.seq);
+                let update = match self.get_order_book(&trading_pair) {
+                    Some(order_book) => {
+                        let order_book = order_book.read().await;
+                        bbo_snapshot(
+                            &trading_pair,
+                            Some(order_book.as_ref()),
+                            seq,
+                            self.clock.now(),
+                        )
+                        .await
+                    }
+                    None => bbo_snapshot(&trading_pair, None, seq, self.clock.now()).await,
+                };
+                let _ = response_tx.send(update).await;
+            }
+            #[cfg(feature = "serde")]
+            Message::SaveState(path, response_tx) => {
+                let result = self.save_state(&path).await;
+                if let Err(e) = &result {
+                    warn!("Failed to save engine state to {:?}: {}", path, e);
+                }
+                let _ = response_tx.send(result).await;
+            }
+            #[cfg(feature = "export")]
+            Message::ExportOrderBookCsv(trading_pair, path, response_tx) => {
+                let result = self.export_orderbook_csv(&trading_pair, &path).await;
+                if let Err(e) = &result {
+                    warn!("Failed to export order book to {:?}: {}", path, e);
+                }
+                let _ = response_tx.send(result).await;
+            }
+            Message::SaveSnapshots(backend, response_tx) => {
+                let result = self.save_snapshots(backend.as_ref()).await;
+                if let Err(e) = &result {
+                    warn!("Failed to save order book snapshots: {}", e);
+                }
+                let _ = response_tx.send(result).await;
+            }
+            Message::RestoreSnapshot(trading_pair, backend, response_tx) => {
+                let result = self.restore_snapshot(backend.as_ref(), &trading_pair).await;
+                let _ = response_tx.send(result).await;
+            }
+            Message::SubscribeToMarketEvents(response_tx) => {
+                let _ = response_tx.send(self.market_events.subscribe()).await;
+            }
+            Message::SubscribeToAllTrades(response_tx) => {
+                let _ = response_tx.send(self.all_trades.subscribe()).await;
+            }
+            Message::PriceUpdate(update) => {
+                self.process_price_update(update).await;
+            }
+            Message::ResumeTrading(trading_pair) => {
+                if self.halted_pairs.remove(&trading_pair) {
+                    info!("Resumed trading for {:?}", trading_pair);
+                }
+            }
+            Message::SetAccountManager(account_manager) => {
+                info!("Installed new account manager");
+                self.account_manager = Some(account_manager);
+            }
+            Message::Deposit(client_id, asset, amount) => {
+                if let Some(account_manager) = &mut self.account_manager {
+                    account_manager.deposit(&client_id, &asset, amount);
+                }
+            }
+            Message::GetAccount(client_id, response_tx) => {
+                let account = self
+                    .account_manager
+                    .as_ref()
+                    .and_then(|account_manager| account_manager.account(&client_id))
+                    .cloned();
+                let _ = response_tx.send(account).await;
+            }
+            Message::GetLiquidationPrice(client_id, trading_pair, response_tx) => {
+                let price = self.account_manager.as_ref().and_then(|account_manager| {
+                    account_manager.liquidation_price(&client_id, &trading_pair)
+                });
+                let _ = response_tx.send(price).await;
+            }
+            Message::SetRiskManager(risk_manager) => {
+                info!("Installed new risk manager");
+                self.risk_manager = Some(risk_manager);
+            }
+            Message::RegisterTradingHours(trading_pair, sessions, response_tx) => {
+                info!("Registered trading hours for {:?}", trading_pair);
+                self.trading_hours.register(trading_pair, sessions);
+                let _ = response_tx.send(()).await;
+            }
+            Message::ReloadConfig(config, response_tx) => {
+                self.process_reload_config(config, response_tx).await;
+            }
+            Message::SetFeeModel(trading_pair, fee_model, response_tx) => {
+                self.process_set_fee_model(trading_pair, fee_model).await;
+                let _ = response_tx.send(()).await;
+            }
+            Message::GetFillReport(trading_pair, order_id, response_tx) => {
+                let report = match self.get_order_book(&trading_pair) {
+                    Some(order_book) => order_book.read().await.get_fill_report(order_id).await,
+                    None => None,
+                };
+                let _ = response_tx.send(report).await;
+            }
+            Message::LoadReferenceData(instruments, response_tx) => {
+                info!(
+                    "Loading reference data for {} instruments",
+                    instruments.len()
+                );
+                {
+                    let mut reference_data = self.reference_data.write();
+                    for info in instruments {
+                        reference_data.register(info);
+                    }
+                }
+                let _ = response_tx.send(()).await;
+            }
+            Message::GetEngineVersion(response_tx) => {
+                let _ = response_tx.send(ENGINE_VERSION).await;
+            }
+            Message::DiagnoseEngine(response_tx) => {
+                let report = self.diagnose().await;
+                for problem in &report.inconsistencies {
+                    warn!("Integrity check: {}", problem);
+                }
+                if !report.orphaned_order_ids.is_empty() {
+                    warn!(
+                        "Integrity check: orphaned orders {:?}",
+                        report.orphaned_order_ids
+                    );
+                }
+                let _ = response_tx.send(report).await;
+            }
+            Message::GetMetrics(response_tx) => {
+                let _ = response_tx.send(self.metrics.snapshot()).await;
+            }
+            Message::ResetMetrics(response_tx) => {
+                self.metrics.reset();
+                let _ = response_tx.send(()).await;
+            }
+            Message::WithCorrelationId(..) | Message::Forwarded(..) => {
+                unreachable!("unwrapped by handle_message")
+            }
+            Message::Broadcast(payload, response_tx) => {
+                let affected = self.process_broadcast(payload).await;
+                let _ = response_tx.send(affected).await;
+            }
+            Message::SetLogFilter(message_type, level, response_tx) => {
+                self.config.log_filter.insert(message_type, level);
+                let _ = response_tx.send(()).await;
+            }
+            Message::Shutdown => {
+                info!("Received shutdown signal.");
+                // A drain in progress shuts down once the loop ends.
+                if drain.is_none() {
+                    self.shutdown().await;
+                }
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Shuts a drained engine down as `Message::Shutdown` would, then
+    /// replies with how many queued messages the drain processed.
+    async fn finish_drain(&mut self, drain: Option<DrainState>) {
+        if let Some((response_tx, processed)) = drain {
+            info!(processed, "Drained engine");
+            self.shutdown().await;
+            let _ = response_tx.send(processed).await;
+        }
+    }
+}
+
+/// Reply channel of a drain and how many queued messages it has processed.
+type DrainState = (mpsc::Sender<usize>, usize);
+
+pub fn start_engine<F>(order_book_factory: F) -> mpsc::Sender<Message>
+where
+    F: Fn(TradingPair) -> Box<dyn OrderBook> + Send + Sync + 'static,
+{
+    start_engine_with_config(EngineConfig::default(), order_book_factory)
+}
+
+pub fn start_engine_with_config<F>(
+    config: EngineConfig,
+    order_book_factory: F,
+) -> mpsc::Sender<Message>
+where
+    F: Fn(TradingPair) -> Box<dyn OrderBook> + Send + Sync + 'static,
+{
+    spawn_engine(Engine::with_config(config, order_book_factory))
+}
+
+/// Restores an engine with `Engine::load_state` and starts it.
+#[cfg(feature = "serde")]
+pub async fn start_engine_from_state<F>(
+    path: PathBuf,
+    order_book_factory: F,
+) -> io::Result<mpsc::Sender<Message>>
+where
+    F: Fn(TradingPair) -> Box<dyn OrderBook> + Send + Sync + 'static,
+{
+    let engine = Engine::load_state(path, order_book_factory).await?;
+    Ok(spawn_engine(engine))
+}
+
+/// Feeds a book's post-match prices back to the engine as `PriceUpdate`
+/// messages. The book cannot wait, so an update that finds the channel full
+/// is dropped; the next match publishes a fresher one.
+fn price_update_callback(engine_tx: mpsc::WeakSender<Message>) -> PriceUpdateCallback {
+    Arc::new(move |update: PriceUpdate| {
+        let Some(engine_tx) = engine_tx.upgrade() else {
+            return;
+        };
+        if let Err(e) = engine_tx.try_send(Message::PriceUpdate(update)) {
+            warn!("Dropped post-match price update: {}", e);
+        }
+    })
+}
+
+/// Asks the engine to diagnose itself every `DIAGNOSTIC_INTERVAL`, until
+/// the channel closes. The handler logs anything found.
+#[cfg(debug_assertions)]
+fn spawn_diagnostics(engine_tx: mpsc::WeakSender<Message>) {
+    tokio::spawn(async move {
+        let start = tokio::time::Instant::now() + DIAGNOSTIC_INTERVAL;
+        let mut interval = tokio::time::interval_at(start, DIAGNOSTIC_INTERVAL);
+        loop {
+            interval.tick().await;
+            let Some(engine_tx) = engine_tx.upgrade() else {
+                break;
+            };
+            let (report_tx, _) = mpsc::channel(1);
+            if engine_tx
+                .send(Message::DiagnoseEngine(report_tx))
+                .await
+                .is_err()
+            {
+                break;
+            }
+        }
+    });
+}
+
+/// Warns about every book whose client index holds more entries than it
+/// has resting orders, every `ALLOCATION_CHECK_INTERVAL` until the engine
+/// is dropped. Reads the books directly, so the engine task is not
+/// involved.
+fn spawn_allocation_checks(order_books: Weak<DashMap<TradingPair, SharedOrderBook>>) {
+    tokio::spawn(async move {
+        let start = tokio::time::Instant::now() + ALLOCATION_CHECK_INTERVAL;
+        let mut interval = tokio::time::interval_at(start, ALLOCATION_CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            let Some(order_books) = order_books.upgrade() else {
+                break;
+            };
+            let order_books: Vec<(TradingPair, SharedOrderBook)> = order_books
+                .iter()
+                .map(|entry| (entry.key().clone(), entry.value().clone()))
+                .collect();
+            for (trading_pair, order_book) in order_books {
+                let Some(stats) = order_book.read().await.get_allocation_stats().await else {
+                    continue;
+                };
+                if stats.index_is_leaking() {
+                    warn!(
+                        id_index_entries = stats.id_index_entries,
+                        resting_orders = stats.bid_entries + stats.ask_entries,
+                        "Client index for {} is larger than its book; entries are leaking",
+                        trading_pair
+                    );
+                }
+            }
+        }
+    });
+}
+
+fn spawn_engine(mut engine: Engine) -> mpsc::Sender<Message> {
+    let (tx, rx) = mpsc::channel(engine.config.channel_capacity);
+    engine.engine_tx = Some(tx.downgrade());
+    engine.metrics.watch_queue_depth(tx.downgrade());
+    #[cfg(debug_assertions)]
+    spawn_diagnostics(tx.downgrade());
+    spawn_allocation_checks(Arc::downgrade(&engine.order_books));
 
     tokio::spawn(async move {
-        let mut engine = Engine::new(order_book_factory);
         engine.run(rx).await;
     });
 
@@ -0,0 +1,308 @@
+use crate::engine::api::PriceLevel;
+use crate::engine::models::TradingPair;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+
+/// Number of levels per side covered by `book_checksum`.
+pub const CHECKSUM_DEPTH: usize = 10;
+
+/// Diffs a book keeps for `take_snapshot_delta` before folding the oldest
+/// into its base snapshot.
+pub const DEFAULT_DELTA_RETENTION: usize = 1_000;
+
+/// Change to the aggregated book since the previous diff. A level whose
+/// quantity or order count changed appears in `*_removed` as it was and in
+/// `*_added` as it is now, so applying removals before additions always
+/// lands on the new state.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct OrderBookDiff {
+    pub pair: TradingPair,
+    /// Starts at 1 and increases by one per diff.
+    pub seq: u64,
+    pub bids_added: Vec<PriceLevel>,
+    pub bids_removed: Vec<PriceLevel>,
+    pub asks_added: Vec<PriceLevel>,
+    pub asks_removed: Vec<PriceLevel>,
+    /// `book_checksum` of the book after this diff.
+    pub checksum: u64,
+}
+
+/// FNV-1a over the prices and quantities of the best `CHECKSUM_DEPTH` bids
+/// and asks. Both slices must be
+/// ordered best price first, as `OrderBook::get_order_book` returns them.
+pub fn book_checksum(bids: &[PriceLevel], asks: &[PriceLevel]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let levels = bids
+        .iter()
+        .take(CHECKSUM_DEPTH)
+        .chain(asks.iter().take(CHECKSUM_DEPTH));
+    for entry in levels {
+        for byte in entry
+            .price
+            .to_bits()
+            .to_le_bytes()
+            .into_iter()
+            .chain(entry.total_quantity.to_bits().to_le_bytes())
+        {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiffError {
+    WrongPair(TradingPair),
+    /// A diff was missed; the local book must be rebuilt from a snapshot.
+    SequenceGap {
+        expected: u64,
+        received: u64,
+    },
+    ChecksumMismatch {
+        expected: u64,
+        actual: u64,
+    },
+}
+
+impl fmt::Display for DiffError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiffError::WrongPair(pair) => write!(f, "diff is for {:?}", pair),
+            DiffError::SequenceGap { expected, received } => {
+                write!(f, "expected diff {} but received {}", expected, received)
+            }
+            DiffError::ChecksumMismatch { expected, actual } => {
+                write!(f, "checksum {:x} does not match {:x}", actual, expected)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DiffError {}
+
+/// Client-side copy of an aggregated book kept current by applying diffs.
+/// Prices are keyed by their bit pattern, which orders correctly for the
+/// non-negative prices a book holds.
+#[derive(Debug, Clone)]
+pub struct DiffApplicator {
+    pair: TradingPair,
+    seq: u64,
+    bids: BTreeMap<u64, PriceLevel>,
+    asks: BTreeMap<u64, PriceLevel>,
+}
+
+impl DiffApplicator {
+    /// An empty book that expects the first diff the pair ever publishes.
+    pub fn new(pair: TradingPair) -> Self {
+        DiffApplicator::from_snapshot(pair, 0, &[], &[])
+    }
+
+    /// A book seeded from a snapshot taken at diff `seq`.
+    pub fn from_snapshot(
+        pair: TradingPair,
+        seq: u64,
+        bids: &[PriceLevel],
+        asks: &[PriceLevel],
+    ) -> Self {
+        let levels = |entries: &[PriceLevel]| {
+            entries
+                .iter()
+                .map(|entry| (entry.price.to_bits(), entry.clone()))
+                .collect()
+        };
+        DiffApplicator {
+            pair,
+            seq,
+            bids: levels(bids),
+            asks: levels(asks),
+        }
+    }
+
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    /// Applies `diff` and checks the result against its checksum. On error
+    /// the local book is left unchanged.
+    pub fn apply(&mut self, diff: &OrderBookDiff) -> Result<(), DiffError> {
+        if diff.pair != self.pair {
+            return Err(DiffError::WrongPair(diff.pair.clone()));
+        }
+        if diff.seq != self.seq + 1 {
+            return Err(DiffError::SequenceGap {
+                expected: self.seq + 1,
+                received: diff.seq,
+            });
+        }
+
+        let mut bids = self.bids.clone();
+        let mut asks = self.asks.clone();
+        apply_side(&mut bids, &diff.bids_removed, &diff.bids_added);
+        apply_side(&mut asks, &diff.asks_removed, &diff.asks_added);
+
+        let actual = book_checksum(&side_entries(&bids, true), &side_entries(&asks, false));
+        if actual != diff.checksum {
+            return Err(DiffError::ChecksumMismatch {
+                expected: diff.checksum,
+                actual,
+            });
+        }
+
+        self.bids = bids;
+        self.asks = asks;
+        self.seq = diff.seq;
+        Ok(())
+    }
+
+    /// Best bid first.
+    pub fn bids(&self) -> Vec<PriceLevel> {
+        side_entries(&self.bids, true)
+    }
+
+    /// Best ask first.
+    pub fn asks(&self) -> Vec<PriceLevel> {
+        side_entries(&self.asks, false)
+    }
+
+    pub fn checksum(&self) -> u64 {
+        book_checksum(&self.bids(), &self.asks())
+    }
+}
+
+fn apply_side(
+    levels: &mut BTreeMap<u64, PriceLevel>,
+    removed: &[PriceLevel],
+    added: &[PriceLevel],
+) {
+    for entry in removed {
+        levels.remove(&entry.price.to_bits());
+    }
+    for entry in added {
+        levels.insert(entry.price.to_bits(), entry.clone());
+    }
+}
+
+fn side_entries(levels: &BTreeMap<u64, PriceLevel>, descending: bool) -> Vec<PriceLevel> {
+    if descending {
+        levels.values().rev().cloned().collect()
+    } else {
+        levels.values().cloned().collect()
+    }
+}
+
+/// The diffs a book has published, newest `retention` kept as they are and
+/// older ones folded into a snapshot of the book as of `base_seq`.
+#[derive(Debug, Clone)]
+pub(crate) struct DeltaLog {
+    base_seq: u64,
+    base_bids: BTreeMap<u64, PriceLevel>,
+    base_asks: BTreeMap<u64, PriceLevel>,
+    diffs: VecDeque<OrderBookDiff>,
+    /// Checksum of the book after the newest diff.
+    checksum: u64,
+}
+
+impl Default for DeltaLog {
+    fn default() -> Self {
+        DeltaLog {
+            base_seq: 0,
+            base_bids: BTreeMap::new(),
+            base_asks: BTreeMap::new(),
+            diffs: VecDeque::new(),
+            checksum: book_checksum(&[], &[]),
+        }
+    }
+}
+
+impl DeltaLog {
+    /// Logs `diff`, then compacts the log down to `retention` diffs.
+    pub(crate) fn push(&mut self, diff: OrderBookDiff, retention: usize) {
+        self.checksum = diff.checksum;
+        self.diffs.push_back(diff);
+        while self.diffs.len() > retention {
+            let Some(oldest) = self.diffs.pop_front() else {
+                break;
+            };
+            apply_side(
+                &mut self.base_bids,
+                &oldest.bids_removed,
+                &oldest.bids_added,
+            );
+            apply_side(
+                &mut self.base_asks,
+                &oldest.asks_removed,
+                &oldest.asks_added,
+            );
+            self.base_seq = oldest.seq;
+        }
+    }
+
+    /// One diff taking a book at `since_seq` to the newest logged state.
+    /// If `since_seq` predates the kept diffs it cannot be rebuilt, so the
+    /// diff starts from an empty book instead.
+    pub(crate) fn delta(&self, pair: TradingPair, since_seq: u64) -> OrderBookDiff {
+        let mut bids = LevelChanges::default();
+        let mut asks = LevelChanges::default();
+        if since_seq < self.base_seq {
+            bids.apply(&[], self.base_bids.values());
+            asks.apply(&[], self.base_asks.values());
+        }
+        for diff in self.diffs.iter().filter(|diff| diff.seq > since_seq) {
+            bids.apply(&diff.bids_removed, &diff.bids_added);
+            asks.apply(&diff.asks_removed, &diff.asks_added);
+        }
+
+        let (bids_added, bids_removed) = bids.into_changes();
+        let (asks_added, asks_removed) = asks.into_changes();
+        OrderBookDiff {
+            pair,
+            seq: self.diffs.back().map_or(self.base_seq, |diff| diff.seq),
+            bids_added,
+            bids_removed,
+            asks_added,
+            asks_removed,
+            checksum: self.checksum,
+        }
+    }
+}
+
+/// Each touched level of one side as it was before the first diff touched
+/// it and after the last, `None` where nothing rested.
+#[derive(Default)]
+struct LevelChanges(BTreeMap<u64, (Option<PriceLevel>, Option<PriceLevel>)>);
+
+impl LevelChanges {
+    fn apply<'a>(
+        &mut self,
+        removed: &[PriceLevel],
+        added: impl IntoIterator<Item = &'a PriceLevel>,
+    ) {
+        for entry in removed {
+            self.0
+                .entry(entry.price.to_bits())
+                .or_insert_with(|| (Some(entry.clone()), None))
+                .1 = None;
+        }
+        for entry in added {
+            self.0.entry(entry.price.to_bits()).or_default().1 = Some(entry.clone());
+        }
+    }
+
+    /// `(added, removed)`, lowest price first.
+    fn into_changes(self) -> (Vec<PriceLevel>, Vec<PriceLevel>) {
+        let mut added = Vec::new();
+        let mut removed = Vec::new();
+        for (before, after) in self.0.into_values() {
+            if before == after {
+                continue;
+            }
+            removed.extend(before);
+            added.extend(after);
+        }
+        (added, removed)
+    }
+}
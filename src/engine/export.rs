@@ -0,0 +1,53 @@
+use crate::engine::models::{Order, OrderType};
+use std::io;
+
+const HEADER: [&str; 6] = [
+    "side",
+    "price",
+    "quantity",
+    "order_count",
+    "top_order_id",
+    "top_order_timestamp",
+];
+
+/// Writes resting `orders` as one CSV row per price level: bids from the
+/// highest price down, an empty separator row, then asks from the lowest
+/// price up. The top order of a level is the first one in `orders` at that
+/// price, so `orders` should be in queue order within each level, as
+/// `OrderBook::get_active_orders` returns them.
+pub fn write_order_book_csv<W: io::Write>(orders: &[Order], writer: W) -> csv::Result<()> {
+    let mut bids: Vec<&Order> = orders.iter().filter(|order| order.is_buy()).collect();
+    let mut asks: Vec<&Order> = orders.iter().filter(|order| order.is_sell()).collect();
+    // Stable sorts keep queue order within a level.
+    bids.sort_by(|a, b| b.price.value().total_cmp(&a.price.value()));
+    asks.sort_by(|a, b| a.price.value().total_cmp(&b.price.value()));
+
+    let mut writer = csv::Writer::from_writer(writer);
+    writer.write_record(HEADER)?;
+    write_levels(&mut writer, &bids)?;
+    writer.write_record([""; HEADER.len()])?;
+    write_levels(&mut writer, &asks)?;
+    writer.flush()?;
+    Ok(())
+}
+
+fn write_levels<W: io::Write>(writer: &mut csv::Writer<W>, orders: &[&Order]) -> csv::Result<()> {
+    for level in orders.chunk_by(|a, b| a.price == b.price) {
+        let top = level[0];
+        let side = match top.order_type {
+            OrderType::Buy => "bid",
+            OrderType::Sell => "ask",
+            OrderType::Convert { .. } => continue,
+        };
+        let quantity: f64 = level.iter().map(|order| order.quantity.value()).sum();
+        writer.write_record([
+            side.to_string(),
+            top.price.to_string(),
+            quantity.to_string(),
+            level.len().to_string(),
+            top.id.to_string(),
+            top.timestamp.to_rfc3339(),
+        ])?;
+    }
+    Ok(())
+}
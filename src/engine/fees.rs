@@ -0,0 +1,84 @@
+use crate::engine::models::{OrderType, Trade, TradingPair, COMMON_STABLECOINS};
+
+/// Fee schedule applied by order books when they execute trades. Fees are in
+/// the quote currency unless `fee_in_quote` says otherwise.
+pub trait FeeModel: Send + Sync {
+    fn maker_fee(&self, notional: f64) -> f64;
+    fn taker_fee(&self, notional: f64) -> f64;
+
+    /// Whether fees on `trading_pair` are taken in its quote asset rather
+    /// than its base.
+    fn fee_in_quote(&self, _trading_pair: &TradingPair) -> bool {
+        true
+    }
+
+    /// Fills in `buy_fee` and `sell_fee`, charging the aggressor as taker.
+    /// Fees in the base are charged on the traded quantity.
+    fn charge(&self, trade: &mut Trade) {
+        let notional = if self.fee_in_quote(&trade.trading_pair) {
+            trade.notional_value()
+        } else {
+            trade.quantity.value()
+        };
+        let (maker_fee, taker_fee) = (self.maker_fee(notional), self.taker_fee(notional));
+        (trade.buy_fee, trade.sell_fee) = match trade.aggressor_side {
+            OrderType::Buy => (taker_fee, maker_fee),
+            OrderType::Sell => (maker_fee, taker_fee),
+            // Neither side rested, so both take.
+            OrderType::Convert { .. } => (taker_fee, taker_fee),
+        };
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ZeroFeeModel;
+
+impl FeeModel for ZeroFeeModel {
+    fn maker_fee(&self, _notional: f64) -> f64 {
+        0.0
+    }
+
+    fn taker_fee(&self, _notional: f64) -> f64 {
+        0.0
+    }
+}
+
+/// Fixed maker and taker rates in basis points of notional. A negative maker
+/// rate pays a rebate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FlatFeeModel {
+    pub maker_bps: f64,
+    pub taker_bps: f64,
+    /// Charge every pair in its quote asset. When unset only pairs quoted
+    /// in one of `COMMON_STABLECOINS` are; the rest are charged in the base.
+    pub charge_in_quote: bool,
+}
+
+impl FlatFeeModel {
+    pub fn new(maker_bps: f64, taker_bps: f64) -> Self {
+        FlatFeeModel {
+            maker_bps,
+            taker_bps,
+            charge_in_quote: true,
+        }
+    }
+
+    pub fn with_charge_in_quote(mut self, charge_in_quote: bool) -> Self {
+        self.charge_in_quote = charge_in_quote;
+        self
+    }
+}
+
+impl FeeModel for FlatFeeModel {
+    fn maker_fee(&self, notional: f64) -> f64 {
+        notional * self.maker_bps / 10_000.0
+    }
+
+    fn taker_fee(&self, notional: f64) -> f64 {
+        notional * self.taker_bps / 10_000.0
+    }
+
+    fn fee_in_quote(&self, trading_pair: &TradingPair) -> bool {
+        self.charge_in_quote || trading_pair.quote_is_stablecoin(COMMON_STABLECOINS)
+    }
+}
@@ -0,0 +1,102 @@
+use crate::engine::core::Message;
+use crate::engine::models::TradingPair;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+
+/// Front doors to two engines joined by `Engine::connect_engines`. Every
+/// message sent to `a_tx` reaches engine A and every one sent to `b_tx`
+/// reaches engine B; `NewOrder`, `CancelOrder` and `MatchOrders` for the
+/// linked pairs are also copied to the other engine, whose copy answers to
+/// nobody.
+pub struct EngineLink {
+    pub a_tx: mpsc::Sender<Message>,
+    pub b_tx: mpsc::Sender<Message>,
+    cancel_token: CancellationToken,
+}
+
+impl EngineLink {
+    /// Cancelling the returned token stops the copying. The front doors
+    /// keep passing messages through to their own engine.
+    pub fn cancel_token(&self) -> CancellationToken {
+        self.cancel_token.clone()
+    }
+}
+
+pub(crate) fn connect(
+    engine_a_tx: mpsc::Sender<Message>,
+    engine_b_tx: mpsc::Sender<Message>,
+    pairs: Vec<TradingPair>,
+) -> EngineLink {
+    let pairs = Arc::new(pairs);
+    let cancel_token = CancellationToken::new();
+    let a_tx = spawn_front_door(
+        engine_a_tx.clone(),
+        engine_b_tx.clone(),
+        pairs.clone(),
+        cancel_token.clone(),
+    );
+    let b_tx = spawn_front_door(engine_b_tx, engine_a_tx, pairs, cancel_token.clone());
+    EngineLink {
+        a_tx,
+        b_tx,
+        cancel_token,
+    }
+}
+
+/// Runs until every sender of the returned front door is dropped or its
+/// engine shuts down.
+fn spawn_front_door(
+    own_tx: mpsc::Sender<Message>,
+    other_tx: mpsc::Sender<Message>,
+    pairs: Arc<Vec<TradingPair>>,
+    cancel_token: CancellationToken,
+) -> mpsc::Sender<Message> {
+    let (tx, mut rx) = mpsc::channel(own_tx.max_capacity());
+    tokio::spawn(async move {
+        while let Some(message) = rx.recv().await {
+            let copy = if cancel_token.is_cancelled() {
+                None
+            } else {
+                mirror(&message, &pairs)
+            };
+            if own_tx.send(message).await.is_err() {
+                break;
+            }
+            if let Some(copy) = copy {
+                if other_tx
+                    .send(Message::Forwarded(Box::new(copy)))
+                    .await
+                    .is_err()
+                {
+                    info!("Linked engine shut down; no longer forwarding");
+                    cancel_token.cancel();
+                }
+            }
+        }
+    });
+    tx
+}
+
+/// The copy of `message` for the other engine, if it is one that is
+/// forwarded. Messages that were themselves forwarded never are, so links
+/// chained into a cycle cannot bounce a message back and forth.
+fn mirror(message: &Message, pairs: &[TradingPair]) -> Option<Message> {
+    let linked = |trading_pair: &TradingPair| pairs.contains(trading_pair);
+    match message {
+        Message::NewOrder(order) if linked(&order.trading_pair) => {
+            Some(Message::NewOrder(order.clone()))
+        }
+        Message::CancelOrder(trading_pair, order_id, _) if linked(trading_pair) => Some(
+            Message::CancelOrder(trading_pair.clone(), *order_id, mpsc::channel(1).0),
+        ),
+        Message::MatchOrders(trading_pair, _) if linked(trading_pair) => Some(
+            Message::MatchOrders(trading_pair.clone(), mpsc::channel(1).0),
+        ),
+        Message::WithCorrelationId(correlation_id, inner) => {
+            mirror(inner, pairs).map(|copy| copy.with_correlation_id(*correlation_id))
+        }
+        _ => None,
+    }
+}
@@ -1,5 +1,5 @@
-use crate::engine::api::OrderBookEntry;
-use crate::engine::models::{Order, OrderType, Trade, TradingPair};
+use crate::engine::api::PriceLevel;
+use crate::engine::models::{MatchResult, Order, OrderType, Quantity, Trade, TradingPair};
 use async_trait::async_trait;
 use crossbeam_skiplist::SkipMap;
 use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
@@ -22,26 +22,26 @@ impl AtomicPriceLevel {
 
     fn add_order(&self, order: Order) {
         // Convert f64 to u64 bits for atomic operations
-        let quantity_bits = order.quantity.to_bits();
+        let quantity_bits = order.quantity.value().to_bits();
         self.total_quantity
             .fetch_add(quantity_bits, Ordering::AcqRel);
         self.order_count.fetch_add(1, Ordering::AcqRel);
         self.head.push(order);
     }
 
-    fn try_match(&self, quantity_needed: f64) -> Option<(Order, f64)> {
+    fn try_match(&self, quantity_needed: Quantity) -> Option<(Order, Quantity)> {
         if self.order_count.load(Ordering::Acquire) == 0 {
             return None;
         }
 
         if let Some(mut order) = self.head.pop() {
-            let match_quantity = f64::min(order.quantity, quantity_needed);
-            let quantity_bits = match_quantity.to_bits();
+            let match_quantity = order.quantity.min(quantity_needed);
+            let quantity_bits = match_quantity.value().to_bits();
             self.total_quantity
                 .fetch_sub(quantity_bits, Ordering::AcqRel);
 
-            order.quantity -= match_quantity;
-            if order.quantity > 0.0 {
+            order.fill(match_quantity, order.price);
+            if order.quantity > Quantity::ZERO {
                 self.head.push(order.clone());
             } else {
                 self.order_count.fetch_sub(1, Ordering::AcqRel);
@@ -53,6 +53,37 @@ impl AtomicPriceLevel {
         }
     }
 
+    fn remove_order(&self, order_id: u64) -> Option<Order> {
+        // SegQueue has no random access, so rotate the queue once and keep
+        // everything except the cancelled order in its original sequence.
+        let mut removed = None;
+        for _ in 0..self.head.len() {
+            let order = self.head.pop()?;
+            if removed.is_none() && order.id == order_id {
+                self.total_quantity
+                    .fetch_sub(order.quantity.value().to_bits(), Ordering::AcqRel);
+                self.order_count.fetch_sub(1, Ordering::AcqRel);
+                removed = Some(order);
+            } else {
+                self.head.push(order);
+            }
+        }
+        removed
+    }
+
+    fn snapshot_orders(&self) -> Vec<Order> {
+        // Same rotation as `remove_order`, cloning instead of removing.
+        let mut orders = Vec::with_capacity(self.head.len());
+        for _ in 0..self.head.len() {
+            let Some(order) = self.head.pop() else {
+                break;
+            };
+            orders.push(order.clone());
+            self.head.push(order);
+        }
+        orders
+    }
+
     fn get_total_quantity(&self) -> f64 {
         f64::from_bits(self.total_quantity.load(Ordering::Acquire))
     }
@@ -88,47 +119,63 @@ impl LockFreeOrderBook {
         let (matching_levels, resting_levels) = match incoming_order.order_type {
             OrderType::Buy => (&self.sell_levels, &self.buy_levels),
             OrderType::Sell => (&self.buy_levels, &self.sell_levels),
+            // Refused by `add_order`.
+            OrderType::Convert { .. } => return trades,
         };
 
         // Convert price to bits for comparison
-        let order_price_bits = incoming_order.price.to_bits();
+        let order_price_bits = incoming_order.price.value().to_bits();
 
         // Try matching with existing orders
-        while incoming_order.quantity > 0.0 {
-            let matched = match incoming_order.order_type {
-                OrderType::Buy => matching_levels
+        while incoming_order.quantity > Quantity::ZERO {
+            let matched = if incoming_order.is_buy() {
+                matching_levels
                     .iter()
                     .take_while(|entry| entry.key() <= &order_price_bits)
-                    .next(),
-                OrderType::Sell => matching_levels
+                    .next()
+            } else {
+                matching_levels
                     .iter()
                     .rev()
                     .take_while(|entry| entry.key() >= &order_price_bits)
-                    .next(),
+                    .next()
             };
 
             if let Some(level_entry) = matched {
                 if let Some((resting_order, match_quantity)) =
                     level_entry.value().try_match(incoming_order.quantity)
                 {
-                    incoming_order.quantity -= match_quantity;
+                    incoming_order.fill(match_quantity, resting_order.price);
 
                     let trade = Trade {
                         id: self.next_trade_id.fetch_add(1, Ordering::AcqRel),
                         trading_pair: self.trading_pair.clone(),
                         price: resting_order.price,
                         quantity: match_quantity,
-                        buy_order_id: if incoming_order.order_type == OrderType::Buy {
+                        aggressor_side: incoming_order.order_type.clone(),
+                        buy_order_id: if incoming_order.is_buy() {
                             incoming_order.id
                         } else {
                             resting_order.id
                         },
-                        sell_order_id: if incoming_order.order_type == OrderType::Buy {
+                        sell_order_id: if incoming_order.is_buy() {
                             resting_order.id
                         } else {
                             incoming_order.id
                         },
                         timestamp: chrono::Utc::now(),
+                        buy_client_id: if incoming_order.is_buy() {
+                            incoming_order.client_id.clone()
+                        } else {
+                            resting_order.client_id.clone()
+                        },
+                        sell_client_id: if incoming_order.is_buy() {
+                            resting_order.client_id.clone()
+                        } else {
+                            incoming_order.client_id.clone()
+                        },
+                        buy_fee: 0.0,
+                        sell_fee: 0.0,
                     };
                     trades.push(trade);
                 } else {
@@ -140,7 +187,7 @@ impl LockFreeOrderBook {
         }
 
         // Add remaining order to book
-        if incoming_order.quantity > 0.0 {
+        if incoming_order.quantity > Quantity::ZERO {
             let price_level =
                 resting_levels.get_or_insert(order_price_bits, AtomicPriceLevel::new());
             price_level.value().add_order(incoming_order);
@@ -152,15 +199,26 @@ impl LockFreeOrderBook {
 
 #[async_trait]
 impl crate::engine::order_book::OrderBook for LockFreeOrderBook {
-    async fn add_order(&self, order: Order) {
+    async fn add_order(
+        &self,
+        order: Order,
+    ) -> Result<(), crate::engine::order_book::OrderBookError> {
+        if let OrderType::Convert { .. } = order.order_type {
+            return Err(crate::engine::order_book::OrderBookError::CannotRest);
+        }
         let trades = self.process_order(order).await;
         for trade in trades {
             let _ = self.trade_tx.send(trade);
         }
+        Ok(())
     }
 
-    async fn match_orders(&self) -> Vec<Trade> {
-        Vec::new() // Real-time matching is done in process_order
+    async fn match_orders(&self) -> MatchResult {
+        // Real-time matching is done in process_order
+        MatchResult {
+            no_match_reason: Some("orders are matched on arrival".to_string()),
+            ..MatchResult::default()
+        }
     }
 
     async fn get_current_price(&self) -> Option<f64> {
@@ -183,22 +241,24 @@ impl crate::engine::order_book::OrderBook for LockFreeOrderBook {
         }
     }
 
-    async fn get_order_book(&self) -> (Vec<OrderBookEntry>, Vec<OrderBookEntry>) {
-        let bids: Vec<OrderBookEntry> = self
+    async fn get_order_book(&self) -> (Vec<PriceLevel>, Vec<PriceLevel>) {
+        let bids: Vec<PriceLevel> = self
             .buy_levels
             .iter()
-            .map(|entry| OrderBookEntry {
+            .map(|entry| PriceLevel {
                 price: f64::from_bits(*entry.key()),
-                quantity: entry.value().get_total_quantity(),
+                total_quantity: entry.value().get_total_quantity(),
+                order_count: entry.value().order_count.load(Ordering::Acquire),
             })
             .collect();
 
-        let asks: Vec<OrderBookEntry> = self
+        let asks: Vec<PriceLevel> = self
             .sell_levels
             .iter()
-            .map(|entry| OrderBookEntry {
+            .map(|entry| PriceLevel {
                 price: f64::from_bits(*entry.key()),
-                quantity: entry.value().get_total_quantity(),
+                total_quantity: entry.value().get_total_quantity(),
+                order_count: entry.value().order_count.load(Ordering::Acquire),
             })
             .collect();
 
@@ -224,4 +284,19 @@ impl crate::engine::order_book::OrderBook for LockFreeOrderBook {
 
         buy_count + sell_count
     }
+    async fn cancel_order(&self, order_id: u64) -> Option<Order> {
+        self.buy_levels
+            .iter()
+            .chain(self.sell_levels.iter())
+            .find_map(|entry| entry.value().remove_order(order_id))
+    }
+
+    async fn get_active_orders(&self) -> Vec<Order> {
+        self.buy_levels
+            .iter()
+            .rev()
+            .chain(self.sell_levels.iter())
+            .flat_map(|entry| entry.value().snapshot_orders())
+            .collect()
+    }
 }
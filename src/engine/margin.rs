@@ -0,0 +1,66 @@
+use crate::engine::models::{Account, Position, TradingPair};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Margin requirements for an isolated-margin derivative, as percentages of
+/// notional.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MarginCalculator {
+    pub initial_margin_pct: f64,
+    pub maintenance_margin_pct: f64,
+}
+
+impl MarginCalculator {
+    pub fn new(initial_margin_pct: f64, maintenance_margin_pct: f64) -> Self {
+        MarginCalculator {
+            initial_margin_pct,
+            maintenance_margin_pct,
+        }
+    }
+
+    pub fn initial_margin(&self, price: f64, qty: f64) -> f64 {
+        price * qty.abs() * self.initial_margin_pct / 100.0
+    }
+
+    pub fn maintenance_margin(&self, price: f64, qty: f64) -> f64 {
+        price * qty.abs() * self.maintenance_margin_pct / 100.0
+    }
+
+    /// Margin plus unrealized PnL at `mark_price`.
+    pub fn equity(&self, position: &Position, mark_price: f64) -> f64 {
+        position.margin + position.quantity * (mark_price - position.entry_price)
+    }
+
+    /// Whether the client's position in `trading_pair` has fallen below
+    /// maintenance margin at `mark_price`.
+    pub fn check_liquidation(
+        &self,
+        trading_pair: &TradingPair,
+        mark_price: f64,
+        account: &Account,
+    ) -> bool {
+        match account.positions.get(trading_pair) {
+            Some(position) if position.quantity != 0.0 => {
+                self.equity(position, mark_price)
+                    < self.maintenance_margin(mark_price, position.quantity)
+            }
+            _ => false,
+        }
+    }
+
+    /// Mark price at which equity equals maintenance margin.
+    pub fn liquidation_price(&self, position: &Position) -> Option<f64> {
+        let size = position.quantity.abs();
+        if size == 0.0 {
+            return None;
+        }
+        let maintenance = self.maintenance_margin_pct / 100.0;
+        let price = if position.quantity > 0.0 {
+            (size * position.entry_price - position.margin) / (size * (1.0 - maintenance))
+        } else {
+            (size * position.entry_price + position.margin) / (size * (1.0 + maintenance))
+        };
+        (price.is_finite() && price > 0.0).then_some(price)
+    }
+}
@@ -0,0 +1,364 @@
+use crate::engine::core::Message;
+use crate::engine::models::TradingPair;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+const ORDERS_ACCEPTED: &str = "engine_orders_accepted_total";
+const ORDERS_REJECTED: &str = "engine_orders_rejected_total";
+const ORDERS_CANCELLED: &str = "engine_orders_cancelled_total";
+const TRADES_EXECUTED: &str = "engine_trades_executed_total";
+const ORDER_BOOKS: &str = "engine_order_books";
+const SHUTDOWN_DURATION: &str = "engine_shutdown_duration_seconds";
+const SHUTDOWN_TRADES: &str = "engine_shutdown_trades_total";
+const SHUTDOWN_CANCELLED_ORDERS: &str = "engine_shutdown_cancelled_orders_total";
+const UPTIME: &str = "engine_uptime_seconds";
+const BOOK_DEPTH: &str = "engine_book_depth_levels";
+const QUEUE_DEPTH: &str = "engine_message_queue_depth";
+const ORDERS_SUBMITTED: &str = "engine_orders_submitted_total";
+const ACTIVE_ORDERS: &str = "engine_active_orders";
+const SPREAD: &str = "engine_spread";
+
+/// Engine counters and gauges. Every update goes both to the `metrics`
+/// facade, for whichever exporter is installed, and to a local atomic.
+/// Snapshots read the local copies, so `reset` can zero them even though
+/// exported counters never go backward.
+#[derive(Debug, Default)]
+pub struct EngineMetrics {
+    orders_accepted: AtomicU64,
+    orders_rejected: AtomicU64,
+    orders_cancelled: AtomicU64,
+    trades_executed: AtomicU64,
+    order_books: AtomicU64,
+    /// Duration of the last shutdown.
+    shutdown_duration_ms: AtomicU64,
+    shutdown_trades: AtomicU64,
+    shutdown_cancelled_orders: AtomicU64,
+    // Shared with the task from `spawn_uptime_updates`, which holds it
+    // weakly and so stops once the metrics are dropped.
+    uptime_seconds: Arc<AtomicU64>,
+    // Shared with the task from `spawn_queue_depth_updates` in the same way.
+    message_queue_depth: Arc<AtomicU64>,
+    /// Price levels per pair and side, as last set.
+    book_depth: parking_lot::Mutex<HashMap<(TradingPair, String), usize>>,
+    orders_submitted: parking_lot::Mutex<HashMap<TradingPair, u64>>,
+    active_orders: parking_lot::Mutex<HashMap<TradingPair, u64>>,
+    /// Per pair, as last set.
+    spread: parking_lot::Mutex<HashMap<TradingPair, f64>>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct EngineMetricsSnapshot {
+    pub orders_accepted: u64,
+    pub orders_rejected: u64,
+    pub orders_cancelled: u64,
+    pub trades_executed: u64,
+    pub order_books: u64,
+    pub shutdown_duration_ms: u64,
+    pub shutdown_trades: u64,
+    pub shutdown_cancelled_orders: u64,
+    pub uptime_seconds: u64,
+    /// Messages waiting in the engine channel when last sampled.
+    pub message_queue_depth: u64,
+}
+
+/// What the engine records, so benchmarks can swap `EngineMetrics` for
+/// `NoopEngineMetrics`.
+pub trait EngineMetricsInterface: Send + Sync {
+    fn record_order_accepted(&self);
+    fn record_order_rejected(&self);
+    fn record_order_cancelled(&self);
+    fn record_trades(&self, count: u64);
+    fn set_order_books(&self, count: u64);
+    /// Number of price levels on `side` of the pair's book, where `side` is
+    /// `"bid"` or `"ask"`. Exported with `pair` and `side` labels.
+    fn set_book_depth(&self, pair: &TradingPair, side: &str, levels: usize);
+    /// The depth last set for the pair and side, if any.
+    fn book_depth(&self, pair: &TradingPair, side: &str) -> Option<usize>;
+    /// Messages waiting in the engine channel, for alerting before it
+    /// fills up.
+    fn record_queue_depth(&self, depth: usize);
+    /// Counts an order submitted on the pair, whether or not it is accepted.
+    fn increment_orders_submitted(&self, pair: &TradingPair);
+    /// Orders that started resting on the pair.
+    fn increment_active_orders(&self, pair: &TradingPair, n: usize);
+    /// Orders that stopped resting on the pair, by trading out or being
+    /// cancelled. The gauge does not go below zero.
+    fn decrement_active_orders(&self, pair: &TradingPair, n: usize);
+    /// Best ask less best bid, set whenever the pair's book has both.
+    fn set_spread(&self, pair: &TradingPair, spread: f64);
+    /// Orders submitted on the pair since the last `reset`.
+    fn orders_submitted(&self, pair: &TradingPair) -> u64;
+    fn active_orders(&self, pair: &TradingPair) -> u64;
+    /// The spread last set for the pair, if any.
+    fn spread(&self, pair: &TradingPair) -> Option<f64>;
+    fn record_shutdown_duration(&self, duration: Duration);
+    /// Trades from the final match at shutdown.
+    fn record_shutdown_trades(&self, count: usize);
+    /// Orders still resting after the final match, cancelled at shutdown.
+    fn record_shutdown_cancelled_orders(&self, count: usize);
+    fn update_uptime(&self, seconds: u64);
+    fn snapshot(&self) -> EngineMetricsSnapshot;
+    /// Zeroes the local counters and gauges, apart from uptime and the
+    /// per-pair gauges, which follow the books. Exported gauges are zeroed
+    /// too; exported counters keep their totals.
+    fn reset(&self);
+    /// Starts sampling the depth of the engine's message channel, if these
+    /// metrics keep it.
+    fn watch_queue_depth(&self, _engine_tx: mpsc::WeakSender<Message>) {}
+}
+
+impl EngineMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Samples how many messages are queued on `engine_tx`'s channel every
+    /// second, on a background task that stops once these metrics or the
+    /// channel are dropped. Holding the sender weakly keeps the task from
+    /// holding the channel open.
+    pub fn spawn_queue_depth_updates<T: Send + 'static>(&self, engine_tx: mpsc::WeakSender<T>) {
+        let depth: Weak<AtomicU64> = Arc::downgrade(&self.message_queue_depth);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(1));
+            loop {
+                interval.tick().await;
+                let (Some(depth), Some(engine_tx)) = (depth.upgrade(), engine_tx.upgrade()) else {
+                    break;
+                };
+                set_queue_depth(&depth, engine_tx.max_capacity() - engine_tx.capacity());
+            }
+        });
+    }
+
+    /// Updates the uptime gauge, measured from `start_time`, every second
+    /// on a background task until these metrics are dropped.
+    pub fn spawn_uptime_updates(&self, start_time: Instant) {
+        let uptime: Weak<AtomicU64> = Arc::downgrade(&self.uptime_seconds);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(1));
+            loop {
+                interval.tick().await;
+                let Some(uptime) = uptime.upgrade() else {
+                    break;
+                };
+                set_uptime(&uptime, start_time.elapsed().as_secs());
+            }
+        });
+    }
+}
+
+impl EngineMetricsInterface for EngineMetrics {
+    fn record_order_accepted(&self) {
+        self.orders_accepted.fetch_add(1, Ordering::Relaxed);
+        metrics::increment_counter!(ORDERS_ACCEPTED);
+    }
+
+    fn record_order_rejected(&self) {
+        self.orders_rejected.fetch_add(1, Ordering::Relaxed);
+        metrics::increment_counter!(ORDERS_REJECTED);
+    }
+
+    fn record_order_cancelled(&self) {
+        self.orders_cancelled.fetch_add(1, Ordering::Relaxed);
+        metrics::increment_counter!(ORDERS_CANCELLED);
+    }
+
+    fn record_trades(&self, count: u64) {
+        if count == 0 {
+            return;
+        }
+        self.trades_executed.fetch_add(count, Ordering::Relaxed);
+        metrics::counter!(TRADES_EXECUTED, count);
+    }
+
+    fn set_order_books(&self, count: u64) {
+        self.order_books.store(count, Ordering::Relaxed);
+        metrics::gauge!(ORDER_BOOKS, count as f64);
+    }
+
+    fn set_book_depth(&self, pair: &TradingPair, side: &str, levels: usize) {
+        self.book_depth
+            .lock()
+            .insert((pair.clone(), side.to_string()), levels);
+        metrics::gauge!(
+            BOOK_DEPTH,
+            levels as f64,
+            "pair" => pair.to_string(),
+            "side" => side.to_string()
+        );
+    }
+
+    fn book_depth(&self, pair: &TradingPair, side: &str) -> Option<usize> {
+        self.book_depth
+            .lock()
+            .get(&(pair.clone(), side.to_string()))
+            .copied()
+    }
+
+    fn record_queue_depth(&self, depth: usize) {
+        set_queue_depth(&self.message_queue_depth, depth);
+    }
+
+    fn increment_orders_submitted(&self, pair: &TradingPair) {
+        *self
+            .orders_submitted
+            .lock()
+            .entry(pair.clone())
+            .or_default() += 1;
+        metrics::increment_counter!(ORDERS_SUBMITTED, "pair" => pair.to_string());
+    }
+
+    fn increment_active_orders(&self, pair: &TradingPair, n: usize) {
+        if n == 0 {
+            return;
+        }
+        let mut active_orders = self.active_orders.lock();
+        let active = active_orders.entry(pair.clone()).or_default();
+        *active += n as u64;
+        metrics::gauge!(ACTIVE_ORDERS, *active as f64, "pair" => pair.to_string());
+    }
+
+    fn decrement_active_orders(&self, pair: &TradingPair, n: usize) {
+        if n == 0 {
+            return;
+        }
+        let mut active_orders = self.active_orders.lock();
+        let active = active_orders.entry(pair.clone()).or_default();
+        *active = active.saturating_sub(n as u64);
+        metrics::gauge!(ACTIVE_ORDERS, *active as f64, "pair" => pair.to_string());
+    }
+
+    fn set_spread(&self, pair: &TradingPair, spread: f64) {
+        self.spread.lock().insert(pair.clone(), spread);
+        metrics::gauge!(SPREAD, spread, "pair" => pair.to_string());
+    }
+
+    fn orders_submitted(&self, pair: &TradingPair) -> u64 {
+        self.orders_submitted.lock().get(pair).copied().unwrap_or(0)
+    }
+
+    fn active_orders(&self, pair: &TradingPair) -> u64 {
+        self.active_orders.lock().get(pair).copied().unwrap_or(0)
+    }
+
+    fn spread(&self, pair: &TradingPair) -> Option<f64> {
+        self.spread.lock().get(pair).copied()
+    }
+
+    fn record_shutdown_duration(&self, duration: Duration) {
+        self.shutdown_duration_ms
+            .store(duration.as_millis() as u64, Ordering::Relaxed);
+        metrics::histogram!(SHUTDOWN_DURATION, duration.as_secs_f64());
+    }
+
+    fn record_shutdown_trades(&self, count: usize) {
+        self.shutdown_trades
+            .fetch_add(count as u64, Ordering::Relaxed);
+        metrics::counter!(SHUTDOWN_TRADES, count as u64);
+    }
+
+    fn record_shutdown_cancelled_orders(&self, count: usize) {
+        self.shutdown_cancelled_orders
+            .fetch_add(count as u64, Ordering::Relaxed);
+        metrics::counter!(SHUTDOWN_CANCELLED_ORDERS, count as u64);
+    }
+
+    fn update_uptime(&self, seconds: u64) {
+        set_uptime(&self.uptime_seconds, seconds);
+    }
+
+    fn snapshot(&self) -> EngineMetricsSnapshot {
+        EngineMetricsSnapshot {
+            orders_accepted: self.orders_accepted.load(Ordering::Relaxed),
+            orders_rejected: self.orders_rejected.load(Ordering::Relaxed),
+            orders_cancelled: self.orders_cancelled.load(Ordering::Relaxed),
+            trades_executed: self.trades_executed.load(Ordering::Relaxed),
+            order_books: self.order_books.load(Ordering::Relaxed),
+            shutdown_duration_ms: self.shutdown_duration_ms.load(Ordering::Relaxed),
+            shutdown_trades: self.shutdown_trades.load(Ordering::Relaxed),
+            shutdown_cancelled_orders: self.shutdown_cancelled_orders.load(Ordering::Relaxed),
+            uptime_seconds: self.uptime_seconds.load(Ordering::Relaxed),
+            message_queue_depth: self.message_queue_depth.load(Ordering::Relaxed),
+        }
+    }
+
+    fn reset(&self) {
+        self.orders_accepted.store(0, Ordering::Relaxed);
+        self.orders_rejected.store(0, Ordering::Relaxed);
+        self.orders_cancelled.store(0, Ordering::Relaxed);
+        self.trades_executed.store(0, Ordering::Relaxed);
+        self.shutdown_duration_ms.store(0, Ordering::Relaxed);
+        self.shutdown_trades.store(0, Ordering::Relaxed);
+        self.shutdown_cancelled_orders.store(0, Ordering::Relaxed);
+        self.orders_submitted.lock().clear();
+        self.set_order_books(0);
+        self.record_queue_depth(0);
+    }
+
+    fn watch_queue_depth(&self, engine_tx: mpsc::WeakSender<Message>) {
+        self.spawn_queue_depth_updates(engine_tx);
+    }
+}
+
+/// Records nothing, for `EngineConfig::benchmark_mode`. Snapshots are all
+/// zero.
+#[derive(Debug, Default)]
+pub struct NoopEngineMetrics;
+
+impl EngineMetricsInterface for NoopEngineMetrics {
+    fn record_order_accepted(&self) {}
+    fn record_order_rejected(&self) {}
+    fn record_order_cancelled(&self) {}
+    fn record_trades(&self, _count: u64) {}
+    fn set_order_books(&self, _count: u64) {}
+    fn set_book_depth(&self, _pair: &TradingPair, _side: &str, _levels: usize) {}
+
+    fn book_depth(&self, _pair: &TradingPair, _side: &str) -> Option<usize> {
+        None
+    }
+
+    fn record_queue_depth(&self, _depth: usize) {}
+    fn increment_orders_submitted(&self, _pair: &TradingPair) {}
+    fn increment_active_orders(&self, _pair: &TradingPair, _n: usize) {}
+    fn decrement_active_orders(&self, _pair: &TradingPair, _n: usize) {}
+    fn set_spread(&self, _pair: &TradingPair, _spread: f64) {}
+
+    fn orders_submitted(&self, _pair: &TradingPair) -> u64 {
+        0
+    }
+
+    fn active_orders(&self, _pair: &TradingPair) -> u64 {
+        0
+    }
+
+    fn spread(&self, _pair: &TradingPair) -> Option<f64> {
+        None
+    }
+
+    fn record_shutdown_duration(&self, _duration: Duration) {}
+    fn record_shutdown_trades(&self, _count: usize) {}
+    fn record_shutdown_cancelled_orders(&self, _count: usize) {}
+    fn update_uptime(&self, _seconds: u64) {}
+
+    fn snapshot(&self) -> EngineMetricsSnapshot {
+        EngineMetricsSnapshot::default()
+    }
+
+    fn reset(&self) {}
+}
+
+fn set_queue_depth(queue_depth: &AtomicU64, depth: usize) {
+    queue_depth.store(depth as u64, Ordering::Relaxed);
+    metrics::gauge!(QUEUE_DEPTH, depth as f64);
+}
+
+fn set_uptime(uptime: &AtomicU64, seconds: u64) {
+    uptime.store(seconds, Ordering::Relaxed);
+    metrics::gauge!(UPTIME, seconds as f64);
+}
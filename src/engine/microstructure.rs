@@ -0,0 +1,238 @@
+use crate::engine::models::{OrderType, Trade};
+use std::collections::VecDeque;
+
+/// `(bid - ask) / (bid + ask)` for resting quantity at one price level, in
+/// [-1, 1]. A side without the level counts as zero, so a one-sided book
+/// gives +1 or -1; `None` when neither side has it.
+pub fn depth_imbalance(bid_qty: Option<f64>, ask_qty: Option<f64>) -> Option<f64> {
+    if bid_qty.is_none() && ask_qty.is_none() {
+        return None;
+    }
+    let (bid_qty, ask_qty) = (bid_qty.unwrap_or(0.0), ask_qty.unwrap_or(0.0));
+    let total = bid_qty + ask_qty;
+    (total > 0.0).then(|| (bid_qty - ask_qty) / total)
+}
+
+/// Effective spread of buying and then selling `trade_size` against resting
+/// `(price, quantity)` levels, best first, in basis points of the mid:
+/// `2 * (avg_buy_price - avg_sell_price) / mid_price * 10_000`. `None` if
+/// either side is too thin to fill `trade_size`.
+pub fn effective_spread_bps(
+    bids: impl IntoIterator<Item = (f64, f64)>,
+    asks: impl IntoIterator<Item = (f64, f64)>,
+    trade_size: f64,
+) -> Option<f64> {
+    let (best_bid, avg_sell_price) = walk_levels(bids, trade_size)?;
+    let (best_ask, avg_buy_price) = walk_levels(asks, trade_size)?;
+    let mid_price = (best_bid + best_ask) / 2.0;
+    Some(2.0 * (avg_buy_price - avg_sell_price) / mid_price * 10_000.0)
+}
+
+/// Expected outcome of taking `quantity` from one side of a book.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PriceImpact {
+    /// Average price of the fills, NaN if nothing would fill.
+    pub estimated_avg_price: f64,
+    pub expected_mid_price_after: f64,
+    /// Move of the mid in basis points, positive when it rises.
+    pub impact_bps: f64,
+}
+
+/// Walks a `side` order of `quantity` through the opposing `(price,
+/// quantity)` levels, best first, without touching the book. The new best
+/// opposing price is the level the walk stops in, or the next one if it
+/// empties that level; an order that sweeps the side leaves the last price
+/// taken. A side that is too thin fills as far as it goes.
+pub fn price_impact(
+    side: &OrderType,
+    bids: impl IntoIterator<Item = (f64, f64)>,
+    asks: impl IntoIterator<Item = (f64, f64)>,
+    quantity: f64,
+) -> PriceImpact {
+    let bids: Vec<(f64, f64)> = bids.into_iter().collect();
+    let asks: Vec<(f64, f64)> = asks.into_iter().collect();
+    let (taken, resting) = match side {
+        OrderType::Buy => (&asks, &bids),
+        OrderType::Sell => (&bids, &asks),
+        // Takes from neither side: the walk fills nothing.
+        OrderType::Convert { .. } => (&vec![], &vec![]),
+    };
+    let mid = |a: Option<f64>, b: Option<f64>| match (a, b) {
+        (Some(a), Some(b)) => Some((a + b) / 2.0),
+        (Some(price), None) | (None, Some(price)) => Some(price),
+        (None, None) => None,
+    };
+
+    let best_resting = resting.first().map(|&(price, _)| price);
+    let mut best_taken = taken.first().map(|&(price, _)| price);
+    let mid_before = mid(best_resting, best_taken).unwrap_or(f64::NAN);
+    let mut remaining = quantity;
+    let (mut filled, mut notional) = (0.0, 0.0);
+    for (index, &(price, level_qty)) in taken.iter().enumerate() {
+        if remaining.is_nan() || remaining <= 0.0 {
+            break;
+        }
+        let fill = level_qty.min(remaining);
+        filled += fill;
+        notional += fill * price;
+        remaining -= fill;
+        best_taken = match taken.get(index + 1) {
+            Some(&(next, _)) if fill >= level_qty => Some(next),
+            _ => Some(price),
+        };
+    }
+
+    let mid_after = mid(best_resting, best_taken).unwrap_or(mid_before);
+    PriceImpact {
+        estimated_avg_price: if filled > 0.0 {
+            notional / filled
+        } else {
+            f64::NAN
+        },
+        expected_mid_price_after: mid_after,
+        impact_bps: if mid_before > 0.0 {
+            (mid_after - mid_before) / mid_before * 10_000.0
+        } else {
+            0.0
+        },
+    }
+}
+
+/// Best price and average fill price of taking `size` from `levels`.
+fn walk_levels(levels: impl IntoIterator<Item = (f64, f64)>, size: f64) -> Option<(f64, f64)> {
+    if size.is_nan() || size <= 0.0 {
+        return None;
+    }
+    let mut levels = levels.into_iter().peekable();
+    let best_price = levels.peek()?.0;
+    let mut remaining = size;
+    let mut notional = 0.0;
+    for (price, quantity) in levels {
+        let filled = quantity.min(remaining);
+        notional += filled * price;
+        remaining -= filled;
+        if remaining <= 0.0 {
+            return Some((best_price, notional / size));
+        }
+    }
+    None
+}
+
+/// Estimates Kyle's lambda as the OLS slope of trade-to-trade price changes
+/// on signed order flow, where buyer-initiated volume counts as positive.
+/// Returns `None` with fewer than two price changes or no variation in flow.
+pub fn kyle_lambda(trades: &[Trade]) -> Option<f64> {
+    let observations: Vec<(f64, f64)> = trades
+        .windows(2)
+        .map(|pair| {
+            let signed_flow = match pair[1].aggressor_side {
+                OrderType::Buy => pair[1].quantity.value(),
+                OrderType::Sell => -pair[1].quantity.value(),
+                OrderType::Convert { .. } => 0.0,
+            };
+            (signed_flow, (pair[1].price - pair[0].price).value())
+        })
+        .collect();
+
+    if observations.len() < 2 {
+        return None;
+    }
+
+    let n = observations.len() as f64;
+    let mean_flow = observations.iter().map(|(flow, _)| flow).sum::<f64>() / n;
+    let mean_change = observations.iter().map(|(_, change)| change).sum::<f64>() / n;
+
+    let (covariance, variance) =
+        observations
+            .iter()
+            .fold((0.0, 0.0), |(covariance, variance), (flow, change)| {
+                let flow_deviation = flow - mean_flow;
+                (
+                    covariance + flow_deviation * (change - mean_change),
+                    variance + flow_deviation * flow_deviation,
+                )
+            });
+
+    if variance == 0.0 {
+        return None;
+    }
+    Some(covariance / variance)
+}
+
+/// Rolling VPIN over equal-volume buckets. Each completed bucket contributes
+/// `|buy_volume - sell_volume| / bucket_volume`, and the estimate is the mean
+/// over the last `num_buckets` of them.
+#[derive(Debug, Clone)]
+pub struct VpinCalculator {
+    bucket_volume: f64,
+    num_buckets: usize,
+    current_buy_volume: f64,
+    current_sell_volume: f64,
+    bucket_imbalances: VecDeque<f64>,
+}
+
+impl VpinCalculator {
+    pub fn new(bucket_volume: f64, num_buckets: usize) -> Self {
+        VpinCalculator {
+            bucket_volume,
+            num_buckets,
+            current_buy_volume: 0.0,
+            current_sell_volume: 0.0,
+            bucket_imbalances: VecDeque::with_capacity(num_buckets),
+        }
+    }
+
+    /// Classifies the trade by its aggressor and fills buckets with it,
+    /// spilling volume into the next bucket when one completes.
+    pub fn add_trade(&mut self, trade: &Trade) {
+        if self.bucket_volume <= 0.0 || self.num_buckets == 0 {
+            return;
+        }
+        let is_buy = match trade.aggressor_side {
+            OrderType::Buy => true,
+            OrderType::Sell => false,
+            OrderType::Convert { .. } => return,
+        };
+
+        let mut remaining = trade.quantity.value();
+        while remaining > 0.0 {
+            let room = self.bucket_volume - self.current_buy_volume - self.current_sell_volume;
+            let filled = remaining.min(room);
+            if is_buy {
+                self.current_buy_volume += filled;
+            } else {
+                self.current_sell_volume += filled;
+            }
+            remaining -= filled;
+
+            if filled >= room {
+                self.complete_bucket();
+            }
+        }
+    }
+
+    pub fn add_trades(&mut self, trades: &[Trade]) {
+        for trade in trades {
+            self.add_trade(trade);
+        }
+    }
+
+    /// `None` until the first bucket has filled.
+    pub fn vpin(&self) -> Option<f64> {
+        if self.bucket_imbalances.is_empty() {
+            return None;
+        }
+        Some(self.bucket_imbalances.iter().sum::<f64>() / self.bucket_imbalances.len() as f64)
+    }
+
+    fn complete_bucket(&mut self) {
+        let imbalance =
+            (self.current_buy_volume - self.current_sell_volume).abs() / self.bucket_volume;
+        if self.bucket_imbalances.len() == self.num_buckets {
+            self.bucket_imbalances.pop_front();
+        }
+        self.bucket_imbalances.push_back(imbalance);
+        self.current_buy_volume = 0.0;
+        self.current_sell_volume = 0.0;
+    }
+}
@@ -1,6 +1,33 @@
+pub mod accounts;
+pub mod algorithms;
+pub mod analytics;
 pub mod api;
+pub mod backtest;
+#[cfg(feature = "serde")]
+pub mod bridge;
+pub mod clock;
 pub mod concurrent;
+pub mod config;
+pub mod convert;
 pub mod core;
+pub mod diff;
+#[cfg(feature = "export")]
+pub mod export;
+pub mod fees;
+pub mod link;
 pub mod lockfree;
+pub mod margin;
+pub mod metrics;
+pub mod microstructure;
 pub mod models;
 pub mod order_book;
+pub mod persistence;
+pub mod position;
+pub mod quotes;
+pub mod reference_data;
+pub mod risk;
+pub mod surveillance;
+#[cfg(feature = "sync-channel")]
+pub mod sync;
+pub mod testing;
+pub mod version;
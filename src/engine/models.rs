@@ -1,23 +1,64 @@
+use crate::engine::algorithms;
+use crate::engine::diff::OrderBookDiff;
+use crate::engine::surveillance::ArbitrageOpportunity;
 use chrono::{DateTime, Utc};
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::{Add, AddAssign, Mul, Sub, SubAssign};
 use std::str::FromStr;
+use std::time::Duration;
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum OrderType {
     Buy,
     Sell,
+    /// Converts `quantity` of `from_asset` into `to_asset`. Never rests: the
+    /// engine routes it through `Message::ConvertCurrency`, and order books
+    /// refuse it.
+    Convert {
+        quantity: f64,
+        from_asset: String,
+        to_asset: String,
+    },
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+/// USD stablecoins for `TradingPair::quote_is_stablecoin`.
+pub const COMMON_STABLECOINS: &[&str] = &["USDT", "USDC", "DAI", "BUSD", "TUSD"];
+
+/// Asset symbols are kept uppercase, so `btc/usdt` and `BTC/USDT` hash to
+/// the same order book. `new`, `from_string` and deserialization all
+/// normalize; only a struct literal can build a lowercase pair.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "RawTradingPair"))]
 pub struct TradingPair {
     pub base: String,
     pub quote: String,
 }
 
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct RawTradingPair {
+    base: String,
+    quote: String,
+}
+
+#[cfg(feature = "serde")]
+impl From<RawTradingPair> for TradingPair {
+    fn from(raw: RawTradingPair) -> Self {
+        TradingPair::new(raw.base, raw.quote)
+    }
+}
+
 impl TradingPair {
-    #[allow(dead_code)]
     pub fn new(base: String, quote: String) -> Self {
-        TradingPair { base, quote }
+        TradingPair {
+            base: base.to_uppercase(),
+            quote: quote.to_uppercase(),
+        }
     }
 
     pub fn from_string(s: &str) -> Result<Self, String> {
@@ -25,10 +66,340 @@ impl TradingPair {
         if parts.len() != 2 {
             return Err("Invalid trading pair format. Use BASE/QUOTE".to_string());
         }
-        Ok(TradingPair {
-            base: parts[0].to_string(),
-            quote: parts[1].to_string(),
-        })
+        Ok(TradingPair::new(parts[0].to_string(), parts[1].to_string()))
+    }
+
+    /// Whether `other` quotes the same two assets the other way round.
+    pub fn is_inverse(&self, other: &TradingPair) -> bool {
+        self.base == other.quote && self.quote == other.base
+    }
+
+    pub fn inverse(&self) -> TradingPair {
+        TradingPair::new(self.quote.clone(), self.base.clone())
+    }
+
+    /// Whether the quote asset is one of `stablecoins`, usually
+    /// `COMMON_STABLECOINS`. Case-insensitive.
+    pub fn quote_is_stablecoin(&self, stablecoins: &[&str]) -> bool {
+        stablecoins
+            .iter()
+            .any(|stablecoin| self.quote.eq_ignore_ascii_case(stablecoin))
+    }
+
+    /// Every ordered pair of distinct assets, so both `A/B` and `B/A`.
+    #[cfg(any(test, feature = "testing"))]
+    pub fn all_combinations(assets: &[&str]) -> Vec<TradingPair> {
+        let mut pairs = Vec::new();
+        for base in assets {
+            for quote in assets.iter().filter(|quote| *quote != base) {
+                pairs.push(TradingPair::new(base.to_string(), quote.to_string()));
+            }
+        }
+        pairs
+    }
+
+    /// Each base quoted in each quote asset, skipping an asset quoted in
+    /// itself.
+    #[cfg(any(test, feature = "testing"))]
+    pub fn spot_matrix(bases: &[&str], quotes: &[&str]) -> Vec<TradingPair> {
+        let mut pairs = Vec::new();
+        for base in bases {
+            for quote in quotes.iter().filter(|quote| *quote != base) {
+                pairs.push(TradingPair::new(base.to_string(), quote.to_string()));
+            }
+        }
+        pairs
+    }
+
+    /// Checks the order's price and quantity against the pair's increments.
+    /// Returns every violation, or an empty vec for a valid order.
+    pub fn validate_order(order: &Order, info: &TradingPairInfo) -> Vec<OrderValidationError> {
+        let mut errors = Vec::new();
+        if !is_multiple_of(order.price.value(), info.tick_size) {
+            errors.push(OrderValidationError::PricePrecision {
+                price: order.price.value(),
+                tick_size: info.tick_size,
+            });
+        }
+        if !is_multiple_of(order.quantity.value(), info.lot_size) {
+            errors.push(OrderValidationError::QuantityPrecision {
+                quantity: order.quantity.value(),
+                lot_size: info.lot_size,
+            });
+        }
+        errors
+    }
+}
+
+/// `%` on decimal steps like 0.01 leaves a remainder just under `step` for
+/// exact multiples, so both ends of the range count as zero.
+fn is_multiple_of(value: f64, step: f64) -> bool {
+    if step <= 0.0 {
+        return true;
+    }
+    let remainder = (value % step).abs();
+    let tolerance = f64::EPSILON * value.abs().max(1.0);
+    remainder < tolerance || (step - remainder) < tolerance
+}
+
+/// Instrument master data for a trading pair.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TradingPairInfo {
+    pub trading_pair: TradingPair,
+    pub tick_size: f64,
+    pub lot_size: f64,
+    /// Display name, e.g. `"Bitcoin / US Dollar"`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub name: Option<String>,
+    /// Last trading time for dated instruments.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub expiry: Option<DateTime<Utc>>,
+    /// Resting orders allowed on each side of the book. Overrides
+    /// `EngineConfig::default_max_orders_per_side`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub max_orders_per_side: Option<usize>,
+    /// Empty-to-non-empty price level ratio above which an add triggers
+    /// `OrderBook::rebalance_price_levels`. Never rebalanced when unset.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub rebalance_threshold: Option<f64>,
+}
+
+impl TradingPairInfo {
+    pub fn new(trading_pair: TradingPair, tick_size: f64, lot_size: f64) -> Self {
+        TradingPairInfo {
+            trading_pair,
+            tick_size,
+            lot_size,
+            name: None,
+            expiry: None,
+            max_orders_per_side: None,
+            rebalance_threshold: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OrderValidationError {
+    PricePrecision {
+        price: f64,
+        tick_size: f64,
+    },
+    QuantityPrecision {
+        quantity: f64,
+        lot_size: f64,
+    },
+    /// The stop is not a positive price, or is on the wrong side of the
+    /// limit: above it for a buy, below it for a sell.
+    StopPrice {
+        stop_price: f64,
+        price: f64,
+    },
+    /// Not a positive number; see `Price::new`.
+    InvalidPrice(f64),
+    /// Negative or NaN; see `Quantity::new`.
+    InvalidQuantity(f64),
+}
+
+impl fmt::Display for OrderValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OrderValidationError::PricePrecision { price, tick_size } => {
+                write!(
+                    f,
+                    "price {} is not a multiple of tick size {}",
+                    price, tick_size
+                )
+            }
+            OrderValidationError::QuantityPrecision { quantity, lot_size } => write!(
+                f,
+                "quantity {} is not a multiple of lot size {}",
+                quantity, lot_size
+            ),
+            OrderValidationError::StopPrice { stop_price, price } => write!(
+                f,
+                "stop price {} is not valid for limit price {}",
+                stop_price, price
+            ),
+            OrderValidationError::InvalidPrice(price) => {
+                write!(f, "price {} is not positive", price)
+            }
+            OrderValidationError::InvalidQuantity(quantity) => {
+                write!(f, "quantity {} is negative", quantity)
+            }
+        }
+    }
+}
+
+impl std::error::Error for OrderValidationError {}
+
+/// A price, kept apart from quantities by the type system. Serialized as a
+/// plain number; deserializing and `From<f64>` do not run the `new` check,
+/// so `Order::validate` still looks at the value.
+#[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct Price(f64);
+
+impl Price {
+    pub fn new(value: f64) -> Result<Self, OrderValidationError> {
+        if value.is_nan() || value <= 0.0 {
+            return Err(OrderValidationError::InvalidPrice(value));
+        }
+        Ok(Price(value))
+    }
+
+    pub fn value(self) -> f64 {
+        self.0
+    }
+}
+
+/// A base-asset quantity. Zero is allowed, for filled orders. Serialized as
+/// a plain number; deserializing and `From<f64>` do not run the `new` check.
+#[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct Quantity(f64);
+
+impl Quantity {
+    pub const ZERO: Quantity = Quantity(0.0);
+
+    pub fn new(value: f64) -> Result<Self, OrderValidationError> {
+        if value.is_nan() || value < 0.0 {
+            return Err(OrderValidationError::InvalidQuantity(value));
+        }
+        Ok(Quantity(value))
+    }
+
+    pub fn value(self) -> f64 {
+        self.0
+    }
+
+    pub fn min(self, other: Quantity) -> Quantity {
+        Quantity(self.0.min(other.0))
+    }
+}
+
+/// Price times quantity, in the quote asset.
+#[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct Notional(pub f64);
+
+impl Add for Price {
+    type Output = Price;
+
+    fn add(self, other: Price) -> Price {
+        Price(self.0 + other.0)
+    }
+}
+
+impl Sub for Price {
+    type Output = Price;
+
+    fn sub(self, other: Price) -> Price {
+        Price(self.0 - other.0)
+    }
+}
+
+impl Mul<Quantity> for Price {
+    type Output = Notional;
+
+    fn mul(self, quantity: Quantity) -> Notional {
+        Notional(self.0 * quantity.0)
+    }
+}
+
+impl Add for Quantity {
+    type Output = Quantity;
+
+    fn add(self, other: Quantity) -> Quantity {
+        Quantity(self.0 + other.0)
+    }
+}
+
+impl Sub for Quantity {
+    type Output = Quantity;
+
+    fn sub(self, other: Quantity) -> Quantity {
+        Quantity(self.0 - other.0)
+    }
+}
+
+impl AddAssign for Quantity {
+    fn add_assign(&mut self, other: Quantity) {
+        self.0 += other.0;
+    }
+}
+
+impl SubAssign for Quantity {
+    fn sub_assign(&mut self, other: Quantity) {
+        self.0 -= other.0;
+    }
+}
+
+impl Mul<Price> for Quantity {
+    type Output = Notional;
+
+    fn mul(self, price: Price) -> Notional {
+        price * self
+    }
+}
+
+impl Add for Notional {
+    type Output = Notional;
+
+    fn add(self, other: Notional) -> Notional {
+        Notional(self.0 + other.0)
+    }
+}
+
+impl From<f64> for Price {
+    fn from(value: f64) -> Price {
+        Price(value)
+    }
+}
+
+impl From<f64> for Quantity {
+    fn from(value: f64) -> Quantity {
+        Quantity(value)
+    }
+}
+
+impl From<Price> for f64 {
+    fn from(price: Price) -> f64 {
+        price.0
+    }
+}
+
+impl From<Quantity> for f64 {
+    fn from(quantity: Quantity) -> f64 {
+        quantity.0
+    }
+}
+
+impl From<Notional> for f64 {
+    fn from(notional: Notional) -> f64 {
+        notional.0
+    }
+}
+
+impl fmt::Display for Price {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// `BASE/QUOTE`, as `TradingPair::from_string` parses.
+impl fmt::Display for TradingPair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.base, self.quote)
     }
 }
 
@@ -39,18 +410,368 @@ impl FromStr for TradingPair {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Order {
     pub id: u64,
     pub trading_pair: TradingPair,
     pub order_type: OrderType,
+    pub price: Price,
+    pub quantity: Quantity,
+    #[cfg_attr(feature = "serde", serde(with = "chrono::serde::ts_seconds"))]
+    pub timestamp: DateTime<Utc>,
+    /// Participant that submitted the order, for per-client risk and
+    /// reporting.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub client_id: Option<String>,
+    /// Connection or session the order arrived on.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub session_id: Option<String>,
+    /// Volume-weighted price of the fills so far, `None` until the first.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub average_fill_price: Option<f64>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub cumulative_filled_quantity: f64,
+    /// Makes this a stop-limit order, held off the book until the market
+    /// trades through this price and then resting as a limit at `price`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub stop_price: Option<f64>,
+    /// When the engine accepted the order, stamped as it goes onto the
+    /// book. `timestamp` is whatever the submitter set.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub received_at: Option<DateTime<Utc>>,
+    /// Where the order arrived in its book, stamped by books that keep
+    /// arrival order; smaller is earlier. Decides which side of a cross was
+    /// resting, where `timestamp` could tie or be set by the submitter.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub arrival_seq: u64,
+}
+
+impl Order {
+    pub fn with_client(mut self, client_id: impl Into<String>) -> Self {
+        self.client_id = Some(client_id.into());
+        self
+    }
+
+    pub fn with_session(mut self, session_id: impl Into<String>) -> Self {
+        self.session_id = Some(session_id.into());
+        self
+    }
+
+    pub fn with_stop_price(mut self, stop_price: f64) -> Self {
+        self.stop_price = Some(stop_price);
+        self
+    }
+
+    /// Time since the order's own `timestamp`.
+    pub fn age(&self, now: DateTime<Utc>) -> chrono::Duration {
+        now - self.timestamp
+    }
+
+    /// Time since the engine received the order, by `received_at`. Orders
+    /// the engine never stamped fall back to their `age`.
+    pub fn time_in_book(&self, now: DateTime<Utc>) -> chrono::Duration {
+        now - self.received_at.unwrap_or(self.timestamp)
+    }
+
+    /// Checks the fields that need no reference data: the price and
+    /// quantity, which `From<f64>` and deserializing let through unchecked,
+    /// and the stop of a stop-limit order. Returns every violation.
+    pub fn validate(&self) -> Vec<OrderValidationError> {
+        let mut errors = Vec::new();
+        let price = self.price.value();
+        if price.is_nan() || price <= 0.0 {
+            errors.push(OrderValidationError::InvalidPrice(price));
+        }
+        let quantity = self.quantity.value();
+        if quantity.is_nan() || quantity < 0.0 {
+            errors.push(OrderValidationError::InvalidQuantity(quantity));
+        }
+        if let Some(stop_price) = self.stop_price {
+            let wrong_side = match self.order_type {
+                OrderType::Buy => stop_price > price,
+                OrderType::Sell => stop_price < price,
+                OrderType::Convert { .. } => true,
+            };
+            if stop_price.is_nan() || stop_price <= 0.0 || wrong_side {
+                errors.push(OrderValidationError::StopPrice { stop_price, price });
+            }
+        }
+        errors
+    }
+
+    /// Whether a trade at `last_price` sets off this stop: at or above the
+    /// stop for a buy, at or below it for a sell. Always false for orders
+    /// without a stop.
+    pub fn is_stop_triggered(&self, last_price: f64) -> bool {
+        match (self.stop_price, &self.order_type) {
+            (Some(stop_price), OrderType::Buy) => last_price >= stop_price,
+            (Some(stop_price), OrderType::Sell) => last_price <= stop_price,
+            (Some(_), OrderType::Convert { .. }) | (None, _) => false,
+        }
+    }
+
+    /// FNV-1a hash of the client, pair, side, price, quantity and timestamp. A
+    /// retried submission of the same order has the same fingerprint even if
+    /// it was given a new id.
+    pub fn fingerprint(&self) -> u64 {
+        const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+        const PRIME: u64 = 0x0100_0000_01b3;
+        // Strings end in 0xff, which never appears in UTF-8, so adjacent
+        // fields cannot run together.
+        let client_id = self.client_id.as_deref().unwrap_or_default();
+        let fields = [
+            client_id.as_bytes(),
+            &[0xff],
+            self.trading_pair.base.as_bytes(),
+            &[0xff],
+            self.trading_pair.quote.as_bytes(),
+            &[0xff],
+            match self.order_type {
+                OrderType::Buy => &[0],
+                OrderType::Sell => &[1],
+                OrderType::Convert { .. } => &[2],
+            },
+            &self.price.value().to_bits().to_le_bytes(),
+            &self.quantity.value().to_bits().to_le_bytes(),
+            &self.timestamp.timestamp().to_le_bytes(),
+            &self.timestamp.timestamp_subsec_nanos().to_le_bytes(),
+        ];
+        fields
+            .iter()
+            .flat_map(|field| field.iter())
+            .fold(OFFSET_BASIS, |hash, &byte| {
+                (hash ^ byte as u64).wrapping_mul(PRIME)
+            })
+    }
+
+    /// Price times remaining quantity, in the quote currency.
+    pub fn notional_value(&self) -> f64 {
+        (self.price * self.quantity).into()
+    }
+
+    pub fn is_buy(&self) -> bool {
+        self.order_type == OrderType::Buy
+    }
+
+    pub fn is_sell(&self) -> bool {
+        self.order_type == OrderType::Sell
+    }
+
+    /// Splits the remaining quantity into two unfilled child orders, the
+    /// first taking `ratio` of it and the second the rest. Children get new
+    /// IDs from the child-order range and otherwise copy the parent.
+    ///
+    /// Panics unless `0.0 < ratio < 1.0`.
+    pub fn split(&self, ratio: f64) -> (Order, Order) {
+        assert!(
+            ratio > 0.0 && ratio < 1.0,
+            "split ratio must be strictly between 0 and 1, got {}",
+            ratio
+        );
+        let child = |quantity| Order {
+            id: algorithms::next_child_order_id(),
+            quantity,
+            average_fill_price: None,
+            cumulative_filled_quantity: 0.0,
+            ..self.clone()
+        };
+        let quantity = self.quantity.value();
+        (
+            child(Quantity(quantity * ratio)),
+            child(Quantity(quantity * (1.0 - ratio))),
+        )
+    }
+
+    /// Records a fill of `qty` at `fill_price`, reducing the remaining
+    /// quantity and folding the price into the running average. Fills that
+    /// are not positive are ignored.
+    pub fn fill(&mut self, qty: Quantity, fill_price: Price) {
+        let (qty, fill_price) = (qty.value(), fill_price.value());
+        if qty.is_nan() || qty <= 0.0 {
+            return;
+        }
+        let filled = self.cumulative_filled_quantity;
+        let average = self.average_fill_price.unwrap_or(0.0);
+        self.average_fill_price = Some((average * filled + fill_price * qty) / (filled + qty));
+        self.cumulative_filled_quantity = filled + qty;
+        self.quantity -= Quantity(qty);
+    }
+
+    /// Reverses a `fill` of `qty` at `fill_price`, for a busted trade.
+    pub fn unfill(&mut self, qty: Quantity, fill_price: Price) {
+        let (qty, fill_price) = (qty.value(), fill_price.value());
+        let filled = self.cumulative_filled_quantity - qty;
+        self.average_fill_price = match self.average_fill_price {
+            Some(average) if filled > 0.0 => {
+                Some((average * self.cumulative_filled_quantity - fill_price * qty) / filled)
+            }
+            _ => None,
+        };
+        self.cumulative_filled_quantity = filled.max(0.0);
+        self.quantity += Quantity(qty);
+    }
+}
+
+/// `Order#7 Buy 1.5 BTC/USD @ 100 [PartiallyFilled]`. The status is `Open`,
+/// `PartiallyFilled` or `Filled`, from the fills so far; an order does not
+/// record being cancelled.
+impl fmt::Display for Order {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = if self.cumulative_filled_quantity <= 0.0 {
+            "Open"
+        } else if self.quantity > Quantity::ZERO {
+            "PartiallyFilled"
+        } else {
+            "Filled"
+        };
+        write!(
+            f,
+            "Order#{} {:?} {} {} @ {} [{}]",
+            self.id, self.order_type, self.quantity, self.trading_pair, self.price, status
+        )
+    }
+}
+
+impl Default for Order {
+    fn default() -> Self {
+        Order {
+            id: 0,
+            trading_pair: TradingPair::new("BTC".to_string(), "USDT".to_string()),
+            order_type: OrderType::Buy,
+            price: Price(0.0),
+            quantity: Quantity::ZERO,
+            timestamp: DateTime::<Utc>::default(),
+            client_id: None,
+            session_id: None,
+            average_fill_price: None,
+            cumulative_filled_quantity: 0.0,
+            stop_price: None,
+            received_at: None,
+            arrival_seq: 0,
+        }
+    }
+}
+
+/// One page of a pair's trade history, oldest first.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TradePage {
+    pub trades: Vec<Trade>,
+    /// Trades in the whole history.
+    pub total_count: u64,
+    /// Whether trades follow this page.
+    pub has_more: bool,
+}
+
+impl TradePage {
+    /// The `limit` trades of `history` after the first `offset`.
+    pub fn from_history(history: &[Trade], offset: u64, limit: usize) -> Self {
+        let start = usize::try_from(offset)
+            .unwrap_or(usize::MAX)
+            .min(history.len());
+        let end = start.saturating_add(limit).min(history.len());
+        TradePage {
+            trades: history[start..end].to_vec(),
+            total_count: history.len() as u64,
+            has_more: end < history.len(),
+        }
+    }
+}
+
+/// Outcome of one `OrderBook::match_orders` call.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MatchResult {
+    pub trades: Vec<Trade>,
+    /// Orders that traded to zero in this call.
+    pub fully_filled: Vec<u64>,
+    /// Orders that traded but still rest with some quantity.
+    pub partially_filled: Vec<u64>,
+    /// Why nothing traded; `None` whenever `trades` is non-empty.
+    pub no_match_reason: Option<String>,
+    /// Held stop orders this call set off, which now rest as limit orders.
+    pub triggered_stops: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum OrderStatus {
+    PartiallyFilled,
+    Filled,
+    /// Cancelled with or without earlier partial fills.
+    Cancelled,
+}
+
+/// One match against an order.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Fill {
     pub price: f64,
     pub quantity: f64,
-    #[serde(with = "chrono::serde::ts_seconds")]
+    pub trade_id: u64,
+    #[cfg_attr(feature = "serde", serde(with = "chrono::serde::ts_seconds"))]
     pub timestamp: DateTime<Utc>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Execution summary for an order that has been filled or cancelled.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FillReport {
+    pub order_id: u64,
+    pub fills: Vec<Fill>,
+    /// Zero until the first fill.
+    pub average_price: f64,
+    pub total_quantity: f64,
+    pub total_fee: f64,
+    pub status: OrderStatus,
+}
+
+impl FillReport {
+    pub fn new(order_id: u64, status: OrderStatus) -> Self {
+        FillReport {
+            order_id,
+            fills: Vec::new(),
+            average_price: 0.0,
+            total_quantity: 0.0,
+            total_fee: 0.0,
+            status,
+        }
+    }
+
+    pub fn add_fill(&mut self, fill: Fill, fee: f64) {
+        let total_quantity = self.total_quantity + fill.quantity;
+        self.average_price = (self.average_price * self.total_quantity
+            + fill.price * fill.quantity)
+            / total_quantity;
+        self.total_quantity = total_quantity;
+        self.total_fee += fee;
+        self.fills.push(fill);
+    }
+
+    /// Takes out the fill from `trade_id`, which charged `fee`. Does
+    /// nothing if the report has no such fill.
+    pub fn remove_fill(&mut self, trade_id: u64, fee: f64) {
+        let Some(index) = self.fills.iter().position(|fill| fill.trade_id == trade_id) else {
+            return;
+        };
+        let fill = self.fills.remove(index);
+        let total_quantity = self.total_quantity - fill.quantity;
+        if self.fills.is_empty() {
+            self.average_price = 0.0;
+            self.total_quantity = 0.0;
+        } else {
+            self.average_price = (self.average_price * self.total_quantity
+                - fill.price * fill.quantity)
+                / total_quantity;
+            self.total_quantity = total_quantity;
+        }
+        self.total_fee -= fee;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Trade {
     pub id: u64,
     pub trading_pair: TradingPair,
@@ -58,8 +779,182 @@ pub struct Trade {
     pub buy_order_id: u64,
     #[allow(dead_code)]
     pub sell_order_id: u64,
-    pub price: f64,
+    pub price: Price,
+    pub quantity: Quantity,
+    /// Side of the incoming order that crossed the spread.
+    pub aggressor_side: OrderType,
+    #[cfg_attr(feature = "serde", serde(with = "chrono::serde::ts_seconds"))]
+    pub timestamp: DateTime<Utc>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub buy_client_id: Option<String>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub sell_client_id: Option<String>,
+    /// Fees charged to each side, in the quote currency unless the fee
+    /// model charged this pair in the base; see `FeeModel::fee_in_quote`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub buy_fee: f64,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub sell_fee: f64,
+}
+
+impl Trade {
+    pub fn notional_value(&self) -> f64 {
+        (self.price * self.quantity).into()
+    }
+}
+
+/// `Trade#3 0.5 BTC/USD @ 100 at 2024-01-01 00:00:00 UTC`.
+impl fmt::Display for Trade {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Trade#{} {} {} @ {} at {}",
+            self.id, self.quantity, self.trading_pair, self.price, self.timestamp
+        )
+    }
+}
+
+/// An isolated-margin derivative position. `quantity` is positive for
+/// longs and negative for shorts.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Position {
     pub quantity: f64,
-    #[serde(with = "chrono::serde::ts_seconds")]
+    pub entry_price: f64,
+    /// Collateral allocated to this position alone.
+    pub margin: f64,
+}
+
+/// Balances available to a client, by asset symbol. Funds locked by resting
+/// orders or allocated to positions are not included.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Account {
+    pub client_id: String,
+    pub balances: HashMap<String, f64>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub positions: HashMap<TradingPair, Position>,
+}
+
+impl Account {
+    pub fn new(client_id: &str) -> Self {
+        Account {
+            client_id: client_id.to_string(),
+            balances: HashMap::new(),
+            positions: HashMap::new(),
+        }
+    }
+
+    pub fn balance(&self, asset: &str) -> f64 {
+        self.balances.get(asset).copied().unwrap_or(0.0)
+    }
+}
+
+/// Top of book for a pair. `seq` increases by one per update on the pair.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BboUpdate {
+    pub pair: TradingPair,
+    pub bid: Option<f64>,
+    pub bid_qty: f64,
+    pub ask: Option<f64>,
+    pub ask_qty: f64,
+    pub seq: u64,
+    #[cfg_attr(feature = "serde", serde(with = "chrono::serde::ts_seconds"))]
     pub timestamp: DateTime<Utc>,
 }
+
+impl BboUpdate {
+    /// Whether both carry the same prices and quantities.
+    pub fn same_quote(&self, other: &BboUpdate) -> bool {
+        (self.bid, self.bid_qty, self.ask, self.ask_qty)
+            == (other.bid, other.bid_qty, other.ask, other.ask_qty)
+    }
+}
+
+/// An external reference price for a pair, e.g. an index or oracle feed.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PriceUpdate {
+    pub trading_pair: TradingPair,
+    pub price: f64,
+    pub source: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum MarketEvent {
+    Trade(Trade),
+    BookDiff(OrderBookDiff),
+    MarkPriceUpdate(PriceUpdate),
+    /// Trading on the pair was halted because the mark moved from
+    /// `reference_price` to `price`, past the configured threshold.
+    CircuitBreakerTripped {
+        trading_pair: TradingPair,
+        reference_price: f64,
+        price: f64,
+    },
+    /// Summary of a match cycle that produced trades, published after its
+    /// `Trade` events.
+    MatchCompleted(MatchResult),
+    /// Top-of-book prices across three pairs imply a profitable cycle.
+    ArbitrageDetected(ArbitrageOpportunity),
+    /// A margin position fell below maintenance margin at `mark_price`.
+    LiquidationTriggered {
+        client_id: String,
+        trading_pair: TradingPair,
+        mark_price: f64,
+    },
+    /// The trade was cancelled as erroneous and its quantity returned to
+    /// both orders. Trade IDs are only unique within a pair's book.
+    TradeBusted {
+        trading_pair: TradingPair,
+        trade_id: u64,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct OhlcvBar {
+    #[cfg_attr(feature = "serde", serde(with = "chrono::serde::ts_seconds"))]
+    pub open_time: DateTime<Utc>,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: f64,
+}
+
+impl OhlcvBar {
+    /// Buckets time-ordered trades at or after `since` into bars of `interval`
+    /// width aligned to the Unix epoch. Intervals with no trades produce no bar.
+    pub fn from_trades(trades: &[Trade], interval: Duration, since: DateTime<Utc>) -> Vec<Self> {
+        let width = interval.as_secs().max(1) as i64;
+        let mut bars: Vec<OhlcvBar> = Vec::new();
+
+        for trade in trades.iter().filter(|trade| trade.timestamp >= since) {
+            let seconds = trade.timestamp.timestamp();
+            let open_seconds = seconds - seconds.rem_euclid(width);
+
+            let (price, quantity) = (trade.price.value(), trade.quantity.value());
+            match bars.last_mut() {
+                Some(bar) if bar.open_time.timestamp() == open_seconds => {
+                    bar.high = bar.high.max(price);
+                    bar.low = bar.low.min(price);
+                    bar.close = price;
+                    bar.volume += quantity;
+                }
+                _ => bars.push(OhlcvBar {
+                    open_time: DateTime::from_timestamp(open_seconds, 0).unwrap_or_default(),
+                    open: price,
+                    high: price,
+                    low: price,
+                    close: price,
+                    volume: quantity,
+                }),
+            }
+        }
+
+        bars
+    }
+}
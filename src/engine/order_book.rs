@@ -1,10 +1,29 @@
-use crate::engine::api::OrderBookEntry;
-use crate::engine::models::{Order, Trade, TradingPair};
+use crate::engine::accounts::AccountError;
+use crate::engine::api::{PriceLadderEntry, PriceLevel};
+use crate::engine::clock::{Clock, SystemClock};
+use crate::engine::convert::ConversionError;
+use crate::engine::diff::{
+    book_checksum, DeltaLog, OrderBookDiff, CHECKSUM_DEPTH, DEFAULT_DELTA_RETENTION,
+};
+use crate::engine::fees::{FeeModel, ZeroFeeModel};
+use crate::engine::microstructure::{self, PriceImpact};
+use crate::engine::models::{
+    Fill, FillReport, MatchResult, Order, OrderStatus, OrderType, OrderValidationError, Price,
+    PriceUpdate, Quantity, Trade, TradePage, TradingPair,
+};
+use crate::engine::persistence::OrderBookSnapshot;
+use crate::engine::reference_data::SharedReferenceData;
+use crate::engine::risk::RiskError;
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use std::cmp::Ordering;
-use std::collections::BTreeMap;
-use tokio::sync::Mutex;
-use tracing::{info, instrument};
+use std::collections::{btree_map, BTreeMap, HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering as AtomicOrdering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, MutexGuard};
+use tracing::{field, info, instrument, warn, Span};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 struct OrderPrice(f64);
@@ -23,23 +42,921 @@ impl PartialOrd for OrderPrice {
     }
 }
 
+/// Why an order was not added to its book.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OrderBookError {
+    TradingHalted(TradingPair),
+    Invalid(OrderValidationError),
+    Risk(RiskError),
+    /// The book's estimated footprint is at `max_memory_per_book`.
+    BookFull {
+        used: usize,
+        limit: usize,
+    },
+    Account(AccountError),
+    /// `side` already holds `limit` resting orders.
+    SideFull {
+        side: OrderType,
+        limit: usize,
+    },
+    /// An order with this fingerprint was accepted within the dedup window;
+    /// see `Order::fingerprint`.
+    DuplicateSubmission(u64),
+    /// The order has a stop price and the book cannot hold stop orders.
+    StopOrdersUnsupported,
+    /// No trade with this ID is in the book's history, or the fill report
+    /// of an order it filled has been pruned.
+    TradeNotFound(u64),
+    /// The book cannot reverse trades.
+    TradeBustUnsupported,
+    /// An account or risk manager has already counted the trade, and a bust
+    /// cannot undo that.
+    TradeSettled(u64),
+    /// New orders at this price are refused while a `PriceLevelLock` on it
+    /// is held.
+    PriceLevelLocked(f64),
+    /// An `OrderType::Convert` order could not be carried out.
+    Conversion(Box<ConversionError>),
+    /// The order is an `OrderType::Convert`, which the engine carries out
+    /// rather than resting in a book.
+    CannotRest,
+}
+
+impl fmt::Display for OrderBookError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OrderBookError::TradingHalted(trading_pair) => {
+                write!(
+                    f,
+                    "trading halted for {}/{}",
+                    trading_pair.base, trading_pair.quote
+                )
+            }
+            OrderBookError::Invalid(e) => write!(f, "{}", e),
+            OrderBookError::Risk(e) => write!(f, "risk check failed: {}", e),
+            OrderBookError::BookFull { used, limit } => {
+                write!(f, "order book is full ({} of {} bytes)", used, limit)
+            }
+            OrderBookError::Account(e) => write!(f, "{}", e),
+            OrderBookError::SideFull { side, limit } => {
+                write!(f, "{:?} side is full ({} orders)", side, limit)
+            }
+            OrderBookError::DuplicateSubmission(fingerprint) => {
+                write!(f, "duplicate submission (fingerprint {:016x})", fingerprint)
+            }
+            OrderBookError::StopOrdersUnsupported => {
+                write!(f, "order book does not support stop orders")
+            }
+            OrderBookError::TradeNotFound(trade_id) => write!(f, "trade {} not found", trade_id),
+            OrderBookError::TradeBustUnsupported => {
+                write!(f, "order book does not support busting trades")
+            }
+            OrderBookError::TradeSettled(trade_id) => {
+                write!(f, "trade {} is settled and cannot be busted", trade_id)
+            }
+            OrderBookError::PriceLevelLocked(price) => {
+                write!(f, "price level {} is locked", price)
+            }
+            OrderBookError::Conversion(e) => write!(f, "{}", e),
+            OrderBookError::CannotRest => write!(f, "convert orders cannot rest in a book"),
+        }
+    }
+}
+
+impl std::error::Error for OrderBookError {}
+
+// Outstanding locks per price.
+type LockedLevels = Arc<parking_lot::Mutex<BTreeMap<OrderPrice, usize>>>;
+
+/// Keeps new orders off one price level until dropped; see
+/// `OrderBook::lock_price_level`. Locks on the same price stack, so the
+/// level opens once the last is dropped.
+#[must_use = "the level is unlocked as soon as the lock is dropped"]
+pub struct PriceLevelLock {
+    locked: LockedLevels,
+    price: OrderPrice,
+}
+
+impl PriceLevelLock {
+    fn new(locked: LockedLevels, price: f64) -> Self {
+        let price = OrderPrice(price);
+        *locked.lock().entry(price).or_default() += 1;
+        PriceLevelLock { locked, price }
+    }
+
+    pub fn price(&self) -> f64 {
+        self.price.0
+    }
+}
+
+impl Drop for PriceLevelLock {
+    fn drop(&mut self) {
+        let mut locked = self.locked.lock();
+        if let btree_map::Entry::Occupied(mut entry) = locked.entry(self.price) {
+            *entry.get_mut() -= 1;
+            if *entry.get() == 0 {
+                entry.remove();
+            }
+        }
+    }
+}
+
+/// Problems found by `OrderBook::check_book_integrity`; empty when the
+/// book is consistent.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IntegrityReport {
+    pub inconsistencies: Vec<String>,
+    /// Orders a secondary index knows of that are not resting in the book.
+    pub orphaned_order_ids: Vec<u64>,
+}
+
+/// Sizes of a book's collections, for spotting one that grows when it
+/// should not; see `SimpleOrderBook::allocation_stats`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AllocationStats {
+    /// Resting bid orders, not levels.
+    pub bid_entries: usize,
+    pub ask_entries: usize,
+    /// Orders in the index by client, the book's only index of resting
+    /// orders. It equals `bid_entries + ask_entries` when every resting
+    /// order has a `client_id`; orders without one are not indexed, so in
+    /// general it is at most that sum, and more means a leak.
+    pub id_index_entries: usize,
+    pub trade_history_len: usize,
+    pub stop_orders: usize,
+    /// Recent submissions remembered for duplicate detection.
+    pub fingerprint_cache_size: usize,
+}
+
+impl AllocationStats {
+    /// More index entries than resting orders, so entries for orders that
+    /// have left the book are not being removed.
+    pub fn index_is_leaking(&self) -> bool {
+        self.id_index_entries > self.bid_entries + self.ask_entries
+    }
+}
+
+/// Checks aggregated levels for what every book must uphold: positive
+/// quantities and prices in priority order on each side.
+fn check_levels(bids: &[PriceLevel], asks: &[PriceLevel]) -> Vec<String> {
+    let mut inconsistencies = Vec::new();
+    for (side, levels, in_order) in [
+        (
+            "bid",
+            bids,
+            (|a: f64, b: f64| a > b) as fn(f64, f64) -> bool,
+        ),
+        ("ask", asks, |a, b| a < b),
+    ] {
+        for level in levels {
+            if level.total_quantity.is_nan() || level.total_quantity < 0.0 {
+                inconsistencies.push(format!(
+                    "{} level {} has quantity {}",
+                    side, level.price, level.total_quantity
+                ));
+            }
+        }
+        for pair in levels.windows(2) {
+            if !in_order(pair[0].price, pair[1].price) {
+                inconsistencies.push(format!(
+                    "{} level {} is out of order after {}",
+                    side, pair[1].price, pair[0].price
+                ));
+            }
+        }
+    }
+    inconsistencies
+}
+
+/// Called with the last trade price after each match that trades.
+pub type PriceUpdateCallback = Arc<dyn Fn(PriceUpdate) + Send + Sync>;
+
+/// `PriceUpdate::source` of the updates books publish after matching.
+pub const LAST_TRADE_SOURCE: &str = "last_trade";
+
 #[async_trait]
 pub trait OrderBook: Send + Sync {
-    async fn add_order(&self, order: Order);
+    /// Rests `order`, or holds it if it has a stop. Refused with
+    /// `OrderBookError::PriceLevelLocked` while its price is locked.
+    async fn add_order(&self, order: Order) -> Result<(), OrderBookError>;
     #[allow(dead_code)]
-    async fn match_orders(&self) -> Vec<Trade>;
+    async fn match_orders(&self) -> MatchResult;
     async fn get_current_price(&self) -> Option<f64>;
-    async fn get_order_book(&self) -> (Vec<OrderBookEntry>, Vec<OrderBookEntry>);
+    async fn get_order_book(&self) -> (Vec<PriceLevel>, Vec<PriceLevel>);
     async fn get_trade_history(&self) -> Vec<Trade>;
     #[allow(dead_code)]
     async fn get_active_orders_count(&self) -> usize;
+    /// Resting orders on `side` alone.
+    async fn get_side_orders_count(&self, side: OrderType) -> usize {
+        self.get_active_orders()
+            .await
+            .iter()
+            .filter(|order| order.order_type == side)
+            .count()
+    }
+    async fn cancel_order(&self, order_id: u64) -> Option<Order>;
+    /// Resting orders on both sides, bids first, in time priority per level.
+    async fn get_active_orders(&self) -> Vec<Order>;
+
+    /// Resting orders submitted by `client_id`, in no particular order.
+    async fn get_active_orders_by_client(&self, client_id: &str) -> Vec<Order> {
+        self.get_active_orders()
+            .await
+            .into_iter()
+            .filter(|order| order.client_id.as_deref() == Some(client_id))
+            .collect()
+    }
+
+    /// Re-adds the snapshot's resting orders. Books that keep a trade
+    /// history should also restore `snapshot.trades`.
+    async fn restore(&self, snapshot: OrderBookSnapshot) {
+        for order in snapshot.orders {
+            let order_id = order.id;
+            if let Err(e) = self.add_order(order).await {
+                warn!("Not restoring order {}: {}", order_id, e);
+            }
+        }
+    }
+
+    /// Adds `orders` on top of whatever already rests, without matching
+    /// them; the next `match_orders` crosses any that overlap. Books that
+    /// match on arrival still trade them as they go in.
+    async fn warm_up(&self, orders: Vec<Order>) {
+        for order in orders {
+            let order_id = order.id;
+            if let Err(e) = self.add_order(order).await {
+                warn!("Not warming up order {}: {}", order_id, e);
+            }
+        }
+    }
+
+    /// `warm_up` with the snapshot's resting orders. Unlike `restore`, the
+    /// snapshot's trades are not added to the history.
+    async fn warm_up_from_snapshot(&self, snapshot: &OrderBookSnapshot) {
+        self.warm_up(snapshot.orders.clone()).await;
+    }
+
+    /// Best bid and best ask levels with their total resting quantity.
+    async fn best_bid_offer(&self) -> (Option<PriceLevel>, Option<PriceLevel>) {
+        let (bids, asks) = self.get_order_book().await;
+        (bids.into_iter().next(), asks.into_iter().next())
+    }
+
+    /// `None` for books that do not report their allocations.
+    async fn get_allocation_stats(&self) -> Option<AllocationStats> {
+        None
+    }
+
+    /// Fills so far for an order that has traded or been cancelled. Books
+    /// that do not keep fill records always return `None`.
+    async fn get_fill_report(&self, _order_id: u64) -> Option<FillReport> {
+        None
+    }
+
+    /// Drops reports of finished orders last updated before `cutoff`,
+    /// returning how many were removed.
+    async fn prune_fill_reports(&self, _cutoff: DateTime<Utc>) -> usize {
+        0
+    }
+
+    /// Fee schedule for trades from the next `match_orders` call on. Books
+    /// that do not charge fees ignore it.
+    fn set_fee_model(&self, _fee_model: Arc<dyn FeeModel>) {}
+
+    /// Clock that trade timestamps and fill report updates are read from.
+    /// Books that do not keep time ignore it.
+    fn set_clock(&self, _clock: Arc<dyn Clock>) {}
+
+    /// Instrument specs to check new orders against in `add_order`. Books
+    /// that do not validate orders ignore them.
+    fn set_reference_data(&self, _reference_data: SharedReferenceData) {}
+
+    /// Callback for the last trade price after each match that trades,
+    /// replacing any set before. Books that do not publish prices ignore it.
+    fn set_price_update_callback(&self, _callback: PriceUpdateCallback) {}
+
+    /// Remembers the fingerprint of `order` for `window`, keeping at most
+    /// `capacity` per book, and fails if it is already remembered. Books
+    /// that do not track submissions accept every order.
+    async fn register_submission(
+        &self,
+        _order: &Order,
+        _window: Duration,
+        _capacity: usize,
+    ) -> Result<(), OrderBookError> {
+        Ok(())
+    }
+
+    /// Forgets the fingerprint `register_submission` remembered for `order`,
+    /// for an order rejected after it was registered.
+    async fn forget_submission(&self, _order: &Order) {}
+
+    /// Whether `register_submission` would reject `order` as a duplicate
+    /// within `window`, without remembering it.
+    async fn is_duplicate_submission(&self, _order: &Order, _window: Duration) -> bool {
+        false
+    }
+
+    /// Empty price levels per level still holding orders, over both sides.
+    /// Infinite if every level is empty.
+    async fn empty_level_ratio(&self) -> f64 {
+        0.0
+    }
+
+    /// Drops price levels left without orders and returns how many were
+    /// removed. Books that never keep empty levels have nothing to do.
+    fn rebalance_price_levels(&mut self) -> usize {
+        0
+    }
+
+    /// Level changes since the previous call, or `None` if nothing changed.
+    /// Books that do not track changes always return `None`.
+    async fn take_diff(&self) -> Option<OrderBookDiff> {
+        None
+    }
+
+    /// Number of price levels holding bids.
+    async fn bid_level_count(&self) -> usize {
+        self.get_order_book().await.0.len()
+    }
+
+    /// Number of price levels holding asks.
+    async fn ask_level_count(&self) -> usize {
+        self.get_order_book().await.1.len()
+    }
+
+    /// Whether `add_order` holds orders with a `stop_price` until they
+    /// trigger. The engine rejects stop orders for books that do not.
+    fn supports_stop_orders(&self) -> bool {
+        false
+    }
+
+    /// Stop orders waiting to trigger, in no particular order.
+    async fn get_stop_orders(&self) -> Vec<Order> {
+        Vec::new()
+    }
+
+    /// Rests every stop order that a trade at `last_price` sets off as a
+    /// plain limit order, returning how many did.
+    async fn trigger_stop_orders(&self, _last_price: f64) -> usize {
+        0
+    }
+
+    /// Number of past diffs to keep apart before compacting older ones.
+    /// Books that do not keep diffs ignore it.
+    fn set_delta_retention(&self, _count: usize) {}
+
+    /// Looks for signs of corruption. The default checks the aggregated
+    /// levels; books with their own indexes also cross-check those.
+    async fn check_book_integrity(&self) -> IntegrityReport {
+        let (bids, asks) = self.get_order_book().await;
+        IntegrityReport {
+            inconsistencies: check_levels(&bids, &asks),
+            orphaned_order_ids: Vec::new(),
+        }
+    }
+
+    /// Refuses new orders at exactly `price` while the returned lock is
+    /// held, e.g. to freeze levels during a call auction. `add_order` and
+    /// everything built on it refuse them with
+    /// `OrderBookError::PriceLevelLocked`. Books that cannot lock levels
+    /// refuse the lock itself with the same error.
+    fn lock_price_level(&self, price: f64) -> Result<PriceLevelLock, OrderBookError> {
+        Err(OrderBookError::PriceLevelLocked(price))
+    }
+
+    fn is_price_level_locked(&self, _price: f64) -> bool {
+        false
+    }
+
+    /// Cancels an erroneous trade: drops it from the history and gives its
+    /// quantity back to both orders, resting them again if it had filled
+    /// them. Returns the busted trade.
+    async fn bust_trade(&self, _trade_id: u64) -> Result<Trade, OrderBookError> {
+        Err(OrderBookError::TradeBustUnsupported)
+    }
+
+    /// The `limit` trades after the first `offset`, oldest first.
+    async fn get_trade_history_paged(&self, offset: u64, limit: usize) -> TradePage {
+        TradePage::from_history(&self.get_trade_history().await, offset, limit)
+    }
+
+    /// Total quantity of the trades executed at or after `since`.
+    async fn get_volume_traded_since(&self, since: DateTime<Utc>) -> f64 {
+        self.get_trade_history()
+            .await
+            .iter()
+            .filter(|trade| trade.timestamp >= since)
+            .map(|trade| trade.quantity.value())
+            .sum()
+    }
+
+    /// Drops trades executed before `cutoff`, returning how many were removed.
+    /// Books without a trade history have nothing to prune.
+    async fn prune_trade_history(&self, _cutoff: DateTime<Utc>) -> usize {
+        0
+    }
+
+    /// Rough size of the book's resting orders and trade history in bytes.
+    async fn estimated_memory_bytes(&self) -> usize {
+        self.get_active_orders_count().await * std::mem::size_of::<Order>()
+            + self.get_trade_history().await.len() * std::mem::size_of::<Trade>()
+    }
+
+    /// Resting quantity on `side` priced within `range_pct` percent of
+    /// `center_price`, inclusive.
+    async fn get_liquidity_within_range(
+        &self,
+        center_price: f64,
+        range_pct: f64,
+        side: OrderType,
+    ) -> f64 {
+        let (low, high) = liquidity_bounds(center_price, range_pct);
+        let (bids, asks) = self.get_order_book().await;
+        let levels = match side {
+            OrderType::Buy => bids,
+            OrderType::Sell => asks,
+            OrderType::Convert { .. } => return 0.0,
+        };
+        levels
+            .iter()
+            .filter(|entry| entry.price >= low && entry.price <= high)
+            .map(|entry| entry.total_quantity)
+            .sum()
+    }
+
+    /// Opposing quantity at prices `order` crosses: how much of it would
+    /// fill if matched now.
+    async fn fillable_quantity(&self, order: &Order) -> f64 {
+        let (bids, asks) = self.get_order_book().await;
+        let price = order.price.value();
+        match order.order_type {
+            OrderType::Buy => asks
+                .iter()
+                .take_while(|level| level.price <= price)
+                .map(|level| level.total_quantity)
+                .sum(),
+            OrderType::Sell => bids
+                .iter()
+                .take_while(|level| level.price >= price)
+                .map(|level| level.total_quantity)
+                .sum(),
+            OrderType::Convert { .. } => 0.0,
+        }
+    }
+
+    /// Quantity imbalance at the `level`th best price on each side, 0 being
+    /// the top of book. See `microstructure::depth_imbalance`.
+    async fn get_depth_imbalance_at_level(&self, level: usize) -> Option<f64> {
+        let (bids, asks) = self.get_order_book().await;
+        microstructure::depth_imbalance(
+            bids.get(level).map(|entry| entry.total_quantity),
+            asks.get(level).map(|entry| entry.total_quantity),
+        )
+    }
+
+    /// Round-trip cost of a `trade_size` market order in basis points,
+    /// walking the book on each side. See `microstructure::effective_spread_bps`.
+    async fn get_effective_spread(&self, trade_size: f64) -> Option<f64> {
+        let (bids, asks) = self.get_order_book().await;
+        let levels = |levels: Vec<PriceLevel>| {
+            levels
+                .into_iter()
+                .map(|entry| (entry.price, entry.total_quantity))
+        };
+        microstructure::effective_spread_bps(levels(bids), levels(asks), trade_size)
+    }
+
+    /// Expected fills and mid move of taking `quantity` on `side`; see
+    /// `microstructure::price_impact`.
+    async fn get_price_impact(&self, side: OrderType, quantity: f64) -> PriceImpact {
+        let (bids, asks) = self.get_order_book().await;
+        let levels = |levels: Vec<PriceLevel>| {
+            levels
+                .into_iter()
+                .map(|entry| (entry.price, entry.total_quantity))
+        };
+        microstructure::price_impact(&side, levels(bids), levels(asks), quantity)
+    }
+
+    /// `levels` rungs either side of `center`, `step` apart, highest price
+    /// first. Each rung sums the levels nearest to it; resting orders
+    /// beyond the outer rungs are left out. Empty unless `step` is positive.
+    async fn price_ladder(&self, center: f64, step: f64, levels: usize) -> Vec<PriceLadderEntry> {
+        let (bids, asks) = self.get_order_book().await;
+        build_price_ladder(center, step, levels, bids, asks)
+    }
+
+    /// Kyle's lambda over the most recent `window` trades.
+    async fn kyle_lambda(&self, window: usize) -> Option<f64> {
+        let history = self.get_trade_history().await;
+        let start = history.len().saturating_sub(window);
+        microstructure::kyle_lambda(&history[start..])
+    }
+}
+
+fn build_price_ladder(
+    center: f64,
+    step: f64,
+    levels: usize,
+    bids: impl IntoIterator<Item = PriceLevel>,
+    asks: impl IntoIterator<Item = PriceLevel>,
+) -> Vec<PriceLadderEntry> {
+    if step.is_nan() || step <= 0.0 {
+        return vec![];
+    }
+    let levels = levels as i64;
+    let mut ladder: Vec<PriceLadderEntry> = (-levels..=levels)
+        .rev()
+        .map(|rung| PriceLadderEntry {
+            price: center + rung as f64 * step,
+            bid_qty: 0.0,
+            ask_qty: 0.0,
+            bid_order_count: 0,
+            ask_order_count: 0,
+        })
+        .collect();
+    let rung_of = |price: f64| {
+        let rung = ((price - center) / step).round();
+        (rung.abs() <= levels as f64).then(|| (levels - rung as i64) as usize)
+    };
+    for level in bids {
+        if let Some(index) = rung_of(level.price) {
+            ladder[index].bid_qty += level.total_quantity;
+            ladder[index].bid_order_count += level.order_count;
+        }
+    }
+    for level in asks {
+        if let Some(index) = rung_of(level.price) {
+            ladder[index].ask_qty += level.total_quantity;
+            ladder[index].ask_order_count += level.order_count;
+        }
+    }
+    ladder
+}
+
+fn liquidity_bounds(center_price: f64, range_pct: f64) -> (f64, f64) {
+    let offset = center_price * range_pct.abs() / 100.0;
+    (center_price - offset, center_price + offset)
+}
+
+/// Levels touched since the last diff, as they were before they were
+/// touched, and the diffs taken so far.
+#[derive(Default)]
+struct DiffTracker {
+    seq: u64,
+    bids: BTreeMap<OrderPrice, PriceLevel>,
+    asks: BTreeMap<OrderPrice, PriceLevel>,
+    log: DeltaLog,
+}
+
+type Side = BTreeMap<OrderPrice, VecDeque<Order>>;
+
+/// Price levels of one side of a `SimpleOrderBook`, best price first: bids
+/// descend and asks ascend. Borrows the side's map without allocating.
+pub struct Levels<'a> {
+    inner: btree_map::Iter<'a, OrderPrice, VecDeque<Order>>,
+    descending: bool,
+}
+
+impl<'a> Levels<'a> {
+    fn bids(orders: &'a Side) -> Self {
+        Levels {
+            inner: orders.iter(),
+            descending: true,
+        }
+    }
+
+    fn asks(orders: &'a Side) -> Self {
+        Levels {
+            inner: orders.iter(),
+            descending: false,
+        }
+    }
+}
+
+impl<'a> Iterator for Levels<'a> {
+    type Item = (f64, &'a VecDeque<Order>);
+
+    /// Skips levels that cancels have emptied.
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (&OrderPrice(price), orders) = if self.descending {
+                self.inner.next_back()?
+            } else {
+                self.inner.next()?
+            };
+            if !orders.is_empty() {
+                return Some((price, orders));
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.inner.size_hint().1)
+    }
+}
+
+/// One side of a `SimpleOrderBook`, locked until dropped.
+pub struct BookSide<'a> {
+    orders: MutexGuard<'a, Side>,
+    is_bid: bool,
+}
+
+impl BookSide<'_> {
+    /// Levels with their resting orders in time priority, best price first.
+    pub fn iter(&self) -> Levels<'_> {
+        if self.is_bid {
+            Levels::bids(&self.orders)
+        } else {
+            Levels::asks(&self.orders)
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a BookSide<'_> {
+    type Item = (f64, &'a VecDeque<Order>);
+    type IntoIter = Levels<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Whether the order has a positive quantity left to trade.
+fn is_tradable(order: &Order) -> bool {
+    order.quantity.value() > 0.0
+}
+
+fn aggregate_level(price: f64, orders: &VecDeque<Order>) -> PriceLevel {
+    PriceLevel {
+        price,
+        total_quantity: orders.iter().map(|order| order.quantity.value()).sum(),
+        order_count: orders.len(),
+    }
+}
+
+/// The level at `price`, empty if nothing rests there.
+fn level_at(orders: &Side, price: OrderPrice) -> PriceLevel {
+    match orders.get(&price) {
+        Some(level) => aggregate_level(price.0, level),
+        None => aggregate_level(price.0, &VecDeque::new()),
+    }
+}
+
+fn record_level(
+    touched: &mut BTreeMap<OrderPrice, PriceLevel>,
+    orders: &BTreeMap<OrderPrice, VecDeque<Order>>,
+    price: OrderPrice,
+) {
+    touched
+        .entry(price)
+        .or_insert_with(|| level_at(orders, price));
+}
+
+/// Queues `order` at the back of its price level, noting the level for the
+/// next diff.
+fn rest_order(
+    orders: &mut Side,
+    tracker: &mut DiffTracker,
+    client_index: &mut ClientIndex,
+    order: Order,
+) {
+    let touched = match order.order_type {
+        OrderType::Buy => &mut tracker.bids,
+        OrderType::Sell => &mut tracker.asks,
+        OrderType::Convert { .. } => unreachable!("convert orders never rest"),
+    };
+    record_level(touched, orders, OrderPrice(order.price.value()));
+    client_index.insert(&order);
+    orders
+        .entry(OrderPrice(order.price.value()))
+        .or_default()
+        .push_back(order);
+}
+
+/// Splits touched levels into the entries they had and the entries they now
+/// have, leaving out levels that ended where they started.
+fn level_changes(
+    touched: &BTreeMap<OrderPrice, PriceLevel>,
+    orders: &BTreeMap<OrderPrice, VecDeque<Order>>,
+) -> (Vec<PriceLevel>, Vec<PriceLevel>) {
+    let mut added = Vec::new();
+    let mut removed = Vec::new();
+    for (&price, old_level) in touched {
+        let new_level = level_at(orders, price);
+        if *old_level == new_level {
+            continue;
+        }
+        if old_level.order_count > 0 {
+            removed.push(old_level.clone());
+        }
+        if new_level.order_count > 0 {
+            added.push(new_level);
+        }
+    }
+    (added, removed)
+}
+
+fn top_levels(levels: Levels<'_>) -> Vec<PriceLevel> {
+    levels
+        .take(CHECKSUM_DEPTH)
+        .map(|(price, orders)| aggregate_level(price, orders))
+        .collect()
+}
+
+/// Resting orders that carry a `client_id`, keyed by client, plus where each
+/// one rests so lookups don't scan the book.
+#[derive(Default)]
+struct ClientIndex {
+    by_client: HashMap<String, HashSet<u64>>,
+    locations: HashMap<u64, (String, OrderType, OrderPrice)>,
+}
+
+impl ClientIndex {
+    fn insert(&mut self, order: &Order) {
+        let Some(client_id) = &order.client_id else {
+            return;
+        };
+        self.by_client
+            .entry(client_id.clone())
+            .or_default()
+            .insert(order.id);
+        self.locations.insert(
+            order.id,
+            (
+                client_id.clone(),
+                order.order_type.clone(),
+                OrderPrice(order.price.value()),
+            ),
+        );
+    }
+
+    fn remove(&mut self, order_id: u64) {
+        let Some((client_id, _, _)) = self.locations.remove(&order_id) else {
+            return;
+        };
+        if let Some(ids) = self.by_client.get_mut(&client_id) {
+            ids.remove(&order_id);
+            if ids.is_empty() {
+                self.by_client.remove(&client_id);
+            }
+        }
+    }
+}
+
+/// Fingerprints of recently accepted orders, oldest first.
+#[derive(Default)]
+struct SubmissionWindow {
+    fingerprints: HashSet<u64>,
+    recent: VecDeque<(u64, DateTime<Utc>)>,
+}
+
+impl SubmissionWindow {
+    fn register(
+        &mut self,
+        fingerprint: u64,
+        now: DateTime<Utc>,
+        window: Duration,
+        capacity: usize,
+    ) -> Result<(), OrderBookError> {
+        while let Some(&(_, at)) = self.recent.front() {
+            if within_window(at, now, window) {
+                break;
+            }
+            self.forget_oldest();
+        }
+        if self.fingerprints.contains(&fingerprint) {
+            return Err(OrderBookError::DuplicateSubmission(fingerprint));
+        }
+        if capacity == 0 {
+            return Ok(());
+        }
+        while self.recent.len() >= capacity {
+            self.forget_oldest();
+        }
+        self.fingerprints.insert(fingerprint);
+        self.recent.push_back((fingerprint, now));
+        Ok(())
+    }
+
+    fn contains(&self, fingerprint: u64, now: DateTime<Utc>, window: Duration) -> bool {
+        self.recent
+            .iter()
+            .any(|&(remembered, at)| remembered == fingerprint && within_window(at, now, window))
+    }
+
+    fn forget(&mut self, fingerprint: u64) {
+        if self.fingerprints.remove(&fingerprint) {
+            self.recent
+                .retain(|&(remembered, _)| remembered != fingerprint);
+        }
+    }
+
+    fn forget_oldest(&mut self) {
+        if let Some((fingerprint, _)) = self.recent.pop_front() {
+            self.fingerprints.remove(&fingerprint);
+        }
+    }
+}
+
+/// Whether `at` is less than `window` before `now`. A time after `now`,
+/// from a clock that was set back, counts as within it.
+fn within_window(at: DateTime<Utc>, now: DateTime<Utc>, window: Duration) -> bool {
+    (now - at).to_std().map_or(true, |elapsed| elapsed < window)
+}
+
+struct FillRecord {
+    report: FillReport,
+    updated_at: DateTime<Utc>,
+    // As of its last fill, to rest it again if a trade that filled it is
+    // busted.
+    order: Order,
+}
+
+fn record_fill(records: &mut HashMap<u64, FillRecord>, order: &Order, trade: &Trade, fee: f64) {
+    let record = records.entry(order.id).or_insert_with(|| FillRecord {
+        report: FillReport::new(order.id, OrderStatus::PartiallyFilled),
+        updated_at: trade.timestamp,
+        order: order.clone(),
+    });
+    record.report.add_fill(
+        Fill {
+            price: trade.price.value(),
+            quantity: trade.quantity.value(),
+            trade_id: trade.id,
+            timestamp: trade.timestamp,
+        },
+        fee,
+    );
+    record.report.status = if order.quantity > Quantity::ZERO {
+        OrderStatus::PartiallyFilled
+    } else {
+        OrderStatus::Filled
+    };
+    record.updated_at = trade.timestamp;
+    record.order = order.clone();
+}
+
+/// Summary figures of a `SimpleOrderBook`, computed on first use after the
+/// book changes and reused until it changes again.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrderBookStats {
+    pub mid_price: Option<f64>,
+    pub spread: Option<f64>,
+    pub best_bid: Option<f64>,
+    pub best_ask: Option<f64>,
+    /// Volume-weighted price of the last 100 trades.
+    pub vwap_100: Option<f64>,
+    /// When these figures were computed.
+    pub last_updated: Instant,
+}
+
+const STATS_VWAP_TRADES: usize = 100;
+
+/// `OrderBookStats` and the book version they were computed at.
+#[derive(Default)]
+struct StatsCache {
+    version: u64,
+    stats: Option<OrderBookStats>,
+}
+
+impl StatsCache {
+    /// The stats, unless the book has changed since they were computed.
+    fn fresh(&self, version: u64) -> Option<OrderBookStats> {
+        self.stats.filter(|_| self.version == version)
+    }
 }
 
 pub struct SimpleOrderBook {
     trading_pair: TradingPair,
-    buy_orders: Mutex<BTreeMap<OrderPrice, Vec<Order>>>,
-    sell_orders: Mutex<BTreeMap<OrderPrice, Vec<Order>>>,
+    buy_orders: Mutex<BTreeMap<OrderPrice, VecDeque<Order>>>,
+    sell_orders: Mutex<BTreeMap<OrderPrice, VecDeque<Order>>>,
     trade_history: Mutex<Vec<Trade>>,
+    // Locked after the side maps.
+    diff_tracker: Mutex<DiffTracker>,
+    fee_model: parking_lot::RwLock<Arc<dyn FeeModel>>,
+    clock: parking_lot::RwLock<Arc<dyn Clock>>,
+    reference_data: parking_lot::RwLock<SharedReferenceData>,
+    // Locked after the side maps and the diff tracker.
+    fill_records: Mutex<HashMap<u64, FillRecord>>,
+    // Locked last.
+    client_index: Mutex<ClientIndex>,
+    // Never held with another lock.
+    submissions: Mutex<SubmissionWindow>,
+    // Bumped after every change to the resting orders or trade history.
+    version: AtomicU64,
+    // Locked first.
+    stats: Mutex<StatsCache>,
+    delta_retention: AtomicUsize,
+    // Never held with another lock.
+    stop_orders: Mutex<HashMap<u64, Order>>,
+    price_callback: parking_lot::RwLock<Option<PriceUpdateCallback>>,
+    // Trade IDs are unique within the book so a trade can be busted by ID.
+    next_trade_id: AtomicU64,
+    // Stamped on each order as it is added, so matching sees arrival order.
+    next_arrival_seq: AtomicU64,
+    locked_levels: LockedLevels,
 }
 
 impl SimpleOrderBook {
@@ -49,81 +966,278 @@ impl SimpleOrderBook {
             buy_orders: Mutex::new(BTreeMap::new()),
             sell_orders: Mutex::new(BTreeMap::new()),
             trade_history: Mutex::new(Vec::new()),
+            diff_tracker: Mutex::new(DiffTracker::default()),
+            fee_model: parking_lot::RwLock::new(Arc::new(ZeroFeeModel)),
+            clock: parking_lot::RwLock::new(Arc::new(SystemClock)),
+            reference_data: parking_lot::RwLock::default(),
+            fill_records: Mutex::new(HashMap::new()),
+            client_index: Mutex::new(ClientIndex::default()),
+            submissions: Mutex::new(SubmissionWindow::default()),
+            version: AtomicU64::new(0),
+            stats: Mutex::new(StatsCache::default()),
+            delta_retention: AtomicUsize::new(DEFAULT_DELTA_RETENTION),
+            stop_orders: Mutex::new(HashMap::new()),
+            price_callback: parking_lot::RwLock::new(None),
+            next_trade_id: AtomicU64::new(1),
+            next_arrival_seq: AtomicU64::new(1),
+            locked_levels: LockedLevels::default(),
+        }
+    }
+
+    /// A book that passes `callback` a `PriceUpdate` with the last trade
+    /// price after each match that trades.
+    pub fn new_with_callback(trading_pair: TradingPair, callback: PriceUpdateCallback) -> Self {
+        let order_book = SimpleOrderBook::new(trading_pair);
+        order_book.set_price_update_callback(callback);
+        order_book
+    }
+
+    /// The cached `OrderBookStats`, recomputed first if the book has changed
+    /// since they were last computed.
+    pub async fn stats(&self) -> OrderBookStats {
+        let mut cache = self.stats.lock().await;
+        let version = self.version.load(AtomicOrdering::Acquire);
+        if let Some(stats) = cache.fresh(version) {
+            return stats;
         }
+
+        let (best_bid, best_ask) = {
+            let buy_orders = self.buy_orders.lock().await;
+            let sell_orders = self.sell_orders.lock().await;
+            (
+                Levels::bids(&buy_orders).next().map(|(price, _)| price),
+                Levels::asks(&sell_orders).next().map(|(price, _)| price),
+            )
+        };
+        let vwap_100 = {
+            let history = self.trade_history.lock().await;
+            let recent = &history[history.len().saturating_sub(STATS_VWAP_TRADES)..];
+            let quantity: f64 = recent.iter().map(|trade| trade.quantity.value()).sum();
+            let notional: f64 = recent.iter().map(|trade| trade.notional_value()).sum();
+            (quantity > 0.0).then(|| notional / quantity)
+        };
+        let both = best_bid.zip(best_ask);
+        let stats = OrderBookStats {
+            mid_price: both.map(|(bid, ask)| (bid + ask) / 2.0),
+            spread: both.map(|(bid, ask)| ask - bid),
+            best_bid,
+            best_ask,
+            vwap_100,
+            last_updated: Instant::now(),
+        };
+        *cache = StatsCache {
+            version,
+            stats: Some(stats),
+        };
+        stats
+    }
+
+    pub async fn get_spread(&self) -> Option<f64> {
+        self.stats().await.spread
+    }
+
+    pub async fn get_mid_price(&self) -> Option<f64> {
+        self.stats().await.mid_price
+    }
+
+    /// Volume-weighted price of the last 100 trades.
+    pub async fn get_vwap(&self) -> Option<f64> {
+        self.stats().await.vwap_100
+    }
+
+    /// One diff taking a copy of the book at diff `since_seq` to the state
+    /// after the latest `take_diff`; changes not yet taken are left for the
+    /// next one. Once `since_seq` is older than the retained diffs, the
+    /// result is the whole book against an empty one, and the copy should
+    /// be cleared before applying it.
+    #[instrument(level = "debug", skip_all, fields(pair = ?self.trading_pair, since_seq = since_seq))]
+    pub async fn take_snapshot_delta(&self, since_seq: u64) -> OrderBookDiff {
+        let tracker = self.diff_tracker.lock().await;
+        tracker.log.delta(self.trading_pair.clone(), since_seq)
+    }
+
+    fn invalidate_stats(&self) {
+        self.version.fetch_add(1, AtomicOrdering::Release);
+    }
+
+    /// A book holding `orders`, in the order given. Nothing is matched.
+    pub fn from_orders(trading_pair: TradingPair, orders: impl IntoIterator<Item = Order>) -> Self {
+        let mut order_book = SimpleOrderBook::new(trading_pair);
+        order_book.extend(orders);
+        order_book
     }
-}
 
-#[async_trait]
-impl OrderBook for SimpleOrderBook {
-    #[instrument(skip(self))]
-    async fn add_order(&self, order: Order) {
-        let start = std::time::Instant::now();
-        let orders = match order.order_type {
-            crate::engine::models::OrderType::Buy => &self.buy_orders,
-            crate::engine::models::OrderType::Sell => &self.sell_orders,
+    /// Moves every resting and stop order from `other` into this book, as
+    /// after a pair rename, retagging each with this book's pair. Orders
+    /// keep their time priority within `other` but queue behind this book's
+    /// orders at the same price. Nothing is matched, and `other`'s trades and
+    /// fill reports are dropped, as are its orders at a price locked in this
+    /// book. Returns how many orders moved.
+    pub fn merge_with(&mut self, other: SimpleOrderBook) -> usize {
+        let orders: Vec<Order> = other
+            .buy_orders
+            .into_inner()
+            .into_values()
+            .chain(other.sell_orders.into_inner().into_values())
+            .flatten()
+            .chain(other.stop_orders.into_inner().into_values())
+            .map(|order| Order {
+                trading_pair: self.trading_pair.clone(),
+                ..order
+            })
+            .collect();
+        let held = |book: &mut SimpleOrderBook| {
+            book.buy_orders
+                .get_mut()
+                .values()
+                .map(VecDeque::len)
+                .sum::<usize>()
+                + book
+                    .sell_orders
+                    .get_mut()
+                    .values()
+                    .map(VecDeque::len)
+                    .sum::<usize>()
+                + book.stop_orders.get_mut().len()
         };
+        let before = held(self);
+        self.extend(orders);
+        held(self) - before
+    }
 
-        let mut orders = orders.lock().await;
-        orders
-            .entry(OrderPrice(order.price))
-            .or_insert_with(Vec::new)
-            .push(order);
+    /// Counts what each collection holds, taking every lock in turn.
+    pub async fn allocation_stats(&self) -> AllocationStats {
+        let count = |orders: &BTreeMap<OrderPrice, VecDeque<Order>>| -> usize {
+            orders.values().map(VecDeque::len).sum()
+        };
+        let (bid_entries, ask_entries, id_index_entries) = {
+            let buy_orders = self.buy_orders.lock().await;
+            let sell_orders = self.sell_orders.lock().await;
+            let client_index = self.client_index.lock().await;
+            (
+                count(&buy_orders),
+                count(&sell_orders),
+                client_index.locations.len(),
+            )
+        };
+        AllocationStats {
+            bid_entries,
+            ask_entries,
+            id_index_entries,
+            trade_history_len: self.trade_history.lock().await.len(),
+            stop_orders: self.stop_orders.lock().await.len(),
+            fingerprint_cache_size: self.submissions.lock().await.fingerprints.len(),
+        }
+    }
 
-        info!(
-            duration_ms = ?start.elapsed().as_millis(),
-            "Order added to order book."
-        );
+    /// Locks the bid side for iteration, highest price first.
+    pub async fn bids(&self) -> BookSide<'_> {
+        BookSide {
+            orders: self.buy_orders.lock().await,
+            is_bid: true,
+        }
+    }
+
+    /// Locks the ask side for iteration, lowest price first.
+    pub async fn asks(&self) -> BookSide<'_> {
+        BookSide {
+            orders: self.sell_orders.lock().await,
+            is_bid: false,
+        }
     }
 
-    async fn match_orders(&self) -> Vec<Trade> {
+    /// One pass of matching crossed levels, without stop orders or the
+    /// price callback.
+    async fn match_crossed(&self) -> MatchResult {
         let mut buy_orders = self.buy_orders.lock().await;
         let mut sell_orders = self.sell_orders.lock().await;
+        let mut tracker = self.diff_tracker.lock().await;
+        let fee_model = self.fee_model.read().clone();
+        let clock = self.clock.read().clone();
+        let mut fill_records = self.fill_records.lock().await;
         let mut trades = Vec::new();
+        let mut touched = Vec::new();
+        let mut fully_filled = HashSet::new();
+        let mut dropped = Vec::new();
 
-        loop {
-            let buy_max = buy_orders
-                .keys()
-                .next_back()
-                .map(|&OrderPrice(price)| price);
-            let sell_min = sell_orders.keys().next().map(|&OrderPrice(price)| price);
+        let no_match_reason = loop {
+            let buy_max = Levels::bids(&buy_orders).next().map(|(price, _)| price);
+            let sell_min = Levels::asks(&sell_orders).next().map(|(price, _)| price);
 
             match (buy_max, sell_min) {
                 (Some(buy_price), Some(sell_price)) if buy_price >= sell_price => {
+                    record_level(&mut tracker.bids, &buy_orders, OrderPrice(buy_price));
+                    record_level(&mut tracker.asks, &sell_orders, OrderPrice(sell_price));
                     let buy_list = buy_orders.get_mut(&OrderPrice(buy_price)).unwrap();
                     let sell_list = sell_orders.get_mut(&OrderPrice(sell_price)).unwrap();
 
-                    let mut i = 0;
-                    let mut j = 0;
-
-                    while i < buy_list.len() && j < sell_list.len() {
-                        let buy = &mut buy_list[i];
-                        let sell = &mut sell_list[j];
+                    while let (Some(buy), Some(sell)) =
+                        (buy_list.front_mut(), sell_list.front_mut())
+                    {
+                        // An order without a positive quantity, e.g. from a
+                        // restored snapshot, would never fill and never leave
+                        // the level; drop it instead of matching it.
+                        let (buy_dead, sell_dead) = (!is_tradable(buy), !is_tradable(sell));
+                        if buy_dead || sell_dead {
+                            if buy_dead {
+                                dropped.extend(buy_list.pop_front());
+                            }
+                            if sell_dead {
+                                dropped.extend(sell_list.pop_front());
+                            }
+                            continue;
+                        }
                         let trade_quantity = buy.quantity.min(sell.quantity);
+                        // The resting order sets the price, so a marketable order
+                        // never trades at its own limit.
+                        let (trade_price, aggressor_side) = if buy.arrival_seq < sell.arrival_seq {
+                            (Price::from(buy_price), OrderType::Sell)
+                        } else {
+                            (Price::from(sell_price), OrderType::Buy)
+                        };
 
-                        trades.push(Trade {
-                            id: (trades.len() as u64) + 1,
+                        let mut trade = Trade {
+                            id: self.next_trade_id.fetch_add(1, AtomicOrdering::Relaxed),
                             trading_pair: self.trading_pair.clone(),
                             buy_order_id: buy.id,
                             sell_order_id: sell.id,
-                            price: sell_price,
+                            price: trade_price,
                             quantity: trade_quantity,
-                            timestamp: chrono::Utc::now(),
-                        });
+                            aggressor_side,
+                            timestamp: clock.now(),
+                            buy_client_id: buy.client_id.clone(),
+                            sell_client_id: sell.client_id.clone(),
+                            buy_fee: 0.0,
+                            sell_fee: 0.0,
+                        };
+                        fee_model.charge(&mut trade);
 
-                        buy.quantity -= trade_quantity;
-                        sell.quantity -= trade_quantity;
+                        buy.fill(trade_quantity, trade_price);
+                        sell.fill(trade_quantity, trade_price);
+                        record_fill(&mut fill_records, buy, &trade, trade.buy_fee);
+                        record_fill(&mut fill_records, sell, &trade, trade.sell_fee);
+                        trades.push(trade);
+                        for order in [&*buy, &*sell] {
+                            if !touched.contains(&order.id) {
+                                touched.push(order.id);
+                            }
+                            if order.quantity == Quantity::ZERO {
+                                fully_filled.insert(order.id);
+                            }
+                        }
 
-                        if buy.quantity == 0.0 {
-                            i += 1;
+                        let (buy_done, sell_done) = (
+                            buy.quantity == Quantity::ZERO,
+                            sell.quantity == Quantity::ZERO,
+                        );
+                        if buy_done {
+                            buy_list.pop_front();
                         }
-                        if sell.quantity == 0.0 {
-                            j += 1;
+                        if sell_done {
+                            sell_list.pop_front();
                         }
                     }
 
-                    buy_list.drain(0..i);
-                    sell_list.drain(0..j);
-
                     if buy_list.is_empty() {
                         buy_orders.remove(&OrderPrice(buy_price));
                     }
@@ -131,20 +1245,189 @@ impl OrderBook for SimpleOrderBook {
                         sell_orders.remove(&OrderPrice(sell_price));
                     }
                 }
-                _ => {
-                    break;
+                (None, None) => break "order book is empty".to_string(),
+                (Some(_), None) => break "no asks".to_string(),
+                (None, Some(_)) => break "no bids".to_string(),
+                (Some(buy_price), Some(sell_price)) => {
+                    break format!("best bid {} is below best ask {}", buy_price, sell_price)
                 }
             }
-        }
+        };
 
         let mut history = self.trade_history.lock().await;
         for trade in &trades {
             history.push(trade.clone());
         }
 
-        trades
+        let mut client_index = self.client_index.lock().await;
+        for &order_id in &fully_filled {
+            client_index.remove(order_id);
+        }
+        for order in &dropped {
+            info!(
+                order_id = order.id,
+                quantity = order.quantity.value(),
+                "Dropped resting order without a positive quantity"
+            );
+            client_index.remove(order.id);
+        }
+        if !trades.is_empty() {
+            self.invalidate_stats();
+        }
+
+        drop((
+            buy_orders,
+            sell_orders,
+            tracker,
+            fill_records,
+            history,
+            client_index,
+        ));
+
+        let (fully_filled, partially_filled) = touched
+            .into_iter()
+            .partition(|id| fully_filled.contains(id));
+        MatchResult {
+            no_match_reason: trades.is_empty().then_some(no_match_reason),
+            trades,
+            fully_filled,
+            partially_filled,
+            triggered_stops: 0,
+        }
+    }
+}
+
+/// Adds each order as `add_order` would, without matching. Needs no locks,
+/// since the book is borrowed mutably. Orders at a locked price are dropped.
+impl Extend<Order> for SimpleOrderBook {
+    fn extend<I: IntoIterator<Item = Order>>(&mut self, orders: I) {
+        let locked_levels = self.locked_levels.lock();
+        let tracker = self.diff_tracker.get_mut();
+        let client_index = self.client_index.get_mut();
+        for mut order in orders {
+            let side = match order.order_type {
+                OrderType::Buy => self.buy_orders.get_mut(),
+                OrderType::Sell => self.sell_orders.get_mut(),
+                OrderType::Convert { .. } => {
+                    warn!("Skipping convert order {}, which cannot rest.", order.id);
+                    continue;
+                }
+            };
+            if locked_levels.contains_key(&OrderPrice(order.price.value())) {
+                warn!(
+                    "Dropping order {} at locked price {}.",
+                    order.id, order.price
+                );
+                continue;
+            }
+            order.arrival_seq = *self.next_arrival_seq.get_mut();
+            *self.next_arrival_seq.get_mut() += 1;
+            if order.stop_price.is_some() {
+                self.stop_orders.get_mut().insert(order.id, order);
+                continue;
+            }
+            rest_order(side, tracker, client_index, order);
+        }
+        *self.version.get_mut() += 1;
+    }
+}
+
+#[async_trait]
+impl OrderBook for SimpleOrderBook {
+    #[instrument(level = "debug", skip_all, fields(pair = ?self.trading_pair, order_id = order.id))]
+    async fn add_order(&self, mut order: Order) -> Result<(), OrderBookError> {
+        let orders = match order.order_type {
+            OrderType::Buy => &self.buy_orders,
+            OrderType::Sell => &self.sell_orders,
+            OrderType::Convert { .. } => return Err(OrderBookError::CannotRest),
+        };
+        if self.is_price_level_locked(order.price.value()) {
+            return Err(OrderBookError::PriceLevelLocked(order.price.value()));
+        }
+        if let Some(info) = self.reference_data.read().read().get(&order.trading_pair) {
+            if let Some(e) = TradingPair::validate_order(&order, info).into_iter().next() {
+                return Err(OrderBookError::Invalid(e));
+            }
+        }
+        order.arrival_seq = self.next_arrival_seq.fetch_add(1, AtomicOrdering::Relaxed);
+        if order.stop_price.is_some() {
+            info!(
+                stop_price = order.stop_price,
+                "Holding stop order {} until triggered.", order
+            );
+            self.stop_orders.lock().await.insert(order.id, order);
+            return Ok(());
+        }
+        let start = std::time::Instant::now();
+
+        let mut orders = orders.lock().await;
+        let mut tracker = self.diff_tracker.lock().await;
+        let mut client_index = self.client_index.lock().await;
+        rest_order(&mut orders, &mut tracker, &mut client_index, order);
+        self.invalidate_stats();
+
+        info!(
+            duration_ms = ?start.elapsed().as_millis(),
+            "Order added to order book."
+        );
+        Ok(())
+    }
+
+    #[instrument(level = "debug", skip_all, fields(pair = ?self.trading_pair, trades = field::Empty))]
+    async fn match_orders(&self) -> MatchResult {
+        // Stops the last trade before this call already set off.
+        let last_price = self
+            .trade_history
+            .lock()
+            .await
+            .last()
+            .map(|trade| trade.price.value());
+        let mut triggered_stops = match last_price {
+            Some(last_price) => self.trigger_stop_orders(last_price).await,
+            None => 0,
+        };
+
+        // Stops set off by this call's trades rest and match in it too,
+        // until a pass triggers nothing more.
+        let mut result = self.match_crossed().await;
+        while let Some(last_price) = result.trades.last().map(|trade| trade.price.value()) {
+            let triggered = self.trigger_stop_orders(last_price).await;
+            if triggered == 0 {
+                break;
+            }
+            triggered_stops += triggered;
+            let next = self.match_crossed().await;
+            if next.trades.is_empty() {
+                break;
+            }
+            result
+                .partially_filled
+                .retain(|id| !next.fully_filled.contains(id));
+            for id in next.partially_filled {
+                if !result.partially_filled.contains(&id) {
+                    result.partially_filled.push(id);
+                }
+            }
+            result.fully_filled.extend(next.fully_filled);
+            result.trades.extend(next.trades);
+        }
+        result.triggered_stops = triggered_stops;
+
+        // Called after every lock is released, so the callback may use the
+        // book.
+        let callback = self.price_callback.read().clone();
+        if let (Some(callback), Some(trade)) = (callback, result.trades.last()) {
+            callback(PriceUpdate {
+                trading_pair: self.trading_pair.clone(),
+                price: trade.price.value(),
+                source: LAST_TRADE_SOURCE.to_string(),
+            });
+        }
+        Span::current().record("trades", result.trades.len());
+        result
     }
 
+    #[instrument(level = "debug", skip_all, fields(pair = ?self.trading_pair))]
     async fn get_trade_history(&self) -> Vec<Trade> {
         let history = self.trade_history.lock().await;
         let mut result = Vec::new();
@@ -154,21 +1437,29 @@ impl OrderBook for SimpleOrderBook {
         result
     }
 
+    #[instrument(level = "debug", skip_all, fields(pair = ?self.trading_pair, offset = offset, limit = limit))]
+    async fn get_trade_history_paged(&self, offset: u64, limit: usize) -> TradePage {
+        TradePage::from_history(&self.trade_history.lock().await, offset, limit)
+    }
+
+    #[instrument(level = "debug", skip_all, fields(pair = ?self.trading_pair))]
     async fn get_current_price(&self) -> Option<f64> {
         info!("Getting current price from order book");
         let buy_orders = self.buy_orders.lock().await;
         let sell_orders = self.sell_orders.lock().await;
 
-        let price = match (buy_orders.keys().next_back(), sell_orders.keys().next()) {
-            (Some(&OrderPrice(bid)), Some(&OrderPrice(ask))) => {
+        let best_bid = Levels::bids(&buy_orders).next().map(|(price, _)| price);
+        let best_ask = Levels::asks(&sell_orders).next().map(|(price, _)| price);
+        let price = match (best_bid, best_ask) {
+            (Some(bid), Some(ask)) => {
                 info!("Found bid and ask prices");
                 Some((bid + ask) / 2.0)
             }
-            (Some(&OrderPrice(bid)), None) => {
+            (Some(bid), None) => {
                 info!("Found only bid price");
                 Some(bid)
             }
-            (None, Some(&OrderPrice(ask))) => {
+            (None, Some(ask)) => {
                 info!("Found only ask price");
                 Some(ask)
             }
@@ -181,30 +1472,41 @@ impl OrderBook for SimpleOrderBook {
         price
     }
 
-    async fn get_order_book(&self) -> (Vec<OrderBookEntry>, Vec<OrderBookEntry>) {
+    #[instrument(level = "debug", skip_all, fields(pair = ?self.trading_pair, bids = field::Empty, asks = field::Empty))]
+    async fn get_order_book(&self) -> (Vec<PriceLevel>, Vec<PriceLevel>) {
         let buy_orders = self.buy_orders.lock().await;
         let sell_orders = self.sell_orders.lock().await;
 
-        let bids: Vec<OrderBookEntry> = buy_orders
-            .iter()
-            .rev()
-            .map(|(&OrderPrice(price), orders)| {
-                let quantity: f64 = orders.iter().map(|order| order.quantity).sum();
-                OrderBookEntry { price, quantity }
-            })
+        let bids: Vec<PriceLevel> = Levels::bids(&buy_orders)
+            .map(|(price, orders)| aggregate_level(price, orders))
             .collect();
 
-        let asks: Vec<OrderBookEntry> = sell_orders
-            .iter()
-            .map(|(&OrderPrice(price), orders)| {
-                let quantity: f64 = orders.iter().map(|order| order.quantity).sum();
-                OrderBookEntry { price, quantity }
-            })
+        let asks: Vec<PriceLevel> = Levels::asks(&sell_orders)
+            .map(|(price, orders)| aggregate_level(price, orders))
             .collect();
 
+        Span::current()
+            .record("bids", bids.len())
+            .record("asks", asks.len());
         (bids, asks)
     }
 
+    #[instrument(level = "debug", skip_all, fields(pair = ?self.trading_pair, ?side))]
+    async fn get_side_orders_count(&self, side: OrderType) -> usize {
+        let orders = match side {
+            OrderType::Buy => &self.buy_orders,
+            OrderType::Sell => &self.sell_orders,
+            OrderType::Convert { .. } => return 0,
+        };
+        orders
+            .lock()
+            .await
+            .values()
+            .map(|orders| orders.len())
+            .sum()
+    }
+
+    #[instrument(level = "debug", skip_all, fields(pair = ?self.trading_pair))]
     async fn get_active_orders_count(&self) -> usize {
         let buy_count = self
             .buy_orders
@@ -224,4 +1526,560 @@ impl OrderBook for SimpleOrderBook {
 
         buy_count + sell_count
     }
+    #[instrument(level = "debug", skip_all, fields(pair = ?self.trading_pair, order_id = order_id, cancelled = field::Empty))]
+    async fn cancel_order(&self, order_id: u64) -> Option<Order> {
+        if let Some(order) = self.stop_orders.lock().await.remove(&order_id) {
+            Span::current().record("cancelled", true);
+            return Some(order);
+        }
+        for (is_bid, orders) in [(true, &self.buy_orders), (false, &self.sell_orders)] {
+            let mut orders = orders.lock().await;
+            let found = orders.iter().find_map(|(&price, list)| {
+                list.iter()
+                    .position(|order| order.id == order_id)
+                    .map(|index| (price, index))
+            });
+
+            if let Some((price, index)) = found {
+                let mut tracker = self.diff_tracker.lock().await;
+                let touched = if is_bid {
+                    &mut tracker.bids
+                } else {
+                    &mut tracker.asks
+                };
+                record_level(touched, &orders, price);
+
+                // A level this empties stays in the map until
+                // `rebalance_price_levels` sweeps it.
+                let order = orders.get_mut(&price).unwrap().remove(index)?;
+
+                let now = self.clock.read().now();
+                let mut fill_records = self.fill_records.lock().await;
+                let record = fill_records.entry(order_id).or_insert_with(|| FillRecord {
+                    report: FillReport::new(order_id, OrderStatus::Cancelled),
+                    updated_at: now,
+                    order: order.clone(),
+                });
+                record.report.status = OrderStatus::Cancelled;
+                record.updated_at = now;
+                self.client_index.lock().await.remove(order_id);
+                self.invalidate_stats();
+                Span::current().record("cancelled", true);
+                return Some(order);
+            }
+        }
+        Span::current().record("cancelled", false);
+        None
+    }
+
+    #[instrument(level = "debug", skip_all, fields(pair = ?self.trading_pair))]
+    async fn get_active_orders(&self) -> Vec<Order> {
+        let buy_orders = self.buy_orders.lock().await;
+        let sell_orders = self.sell_orders.lock().await;
+
+        buy_orders
+            .values()
+            .rev()
+            .chain(sell_orders.values())
+            .flatten()
+            .cloned()
+            .collect()
+    }
+
+    #[instrument(level = "debug", skip_all, fields(pair = ?self.trading_pair, orders = snapshot.orders.len()))]
+    async fn restore(&self, snapshot: OrderBookSnapshot) {
+        for order in snapshot.orders {
+            let order_id = order.id;
+            if let Err(e) = self.add_order(order).await {
+                warn!("Not restoring order {}: {}", order_id, e);
+            }
+        }
+        if let Some(last_id) = snapshot.trades.iter().map(|trade| trade.id).max() {
+            self.next_trade_id
+                .fetch_max(last_id + 1, AtomicOrdering::Relaxed);
+        }
+        self.trade_history.lock().await.extend(snapshot.trades);
+        self.invalidate_stats();
+    }
+
+    async fn get_liquidity_within_range(
+        &self,
+        center_price: f64,
+        range_pct: f64,
+        side: OrderType,
+    ) -> f64 {
+        let (low, high) = liquidity_bounds(center_price, range_pct);
+        let orders = match side {
+            OrderType::Buy => self.buy_orders.lock().await,
+            OrderType::Sell => self.sell_orders.lock().await,
+            OrderType::Convert { .. } => return 0.0,
+        };
+        orders
+            .range(OrderPrice(low)..=OrderPrice(high))
+            .flat_map(|(_, orders)| orders.iter())
+            .map(|order| order.quantity.value())
+            .sum()
+    }
+
+    #[instrument(level = "debug", skip_all, fields(pair = ?self.trading_pair, client_id = client_id))]
+    async fn get_active_orders_by_client(&self, client_id: &str) -> Vec<Order> {
+        let buy_orders = self.buy_orders.lock().await;
+        let sell_orders = self.sell_orders.lock().await;
+        let client_index = self.client_index.lock().await;
+        let Some(ids) = client_index.by_client.get(client_id) else {
+            return vec![];
+        };
+
+        ids.iter()
+            .filter_map(|order_id| {
+                let (_, side, price) = client_index.locations.get(order_id)?;
+                let orders = match side {
+                    OrderType::Buy => &buy_orders,
+                    OrderType::Sell => &sell_orders,
+                    OrderType::Convert { .. } => unreachable!("convert orders never rest"),
+                };
+                orders
+                    .get(price)?
+                    .iter()
+                    .find(|order| order.id == *order_id)
+                    .cloned()
+            })
+            .collect()
+    }
+
+    #[instrument(level = "debug", skip_all, fields(pair = ?self.trading_pair, level = level))]
+    async fn get_depth_imbalance_at_level(&self, level: usize) -> Option<f64> {
+        let buy_orders = self.buy_orders.lock().await;
+        let sell_orders = self.sell_orders.lock().await;
+        let quantity = |(_, orders): (f64, &VecDeque<Order>)| {
+            orders.iter().map(|order| order.quantity.value()).sum()
+        };
+        microstructure::depth_imbalance(
+            Levels::bids(&buy_orders).nth(level).map(quantity),
+            Levels::asks(&sell_orders).nth(level).map(quantity),
+        )
+    }
+
+    #[instrument(level = "debug", skip_all, fields(pair = ?self.trading_pair, trade_size = trade_size))]
+    async fn get_effective_spread(&self, trade_size: f64) -> Option<f64> {
+        let buy_orders = self.buy_orders.lock().await;
+        let sell_orders = self.sell_orders.lock().await;
+        let level = |(price, orders): (f64, &VecDeque<Order>)| {
+            (
+                price,
+                orders.iter().map(|order| order.quantity.value()).sum(),
+            )
+        };
+        microstructure::effective_spread_bps(
+            Levels::bids(&buy_orders).map(level),
+            Levels::asks(&sell_orders).map(level),
+            trade_size,
+        )
+    }
+
+    #[instrument(level = "debug", skip_all, fields(pair = ?self.trading_pair, ?side, quantity = quantity))]
+    async fn get_price_impact(&self, side: OrderType, quantity: f64) -> PriceImpact {
+        let buy_orders = self.buy_orders.lock().await;
+        let sell_orders = self.sell_orders.lock().await;
+        let level = |(price, orders): (f64, &VecDeque<Order>)| {
+            (
+                price,
+                orders.iter().map(|order| order.quantity.value()).sum(),
+            )
+        };
+        microstructure::price_impact(
+            &side,
+            Levels::bids(&buy_orders).map(level),
+            Levels::asks(&sell_orders).map(level),
+            quantity,
+        )
+    }
+
+    #[instrument(level = "debug", skip_all, fields(pair = ?self.trading_pair, center = center, step = step))]
+    async fn price_ladder(&self, center: f64, step: f64, levels: usize) -> Vec<PriceLadderEntry> {
+        let buy_orders = self.buy_orders.lock().await;
+        let sell_orders = self.sell_orders.lock().await;
+        let level = |(price, orders): (f64, &VecDeque<Order>)| aggregate_level(price, orders);
+        build_price_ladder(
+            center,
+            step,
+            levels,
+            Levels::bids(&buy_orders).map(level),
+            Levels::asks(&sell_orders).map(level),
+        )
+    }
+
+    #[instrument(level = "debug", skip_all, fields(pair = ?self.trading_pair))]
+    async fn best_bid_offer(&self) -> (Option<PriceLevel>, Option<PriceLevel>) {
+        let buy_orders = self.buy_orders.lock().await;
+        let sell_orders = self.sell_orders.lock().await;
+        let level = |(price, orders): (f64, &VecDeque<Order>)| aggregate_level(price, orders);
+        (
+            Levels::bids(&buy_orders).next().map(level),
+            Levels::asks(&sell_orders).next().map(level),
+        )
+    }
+
+    #[instrument(level = "debug", skip_all, fields(pair = ?self.trading_pair, order_id = order_id))]
+    async fn get_fill_report(&self, order_id: u64) -> Option<FillReport> {
+        self.fill_records
+            .lock()
+            .await
+            .get(&order_id)
+            .map(|record| record.report.clone())
+    }
+
+    #[instrument(level = "debug", skip_all, fields(pair = ?self.trading_pair, pruned = field::Empty))]
+    async fn prune_fill_reports(&self, cutoff: DateTime<Utc>) -> usize {
+        let mut fill_records = self.fill_records.lock().await;
+        let before = fill_records.len();
+        fill_records.retain(|_, record| {
+            record.report.status == OrderStatus::PartiallyFilled || record.updated_at >= cutoff
+        });
+        let pruned = before - fill_records.len();
+        Span::current().record("pruned", pruned);
+        pruned
+    }
+
+    fn lock_price_level(&self, price: f64) -> Result<PriceLevelLock, OrderBookError> {
+        Ok(PriceLevelLock::new(self.locked_levels.clone(), price))
+    }
+
+    async fn get_allocation_stats(&self) -> Option<AllocationStats> {
+        Some(self.allocation_stats().await)
+    }
+
+    #[instrument(level = "debug", skip_all, fields(pair = ?self.trading_pair))]
+    async fn check_book_integrity(&self) -> IntegrityReport {
+        let buy_orders = self.buy_orders.lock().await;
+        let sell_orders = self.sell_orders.lock().await;
+        let client_index = self.client_index.lock().await;
+
+        let bids: Vec<PriceLevel> = Levels::bids(&buy_orders)
+            .map(|(price, orders)| aggregate_level(price, orders))
+            .collect();
+        let asks: Vec<PriceLevel> = Levels::asks(&sell_orders)
+            .map(|(price, orders)| aggregate_level(price, orders))
+            .collect();
+        let mut inconsistencies = check_levels(&bids, &asks);
+
+        // Where each resting order actually is.
+        let mut resting: HashMap<u64, (OrderType, OrderPrice)> = HashMap::new();
+        for (side, orders) in [
+            (OrderType::Buy, &*buy_orders),
+            (OrderType::Sell, &*sell_orders),
+        ] {
+            for (&price, level) in orders {
+                for order in level {
+                    if order.order_type != side || order.price.value() != price.0 {
+                        inconsistencies.push(format!(
+                            "order {} ({:?} at {}) rests on the {:?} side at {}",
+                            order.id, order.order_type, order.price, side, price.0
+                        ));
+                    }
+                    let quantity = order.quantity.value();
+                    if quantity.is_nan() || quantity <= 0.0 {
+                        inconsistencies.push(format!(
+                            "order {} rests with quantity {}",
+                            order.id, order.quantity
+                        ));
+                    }
+                    if resting.insert(order.id, (side.clone(), price)).is_some() {
+                        inconsistencies.push(format!("order {} rests more than once", order.id));
+                    }
+                    if order.client_id.is_some() && !client_index.locations.contains_key(&order.id)
+                    {
+                        inconsistencies.push(format!(
+                            "order {} is missing from the client index",
+                            order.id
+                        ));
+                    }
+                }
+            }
+        }
+
+        let mut orphaned_order_ids: Vec<u64> = client_index
+            .locations
+            .iter()
+            .filter(|&(order_id, (_, side, price))| {
+                resting.get(order_id) != Some(&(side.clone(), *price))
+            })
+            .map(|(&order_id, _)| order_id)
+            .collect();
+        orphaned_order_ids.sort_unstable();
+        IntegrityReport {
+            inconsistencies,
+            orphaned_order_ids,
+        }
+    }
+
+    fn is_price_level_locked(&self, price: f64) -> bool {
+        self.locked_levels.lock().contains_key(&OrderPrice(price))
+    }
+
+    #[instrument(level = "debug", skip_all, fields(pair = ?self.trading_pair, trade_id = trade_id))]
+    async fn bust_trade(&self, trade_id: u64) -> Result<Trade, OrderBookError> {
+        let mut buy_orders = self.buy_orders.lock().await;
+        let mut sell_orders = self.sell_orders.lock().await;
+        let mut tracker = self.diff_tracker.lock().await;
+        let mut fill_records = self.fill_records.lock().await;
+        let mut history = self.trade_history.lock().await;
+        let mut client_index = self.client_index.lock().await;
+
+        let index = history
+            .iter()
+            .position(|trade| trade.id == trade_id)
+            .ok_or(OrderBookError::TradeNotFound(trade_id))?;
+        let trade = &history[index];
+        for order_id in [trade.buy_order_id, trade.sell_order_id] {
+            if !fill_records.contains_key(&order_id) {
+                return Err(OrderBookError::TradeNotFound(trade_id));
+            }
+        }
+        let trade = history.remove(index);
+
+        let DiffTracker { bids, asks, .. } = &mut *tracker;
+        for (order_id, fee, orders, touched) in [
+            (trade.buy_order_id, trade.buy_fee, &mut *buy_orders, bids),
+            (trade.sell_order_id, trade.sell_fee, &mut *sell_orders, asks),
+        ] {
+            let record = fill_records.get_mut(&order_id).unwrap();
+            record.report.remove_fill(trade.id, fee);
+            record.order.unfill(trade.quantity, trade.price);
+            // A cancelled order gets its quantity back but stays cancelled.
+            if record.report.status == OrderStatus::Cancelled {
+                continue;
+            }
+            let price = OrderPrice(record.order.price.value());
+            record_level(touched, orders, price);
+
+            let resting = orders
+                .get_mut(&price)
+                .and_then(|level| level.iter_mut().find(|order| order.id == order_id));
+            match resting {
+                Some(order) => order.unfill(trade.quantity, trade.price),
+                None => {
+                    // Back to where its arrival puts it in the queue.
+                    let order = record.order.clone();
+                    client_index.insert(&order);
+                    let level = orders.entry(price).or_default();
+                    let position = level
+                        .iter()
+                        .position(|resting| resting.arrival_seq > order.arrival_seq)
+                        .unwrap_or(level.len());
+                    level.insert(position, order);
+                }
+            }
+            if record.report.fills.is_empty() {
+                fill_records.remove(&order_id);
+            } else {
+                record.report.status = OrderStatus::PartiallyFilled;
+            }
+        }
+        self.invalidate_stats();
+        Ok(trade)
+    }
+
+    #[instrument(level = "debug", skip_all, fields(pair = ?self.trading_pair))]
+    fn set_fee_model(&self, fee_model: Arc<dyn FeeModel>) {
+        *self.fee_model.write() = fee_model;
+    }
+
+    #[instrument(level = "debug", skip_all, fields(pair = ?self.trading_pair))]
+    fn set_clock(&self, clock: Arc<dyn Clock>) {
+        *self.clock.write() = clock;
+    }
+
+    fn set_reference_data(&self, reference_data: SharedReferenceData) {
+        *self.reference_data.write() = reference_data;
+    }
+
+    #[instrument(level = "debug", skip_all, fields(pair = ?self.trading_pair))]
+    fn set_price_update_callback(&self, callback: PriceUpdateCallback) {
+        *self.price_callback.write() = Some(callback);
+    }
+
+    #[instrument(level = "debug", skip_all, fields(pair = ?self.trading_pair, order_id = order.id))]
+    async fn register_submission(
+        &self,
+        order: &Order,
+        window: Duration,
+        capacity: usize,
+    ) -> Result<(), OrderBookError> {
+        let now = self.clock.read().now();
+        self.submissions
+            .lock()
+            .await
+            .register(order.fingerprint(), now, window, capacity)
+    }
+
+    #[instrument(level = "debug", skip_all, fields(pair = ?self.trading_pair, order_id = order.id))]
+    async fn forget_submission(&self, order: &Order) {
+        self.submissions.lock().await.forget(order.fingerprint());
+    }
+
+    #[instrument(level = "debug", skip_all, fields(pair = ?self.trading_pair, order_id = order.id))]
+    async fn is_duplicate_submission(&self, order: &Order, window: Duration) -> bool {
+        let now = self.clock.read().now();
+        self.submissions
+            .lock()
+            .await
+            .contains(order.fingerprint(), now, window)
+    }
+
+    #[instrument(level = "debug", skip_all, fields(pair = ?self.trading_pair))]
+    async fn empty_level_ratio(&self) -> f64 {
+        let buy_orders = self.buy_orders.lock().await;
+        let sell_orders = self.sell_orders.lock().await;
+        let levels = buy_orders.len() + sell_orders.len();
+        let empty = buy_orders
+            .values()
+            .chain(sell_orders.values())
+            .filter(|orders| orders.is_empty())
+            .count();
+        if empty == 0 {
+            return 0.0;
+        }
+        empty as f64 / (levels - empty) as f64
+    }
+
+    #[instrument(level = "debug", skip_all, fields(pair = ?self.trading_pair, removed = field::Empty))]
+    fn rebalance_price_levels(&mut self) -> usize {
+        let mut removed = 0;
+        for orders in [self.buy_orders.get_mut(), self.sell_orders.get_mut()] {
+            let before = orders.len();
+            orders.retain(|_, orders| !orders.is_empty());
+            removed += before - orders.len();
+        }
+        Span::current().record("removed", removed);
+        removed
+    }
+
+    #[instrument(level = "debug", skip_all, fields(pair = ?self.trading_pair))]
+    async fn take_diff(&self) -> Option<OrderBookDiff> {
+        let buy_orders = self.buy_orders.lock().await;
+        let sell_orders = self.sell_orders.lock().await;
+        let mut tracker = self.diff_tracker.lock().await;
+
+        let (bids_added, bids_removed) = level_changes(&tracker.bids, &buy_orders);
+        let (asks_added, asks_removed) = level_changes(&tracker.asks, &sell_orders);
+        tracker.bids.clear();
+        tracker.asks.clear();
+
+        if bids_added.is_empty()
+            && bids_removed.is_empty()
+            && asks_added.is_empty()
+            && asks_removed.is_empty()
+        {
+            return None;
+        }
+
+        tracker.seq += 1;
+        let checksum = book_checksum(
+            &top_levels(Levels::bids(&buy_orders)),
+            &top_levels(Levels::asks(&sell_orders)),
+        );
+        let diff = OrderBookDiff {
+            pair: self.trading_pair.clone(),
+            seq: tracker.seq,
+            bids_added,
+            bids_removed,
+            asks_added,
+            asks_removed,
+            checksum,
+        };
+        let retention = self.delta_retention.load(AtomicOrdering::Relaxed);
+        tracker.log.push(diff.clone(), retention);
+        Some(diff)
+    }
+
+    #[instrument(level = "debug", skip_all, fields(pair = ?self.trading_pair, count = count))]
+    fn set_delta_retention(&self, count: usize) {
+        self.delta_retention.store(count, AtomicOrdering::Relaxed);
+    }
+
+    /// The map's length, so a level left empty counts until
+    /// `rebalance_price_levels` drops it.
+    #[instrument(level = "debug", skip_all, fields(pair = ?self.trading_pair))]
+    async fn bid_level_count(&self) -> usize {
+        self.buy_orders.lock().await.len()
+    }
+
+    #[instrument(level = "debug", skip_all, fields(pair = ?self.trading_pair))]
+    async fn ask_level_count(&self) -> usize {
+        self.sell_orders.lock().await.len()
+    }
+
+    fn supports_stop_orders(&self) -> bool {
+        true
+    }
+
+    #[instrument(level = "debug", skip_all, fields(pair = ?self.trading_pair))]
+    async fn get_stop_orders(&self) -> Vec<Order> {
+        self.stop_orders.lock().await.values().cloned().collect()
+    }
+
+    /// Triggered stops rest in submission order, as though they had been
+    /// plain limit orders placed at that moment.
+    #[instrument(level = "debug", skip_all, fields(pair = ?self.trading_pair, last_price = last_price, triggered = field::Empty))]
+    async fn trigger_stop_orders(&self, last_price: f64) -> usize {
+        let mut triggered: Vec<Order> = {
+            let mut stop_orders = self.stop_orders.lock().await;
+            // One at a locked price stays held until the level opens.
+            let ids: Vec<u64> = stop_orders
+                .values()
+                .filter(|order| {
+                    order.is_stop_triggered(last_price)
+                        && !self.is_price_level_locked(order.price.value())
+                })
+                .map(|order| order.id)
+                .collect();
+            ids.iter().filter_map(|id| stop_orders.remove(id)).collect()
+        };
+        triggered.sort_by_key(|order| order.arrival_seq);
+
+        let mut count = 0;
+        for mut order in triggered {
+            order.stop_price = None;
+            let order_id = order.id;
+            match self.add_order(order).await {
+                Ok(()) => count += 1,
+                Err(e) => warn!("Dropping triggered stop order {}: {}", order_id, e),
+            }
+        }
+        Span::current().record("triggered", count);
+        count
+    }
+
+    /// History is appended in match order, so it is sorted by timestamp and
+    /// the trades since `since` are a suffix.
+    #[instrument(level = "debug", skip_all, fields(pair = ?self.trading_pair))]
+    async fn get_volume_traded_since(&self, since: DateTime<Utc>) -> f64 {
+        let history = self.trade_history.lock().await;
+        let start = history.partition_point(|trade| trade.timestamp < since);
+        history[start..]
+            .iter()
+            .map(|trade| trade.quantity.value())
+            .sum()
+    }
+
+    #[instrument(level = "debug", skip_all, fields(pair = ?self.trading_pair, pruned = field::Empty))]
+    async fn prune_trade_history(&self, cutoff: DateTime<Utc>) -> usize {
+        let mut history = self.trade_history.lock().await;
+        let before = history.len();
+        history.retain(|trade| trade.timestamp >= cutoff);
+        let pruned = before - history.len();
+        if pruned > 0 {
+            self.invalidate_stats();
+        }
+        Span::current().record("pruned", pruned);
+        pruned
+    }
+
+    #[instrument(level = "debug", skip_all, fields(pair = ?self.trading_pair))]
+    async fn estimated_memory_bytes(&self) -> usize {
+        let order_count = self.get_active_orders_count().await;
+        let trade_count = self.trade_history.lock().await.len();
+        order_count * std::mem::size_of::<Order>() + trade_count * std::mem::size_of::<Trade>()
+    }
 }
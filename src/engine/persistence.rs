@@ -0,0 +1,170 @@
+use crate::engine::config::EngineConfig;
+#[cfg(feature = "serde")]
+use crate::engine::config::SerializationFormat;
+use crate::engine::models::{Order, Trade, TradingPair};
+use parking_lot::Mutex;
+#[cfg(feature = "serde")]
+use serde::de::DeserializeOwned;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+#[cfg(feature = "serde")]
+use std::fs;
+use std::io;
+#[cfg(feature = "serde")]
+use std::path::Path;
+use std::sync::Arc;
+
+/// Identifies an engine state file ("BENG").
+pub const STATE_MAGIC: u32 = 0x4245_4E47;
+/// Bumped whenever `EngineState` changes shape.
+pub const STATE_VERSION: u32 = 1;
+
+#[cfg(feature = "serde")]
+pub fn encode<T: Serialize>(value: &T, format: SerializationFormat) -> io::Result<Vec<u8>> {
+    match format {
+        SerializationFormat::Json => Ok(serde_json::to_vec(value)?),
+        #[cfg(feature = "bincode-serde")]
+        SerializationFormat::Bincode => {
+            bincode::serde::encode_to_vec(value, bincode::config::standard())
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        }
+        #[cfg(not(feature = "bincode-serde"))]
+        SerializationFormat::Bincode => Err(bincode_unsupported()),
+    }
+}
+
+#[cfg(feature = "serde")]
+pub fn decode<T: DeserializeOwned>(bytes: &[u8], format: SerializationFormat) -> io::Result<T> {
+    match format {
+        SerializationFormat::Json => Ok(serde_json::from_slice(bytes)?),
+        #[cfg(feature = "bincode-serde")]
+        SerializationFormat::Bincode => {
+            bincode::serde::decode_from_slice(bytes, bincode::config::standard())
+                .map(|(value, _)| value)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        }
+        #[cfg(not(feature = "bincode-serde"))]
+        SerializationFormat::Bincode => Err(bincode_unsupported()),
+    }
+}
+
+#[cfg(all(feature = "serde", not(feature = "bincode-serde")))]
+fn bincode_unsupported() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        "bincode support requires the bincode-serde feature",
+    )
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct OrderBookSnapshot {
+    pub trading_pair: TradingPair,
+    pub orders: Vec<Order>,
+    pub trades: Vec<Trade>,
+}
+
+/// Where `Engine::save_snapshots` keeps order books, one snapshot per pair.
+pub trait PersistenceBackend: Send + Sync {
+    /// Replaces any snapshot already stored for the pair.
+    fn save_snapshot(&self, snapshot: OrderBookSnapshot) -> io::Result<()>;
+    fn load_snapshot(&self, trading_pair: &TradingPair) -> io::Result<Option<OrderBookSnapshot>>;
+}
+
+/// Keeps snapshots in memory, for tests and embedded use without a
+/// filesystem. Clones share the same store.
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryPersistenceBackend {
+    snapshots: Arc<Mutex<HashMap<TradingPair, OrderBookSnapshot>>>,
+}
+
+impl InMemoryPersistenceBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl PersistenceBackend for InMemoryPersistenceBackend {
+    fn save_snapshot(&self, snapshot: OrderBookSnapshot) -> io::Result<()> {
+        self.snapshots
+            .lock()
+            .insert(snapshot.trading_pair.clone(), snapshot);
+        Ok(())
+    }
+
+    fn load_snapshot(&self, trading_pair: &TradingPair) -> io::Result<Option<OrderBookSnapshot>> {
+        Ok(self.snapshots.lock().get(trading_pair).cloned())
+    }
+}
+
+/// The leading fields of `EngineState`, decoded on their own so a foreign
+/// or newer file is rejected before its body is parsed.
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct StateHeader {
+    magic: u32,
+    version: u32,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct EngineState {
+    pub magic: u32,
+    pub version: u32,
+    pub config: EngineConfig,
+    pub order_books: Vec<OrderBookSnapshot>,
+    pub next_child_order_id: u64,
+}
+
+impl EngineState {
+    pub fn new(
+        config: EngineConfig,
+        order_books: Vec<OrderBookSnapshot>,
+        next_child_order_id: u64,
+    ) -> Self {
+        EngineState {
+            magic: STATE_MAGIC,
+            version: STATE_VERSION,
+            config,
+            order_books,
+            next_child_order_id,
+        }
+    }
+
+    /// Writes to a sibling temporary file first and renames it into place,
+    /// so a crash mid-write never leaves a truncated state file behind.
+    #[cfg(feature = "serde")]
+    pub fn write_to(&self, path: &Path, format: SerializationFormat) -> io::Result<()> {
+        let bytes = encode(self, format)?;
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, bytes)?;
+        fs::rename(&tmp_path, path)
+    }
+
+    /// Accepts either format: JSON files always open with `{`, which cannot
+    /// start a bincode-encoded state.
+    #[cfg(feature = "serde")]
+    pub fn read_from(path: &Path) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+        let format = if bytes.first() == Some(&b'{') {
+            SerializationFormat::Json
+        } else {
+            SerializationFormat::Bincode
+        };
+        let header: StateHeader = decode(&bytes, format)?;
+        if header.magic != STATE_MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not an engine state file",
+            ));
+        }
+        if header.version > STATE_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported engine state version {}", header.version),
+            ));
+        }
+        decode(&bytes, format)
+    }
+}
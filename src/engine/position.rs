@@ -0,0 +1,159 @@
+use crate::engine::margin::MarginCalculator;
+use crate::engine::models::{Position, Trade, TradingPair};
+use dashmap::DashMap;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::warn;
+
+/// Called from the engine loop, in trade order, whenever a trade changes a
+/// client's net position. Hooks run before the engine moves on to the next
+/// message, so they should return quickly.
+pub trait PositionChangeHook: Send + Sync {
+    fn on_position_change(
+        &self,
+        client_id: &str,
+        pair: &TradingPair,
+        old_qty: f64,
+        new_qty: f64,
+        trade: &Trade,
+    );
+}
+
+/// Net quantity per client and pair over every trade the engine matches:
+/// buys add, sells subtract. Sides without a `client_id` are not tracked.
+#[derive(Default)]
+pub struct PositionTracker {
+    positions: HashMap<(String, TradingPair), f64>,
+    hooks: Vec<Arc<dyn PositionChangeHook>>,
+}
+
+impl PositionTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_hook(&mut self, hook: Arc<dyn PositionChangeHook>) {
+        self.hooks.push(hook);
+    }
+
+    pub fn position(&self, client_id: &str, pair: &TradingPair) -> f64 {
+        self.positions
+            .get(&(client_id.to_string(), pair.clone()))
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    /// Updates both sides of `trade` and runs the hooks for each, buyer
+    /// first.
+    pub fn apply_trade(&mut self, trade: &Trade) {
+        let sides = [
+            (&trade.buy_client_id, trade.quantity.value()),
+            (&trade.sell_client_id, -trade.quantity.value()),
+        ];
+        for (client_id, signed_quantity) in sides {
+            let Some(client_id) = client_id else {
+                continue;
+            };
+            let key = (client_id.clone(), trade.trading_pair.clone());
+            let position = self.positions.entry(key).or_insert(0.0);
+            let old_qty = *position;
+            *position += signed_quantity;
+            let new_qty = *position;
+            if new_qty == 0.0 {
+                self.positions
+                    .remove(&(client_id.clone(), trade.trading_pair.clone()));
+            }
+
+            for hook in &self.hooks {
+                hook.on_position_change(client_id, &trade.trading_pair, old_qty, new_qty, trade);
+            }
+        }
+    }
+
+    /// Undoes `apply_trade` for a busted trade. Hooks see it as the same
+    /// trade with buyer and seller swapped.
+    pub fn reverse_trade(&mut self, trade: &Trade) {
+        let reversed = Trade {
+            buy_client_id: trade.sell_client_id.clone(),
+            sell_client_id: trade.buy_client_id.clone(),
+            ..trade.clone()
+        };
+        self.apply_trade(&reversed);
+    }
+}
+
+/// Flags positions that would be below maintenance margin at the price they
+/// last traded at. Each position is assumed to hold the initial margin for
+/// its size at its average entry price.
+pub struct LiquidationHook {
+    calculator: MarginCalculator,
+    positions: DashMap<(String, TradingPair), Position>,
+    /// Liquidation price of each flagged position.
+    flagged: DashMap<(String, TradingPair), f64>,
+}
+
+impl LiquidationHook {
+    pub fn new(calculator: MarginCalculator) -> Self {
+        LiquidationHook {
+            calculator,
+            positions: DashMap::new(),
+            flagged: DashMap::new(),
+        }
+    }
+
+    /// The liquidation price if the client's position is flagged.
+    pub fn flagged(&self, client_id: &str, pair: &TradingPair) -> Option<f64> {
+        self.flagged
+            .get(&(client_id.to_string(), pair.clone()))
+            .map(|price| *price)
+    }
+}
+
+impl PositionChangeHook for LiquidationHook {
+    fn on_position_change(
+        &self,
+        client_id: &str,
+        pair: &TradingPair,
+        old_qty: f64,
+        new_qty: f64,
+        trade: &Trade,
+    ) {
+        let key = (client_id.to_string(), pair.clone());
+        let price = trade.price.value();
+        if new_qty == 0.0 {
+            self.positions.remove(&key);
+            self.flagged.remove(&key);
+            return;
+        }
+
+        let mut position = self.positions.entry(key.clone()).or_default();
+        let increases = old_qty == 0.0 || old_qty.signum() == new_qty.signum();
+        if increases && new_qty.abs() > old_qty.abs() {
+            position.entry_price = (position.entry_price * old_qty.abs()
+                + price * (new_qty.abs() - old_qty.abs()))
+                / new_qty.abs();
+        } else if !increases {
+            // Flipped through zero; the new side opened at this trade.
+            position.entry_price = price;
+        }
+        position.quantity = new_qty;
+        position.margin = self
+            .calculator
+            .initial_margin(position.entry_price, new_qty);
+
+        let equity = self.calculator.equity(&position, price);
+        if equity < self.calculator.maintenance_margin(price, new_qty) {
+            let liquidation_price = self
+                .calculator
+                .liquidation_price(&position)
+                .unwrap_or(price);
+            warn!(
+                liquidation_price,
+                "Position of {} in {:?} is below maintenance margin", client_id, pair
+            );
+            self.flagged.insert(key, liquidation_price);
+        } else {
+            self.flagged.remove(&key);
+        }
+    }
+}
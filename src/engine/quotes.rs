@@ -0,0 +1,127 @@
+use crate::engine::algorithms;
+use crate::engine::models::{Order, OrderType, Price, Quantity, TradingPair};
+use crate::engine::order_book::OrderBookError;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+/// A two-sided quote replacing the client's previous one, if any.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct QuoteRequest {
+    pub client_id: String,
+    pub bid_price: f64,
+    pub bid_qty: f64,
+    pub ask_price: f64,
+    pub ask_qty: f64,
+    pub previous_bid_id: Option<u64>,
+    pub previous_ask_id: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct QuoteAck {
+    pub new_bid_id: u64,
+    pub new_ask_id: u64,
+}
+
+#[derive(Debug, Clone)]
+pub enum QuoteError {
+    /// A previous quote order is not resting on the client's side of the
+    /// book, e.g. because it has already filled.
+    UnknownOrder(u64),
+    Crossed {
+        bid_price: f64,
+        ask_price: f64,
+    },
+    /// A new leg failed the engine's order checks. Neither new leg rests.
+    Rejected(OrderBookError),
+}
+
+impl fmt::Display for QuoteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QuoteError::UnknownOrder(order_id) => {
+                write!(f, "order {} is not a resting quote of the client", order_id)
+            }
+            QuoteError::Crossed {
+                bid_price,
+                ask_price,
+            } => write!(f, "bid {} is not below ask {}", bid_price, ask_price),
+            QuoteError::Rejected(e) => write!(f, "quote rejected: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for QuoteError {}
+
+/// Checks quote updates and remembers each client's live quote per pair.
+/// The engine does the cancels and submissions; see `Message::UpdateQuote`.
+#[derive(Debug, Default)]
+pub struct MarketMakerQuoteManager {
+    quotes: HashMap<(String, TradingPair), QuoteAck>,
+}
+
+impl MarketMakerQuoteManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The last quote placed by the client. Either leg may since have
+    /// traded away.
+    pub fn current_quote(&self, client_id: &str, trading_pair: &TradingPair) -> Option<QuoteAck> {
+        self.quotes
+            .get(&(client_id.to_string(), trading_pair.clone()))
+            .copied()
+    }
+
+    /// Validates `request` against the client's resting orders and builds
+    /// the two new legs, bid first.
+    pub(crate) fn prepare(
+        &self,
+        trading_pair: &TradingPair,
+        request: &QuoteRequest,
+        resting: &[Order],
+    ) -> Result<(Order, Order), QuoteError> {
+        if request.bid_price >= request.ask_price {
+            return Err(QuoteError::Crossed {
+                bid_price: request.bid_price,
+                ask_price: request.ask_price,
+            });
+        }
+        for (previous, side) in [
+            (request.previous_bid_id, OrderType::Buy),
+            (request.previous_ask_id, OrderType::Sell),
+        ] {
+            let Some(order_id) = previous else {
+                continue;
+            };
+            if !resting
+                .iter()
+                .any(|order| order.id == order_id && order.order_type == side)
+            {
+                return Err(QuoteError::UnknownOrder(order_id));
+            }
+        }
+
+        let leg = |order_type, price, quantity| Order {
+            id: algorithms::next_child_order_id(),
+            trading_pair: trading_pair.clone(),
+            order_type,
+            price: Price::from(price),
+            quantity: Quantity::from(quantity),
+            timestamp: chrono::Utc::now(),
+            client_id: Some(request.client_id.clone()),
+            ..Order::default()
+        };
+        Ok((
+            leg(OrderType::Buy, request.bid_price, request.bid_qty),
+            leg(OrderType::Sell, request.ask_price, request.ask_qty),
+        ))
+    }
+
+    pub(crate) fn record(&mut self, client_id: String, trading_pair: TradingPair, ack: QuoteAck) {
+        self.quotes.insert((client_id, trading_pair), ack);
+    }
+}
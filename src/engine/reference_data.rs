@@ -0,0 +1,32 @@
+use crate::engine::models::{TradingPair, TradingPairInfo};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// The one copy of the reference data that the engine and its order books
+/// all read. Loads through `Message::LoadReferenceData` reach every holder.
+pub type SharedReferenceData = Arc<parking_lot::RwLock<ReferenceDataManager>>;
+
+/// Instrument master data, kept apart from the order books that trade it.
+#[derive(Debug, Clone, Default)]
+pub struct ReferenceDataManager {
+    instruments: HashMap<TradingPair, TradingPairInfo>,
+}
+
+impl ReferenceDataManager {
+    pub fn new() -> Self {
+        ReferenceDataManager::default()
+    }
+
+    /// Adds the instrument, replacing any earlier entry for its pair.
+    pub fn register(&mut self, info: TradingPairInfo) {
+        self.instruments.insert(info.trading_pair.clone(), info);
+    }
+
+    pub fn get(&self, trading_pair: &TradingPair) -> Option<&TradingPairInfo> {
+        self.instruments.get(trading_pair)
+    }
+
+    pub fn list_all(&self) -> Vec<&TradingPairInfo> {
+        self.instruments.values().collect()
+    }
+}
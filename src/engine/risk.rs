@@ -0,0 +1,300 @@
+use crate::engine::models::{Order, Trade, TradingPair};
+use chrono::{DateTime, Datelike, NaiveTime, Utc, Weekday};
+use dashmap::DashMap;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RiskError {
+    /// Generic rejection for checks without a dedicated variant.
+    Rejected(String),
+    OrderTooLarge {
+        quantity: f64,
+        limit: f64,
+    },
+    BelowMinNotional {
+        notional: f64,
+        min_notional: f64,
+    },
+    DailyLimitExceeded {
+        client_id: String,
+        traded: f64,
+        limit: f64,
+    },
+    MarketClosed(TradingPair),
+}
+
+impl fmt::Display for RiskError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RiskError::Rejected(reason) => write!(f, "order rejected: {}", reason),
+            RiskError::OrderTooLarge { quantity, limit } => {
+                write!(f, "quantity {} exceeds the limit of {}", quantity, limit)
+            }
+            RiskError::BelowMinNotional {
+                notional,
+                min_notional,
+            } => write!(
+                f,
+                "notional {} is below the minimum of {}",
+                notional, min_notional
+            ),
+            RiskError::DailyLimitExceeded {
+                client_id,
+                traded,
+                limit,
+            } => write!(
+                f,
+                "{} has traded {} today, the order would exceed the limit of {}",
+                client_id, traded, limit
+            ),
+            RiskError::MarketClosed(trading_pair) => write!(
+                f,
+                "{}/{} is outside its trading hours",
+                trading_pair.base, trading_pair.quote
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RiskError {}
+
+/// Pre-trade check run by the engine before an order reaches its book.
+pub trait RiskManager: Send + Sync {
+    fn check_order(&self, order: &Order) -> Result<(), RiskError>;
+
+    /// Called for every trade the engine matches, for checks that track
+    /// exposure.
+    fn apply_trade(&self, _trade: &Trade) {}
+}
+
+/// Runs its checks in order and stops at the first rejection.
+#[derive(Default)]
+pub struct CompositeRiskManager {
+    checks: Vec<Box<dyn RiskManager>>,
+}
+
+impl CompositeRiskManager {
+    pub fn new(checks: Vec<Box<dyn RiskManager>>) -> Self {
+        CompositeRiskManager { checks }
+    }
+
+    pub fn add_check(&mut self, check: Box<dyn RiskManager>) {
+        self.checks.push(check);
+    }
+}
+
+impl RiskManager for CompositeRiskManager {
+    fn check_order(&self, order: &Order) -> Result<(), RiskError> {
+        self.checks
+            .iter()
+            .try_for_each(|check| check.check_order(order))
+    }
+
+    fn apply_trade(&self, trade: &Trade) {
+        for check in &self.checks {
+            check.apply_trade(trade);
+        }
+    }
+}
+
+/// Caps order quantity. Pairs without an entry in `max_qty_per_pair` fall
+/// back to `default_limit`, and are unrestricted if that is unset.
+#[derive(Debug, Clone, Default)]
+pub struct MaxOrderSizeRiskManager {
+    pub max_qty_per_pair: HashMap<TradingPair, f64>,
+    pub default_limit: Option<f64>,
+}
+
+impl MaxOrderSizeRiskManager {
+    /// The same limit for every pair.
+    pub fn uniform(limit: f64) -> Self {
+        MaxOrderSizeRiskManager {
+            max_qty_per_pair: HashMap::new(),
+            default_limit: Some(limit),
+        }
+    }
+
+    /// Limits only the listed pairs.
+    pub fn per_pair(map: HashMap<TradingPair, f64>) -> Self {
+        MaxOrderSizeRiskManager {
+            max_qty_per_pair: map,
+            default_limit: None,
+        }
+    }
+
+    fn limit_for(&self, trading_pair: &TradingPair) -> Option<f64> {
+        self.max_qty_per_pair
+            .get(trading_pair)
+            .copied()
+            .or(self.default_limit)
+    }
+}
+
+impl RiskManager for MaxOrderSizeRiskManager {
+    fn check_order(&self, order: &Order) -> Result<(), RiskError> {
+        match self.limit_for(&order.trading_pair) {
+            Some(limit) if order.quantity.value() > limit => Err(RiskError::OrderTooLarge {
+                quantity: order.quantity.value(),
+                limit,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Rejects orders worth less than `min_notional` in the quote currency.
+#[derive(Debug, Clone)]
+pub struct MinNotionalRiskManager {
+    pub min_notional: f64,
+}
+
+impl MinNotionalRiskManager {
+    pub fn new(min_notional: f64) -> Self {
+        MinNotionalRiskManager { min_notional }
+    }
+}
+
+impl RiskManager for MinNotionalRiskManager {
+    fn check_order(&self, order: &Order) -> Result<(), RiskError> {
+        let notional = order.notional_value();
+        if notional < self.min_notional {
+            return Err(RiskError::BelowMinNotional {
+                notional,
+                min_notional: self.min_notional,
+            });
+        }
+        Ok(())
+    }
+}
+
+const SECONDS_PER_DAY: i64 = 86_400;
+
+fn utc_day(time: DateTime<Utc>) -> u64 {
+    time.timestamp().div_euclid(SECONDS_PER_DAY).max(0) as u64
+}
+
+/// Caps the notional each client may trade per UTC day. Orders without a
+/// `client_id` are not limited.
+pub struct DailyVolumeLimitManager {
+    limit_per_client: f64,
+    /// Days since the epoch of the day being counted.
+    day_start: AtomicU64,
+    traded: DashMap<String, f64>,
+}
+
+impl DailyVolumeLimitManager {
+    pub fn new(limit_per_client: f64) -> Self {
+        DailyVolumeLimitManager {
+            limit_per_client,
+            day_start: AtomicU64::new(utc_day(Utc::now())),
+            traded: DashMap::new(),
+        }
+    }
+
+    /// Notional the client has traded in the current day.
+    pub fn traded_today(&self, client_id: &str) -> f64 {
+        self.traded.get(client_id).map(|v| *v).unwrap_or(0.0)
+    }
+
+    /// Clears the counters once `now` has passed midnight UTC. Never moves
+    /// back to an earlier day.
+    pub fn roll_day(&self, now: DateTime<Utc>) {
+        let today = utc_day(now);
+        if self.day_start.fetch_max(today, Ordering::AcqRel) < today {
+            self.traded.clear();
+        }
+    }
+
+    fn add_traded(&self, client_id: &Option<String>, notional: f64) {
+        if let Some(client_id) = client_id {
+            *self.traded.entry(client_id.clone()).or_insert(0.0) += notional;
+        }
+    }
+}
+
+impl RiskManager for DailyVolumeLimitManager {
+    fn check_order(&self, order: &Order) -> Result<(), RiskError> {
+        let Some(client_id) = &order.client_id else {
+            return Ok(());
+        };
+        self.roll_day(Utc::now());
+
+        let traded = self.traded_today(client_id);
+        if traded + order.notional_value() > self.limit_per_client {
+            return Err(RiskError::DailyLimitExceeded {
+                client_id: client_id.clone(),
+                traded,
+                limit: self.limit_per_client,
+            });
+        }
+        Ok(())
+    }
+
+    fn apply_trade(&self, trade: &Trade) {
+        self.roll_day(trade.timestamp);
+        let notional = trade.notional_value();
+        self.add_traded(&trade.buy_client_id, notional);
+        self.add_traded(&trade.sell_client_id, notional);
+    }
+}
+
+/// A daily window, in UTC, during which a pair accepts orders. A session
+/// whose `close` is before its `open` runs past midnight; `days` are the
+/// days it opens on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TradingSession {
+    pub open: NaiveTime,
+    pub close: NaiveTime,
+    pub days: Vec<Weekday>,
+}
+
+impl TradingSession {
+    pub fn contains(&self, time: DateTime<Utc>) -> bool {
+        let time_of_day = time.time();
+        if self.open <= self.close {
+            self.days.contains(&time.weekday())
+                && time_of_day >= self.open
+                && time_of_day < self.close
+        } else if time_of_day >= self.open {
+            self.days.contains(&time.weekday())
+        } else {
+            time_of_day < self.close && self.days.contains(&time.weekday().pred())
+        }
+    }
+}
+
+/// Rejects orders placed outside every session registered for their pair.
+/// Pairs without sessions are always open.
+#[derive(Debug, Clone, Default)]
+pub struct TradingHoursManager {
+    pub sessions: HashMap<TradingPair, Vec<TradingSession>>,
+}
+
+impl TradingHoursManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replaces the pair's sessions. An empty list leaves it always closed.
+    pub fn register(&mut self, trading_pair: TradingPair, sessions: Vec<TradingSession>) {
+        self.sessions.insert(trading_pair, sessions);
+    }
+
+    pub fn is_open(&self, trading_pair: &TradingPair, time: DateTime<Utc>) -> bool {
+        match self.sessions.get(trading_pair) {
+            Some(sessions) => sessions.iter().any(|session| session.contains(time)),
+            None => true,
+        }
+    }
+}
+
+impl RiskManager for TradingHoursManager {
+    fn check_order(&self, order: &Order) -> Result<(), RiskError> {
+        if !self.is_open(&order.trading_pair, Utc::now()) {
+            return Err(RiskError::MarketClosed(order.trading_pair.clone()));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,86 @@
+use crate::engine::models::TradingPair;
+use crate::engine::order_book::OrderBook;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ArbitrageOpportunity {
+    /// Pairs in the order they are traded around the cycle.
+    pub legs: Vec<TradingPair>,
+    /// What a round trip returns over its starting amount, before fees.
+    pub implied_profit_bps: f64,
+}
+
+/// Looks for triangular cycles across books whose top-of-book prices
+/// multiply out to more than one.
+#[derive(Debug, Clone, Default)]
+pub struct ArbitrageDetector {
+    /// Cycles that return less than this are not reported.
+    pub min_profit_bps: f64,
+}
+
+impl ArbitrageDetector {
+    pub fn new(min_profit_bps: f64) -> Self {
+        ArbitrageDetector { min_profit_bps }
+    }
+
+    /// Selling base on a pair earns its best bid in quote; buying base
+    /// costs its best ask. Each cycle is reported once per direction.
+    pub async fn check_triangle(
+        &self,
+        books: &HashMap<TradingPair, &dyn OrderBook>,
+    ) -> Vec<ArbitrageOpportunity> {
+        // Best conversion rate from one asset to another, and the pair that
+        // offers it.
+        let mut rates: HashMap<(&str, &str), (f64, &TradingPair)> = HashMap::new();
+        let mut assets = BTreeSet::new();
+        for (trading_pair, order_book) in books {
+            let (bid, ask) = order_book.best_bid_offer().await;
+            let base = trading_pair.base.as_str();
+            let quote = trading_pair.quote.as_str();
+            let mut offer = |from, to, rate: f64| {
+                let best = rates.entry((from, to)).or_insert((0.0, trading_pair));
+                if rate > best.0 {
+                    *best = (rate, trading_pair);
+                }
+            };
+            if let Some(bid) = bid.filter(|level| level.price > 0.0) {
+                offer(base, quote, bid.price);
+            }
+            if let Some(ask) = ask.filter(|level| level.price > 0.0) {
+                offer(quote, base, 1.0 / ask.price);
+            }
+            assets.insert(base);
+            assets.insert(quote);
+        }
+
+        let mut opportunities = Vec::new();
+        for &a in &assets {
+            // Starting each cycle from its smallest asset avoids reporting
+            // the same loop three times.
+            let later = || assets.iter().copied().filter(move |&asset| asset > a);
+            for b in later() {
+                for c in later() {
+                    if b == c {
+                        continue;
+                    }
+                    let (Some(ab), Some(bc), Some(ca)) =
+                        (rates.get(&(a, b)), rates.get(&(b, c)), rates.get(&(c, a)))
+                    else {
+                        continue;
+                    };
+                    let implied_profit_bps = (ab.0 * bc.0 * ca.0 - 1.0) * 10_000.0;
+                    if implied_profit_bps > self.min_profit_bps.max(0.0) {
+                        opportunities.push(ArbitrageOpportunity {
+                            legs: vec![ab.1.clone(), bc.1.clone(), ca.1.clone()],
+                            implied_profit_bps,
+                        });
+                    }
+                }
+            }
+        }
+        opportunities
+    }
+}
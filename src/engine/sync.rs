@@ -0,0 +1,246 @@
+use crate::engine::api::PriceLevel;
+use crate::engine::core::Message;
+use crate::engine::models::{MatchResult, OhlcvBar, Order, Trade, TradingPair};
+use crate::engine::order_book::{OrderBook, OrderBookError};
+use crate::engine::version::ENGINE_VERSION;
+use crossbeam_channel::{Receiver, Sender};
+use futures::executor::block_on;
+use std::collections::HashMap;
+use std::thread;
+use tracing::{info, warn};
+
+/// Blocking counterpart of `OrderBook` for callers without an async runtime.
+pub trait SyncOrderBook: Send + Sync {
+    fn add_order_blocking(&self, order: Order) -> Result<(), OrderBookError>;
+    fn match_orders_blocking(&self) -> MatchResult;
+    fn get_current_price_blocking(&self) -> Option<f64>;
+    fn get_order_book_blocking(&self) -> (Vec<PriceLevel>, Vec<PriceLevel>);
+    fn get_trade_history_blocking(&self) -> Vec<Trade>;
+    fn get_active_orders_count_blocking(&self) -> usize;
+    fn cancel_order_blocking(&self, order_id: u64) -> Option<Order>;
+}
+
+impl<T: OrderBook + ?Sized> SyncOrderBook for T {
+    fn add_order_blocking(&self, order: Order) -> Result<(), OrderBookError> {
+        block_on(self.add_order(order))
+    }
+
+    fn match_orders_blocking(&self) -> MatchResult {
+        block_on(self.match_orders())
+    }
+
+    fn get_current_price_blocking(&self) -> Option<f64> {
+        block_on(self.get_current_price())
+    }
+
+    fn get_order_book_blocking(&self) -> (Vec<PriceLevel>, Vec<PriceLevel>) {
+        block_on(self.get_order_book())
+    }
+
+    fn get_trade_history_blocking(&self) -> Vec<Trade> {
+        block_on(self.get_trade_history())
+    }
+
+    fn get_active_orders_count_blocking(&self) -> usize {
+        block_on(self.get_active_orders_count())
+    }
+
+    fn cancel_order_blocking(&self, order_id: u64) -> Option<Order> {
+        block_on(self.cancel_order(order_id))
+    }
+}
+
+/// Engine loop that runs on a plain thread and reads from a crossbeam
+/// channel. Algorithm and subscription messages need the async engine; their
+/// response senders are dropped so callers see a closed channel.
+pub struct SyncEngine {
+    order_books: HashMap<TradingPair, Box<dyn OrderBook>>,
+    order_book_factory: Box<dyn Fn(TradingPair) -> Box<dyn OrderBook> + Send + Sync>,
+}
+
+impl SyncEngine {
+    pub fn new<F>(order_book_factory: F) -> Self
+    where
+        F: Fn(TradingPair) -> Box<dyn OrderBook> + Send + Sync + 'static,
+    {
+        SyncEngine {
+            order_books: HashMap::new(),
+            order_book_factory: Box::new(order_book_factory),
+        }
+    }
+
+    fn get_or_create_order_book(&mut self, trading_pair: &TradingPair) -> &dyn OrderBook {
+        let factory = &self.order_book_factory;
+        &**self
+            .order_books
+            .entry(trading_pair.clone())
+            .or_insert_with(|| factory(trading_pair.clone()))
+    }
+
+    pub fn run(&mut self, rx: Receiver<Message>) {
+        info!("Starting sync engine.");
+        while let Ok(message) = rx.recv() {
+            if !self.handle_message(message) {
+                break;
+            }
+        }
+        info!("Sync engine stopped.");
+    }
+
+    /// Processes one message; `false` once the engine should stop. Listed
+    /// without a wildcard so a new `Message` variant has to be placed here.
+    fn handle_message(&mut self, message: Message) -> bool {
+        match message {
+            Message::Ping(response_tx) => {
+                let _ = response_tx.try_send(());
+            }
+            Message::NewOrder(order) => {
+                let trading_pair = order.trading_pair.clone();
+                let order_id = order.id;
+                if let Err(e) = self
+                    .get_or_create_order_book(&trading_pair)
+                    .add_order_blocking(order)
+                {
+                    warn!("Rejecting order {}: {}", order_id, e);
+                }
+            }
+            Message::GetPrice(trading_pair, response_tx) => {
+                let price = self
+                    .get_or_create_order_book(&trading_pair)
+                    .get_current_price_blocking();
+                let _ = response_tx.blocking_send(price);
+            }
+            Message::GetOrderBook(trading_pair, response_tx) => {
+                let book = match self.order_books.get(&trading_pair) {
+                    Some(order_book) => order_book.get_order_book_blocking(),
+                    None => (vec![], vec![]),
+                };
+                let _ = response_tx.blocking_send(book);
+            }
+            Message::GetTradeHistory(trading_pair, response_tx) => {
+                let trades = match self.order_books.get(&trading_pair) {
+                    Some(order_book) => order_book.get_trade_history_blocking(),
+                    None => vec![],
+                };
+                let _ = response_tx.blocking_send(trades);
+            }
+            Message::GetOhlcv(trading_pair, interval, since, response_tx) => {
+                let bars = match self.order_books.get(&trading_pair) {
+                    Some(order_book) => OhlcvBar::from_trades(
+                        &order_book.get_trade_history_blocking(),
+                        interval,
+                        since,
+                    ),
+                    None => vec![],
+                };
+                let _ = response_tx.blocking_send(bars);
+            }
+            Message::MatchOrders(trading_pair, response_tx) => {
+                let trades = match self.order_books.get(&trading_pair) {
+                    Some(order_book) => order_book.match_orders_blocking().trades,
+                    None => vec![],
+                };
+                let _ = response_tx.blocking_send(trades);
+            }
+            Message::CancelOrder(trading_pair, order_id, response_tx) => {
+                let cancelled = self
+                    .order_books
+                    .get(&trading_pair)
+                    .and_then(|order_book| order_book.cancel_order_blocking(order_id));
+                let _ = response_tx.blocking_send(cancelled);
+            }
+            Message::GetEngineVersion(response_tx) => {
+                let _ = response_tx.blocking_send(ENGINE_VERSION);
+            }
+            Message::WithCorrelationId(_, message) | Message::Forwarded(message) => {
+                return self.handle_message(*message);
+            }
+            Message::Shutdown => {
+                info!("Received shutdown signal.");
+                return false;
+            }
+            Message::NewOrderWithCallback(..)
+            | Message::GetTradeHistoryPaginated(..)
+            | Message::GetVolumeSince(..)
+            | Message::GetLiquidityWithinRange(..)
+            | Message::GetKyleLambda(..)
+            | Message::GetVpin(..)
+            | Message::GetDailyStats(..)
+            | Message::GetDepthImbalance(..)
+            | Message::GetEffectiveSpread(..)
+            | Message::GetPriceImpact(..)
+            | Message::GetPriceLadder(..)
+            | Message::WarmUpOrderBook(..)
+            | Message::GetClientOrders(..)
+            | Message::GetClientOrdersAllPairs(..)
+            | Message::GetAllActiveOrders(..)
+            | Message::GetAllActiveOrdersByClient(..)
+            | Message::GetActiveOrderCount(..)
+            | Message::GetStaleOrders(..)
+            | Message::GetActiveOrderCountAllPairs(..)
+            | Message::GetAllPairStats(..)
+            | Message::GetAllocationStats(..)
+            | Message::RebalanceOrderBook(..)
+            | Message::MergeTradingPairs(..)
+            | Message::BustTrade(..)
+            | Message::ConvertCurrency(..)
+            | Message::UpdateQuote(..)
+            | Message::StartTwapExecution(..)
+            | Message::StartVwapExecution(..)
+            | Message::StartParticipation(..)
+            | Message::SubscribeToPair(..)
+            | Message::SubscribeToBbo(..)
+            | Message::GetTopOfBook(..)
+            | Message::SubscribeToMarketEvents(..)
+            | Message::SubscribeToAllTrades(..)
+            | Message::PriceUpdate(..)
+            | Message::ResumeTrading(..)
+            | Message::SetRiskManager(..)
+            | Message::RegisterTradingHours(..)
+            | Message::SetAccountManager(..)
+            | Message::Deposit(..)
+            | Message::GetAccount(..)
+            | Message::GetLiquidationPrice(..)
+            | Message::SaveSnapshots(..)
+            | Message::RestoreSnapshot(..)
+            | Message::ReloadConfig(..)
+            | Message::GetMetrics(..)
+            | Message::ResetMetrics(..)
+            | Message::Drain(..)
+            | Message::LoadReferenceData(..)
+            | Message::GetFillReport(..)
+            | Message::DiagnoseEngine(..)
+            | Message::SetFeeModel(..)
+            | Message::Broadcast(..)
+            | Message::SetLogFilter(..) => {
+                unsupported(&message);
+            }
+            #[cfg(feature = "serde")]
+            Message::SaveState(..) => unsupported(&message),
+            #[cfg(feature = "export")]
+            Message::ExportOrderBookCsv(..) => unsupported(&message),
+        }
+        true
+    }
+}
+
+fn unsupported(message: &Message) {
+    warn!(
+        message_type = ?message.message_type(),
+        "Message not supported by the sync engine"
+    );
+}
+
+pub fn start_engine_sync<F>(order_book_factory: F) -> Sender<Message>
+where
+    F: Fn(TradingPair) -> Box<dyn OrderBook> + Send + Sync + 'static,
+{
+    let (tx, rx) = crossbeam_channel::bounded(100);
+
+    thread::spawn(move || {
+        let mut engine = SyncEngine::new(order_book_factory);
+        engine.run(rx);
+    });
+
+    tx
+}
@@ -0,0 +1,144 @@
+use crate::engine::algorithms;
+use crate::engine::core::Message;
+use crate::engine::models::{MarketEvent, OrderType, TradingPair};
+use crate::engine::testing::OrderBuilder;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc};
+use tokio::time::Instant;
+
+/// Client ID on every quote.
+pub const CLIENT_ID: &str = "simulated-market-maker";
+
+/// Load generator that keeps `levels` quotes on each side of a randomly
+/// drifting mid, replacing all of them every `update_interval`. It never
+/// matches; fills come from whatever else is trading the pair.
+pub struct SimulatedMarketMaker {
+    pub pair: TradingPair,
+    /// Mid price of the first quotes.
+    pub mid_price: f64,
+    /// Distance between the best bid and ask, and the step between levels
+    /// either side of them.
+    pub spread_bps: f64,
+    pub qty_per_level: f64,
+    pub levels: usize,
+    pub update_interval: Duration,
+    pub engine_tx: mpsc::Sender<Message>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MarketMakerStats {
+    pub quotes_submitted: u64,
+    /// Trades against the market maker's quotes, counting each partial fill.
+    pub fills_received: u64,
+    /// Mean time from submitting a quote to seeing a fill against it.
+    pub avg_fill_latency: Duration,
+}
+
+impl SimulatedMarketMaker {
+    /// Quotes for `duration`, then cancels whatever is still resting.
+    pub async fn run(&self, duration: Duration) -> MarketMakerStats {
+        let deadline = Instant::now() + duration;
+        let mut stats = MarketMakerStats::default();
+        let mut total_latency = Duration::ZERO;
+        let mut rng = StdRng::from_entropy();
+        let mut mid_price = self.mid_price;
+        // Submission time of each quote submitted since the last update.
+        let mut quotes: HashMap<u64, Instant> = HashMap::new();
+        let mut events = self.subscribe().await;
+        let mut updates = tokio::time::interval(self.update_interval);
+
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep_until(deadline) => break,
+                _ = updates.tick() => {
+                    self.cancel_quotes(&mut quotes).await;
+                    let drift = rng.gen_range(-1.0..=1.0) * self.spread_bps / 20_000.0;
+                    mid_price *= 1.0 + drift;
+                    stats.quotes_submitted += self.submit_quotes(mid_price, &mut quotes).await;
+                }
+                event = recv(&mut events) => match event {
+                    Ok(MarketEvent::Trade(trade)) => {
+                        let submitted = quotes
+                            .get(&trade.buy_order_id)
+                            .or_else(|| quotes.get(&trade.sell_order_id));
+                        if let Some(submitted) = submitted {
+                            stats.fills_received += 1;
+                            total_latency += submitted.elapsed();
+                        }
+                    }
+                    Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => events = None,
+                },
+            }
+        }
+
+        self.cancel_quotes(&mut quotes).await;
+        if stats.fills_received > 0 {
+            stats.avg_fill_latency = total_latency / stats.fills_received as u32;
+        }
+        stats
+    }
+
+    async fn subscribe(&self) -> Option<broadcast::Receiver<MarketEvent>> {
+        let (subscribe_tx, mut subscribe_rx) = mpsc::channel(1);
+        self.engine_tx
+            .send(Message::SubscribeToPair(self.pair.clone(), subscribe_tx))
+            .await
+            .ok()?;
+        subscribe_rx.recv().await
+    }
+
+    /// Returns how many quotes were submitted.
+    async fn submit_quotes(&self, mid_price: f64, quotes: &mut HashMap<u64, Instant>) -> u64 {
+        let step = mid_price * self.spread_bps / 10_000.0;
+        let mut submitted = 0;
+        for level in 0..self.levels {
+            let offset = step * (level as f64 + 0.5);
+            for (side, price) in [
+                (OrderType::Buy, mid_price - offset),
+                (OrderType::Sell, mid_price + offset),
+            ] {
+                let order_id = algorithms::next_child_order_id();
+                let order = OrderBuilder::new()
+                    .id(order_id)
+                    .pair(self.pair.clone())
+                    .side(side)
+                    .price(price)
+                    .quantity(self.qty_per_level)
+                    .build()
+                    .with_client(CLIENT_ID);
+                if self.engine_tx.send(Message::NewOrder(order)).await.is_err() {
+                    return submitted;
+                }
+                quotes.insert(order_id, Instant::now());
+                submitted += 1;
+            }
+        }
+        submitted
+    }
+
+    async fn cancel_quotes(&self, quotes: &mut HashMap<u64, Instant>) {
+        let (cancel_tx, mut cancel_rx) = mpsc::channel(1);
+        for (order_id, _) in quotes.drain() {
+            let cancel = Message::CancelOrder(self.pair.clone(), order_id, cancel_tx.clone());
+            if self.engine_tx.send(cancel).await.is_err() {
+                return;
+            }
+            let _ = cancel_rx.recv().await;
+        }
+    }
+}
+
+/// Waits forever once the subscription is gone, so `select!` only wakes for
+/// updates and the deadline.
+async fn recv(
+    events: &mut Option<broadcast::Receiver<MarketEvent>>,
+) -> Result<MarketEvent, broadcast::error::RecvError> {
+    match events {
+        Some(events) => events.recv().await,
+        None => std::future::pending().await,
+    }
+}
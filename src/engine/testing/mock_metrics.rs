@@ -0,0 +1,137 @@
+use crate::engine::metrics::{EngineMetricsInterface, EngineMetricsSnapshot};
+use crate::engine::models::TradingPair;
+use std::time::Duration;
+
+/// One call made on a `MockEngineMetrics`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MetricCall {
+    OrderAccepted,
+    OrderRejected,
+    OrderCancelled,
+    Trades(u64),
+    OrderBooks(u64),
+    BookDepth(TradingPair, String, usize),
+    QueueDepth(usize),
+    OrderSubmitted(TradingPair),
+    ActiveOrdersIncremented(TradingPair, usize),
+    ActiveOrdersDecremented(TradingPair, usize),
+    Spread(TradingPair, f64),
+    ShutdownDuration(Duration),
+    ShutdownTrades(usize),
+    ShutdownCancelledOrders(usize),
+    Uptime(u64),
+    Reset,
+}
+
+/// Records every call instead of exporting it, for tests that check what
+/// the engine measured; install it with `Engine::with_metrics`. Queries
+/// answer as if nothing had been recorded.
+#[derive(Debug, Default)]
+pub struct MockEngineMetrics {
+    calls: parking_lot::Mutex<Vec<MetricCall>>,
+}
+
+impl MockEngineMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Every call so far, oldest first.
+    pub fn calls(&self) -> Vec<MetricCall> {
+        self.calls.lock().clone()
+    }
+
+    fn record(&self, call: MetricCall) {
+        self.calls.lock().push(call);
+    }
+}
+
+impl EngineMetricsInterface for MockEngineMetrics {
+    fn record_order_accepted(&self) {
+        self.record(MetricCall::OrderAccepted);
+    }
+
+    fn record_order_rejected(&self) {
+        self.record(MetricCall::OrderRejected);
+    }
+
+    fn record_order_cancelled(&self) {
+        self.record(MetricCall::OrderCancelled);
+    }
+
+    fn record_trades(&self, count: u64) {
+        self.record(MetricCall::Trades(count));
+    }
+
+    fn set_order_books(&self, count: u64) {
+        self.record(MetricCall::OrderBooks(count));
+    }
+
+    fn set_book_depth(&self, pair: &TradingPair, side: &str, levels: usize) {
+        self.record(MetricCall::BookDepth(
+            pair.clone(),
+            side.to_string(),
+            levels,
+        ));
+    }
+
+    fn book_depth(&self, _pair: &TradingPair, _side: &str) -> Option<usize> {
+        None
+    }
+
+    fn record_queue_depth(&self, depth: usize) {
+        self.record(MetricCall::QueueDepth(depth));
+    }
+
+    fn increment_orders_submitted(&self, pair: &TradingPair) {
+        self.record(MetricCall::OrderSubmitted(pair.clone()));
+    }
+
+    fn increment_active_orders(&self, pair: &TradingPair, n: usize) {
+        self.record(MetricCall::ActiveOrdersIncremented(pair.clone(), n));
+    }
+
+    fn decrement_active_orders(&self, pair: &TradingPair, n: usize) {
+        self.record(MetricCall::ActiveOrdersDecremented(pair.clone(), n));
+    }
+
+    fn set_spread(&self, pair: &TradingPair, spread: f64) {
+        self.record(MetricCall::Spread(pair.clone(), spread));
+    }
+
+    fn orders_submitted(&self, _pair: &TradingPair) -> u64 {
+        0
+    }
+
+    fn active_orders(&self, _pair: &TradingPair) -> u64 {
+        0
+    }
+
+    fn spread(&self, _pair: &TradingPair) -> Option<f64> {
+        None
+    }
+
+    fn record_shutdown_duration(&self, duration: Duration) {
+        self.record(MetricCall::ShutdownDuration(duration));
+    }
+
+    fn record_shutdown_trades(&self, count: usize) {
+        self.record(MetricCall::ShutdownTrades(count));
+    }
+
+    fn record_shutdown_cancelled_orders(&self, count: usize) {
+        self.record(MetricCall::ShutdownCancelledOrders(count));
+    }
+
+    fn update_uptime(&self, seconds: u64) {
+        self.record(MetricCall::Uptime(seconds));
+    }
+
+    fn snapshot(&self) -> EngineMetricsSnapshot {
+        EngineMetricsSnapshot::default()
+    }
+
+    fn reset(&self) {
+        self.record(MetricCall::Reset);
+    }
+}
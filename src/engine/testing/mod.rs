@@ -0,0 +1,125 @@
+#[cfg(any(test, feature = "testing"))]
+pub mod market_maker;
+#[cfg(any(test, feature = "testing"))]
+pub mod mock_metrics;
+
+use crate::engine::models::{Order, OrderType, TradingPair};
+use chrono::{DateTime, Utc};
+#[cfg(feature = "serde")]
+use serde::Deserialize;
+
+#[cfg(any(test, feature = "testing"))]
+pub use market_maker::{MarketMakerStats, SimulatedMarketMaker};
+#[cfg(any(test, feature = "testing"))]
+pub use mock_metrics::{MetricCall, MockEngineMetrics};
+
+/// Chainable `Order` construction for tests. Starts from `Order::default()`
+/// but stamps the order with the current time.
+#[derive(Debug, Clone)]
+pub struct OrderBuilder {
+    order: Order,
+}
+
+impl Default for OrderBuilder {
+    fn default() -> Self {
+        OrderBuilder::new()
+    }
+}
+
+/// Order fields accepted by `OrderBuilder::from_json`; anything missing keeps
+/// the builder's default.
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct OrderSpec {
+    id: Option<u64>,
+    trading_pair: Option<TradingPair>,
+    order_type: Option<OrderType>,
+    price: Option<f64>,
+    quantity: Option<f64>,
+    client_id: Option<String>,
+    session_id: Option<String>,
+}
+
+impl OrderBuilder {
+    /// Parses a partial order, e.g. `{"order_type": "Sell", "price": 101.0}`.
+    /// The timestamp is always the time of the call.
+    #[cfg(feature = "serde")]
+    pub fn from_json(s: &str) -> Result<OrderBuilder, serde_json::Error> {
+        let spec: OrderSpec = serde_json::from_str(s)?;
+        let defaults = Order::default();
+        Ok(OrderBuilder {
+            order: Order {
+                id: spec.id.unwrap_or(defaults.id),
+                trading_pair: spec.trading_pair.unwrap_or(defaults.trading_pair),
+                order_type: spec.order_type.unwrap_or(defaults.order_type),
+                price: spec.price.map_or(defaults.price, Into::into),
+                quantity: spec.quantity.map_or(defaults.quantity, Into::into),
+                timestamp: Utc::now(),
+                client_id: spec.client_id,
+                session_id: spec.session_id,
+                ..defaults
+            },
+        })
+    }
+
+    pub fn new() -> Self {
+        OrderBuilder {
+            order: Order {
+                timestamp: Utc::now(),
+                ..Order::default()
+            },
+        }
+    }
+
+    pub fn id(mut self, id: u64) -> Self {
+        self.order.id = id;
+        self
+    }
+
+    pub fn pair(mut self, trading_pair: TradingPair) -> Self {
+        self.order.trading_pair = trading_pair;
+        self
+    }
+
+    pub fn buy_at(self, price: f64) -> Self {
+        self.side(OrderType::Buy).price(price)
+    }
+
+    pub fn sell_at(self, price: f64) -> Self {
+        self.side(OrderType::Sell).price(price)
+    }
+
+    pub fn side(mut self, order_type: OrderType) -> Self {
+        self.order.order_type = order_type;
+        self
+    }
+
+    pub fn price(mut self, price: f64) -> Self {
+        self.order.price = price.into();
+        self
+    }
+
+    pub fn quantity(mut self, quantity: f64) -> Self {
+        self.order.quantity = quantity.into();
+        self
+    }
+
+    pub fn client_id(mut self, client_id: &str) -> Self {
+        self.order.client_id = Some(client_id.to_string());
+        self
+    }
+
+    pub fn timestamp(mut self, timestamp: DateTime<Utc>) -> Self {
+        self.order.timestamp = timestamp;
+        self
+    }
+
+    pub fn build(self) -> Order {
+        self.order
+    }
+}
+
+/// BTC/USD, the pair most tests trade.
+pub fn btc_usd() -> TradingPair {
+    TradingPair::new("BTC".to_string(), "USD".to_string())
+}
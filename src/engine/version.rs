@@ -0,0 +1,25 @@
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+/// Build metadata baked in at compile time by `build.rs`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct EngineVersion {
+    pub version: &'static str,
+    /// Unix seconds at which the build script last ran.
+    pub build_timestamp: &'static str,
+    /// `"unknown"` when built outside a git checkout.
+    pub git_sha: &'static str,
+}
+
+pub const ENGINE_VERSION: EngineVersion = EngineVersion {
+    version: env!("CARGO_PKG_VERSION"),
+    build_timestamp: match option_env!("BUILD_TIMESTAMP") {
+        Some(timestamp) => timestamp,
+        None => "unknown",
+    },
+    git_sha: match option_env!("GIT_SHA") {
+        Some(sha) => sha,
+        None => "unknown",
+    },
+};
@@ -1,6 +1,5 @@
-mod engine;
-use engine::order_book::SimpleOrderBook;
-use engine::{api::run_api_server, core::start_engine};
+use engine::engine::order_book::SimpleOrderBook;
+use engine::engine::{api::run_api_server, core::start_engine};
 
 #[tokio::main]
 async fn main() {
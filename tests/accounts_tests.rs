@@ -0,0 +1,128 @@
+use engine::engine::accounts::{AccountError, AccountManager};
+use engine::engine::core::{start_engine, Message};
+use engine::engine::order_book::SimpleOrderBook;
+use engine::engine::testing::{btc_usd, OrderBuilder};
+use tokio::sync::mpsc;
+
+#[test]
+fn test_lock_settle_and_release() {
+    let mut accounts = AccountManager::new();
+    accounts.deposit("alice", "USD", 1000.0);
+    accounts.deposit("bob", "BTC", 5.0);
+
+    let buy = OrderBuilder::new()
+        .id(1)
+        .pair(btc_usd())
+        .client_id("alice")
+        .buy_at(100.0)
+        .quantity(4.0)
+        .build();
+    accounts.lock_for_order(&buy).unwrap();
+    assert_eq!(accounts.account("alice").unwrap().balance("USD"), 600.0);
+    assert_eq!(accounts.locked_balance("alice", "USD"), 400.0);
+
+    let too_big = OrderBuilder::new()
+        .id(2)
+        .pair(btc_usd())
+        .client_id("bob")
+        .sell_at(100.0)
+        .quantity(6.0)
+        .build();
+    assert_eq!(
+        accounts.lock_for_order(&too_big),
+        Err(AccountError::InsufficientBalance {
+            asset: "BTC".to_string(),
+            required: 6.0,
+            available: 5.0
+        })
+    );
+    let stranger = OrderBuilder::new().client_id("carol").buy_at(1.0).build();
+    assert_eq!(
+        accounts.lock_for_order(&stranger),
+        Err(AccountError::UnknownClient("carol".to_string()))
+    );
+
+    accounts.release_order(1);
+    assert_eq!(accounts.account("alice").unwrap().balance("USD"), 1000.0);
+    assert_eq!(accounts.locked_balance("alice", "USD"), 0.0);
+}
+
+#[tokio::test]
+async fn test_engine_settles_spot_fills() {
+    let engine_tx = start_engine(|trading_pair| Box::new(SimpleOrderBook::new(trading_pair)));
+    let mut accounts = AccountManager::new();
+    accounts.deposit("alice", "USD", 1000.0);
+    accounts.deposit("bob", "BTC", 5.0);
+    engine_tx
+        .send(Message::SetAccountManager(accounts))
+        .await
+        .unwrap();
+
+    // Bob rests 3 BTC at 90; Alice bids 100 for 4 and fills 3 at 90.
+    let orders = [
+        OrderBuilder::new()
+            .id(1)
+            .pair(btc_usd())
+            .client_id("bob")
+            .sell_at(90.0)
+            .quantity(3.0),
+        OrderBuilder::new()
+            .id(2)
+            .pair(btc_usd())
+            .client_id("alice")
+            .buy_at(100.0)
+            .quantity(4.0),
+        // Rejected: Bob only has 2 BTC left.
+        OrderBuilder::new()
+            .id(3)
+            .pair(btc_usd())
+            .client_id("bob")
+            .sell_at(200.0)
+            .quantity(3.0),
+    ];
+    for order in orders {
+        engine_tx
+            .send(Message::NewOrder(order.build()))
+            .await
+            .unwrap();
+    }
+    let (match_tx, mut match_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::MatchOrders(btc_usd(), match_tx))
+        .await
+        .unwrap();
+    assert_eq!(match_rx.recv().await.unwrap().len(), 1);
+
+    let (cancel_tx, mut cancel_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::CancelOrder(btc_usd(), 2, cancel_tx))
+        .await
+        .unwrap();
+    assert!(cancel_rx.recv().await.unwrap().is_some());
+
+    let (account_tx, mut account_rx) = mpsc::channel(1);
+    for client_id in ["alice", "bob"] {
+        engine_tx
+            .send(Message::GetAccount(
+                client_id.to_string(),
+                account_tx.clone(),
+            ))
+            .await
+            .unwrap();
+    }
+    let alice = account_rx.recv().await.unwrap().unwrap();
+    let bob = account_rx.recv().await.unwrap().unwrap();
+
+    assert_eq!(alice.balance("BTC"), 3.0);
+    assert_eq!(alice.balance("USD"), 730.0);
+    assert_eq!(bob.balance("BTC"), 2.0);
+    assert_eq!(bob.balance("USD"), 270.0);
+
+    let (book_tx, mut book_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::GetOrderBook(btc_usd(), book_tx))
+        .await
+        .unwrap();
+    let (bids, asks) = book_rx.recv().await.unwrap();
+    assert!(bids.is_empty() && asks.is_empty());
+}
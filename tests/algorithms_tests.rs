@@ -0,0 +1,274 @@
+use chrono::{TimeZone, Utc};
+use engine::engine::algorithms::vwap::{profile_start, volume_profile};
+use engine::engine::algorithms::{ParticipationParams, TwapExecutor, TwapParams, VwapParams};
+use engine::engine::core::{start_engine, Message};
+use engine::engine::models::{OhlcvBar, OrderType};
+use engine::engine::order_book::SimpleOrderBook;
+use engine::engine::testing::{btc_usd, OrderBuilder};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+async fn seed_asks(engine_tx: &mpsc::Sender<Message>, prices: &[f64]) {
+    for (i, &price) in prices.iter().enumerate() {
+        let order = OrderBuilder::new()
+            .id(i as u64 + 1)
+            .pair(btc_usd())
+            .sell_at(price)
+            .quantity(1.0)
+            .build();
+        engine_tx.send(Message::NewOrder(order)).await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_twap_execution_through_engine() {
+    let engine_tx = start_engine(|trading_pair| Box::new(SimpleOrderBook::new(trading_pair)));
+    seed_asks(&engine_tx, &[100.0, 101.0, 102.0]).await;
+
+    let params = TwapParams {
+        total_qty: 3.0,
+        num_slices: 3,
+        interval: Duration::from_millis(10),
+        trading_pair: btc_usd(),
+        side: OrderType::Buy,
+        cancel_token: CancellationToken::new(),
+    };
+
+    let (trades_tx, mut trades_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::StartTwapExecution(params, trades_tx))
+        .await
+        .unwrap();
+    let trades = trades_rx.recv().await.unwrap();
+
+    assert_eq!(trades.len(), 3);
+    let total: f64 = trades.iter().map(|trade| trade.quantity.value()).sum();
+    assert_eq!(total, 3.0);
+    let prices: Vec<f64> = trades.iter().map(|trade| trade.price.value()).collect();
+    assert_eq!(prices, vec![100.0, 101.0, 102.0]);
+
+    let (book_tx, mut book_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::GetOrderBook(btc_usd(), book_tx))
+        .await
+        .unwrap();
+    let (bids, asks) = book_rx.recv().await.unwrap();
+    assert!(bids.is_empty());
+    assert!(asks.is_empty());
+}
+
+#[tokio::test]
+async fn test_twap_leaves_no_residual_on_thin_book() {
+    let engine_tx = start_engine(|trading_pair| Box::new(SimpleOrderBook::new(trading_pair)));
+    seed_asks(&engine_tx, &[100.0]).await;
+
+    let executor = TwapExecutor::new(
+        4.0,
+        2,
+        Duration::from_millis(10),
+        btc_usd(),
+        OrderType::Buy,
+        engine_tx.clone(),
+    );
+    let trades = executor.execute().await;
+
+    let total: f64 = trades.iter().map(|trade| trade.quantity.value()).sum();
+    assert_eq!(total, 1.0);
+
+    let (book_tx, mut book_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::GetOrderBook(btc_usd(), book_tx))
+        .await
+        .unwrap();
+    let (bids, _) = book_rx.recv().await.unwrap();
+    assert!(bids.is_empty());
+}
+
+#[tokio::test]
+async fn test_twap_cancel_stops_execution() {
+    let engine_tx = start_engine(|trading_pair| Box::new(SimpleOrderBook::new(trading_pair)));
+    seed_asks(&engine_tx, &[100.0, 101.0, 102.0]).await;
+
+    let executor = TwapExecutor::new(
+        3.0,
+        3,
+        Duration::from_secs(60),
+        btc_usd(),
+        OrderType::Buy,
+        engine_tx.clone(),
+    );
+    let cancel_token = executor.cancel_token();
+
+    let handle = tokio::spawn(async move { executor.execute().await });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    cancel_token.cancel();
+
+    let trades = tokio::time::timeout(Duration::from_secs(1), handle)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(trades.len(), 1);
+    assert_eq!(trades[0].price.value(), 100.0);
+}
+
+fn bar(open_time: chrono::DateTime<Utc>, volume: f64) -> OhlcvBar {
+    OhlcvBar {
+        open_time,
+        open: 100.0,
+        high: 100.0,
+        low: 100.0,
+        close: 100.0,
+        volume,
+    }
+}
+
+#[test]
+fn test_volume_profile_weights_buckets_by_volume() {
+    let now = Utc.with_ymd_and_hms(2024, 1, 1, 12, 30, 0).unwrap();
+    let start = profile_start(now, 3);
+    assert_eq!(start, Utc.with_ymd_and_hms(2024, 1, 1, 10, 0, 0).unwrap());
+
+    let bars = vec![
+        bar(Utc.with_ymd_and_hms(2024, 1, 1, 9, 0, 0).unwrap(), 50.0),
+        bar(Utc.with_ymd_and_hms(2024, 1, 1, 10, 0, 0).unwrap(), 1.0),
+        bar(Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap(), 3.0),
+    ];
+    assert_eq!(volume_profile(&bars, start, 3), vec![0.25, 0.0, 0.75]);
+    assert_eq!(volume_profile(&[], start, 4), vec![0.25; 4]);
+}
+
+#[tokio::test]
+async fn test_vwap_execution_follows_volume_history() {
+    let engine_tx = start_engine(|trading_pair| Box::new(SimpleOrderBook::new(trading_pair)));
+
+    // All historical volume lands in the current hour, so only the final
+    // bucket should trade.
+    seed_asks(&engine_tx, &[100.0]).await;
+    let buy = OrderBuilder::new()
+        .id(10)
+        .pair(btc_usd())
+        .buy_at(100.0)
+        .quantity(1.0)
+        .build();
+    engine_tx.send(Message::NewOrder(buy)).await.unwrap();
+    let (match_tx, mut match_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::MatchOrders(btc_usd(), match_tx))
+        .await
+        .unwrap();
+    assert_eq!(match_rx.recv().await.unwrap().len(), 1);
+
+    seed_asks(&engine_tx, &[101.0, 102.0]).await;
+    let params = VwapParams {
+        total_qty: 2.0,
+        num_buckets: 3,
+        interval: Duration::from_millis(10),
+        trading_pair: btc_usd(),
+        side: OrderType::Buy,
+        cancel_token: CancellationToken::new(),
+    };
+    let (trades_tx, mut trades_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::StartVwapExecution(params, trades_tx))
+        .await
+        .unwrap();
+    let trades = trades_rx.recv().await.unwrap();
+
+    let total: f64 = trades.iter().map(|trade| trade.quantity.value()).sum();
+    assert_eq!(total, 2.0);
+    assert!(trades
+        .windows(2)
+        .all(|pair| pair[0].timestamp <= pair[1].timestamp));
+}
+
+#[test]
+fn test_ohlcv_bars_from_trades() {
+    let trade = |seconds: i64, price: f64, quantity: f64| engine::engine::models::Trade {
+        id: 0,
+        trading_pair: btc_usd(),
+        buy_order_id: 1,
+        sell_order_id: 2,
+        price: price.into(),
+        quantity: quantity.into(),
+        aggressor_side: OrderType::Buy,
+        timestamp: Utc.timestamp_opt(seconds, 0).unwrap(),
+        buy_client_id: None,
+        sell_client_id: None,
+        buy_fee: 0.0,
+        sell_fee: 0.0,
+    };
+    let trades = vec![
+        trade(3600, 100.0, 1.0),
+        trade(3700, 105.0, 2.0),
+        trade(3800, 95.0, 1.0),
+        trade(7300, 98.0, 4.0),
+    ];
+
+    let bars = OhlcvBar::from_trades(
+        &trades,
+        Duration::from_secs(3600),
+        Utc.timestamp_opt(0, 0).unwrap(),
+    );
+    assert_eq!(bars.len(), 2);
+    assert_eq!(bars[0].open_time, Utc.timestamp_opt(3600, 0).unwrap());
+    assert_eq!(
+        (bars[0].open, bars[0].high, bars[0].low, bars[0].close),
+        (100.0, 105.0, 95.0, 95.0)
+    );
+    assert_eq!(bars[0].volume, 4.0);
+    assert_eq!(bars[1].volume, 4.0);
+
+    let recent = OhlcvBar::from_trades(
+        &trades,
+        Duration::from_secs(3600),
+        Utc.timestamp_opt(7200, 0).unwrap(),
+    );
+    assert_eq!(recent.len(), 1);
+}
+
+#[tokio::test]
+async fn test_participation_tracks_market_volume() {
+    let engine_tx = start_engine(|trading_pair| Box::new(SimpleOrderBook::new(trading_pair)));
+    seed_asks(&engine_tx, &[100.0; 20]).await;
+
+    let params = ParticipationParams {
+        target_pct: 0.5,
+        max_total_qty: 1.5,
+        poll_interval: Duration::from_millis(20),
+        trading_pair: btc_usd(),
+        side: OrderType::Buy,
+        cancel_token: CancellationToken::new(),
+    };
+    let (filled_tx, mut filled_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::StartParticipation(params, filled_tx))
+        .await
+        .unwrap();
+
+    // Keep printing one-lot market trades until the executor reaches its cap.
+    let mut next_id = 100;
+    let filled = loop {
+        let buy = OrderBuilder::new()
+            .id(next_id)
+            .pair(btc_usd())
+            .buy_at(100.0)
+            .quantity(1.0)
+            .build();
+        next_id += 1;
+        engine_tx.send(Message::NewOrder(buy)).await.unwrap();
+        let (match_tx, mut match_rx) = mpsc::channel(1);
+        engine_tx
+            .send(Message::MatchOrders(btc_usd(), match_tx))
+            .await
+            .unwrap();
+        match_rx.recv().await.unwrap();
+
+        match tokio::time::timeout(Duration::from_millis(30), filled_rx.recv()).await {
+            Ok(filled) => break filled.unwrap(),
+            Err(_) => assert!(next_id < 200, "participation never completed"),
+        }
+    };
+
+    assert_eq!(filled, 1.5);
+}
@@ -0,0 +1,90 @@
+use chrono::{DateTime, TimeZone, Utc};
+use engine::engine::analytics::TradeAggregator;
+use engine::engine::core::{start_engine, Message};
+use engine::engine::models::{OrderType, Trade};
+use engine::engine::order_book::SimpleOrderBook;
+use engine::engine::testing::{btc_usd, OrderBuilder};
+use tokio::sync::mpsc;
+
+fn trade(price: f64, quantity: f64, timestamp: DateTime<Utc>) -> Trade {
+    Trade {
+        id: 0,
+        trading_pair: btc_usd(),
+        buy_order_id: 1,
+        sell_order_id: 2,
+        price: price.into(),
+        quantity: quantity.into(),
+        aggressor_side: OrderType::Buy,
+        timestamp,
+        buy_client_id: None,
+        sell_client_id: None,
+        buy_fee: 0.0,
+        sell_fee: 0.0,
+    }
+}
+
+#[test]
+fn test_daily_stats_reset_at_midnight_utc() {
+    // The aggregator starts on today's date, so use days after it.
+    let day = Utc::now().date_naive() + chrono::Duration::days(1);
+    let at = |hour, minute| Utc.from_utc_datetime(&day.and_hms_opt(hour, minute, 0).unwrap());
+    let mut aggregator = TradeAggregator::new();
+    aggregator.add_trades(&[
+        trade(100.0, 1.0, at(9, 0)),
+        trade(104.0, 0.5, at(12, 0)),
+        trade(98.0, 2.0, at(23, 59)),
+    ]);
+
+    let stats = aggregator.daily_stats(&btc_usd(), at(23, 59));
+    assert_eq!(stats.date, day);
+    assert_eq!(stats.volume, 3.5);
+    assert_eq!(stats.trade_count, 3);
+    assert_eq!((stats.high, stats.low), (Some(104.0), Some(98.0)));
+
+    let next_day = at(23, 59) + chrono::Duration::minutes(1);
+    let stats = aggregator.daily_stats(&btc_usd(), next_day);
+    assert_eq!(stats.date, next_day.date_naive());
+    assert_eq!((stats.volume, stats.trade_count), (0.0, 0));
+    assert_eq!((stats.high, stats.low), (None, None));
+
+    // A late trade from the previous day does not count towards the new one.
+    aggregator.add_trade(&trade(100.0, 1.0, at(22, 0)));
+    assert_eq!(aggregator.daily_stats(&btc_usd(), next_day).trade_count, 0);
+}
+
+#[tokio::test]
+async fn test_get_daily_stats_message() {
+    let engine_tx = start_engine(|trading_pair| Box::new(SimpleOrderBook::new(trading_pair)));
+    for (id, side, quantity) in [(1, OrderType::Sell, 2.0), (2, OrderType::Buy, 1.5)] {
+        let order = OrderBuilder::new()
+            .id(id)
+            .pair(btc_usd())
+            .side(side)
+            .price(100.0)
+            .quantity(quantity)
+            .build();
+        engine_tx.send(Message::NewOrder(order)).await.unwrap();
+    }
+    let (match_tx, mut match_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::MatchOrders(btc_usd(), match_tx))
+        .await
+        .unwrap();
+    match_rx.recv().await.unwrap();
+
+    let (stats_tx, mut stats_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::GetDailyStats(btc_usd(), stats_tx))
+        .await
+        .unwrap();
+    let stats = stats_rx.recv().await.unwrap();
+    assert_eq!((stats.volume, stats.trade_count), (1.5, 1));
+
+    #[cfg(feature = "serde")]
+    {
+        let json = serde_json::to_value(&stats).unwrap();
+        assert_eq!(json["volume"], 1.5);
+        assert_eq!(json["high"], 100.0);
+        assert_eq!(json["trading_pair"]["base"], "BTC");
+    }
+}
@@ -1,3 +1,5 @@
+#![cfg(feature = "rest-api")]
+
 mod common;
 use axum::{
     body::Body,
@@ -0,0 +1,96 @@
+#![cfg(feature = "serde")]
+
+use chrono::{Duration, TimeZone, Utc};
+use engine::engine::backtest::{ReplayEngine, SimulatedClock};
+use engine::engine::core::{start_engine, Engine, Message};
+use engine::engine::order_book::SimpleOrderBook;
+use engine::engine::testing::{btc_usd, OrderBuilder};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+fn replay_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("engine-{}-{}.ndjson", name, std::process::id()))
+}
+
+#[tokio::test]
+async fn test_replay_orders_in_timestamp_order() {
+    let start = Utc.with_ymd_and_hms(2024, 1, 2, 9, 30, 0).unwrap();
+    let at = |seconds: i64| start + Duration::seconds(seconds);
+    let orders = [
+        // Written out of order: the second buy only trades if the sells
+        // rest first.
+        OrderBuilder::new()
+            .id(3)
+            .buy_at(101.0)
+            .quantity(2.0)
+            .timestamp(at(3)),
+        OrderBuilder::new()
+            .id(1)
+            .sell_at(100.0)
+            .quantity(1.0)
+            .timestamp(at(1)),
+        OrderBuilder::new()
+            .id(2)
+            .sell_at(101.0)
+            .quantity(1.0)
+            .timestamp(at(2)),
+        OrderBuilder::new()
+            .id(4)
+            .buy_at(90.0)
+            .quantity(1.0)
+            .timestamp(at(4)),
+    ];
+    let lines: Vec<String> = orders
+        .into_iter()
+        .map(|order| serde_json::to_string(&order.pair(btc_usd()).build()).unwrap())
+        .collect();
+    let path = replay_path("replay");
+    std::fs::write(&path, lines.join("\n")).unwrap();
+
+    let clock = Arc::new(SimulatedClock::new(start));
+    let mut engine = Engine::new(|trading_pair| Box::new(SimpleOrderBook::new(trading_pair)))
+        .with_clock(clock.clone());
+    let (engine_tx, engine_rx) = mpsc::channel(100);
+    tokio::spawn(async move { engine.run(engine_rx).await });
+    let replay = ReplayEngine::new(path.clone(), engine_tx.clone(), clock.clone());
+    let summary = replay.run().await.unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(summary.total_orders, 4);
+    assert_eq!(summary.total_trades, 2);
+    assert_eq!(summary.total_volume, 2.0);
+    assert_eq!(clock.now(), at(4));
+
+    // Both trades happen as the buy recorded at 3s arrives.
+    let (history_tx, mut history_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::GetTradeHistory(btc_usd(), history_tx))
+        .await
+        .unwrap();
+    let timestamps: Vec<_> = history_rx
+        .recv()
+        .await
+        .unwrap()
+        .iter()
+        .map(|trade| trade.timestamp)
+        .collect();
+    assert_eq!(timestamps, vec![at(3), at(3)]);
+}
+
+#[tokio::test]
+async fn test_replay_rejects_malformed_lines() {
+    let path = replay_path("malformed");
+    std::fs::write(&path, "{\"id\": 1}\nnot json\n").unwrap();
+
+    let engine_tx = start_engine(|trading_pair| Box::new(SimpleOrderBook::new(trading_pair)));
+    let clock = Arc::new(SimulatedClock::new(Utc::now()));
+    let error = ReplayEngine::new(path.clone(), engine_tx, clock)
+        .run()
+        .await
+        .unwrap_err();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    assert!(error.to_string().starts_with("line 1"));
+}
@@ -0,0 +1,80 @@
+#![cfg(feature = "serde")]
+
+use engine::engine::bridge::{MessageBridge, RemoteMessage, RemotePayload};
+use engine::engine::core::{start_engine, Message};
+use engine::engine::models::OrderType;
+use engine::engine::order_book::SimpleOrderBook;
+use engine::engine::testing::{btc_usd, OrderBuilder};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+#[test]
+fn test_remote_message_round_trip() {
+    let message = RemoteMessage {
+        correlation_id: Uuid::new_v4(),
+        payload: RemotePayload::CancelOrder(btc_usd(), 7),
+    };
+    let json = serde_json::to_string(&message).unwrap();
+    assert_eq!(
+        serde_json::from_str::<RemoteMessage>(&json).unwrap(),
+        message
+    );
+}
+
+#[tokio::test]
+async fn test_bridge_forwards_messages_and_routes_replies() {
+    let engine_tx = start_engine(|trading_pair| Box::new(SimpleOrderBook::new(trading_pair)));
+    let (local, remote) = tokio::io::duplex(4096);
+    tokio::spawn(MessageBridge::serve(remote, engine_tx));
+    let bridge = MessageBridge::connect(local);
+
+    for (id, side) in [(1, OrderType::Sell), (2, OrderType::Buy)] {
+        let order = OrderBuilder::new()
+            .id(id)
+            .pair(btc_usd())
+            .side(side)
+            .price(100.0)
+            .quantity(1.0)
+            .build();
+        bridge.forward(Message::NewOrder(order)).await.unwrap();
+    }
+
+    let (match_tx, mut match_rx) = mpsc::channel(1);
+    bridge
+        .forward(Message::MatchOrders(btc_usd(), match_tx))
+        .await
+        .unwrap();
+    let trades = match_rx.recv().await.unwrap();
+    assert_eq!(trades.len(), 1);
+    assert_eq!((trades[0].buy_order_id, trades[0].sell_order_id), (2, 1));
+    assert_eq!(bridge.pending_replies(), 0);
+
+    let (book_tx, mut book_rx) = mpsc::channel(1);
+    bridge
+        .forward(Message::GetOrderBook(btc_usd(), book_tx))
+        .await
+        .unwrap();
+    let (bids, asks) = book_rx.recv().await.unwrap();
+    assert!(bids.is_empty() && asks.is_empty());
+
+    let (drain_tx, _drain_rx) = mpsc::channel(1);
+    let unsupported = bridge.forward(Message::Drain(drain_tx)).await.unwrap_err();
+    assert_eq!(unsupported.kind(), std::io::ErrorKind::Unsupported);
+}
+
+#[tokio::test]
+async fn test_bridge_over_tcp() {
+    let engine_tx = start_engine(|trading_pair| Box::new(SimpleOrderBook::new(trading_pair)));
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        MessageBridge::serve(stream, engine_tx).await
+    });
+
+    let bridge = MessageBridge::connect(TcpStream::connect(address).await.unwrap());
+    let (pong_tx, mut pong_rx) = mpsc::channel(1);
+    bridge.forward(Message::Ping(pong_tx)).await.unwrap();
+    assert_eq!(pong_rx.recv().await, Some(()));
+}
@@ -1,6 +1,7 @@
 use engine::engine::concurrent::ConcurrentOrderBook;
-use engine::engine::models::{Order, OrderType, TradingPair};
+use engine::engine::models::{OrderType, TradingPair};
 use engine::engine::order_book::OrderBook;
+use engine::engine::testing::OrderBuilder;
 use rand::rngs::StdRng;
 use rand::{Rng, SeedableRng};
 use std::sync::Arc;
@@ -36,19 +37,19 @@ async fn stress_test_concurrent_order_book() {
             let mut rng = StdRng::seed_from_u64(task_id as u64);
 
             for i in 0..NUM_ORDERS / NUM_CONCURRENT_TASKS {
-                let order = Order {
-                    id: i as u64,
-                    trading_pair: TradingPair::new("BTC".to_string(), "USD".to_string()),
-                    order_type: if rng.gen_bool(0.5) {
-                        OrderType::Buy
-                    } else {
-                        OrderType::Sell
-                    },
-                    price: rng.gen_range(PRICE_RANGE.0..PRICE_RANGE.1),
-                    quantity: rng.gen_range(QUANTITY_RANGE.0..QUANTITY_RANGE.1),
-                    timestamp: chrono::Utc::now(),
+                let order_type = if rng.gen_bool(0.5) {
+                    OrderType::Buy
+                } else {
+                    OrderType::Sell
                 };
-                order_book.add_order(order).await;
+                let order = OrderBuilder::new()
+                    .id(i as u64)
+                    .pair(TradingPair::new("BTC".to_string(), "USD".to_string()))
+                    .side(order_type)
+                    .price(rng.gen_range(PRICE_RANGE.0..PRICE_RANGE.1))
+                    .quantity(rng.gen_range(QUANTITY_RANGE.0..QUANTITY_RANGE.1))
+                    .build();
+                order_book.add_order(order).await.unwrap();
             }
         });
         handles.push(handle);
@@ -111,11 +112,11 @@ async fn stress_test_concurrent_order_book() {
 //
 //     let order_book_clone = order_book.clone();
 //     let buy_handle = task::spawn(async move {
-//         order_book_clone.add_order(buy_order).await;
+//         order_book_clone.add_order(buy_order).await.unwrap();
 //     });
 //
 //     let sell_handle = task::spawn(async move {
-//         order_book.add_order(sell_order).await;
+//         order_book.add_order(sell_order).await.unwrap();
 //     });
 //
 //     buy_handle.await.unwrap();
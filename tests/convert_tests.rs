@@ -0,0 +1,309 @@
+use async_trait::async_trait;
+use engine::engine::api::PriceLevel;
+use engine::engine::convert::{routes, ConversionError};
+use engine::engine::core::{start_engine, EngineError, Message, OrderAck};
+use engine::engine::models::{MatchResult, Order, OrderType, Trade, TradingPair};
+use engine::engine::order_book::{OrderBook, OrderBookError, SimpleOrderBook};
+use engine::engine::testing::OrderBuilder;
+use tokio::sync::mpsc;
+
+fn pair(base: &str, quote: &str) -> TradingPair {
+    TradingPair::new(base.to_string(), quote.to_string())
+}
+
+async fn rest(
+    engine_tx: &mpsc::Sender<Message>,
+    id: u64,
+    trading_pair: TradingPair,
+    side: OrderType,
+    price: f64,
+    quantity: f64,
+) {
+    let order = OrderBuilder::new()
+        .id(id)
+        .pair(trading_pair)
+        .side(side)
+        .price(price)
+        .quantity(quantity)
+        .build();
+    engine_tx.send(Message::NewOrder(order)).await.unwrap();
+}
+
+async fn convert(
+    engine_tx: &mpsc::Sender<Message>,
+    from_asset: &str,
+    quantity: f64,
+    to_asset: &str,
+) -> Result<Vec<Trade>, EngineError> {
+    let (convert_tx, mut convert_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::ConvertCurrency(
+            from_asset.to_string(),
+            quantity,
+            to_asset.to_string(),
+            convert_tx,
+        ))
+        .await
+        .unwrap();
+    convert_rx.recv().await.unwrap()
+}
+
+#[test]
+fn test_routes_direct_then_through_one_asset() {
+    let pairs = [pair("EUR", "USD"), pair("GBP", "USD"), pair("GBP", "EUR")];
+    assert_eq!(
+        routes(&pairs, "EUR", "GBP"),
+        vec![
+            vec![pair("GBP", "EUR")],
+            vec![pair("EUR", "USD"), pair("GBP", "USD")],
+        ]
+    );
+    assert!(routes(&pairs, "EUR", "JPY").is_empty());
+}
+
+#[tokio::test]
+async fn test_convert_through_intermediate_asset() {
+    let engine_tx = start_engine(|trading_pair| Box::new(SimpleOrderBook::new(trading_pair)));
+    rest(&engine_tx, 1, pair("EUR", "USD"), OrderType::Buy, 1.10, 6.0).await;
+    rest(
+        &engine_tx,
+        2,
+        pair("EUR", "USD"),
+        OrderType::Buy,
+        1.05,
+        10.0,
+    )
+    .await;
+    rest(
+        &engine_tx,
+        3,
+        pair("GBP", "USD"),
+        OrderType::Sell,
+        1.25,
+        20.0,
+    )
+    .await;
+
+    // 10 EUR sells for 6 * 1.10 + 4 * 1.05 = 10.8 USD, which buys 8.64 GBP.
+    let trades = convert(&engine_tx, "EUR", 10.0, "GBP").await.unwrap();
+    let fills: Vec<(TradingPair, f64, f64)> = trades
+        .iter()
+        .map(|trade| {
+            (
+                trade.trading_pair.clone(),
+                trade.price.value(),
+                trade.quantity.value(),
+            )
+        })
+        .collect();
+    assert_eq!(fills.len(), 3);
+    assert_eq!(
+        &fills[..2],
+        &[
+            (pair("EUR", "USD"), 1.10, 6.0),
+            (pair("EUR", "USD"), 1.05, 4.0),
+        ]
+    );
+    assert_eq!((fills[2].0.clone(), fills[2].1), (pair("GBP", "USD"), 1.25));
+    assert!((fills[2].2 - 8.64).abs() < 1e-9);
+
+    // Nothing from the conversion is left resting.
+    let (book_tx, mut book_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::GetOrderBook(pair("GBP", "USD"), book_tx))
+        .await
+        .unwrap();
+    let (bids, asks) = book_rx.recv().await.unwrap();
+    assert!(bids.is_empty());
+    assert!((asks[0].total_quantity - (20.0 - 8.64)).abs() < 1e-9);
+}
+
+#[tokio::test]
+async fn test_convert_is_all_or_nothing() {
+    let engine_tx = start_engine(|trading_pair| Box::new(SimpleOrderBook::new(trading_pair)));
+    rest(
+        &engine_tx,
+        1,
+        pair("EUR", "USD"),
+        OrderType::Buy,
+        1.10,
+        100.0,
+    )
+    .await;
+    rest(
+        &engine_tx,
+        2,
+        pair("GBP", "USD"),
+        OrderType::Sell,
+        1.25,
+        1.0,
+    )
+    .await;
+
+    assert!(matches!(
+        convert(&engine_tx, "EUR", 10.0, "GBP").await,
+        Err(EngineError::Conversion(
+            ConversionError::InsufficientLiquidity
+        ))
+    ));
+    assert!(matches!(
+        convert(&engine_tx, "EUR", 10.0, "JPY").await,
+        Err(EngineError::Conversion(ConversionError::NoRoute { .. }))
+    ));
+
+    let (book_tx, mut book_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::GetOrderBook(pair("EUR", "USD"), book_tx))
+        .await
+        .unwrap();
+    let (bids, asks) = book_rx.recv().await.unwrap();
+    assert_eq!(bids[0].total_quantity, 100.0);
+    assert!(asks.is_empty());
+}
+
+async fn levels(
+    engine_tx: &mpsc::Sender<Message>,
+    trading_pair: TradingPair,
+) -> (Vec<PriceLevel>, Vec<PriceLevel>) {
+    let (book_tx, mut book_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::GetOrderBook(trading_pair, book_tx))
+        .await
+        .unwrap();
+    book_rx.recv().await.unwrap()
+}
+
+/// Shows its depth but reports none of it as fillable, like a book whose
+/// resting orders are all-or-none.
+struct UnfillableBook(SimpleOrderBook);
+
+#[async_trait]
+impl OrderBook for UnfillableBook {
+    async fn add_order(&self, order: Order) -> Result<(), OrderBookError> {
+        self.0.add_order(order).await
+    }
+    async fn match_orders(&self) -> MatchResult {
+        self.0.match_orders().await
+    }
+    async fn get_current_price(&self) -> Option<f64> {
+        self.0.get_current_price().await
+    }
+    async fn get_order_book(&self) -> (Vec<PriceLevel>, Vec<PriceLevel>) {
+        self.0.get_order_book().await
+    }
+    async fn get_trade_history(&self) -> Vec<Trade> {
+        self.0.get_trade_history().await
+    }
+    async fn get_active_orders_count(&self) -> usize {
+        self.0.get_active_orders_count().await
+    }
+    async fn cancel_order(&self, order_id: u64) -> Option<Order> {
+        self.0.cancel_order(order_id).await
+    }
+    async fn get_active_orders(&self) -> Vec<Order> {
+        self.0.get_active_orders().await
+    }
+    async fn fillable_quantity(&self, _order: &Order) -> f64 {
+        0.0
+    }
+}
+
+#[tokio::test]
+async fn test_convert_checks_every_leg_before_matching() {
+    let engine_tx = start_engine(|trading_pair| {
+        if trading_pair.base == "GBP" {
+            Box::new(UnfillableBook(SimpleOrderBook::new(trading_pair)))
+        } else {
+            Box::new(SimpleOrderBook::new(trading_pair))
+        }
+    });
+    rest(
+        &engine_tx,
+        1,
+        pair("EUR", "USD"),
+        OrderType::Buy,
+        1.10,
+        100.0,
+    )
+    .await;
+    rest(
+        &engine_tx,
+        2,
+        pair("GBP", "USD"),
+        OrderType::Sell,
+        1.25,
+        100.0,
+    )
+    .await;
+
+    // Both legs plan, but the second cannot fill, so the first is never
+    // matched.
+    assert!(matches!(
+        convert(&engine_tx, "EUR", 10.0, "GBP").await,
+        Err(EngineError::Conversion(
+            ConversionError::InsufficientLiquidity
+        ))
+    ));
+    let (bids, asks) = levels(&engine_tx, pair("EUR", "USD")).await;
+    assert_eq!((bids.len(), bids[0].total_quantity), (1, 100.0));
+    assert!(asks.is_empty());
+    let (bids, asks) = levels(&engine_tx, pair("GBP", "USD")).await;
+    assert!(bids.is_empty());
+    assert_eq!((asks.len(), asks[0].total_quantity), (1, 100.0));
+}
+
+#[tokio::test]
+async fn test_convert_order_type_routes_through_conversion() {
+    let engine_tx = start_engine(|trading_pair| Box::new(SimpleOrderBook::new(trading_pair)));
+    rest(
+        &engine_tx,
+        1,
+        pair("GBP", "EUR"),
+        OrderType::Sell,
+        1.20,
+        50.0,
+    )
+    .await;
+
+    let order = OrderBuilder::new()
+        .id(2)
+        .side(OrderType::Convert {
+            quantity: 12.0,
+            from_asset: "EUR".to_string(),
+            to_asset: "GBP".to_string(),
+        })
+        .build();
+    let (ack_tx, mut ack_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::NewOrderWithCallback(order, ack_tx))
+        .await
+        .unwrap();
+    assert!(matches!(
+        ack_rx.recv().await.unwrap(),
+        OrderAck::Accepted { order_id: 2, .. }
+    ));
+
+    // 12 EUR buys 10 GBP at 1.20 and nothing of the conversion rests.
+    let (bids, asks) = levels(&engine_tx, pair("GBP", "EUR")).await;
+    assert!(bids.is_empty());
+    assert!((asks[0].total_quantity - 40.0).abs() < 1e-9);
+
+    let order = OrderBuilder::new()
+        .id(3)
+        .side(OrderType::Convert {
+            quantity: 12.0,
+            from_asset: "EUR".to_string(),
+            to_asset: "JPY".to_string(),
+        })
+        .build();
+    let (ack_tx, mut ack_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::NewOrderWithCallback(order, ack_tx))
+        .await
+        .unwrap();
+    assert!(matches!(
+        ack_rx.recv().await.unwrap(),
+        OrderAck::Rejected { order_id: 3, reason: OrderBookError::Conversion(e) }
+            if matches!(*e, ConversionError::NoRoute { .. })
+    ));
+}
@@ -0,0 +1,191 @@
+use engine::engine::core::{start_engine, Message};
+use engine::engine::diff::{DiffApplicator, DiffError};
+use engine::engine::models::{MarketEvent, OrderType};
+use engine::engine::order_book::{OrderBook, SimpleOrderBook};
+use engine::engine::testing::{btc_usd, OrderBuilder};
+use tokio::sync::mpsc;
+
+#[tokio::test]
+async fn test_applied_diffs_track_the_book() {
+    let order_book = SimpleOrderBook::new(btc_usd());
+    let mut local = DiffApplicator::new(btc_usd());
+    assert_eq!(order_book.take_diff().await, None);
+
+    let orders = [
+        (1, OrderType::Buy, 99.0, 1.0),
+        (2, OrderType::Buy, 99.0, 2.0),
+        (3, OrderType::Sell, 101.0, 3.0),
+        (4, OrderType::Sell, 99.0, 2.5),
+    ];
+    for (id, side, price, quantity) in orders {
+        let order = OrderBuilder::new()
+            .id(id)
+            .pair(btc_usd())
+            .side(side)
+            .price(price)
+            .quantity(quantity)
+            .build();
+        order_book.add_order(order).await.unwrap();
+        local.apply(&order_book.take_diff().await.unwrap()).unwrap();
+    }
+
+    assert_eq!(order_book.match_orders().await.trades.len(), 2);
+    let diff = order_book.take_diff().await.unwrap();
+    assert_eq!(diff.bids_removed[0].total_quantity, 3.0);
+    assert_eq!(diff.bids_added[0].total_quantity, 0.5);
+    assert!(diff.asks_added.is_empty());
+    local.apply(&diff).unwrap();
+
+    order_book.cancel_order(3).await.unwrap();
+    local.apply(&order_book.take_diff().await.unwrap()).unwrap();
+
+    let (bids, asks) = order_book.get_order_book().await;
+    assert_eq!(local.bids(), bids);
+    assert_eq!(local.asks(), asks);
+    assert_eq!(local.seq(), 6);
+}
+
+#[tokio::test]
+async fn test_applicator_rejects_gaps_and_bad_checksums() {
+    let order_book = SimpleOrderBook::new(btc_usd());
+    let mut local = DiffApplicator::new(btc_usd());
+
+    for id in 1..=2 {
+        order_book
+            .add_order(
+                OrderBuilder::new()
+                    .id(id)
+                    .pair(btc_usd())
+                    .buy_at(100.0 - id as f64)
+                    .quantity(1.0)
+                    .build(),
+            )
+            .await
+            .unwrap();
+    }
+    let first = order_book.take_diff().await.unwrap();
+    let mut corrupted = first.clone();
+    corrupted.bids_added[0].total_quantity = 5.0;
+    assert!(matches!(
+        local.apply(&corrupted),
+        Err(DiffError::ChecksumMismatch { .. })
+    ));
+    assert_eq!(local.seq(), 0);
+
+    let mut skipped = first;
+    skipped.seq = 2;
+    assert_eq!(
+        local.apply(&skipped),
+        Err(DiffError::SequenceGap {
+            expected: 1,
+            received: 2
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_pair_subscription_streams_trades_and_diffs() {
+    let engine_tx = start_engine(|trading_pair| Box::new(SimpleOrderBook::new(trading_pair)));
+    let (subscribe_tx, mut subscribe_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::SubscribeToPair(btc_usd(), subscribe_tx))
+        .await
+        .unwrap();
+    let mut events = subscribe_rx.recv().await.unwrap();
+
+    for order in [
+        OrderBuilder::new()
+            .id(1)
+            .pair(btc_usd())
+            .sell_at(100.0)
+            .quantity(1.0),
+        OrderBuilder::new()
+            .id(2)
+            .pair(btc_usd())
+            .buy_at(100.0)
+            .quantity(1.0),
+    ] {
+        engine_tx
+            .send(Message::NewOrder(order.build()))
+            .await
+            .unwrap();
+    }
+    let (match_tx, mut match_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::MatchOrders(btc_usd(), match_tx))
+        .await
+        .unwrap();
+    match_rx.recv().await.unwrap();
+
+    let mut local = DiffApplicator::new(btc_usd());
+    let mut trades = 0;
+    let mut matches = 0;
+    for _ in 0..5 {
+        match events.recv().await.unwrap() {
+            MarketEvent::BookDiff(diff) => local.apply(&diff).unwrap(),
+            MarketEvent::Trade(_) => trades += 1,
+            MarketEvent::MatchCompleted(result) => {
+                assert_eq!(result.fully_filled, vec![2, 1]);
+                matches += 1;
+            }
+            event => panic!("unexpected event {:?}", event),
+        }
+    }
+    assert_eq!((trades, matches), (1, 1));
+    assert_eq!(local.seq(), 3);
+    assert!(local.bids().is_empty() && local.asks().is_empty());
+}
+
+#[tokio::test]
+async fn test_snapshot_delta_merges_diffs_since_seq() {
+    let order_book = SimpleOrderBook::new(btc_usd());
+    order_book.set_delta_retention(2);
+    let rest = |id, price, quantity| {
+        OrderBuilder::new()
+            .id(id)
+            .pair(btc_usd())
+            .buy_at(price)
+            .quantity(quantity)
+            .build()
+    };
+
+    order_book.add_order(rest(1, 99.0, 1.0)).await.unwrap();
+    order_book.take_diff().await.unwrap();
+    let (bids, asks) = order_book.get_order_book().await;
+    let mut local = DiffApplicator::from_snapshot(btc_usd(), 1, &bids, &asks);
+
+    order_book.add_order(rest(2, 99.0, 2.0)).await.unwrap();
+    order_book.take_diff().await.unwrap();
+    order_book.add_order(rest(3, 98.0, 1.0)).await.unwrap();
+    order_book.take_diff().await.unwrap();
+    // Not yet taken, so not in the delta.
+    order_book.add_order(rest(4, 97.0, 1.0)).await.unwrap();
+
+    let delta = order_book.take_snapshot_delta(1).await;
+    assert_eq!(delta.seq, 3);
+    assert_eq!(delta.bids_removed.len(), 1);
+    assert_eq!(delta.bids_removed[0].total_quantity, 1.0);
+    let merged: Vec<(f64, f64)> = delta
+        .bids_added
+        .iter()
+        .map(|level| (level.price, level.total_quantity))
+        .collect();
+    assert_eq!(merged, vec![(98.0, 1.0), (99.0, 3.0)]);
+
+    // Applying the delta as the next diff lands on seq 3's book.
+    let mut next = delta.clone();
+    next.seq = 2;
+    local.apply(&next).unwrap();
+    assert_eq!(local.checksum(), delta.checksum);
+
+    // Seq 1 has been compacted away, so seq 0 gets the whole book.
+    order_book.take_diff().await.unwrap();
+    let full = order_book.take_snapshot_delta(0).await;
+    assert!(full.bids_removed.is_empty());
+    assert_eq!(full.bids_added.len(), 3);
+    assert!(order_book
+        .take_snapshot_delta(4)
+        .await
+        .bids_added
+        .is_empty());
+}
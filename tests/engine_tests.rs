@@ -0,0 +1,2100 @@
+use engine::engine::accounts::AccountManager;
+use engine::engine::analytics::PairStats;
+use engine::engine::config::{ConfigError, EngineConfig};
+use engine::engine::core::{
+    start_engine_with_config, BroadcastPayload, CorrelationId, DiagnosticReport, Engine, Message,
+    MessageType, OrderAck,
+};
+use engine::engine::fees::{FlatFeeModel, ZeroFeeModel};
+use engine::engine::metrics::{EngineMetrics, EngineMetricsInterface, EngineMetricsSnapshot};
+use engine::engine::models::{
+    MarketEvent, Order, OrderType, OrderValidationError, PriceUpdate, Trade, TradingPair,
+    TradingPairInfo,
+};
+use engine::engine::order_book::{OrderBookError, SimpleOrderBook};
+use engine::engine::risk::{MaxOrderSizeRiskManager, RiskError};
+use engine::engine::testing::{btc_usd, OrderBuilder};
+#[cfg(feature = "testing")]
+use engine::engine::testing::{MetricCall, MockEngineMetrics};
+use engine::engine::version::ENGINE_VERSION;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio::time::Duration;
+use tracing::instrument::WithSubscriber;
+#[cfg(feature = "serde")]
+use tracing::level_filters::LevelFilter;
+use tracing::{span, Metadata, Subscriber};
+
+fn order(id: u64, order_type: OrderType, price: f64, quantity: f64) -> Order {
+    OrderBuilder::new()
+        .id(id)
+        .pair(btc_usd())
+        .side(order_type)
+        .price(price)
+        .quantity(quantity)
+        .build()
+}
+
+#[tokio::test]
+async fn test_concurrent_books_serve_reads() {
+    let config = EngineConfig {
+        concurrent_books: true,
+        ..EngineConfig::default()
+    };
+    let engine_tx = start_engine_with_config(config, |trading_pair| {
+        Box::new(SimpleOrderBook::new(trading_pair))
+    });
+
+    engine_tx
+        .send(Message::NewOrder(order(1, OrderType::Buy, 99.0, 1.0)))
+        .await
+        .unwrap();
+    engine_tx
+        .send(Message::NewOrder(order(2, OrderType::Sell, 101.0, 2.0)))
+        .await
+        .unwrap();
+
+    let (price_tx, mut price_rx) = mpsc::channel(1);
+    let (book_tx, mut book_rx) = mpsc::channel(1);
+    let (history_tx, mut history_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::GetPrice(btc_usd(), price_tx))
+        .await
+        .unwrap();
+    engine_tx
+        .send(Message::GetOrderBook(btc_usd(), book_tx))
+        .await
+        .unwrap();
+    engine_tx
+        .send(Message::GetTradeHistory(btc_usd(), history_tx))
+        .await
+        .unwrap();
+
+    assert_eq!(price_rx.recv().await.unwrap(), Some(100.0));
+    let (bids, asks) = book_rx.recv().await.unwrap();
+    assert_eq!(bids.len(), 1);
+    assert_eq!(asks[0].total_quantity, 2.0);
+    assert!(history_rx.recv().await.unwrap().is_empty());
+
+    let unknown = TradingPair::new("ETH".to_string(), "USD".to_string());
+    let (book_tx, mut book_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::GetOrderBook(unknown, book_tx))
+        .await
+        .unwrap();
+    let (bids, asks) = book_rx.recv().await.unwrap();
+    assert!(bids.is_empty() && asks.is_empty());
+}
+
+#[tokio::test]
+async fn test_reload_config_applies_live_fields() {
+    let engine_tx = start_engine_with_config(EngineConfig::default(), |trading_pair| {
+        Box::new(SimpleOrderBook::new(trading_pair))
+    });
+
+    let (reload_tx, mut reload_rx) = mpsc::channel(1);
+    let config = EngineConfig {
+        max_memory_per_book: Some(1),
+        ..EngineConfig::default()
+    };
+    engine_tx
+        .send(Message::ReloadConfig(config, reload_tx))
+        .await
+        .unwrap();
+    assert_eq!(reload_rx.recv().await.unwrap(), Ok(()));
+
+    // The first order fits in an empty book; the second is over the cap.
+    engine_tx
+        .send(Message::NewOrder(order(1, OrderType::Buy, 99.0, 1.0)))
+        .await
+        .unwrap();
+    engine_tx
+        .send(Message::NewOrder(order(2, OrderType::Buy, 98.0, 1.0)))
+        .await
+        .unwrap();
+
+    let (book_tx, mut book_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::GetOrderBook(btc_usd(), book_tx))
+        .await
+        .unwrap();
+    let (bids, _) = book_rx.recv().await.unwrap();
+    assert_eq!(bids.len(), 1);
+}
+
+#[tokio::test]
+async fn test_reload_config_rejects_immutable_fields() {
+    let engine_tx = start_engine_with_config(EngineConfig::default(), |trading_pair| {
+        Box::new(SimpleOrderBook::new(trading_pair))
+    });
+
+    let (reload_tx, mut reload_rx) = mpsc::channel(1);
+    let config = EngineConfig {
+        channel_capacity: 10,
+        ..EngineConfig::default()
+    };
+    engine_tx
+        .send(Message::ReloadConfig(config, reload_tx.clone()))
+        .await
+        .unwrap();
+    assert_eq!(
+        reload_rx.recv().await.unwrap(),
+        Err(ConfigError::ImmutableField("channel_capacity".to_string()))
+    );
+
+    let config = EngineConfig {
+        log_level: "not[a filter".to_string(),
+        ..EngineConfig::default()
+    };
+    engine_tx
+        .send(Message::ReloadConfig(config, reload_tx))
+        .await
+        .unwrap();
+    assert!(matches!(
+        reload_rx.recv().await.unwrap(),
+        Err(ConfigError::InvalidLogLevel(_))
+    ));
+}
+
+#[cfg(feature = "serde")]
+#[tokio::test]
+async fn test_log_filter_round_trips_and_can_be_set() {
+    assert_eq!(
+        MessageType::GetPrice.default_log_level(),
+        LevelFilter::DEBUG
+    );
+    assert_eq!(MessageType::NewOrder.default_log_level(), LevelFilter::INFO);
+
+    let config: EngineConfig =
+        serde_json::from_str(r#"{"log_filter": {"GetPrice": "off", "NewOrder": "warn"}}"#).unwrap();
+    assert_eq!(config.log_filter[&MessageType::GetPrice], LevelFilter::OFF);
+    assert_eq!(config.log_filter[&MessageType::NewOrder], LevelFilter::WARN);
+    let json = serde_json::to_string(&config).unwrap();
+    assert_eq!(serde_json::from_str::<EngineConfig>(&json).unwrap(), config);
+    assert!(serde_json::from_str::<EngineConfig>(r#"{"log_filter": {"Ping": "loud"}}"#).is_err());
+
+    let engine_tx = start_engine_with_config(config, |trading_pair| {
+        Box::new(SimpleOrderBook::new(trading_pair))
+    });
+    let (set_tx, mut set_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::SetLogFilter(
+            MessageType::Ping,
+            LevelFilter::TRACE,
+            set_tx,
+        ))
+        .await
+        .unwrap();
+    assert_eq!(set_rx.recv().await, Some(()));
+
+    let (ping_tx, mut ping_rx) = mpsc::channel(1);
+    engine_tx.send(Message::Ping(ping_tx)).await.unwrap();
+    assert_eq!(ping_rx.recv().await, Some(()));
+}
+
+#[tokio::test]
+async fn test_subscribe_to_all_trades_spans_pairs_and_reports_lag() {
+    let config = EngineConfig {
+        all_trades_channel_capacity: 2,
+        ..EngineConfig::default()
+    };
+    let engine_tx = start_engine_with_config(config, |trading_pair| {
+        Box::new(SimpleOrderBook::new(trading_pair))
+    });
+
+    let (subscribe_tx, mut subscribe_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::SubscribeToAllTrades(subscribe_tx))
+        .await
+        .unwrap();
+    let mut trades = subscribe_rx.recv().await.unwrap();
+
+    let eth_usd = TradingPair::new("ETH".to_string(), "USD".to_string());
+    for (id, pair) in [(1, btc_usd()), (3, btc_usd()), (5, eth_usd.clone())] {
+        for (id, side) in [(id, OrderType::Sell), (id + 1, OrderType::Buy)] {
+            let order = OrderBuilder::new()
+                .id(id)
+                .pair(pair.clone())
+                .side(side)
+                .price(100.0)
+                .quantity(1.0)
+                .build();
+            engine_tx.send(Message::NewOrder(order)).await.unwrap();
+        }
+        let (match_tx, mut match_rx) = mpsc::channel(1);
+        engine_tx
+            .send(Message::MatchOrders(pair, match_tx))
+            .await
+            .unwrap();
+        assert_eq!(match_rx.recv().await.unwrap().len(), 1);
+    }
+
+    // Three trades into a buffer of two: the oldest is skipped.
+    assert_eq!(
+        trades.recv().await,
+        Err(tokio::sync::broadcast::error::RecvError::Lagged(1))
+    );
+    let trade = trades.recv().await.unwrap();
+    assert_eq!((trade.trading_pair, trade.sell_order_id), (btc_usd(), 3));
+    let trade = trades.recv().await.unwrap();
+    assert_eq!((trade.trading_pair, trade.sell_order_id), (eth_usd, 5));
+}
+
+#[tokio::test]
+async fn test_messages_with_correlation_id_are_handled() {
+    let engine_tx = start_engine_with_config(EngineConfig::default(), |trading_pair| {
+        Box::new(SimpleOrderBook::new(trading_pair))
+    });
+    let correlation_id = CorrelationId(0x4bf9_2f35_77b3_4da6_a3ce_929d_0e0e_4736);
+    assert_eq!(
+        correlation_id.to_string(),
+        "4bf92f3577b34da6a3ce929d0e0e4736"
+    );
+
+    let message =
+        Message::NewOrder(order(1, OrderType::Buy, 99.0, 1.0)).with_correlation_id(correlation_id);
+    assert_eq!(message.message_type(), MessageType::NewOrder);
+    engine_tx.send(message).await.unwrap();
+
+    let (price_tx, mut price_rx) = mpsc::channel(1);
+    let message = Message::GetPrice(btc_usd(), price_tx)
+        .with_correlation_id(correlation_id)
+        .with_correlation_id(CorrelationId(1));
+    engine_tx.send(message).await.unwrap();
+    assert_eq!(price_rx.recv().await.unwrap(), Some(99.0));
+}
+
+#[tokio::test]
+async fn test_price_update_trips_circuit_breaker() {
+    let config = EngineConfig {
+        circuit_breaker_pct: Some(5.0),
+        ..EngineConfig::default()
+    };
+    let engine_tx = start_engine_with_config(config, |trading_pair| {
+        Box::new(SimpleOrderBook::new(trading_pair))
+    });
+
+    let (subscribe_tx, mut subscribe_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::SubscribeToMarketEvents(subscribe_tx))
+        .await
+        .unwrap();
+    let mut events = subscribe_rx.recv().await.unwrap();
+
+    // Non-positive and NaN prices are ignored rather than taken as marks.
+    for price in [100.0, 0.0, f64::NAN, 104.0, -1.0, 115.0] {
+        engine_tx
+            .send(Message::PriceUpdate(PriceUpdate {
+                trading_pair: btc_usd(),
+                price,
+                source: "index".to_string(),
+            }))
+            .await
+            .unwrap();
+    }
+
+    for price in [100.0, 104.0] {
+        match events.recv().await.unwrap() {
+            MarketEvent::MarkPriceUpdate(update) => assert_eq!(update.price, price),
+            event => panic!("unexpected event {:?}", event),
+        }
+    }
+    assert_eq!(
+        events.recv().await.unwrap(),
+        MarketEvent::CircuitBreakerTripped {
+            trading_pair: btc_usd(),
+            reference_price: 104.0,
+            price: 115.0,
+        }
+    );
+
+    // Orders are rejected until trading is resumed.
+    engine_tx
+        .send(Message::NewOrder(order(1, OrderType::Buy, 99.0, 1.0)))
+        .await
+        .unwrap();
+    engine_tx
+        .send(Message::ResumeTrading(btc_usd()))
+        .await
+        .unwrap();
+    engine_tx
+        .send(Message::NewOrder(order(2, OrderType::Buy, 98.0, 1.0)))
+        .await
+        .unwrap();
+
+    let (book_tx, mut book_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::GetOrderBook(btc_usd(), book_tx))
+        .await
+        .unwrap();
+    let (bids, _) = book_rx.recv().await.unwrap();
+    assert_eq!(bids.len(), 1);
+    assert_eq!(bids[0].price, 98.0);
+}
+
+#[tokio::test]
+async fn test_evicting_idle_book_clears_mark_price_and_keeps_subscribers() {
+    let config = EngineConfig {
+        idle_book_ttl_seconds: Some(0),
+        circuit_breaker_pct: Some(5.0),
+        ..EngineConfig::default()
+    };
+    let engine_tx = start_engine_with_config(config, |trading_pair| {
+        Box::new(SimpleOrderBook::new(trading_pair))
+    });
+    let (pair_sub_tx, mut pair_sub_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::SubscribeToPair(btc_usd(), pair_sub_tx))
+        .await
+        .unwrap();
+    let mut pair_events = pair_sub_rx.recv().await.unwrap();
+    let (subscribe_tx, mut subscribe_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::SubscribeToMarketEvents(subscribe_tx))
+        .await
+        .unwrap();
+    let mut events = subscribe_rx.recv().await.unwrap();
+
+    // Leave the book empty, with a mark, then evict it with an order on
+    // another pair.
+    engine_tx
+        .send(Message::NewOrder(order(1, OrderType::Buy, 99.0, 1.0)))
+        .await
+        .unwrap();
+    let (cancel_tx, mut cancel_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::CancelOrder(btc_usd(), 1, cancel_tx))
+        .await
+        .unwrap();
+    assert!(cancel_rx.recv().await.unwrap().is_some());
+    let eth_usd = TradingPair::new("ETH".to_string(), "USD".to_string());
+    for (price, message) in [
+        (100.0, None),
+        (
+            200.0,
+            Some(Message::NewOrder(Order {
+                trading_pair: eth_usd,
+                ..order(2, OrderType::Buy, 10.0, 1.0)
+            })),
+        ),
+    ] {
+        if let Some(message) = message {
+            engine_tx.send(message).await.unwrap();
+        }
+        engine_tx
+            .send(Message::PriceUpdate(PriceUpdate {
+                trading_pair: btc_usd(),
+                price,
+                source: "index".to_string(),
+            }))
+            .await
+            .unwrap();
+    }
+
+    // The stale mark is gone, so the jump to 200 does not trip the breaker.
+    for price in [100.0, 200.0] {
+        match events.recv().await.unwrap() {
+            MarketEvent::MarkPriceUpdate(update) => assert_eq!(update.price, price),
+            event => panic!("unexpected event {:?}", event),
+        }
+    }
+    // The pair subscriber still hears the book once it is back.
+    cross(&engine_tx, 3, 4).await;
+    loop {
+        if let MarketEvent::Trade(trade) = pair_events.recv().await.unwrap() {
+            assert_eq!((trade.sell_order_id, trade.buy_order_id), (3, 4));
+            break;
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_get_engine_version() {
+    let engine_tx = start_engine_with_config(EngineConfig::default(), |trading_pair| {
+        Box::new(SimpleOrderBook::new(trading_pair))
+    });
+
+    let (version_tx, mut version_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::GetEngineVersion(version_tx))
+        .await
+        .unwrap();
+    let version = version_rx.recv().await.unwrap();
+    assert_eq!(version, ENGINE_VERSION);
+    assert_eq!(version.version, env!("CARGO_PKG_VERSION"));
+    assert!(!version.git_sha.is_empty());
+}
+
+#[tokio::test]
+async fn test_ping_answers_within_timeout() {
+    let engine_tx = start_engine_with_config(EngineConfig::default(), |trading_pair| {
+        Box::new(SimpleOrderBook::new(trading_pair))
+    });
+
+    let (pong_tx, mut pong_rx) = mpsc::channel(1);
+    engine_tx.send(Message::Ping(pong_tx)).await.unwrap();
+    let pong = tokio::time::timeout(Duration::from_millis(500), pong_rx.recv()).await;
+    assert_eq!(pong, Ok(Some(())));
+}
+
+/// Rests a sell at 100 and crosses it with a buy, returning the trade.
+async fn cross(engine_tx: &mpsc::Sender<Message>, sell_id: u64, buy_id: u64) -> Trade {
+    for order in [
+        order(sell_id, OrderType::Sell, 100.0, 1.0),
+        order(buy_id, OrderType::Buy, 100.0, 1.0),
+    ] {
+        engine_tx.send(Message::NewOrder(order)).await.unwrap();
+    }
+    let (match_tx, mut match_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::MatchOrders(btc_usd(), match_tx))
+        .await
+        .unwrap();
+    match_rx.recv().await.unwrap().remove(0)
+}
+
+#[tokio::test]
+async fn test_set_fee_model_applies_to_next_match() {
+    let engine_tx = start_engine_with_config(EngineConfig::default(), |trading_pair| {
+        Box::new(SimpleOrderBook::new(trading_pair))
+    });
+
+    let (fee_tx, mut fee_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::SetFeeModel(
+            None,
+            Arc::new(FlatFeeModel::new(10.0, 20.0)),
+            fee_tx.clone(),
+        ))
+        .await
+        .unwrap();
+    fee_rx.recv().await.unwrap();
+
+    // The buy crossed the resting sell, so it pays the taker rate.
+    let trade = cross(&engine_tx, 1, 2).await;
+    assert!((trade.buy_fee - 0.2).abs() < 1e-9);
+    assert!((trade.sell_fee - 0.1).abs() < 1e-9);
+
+    engine_tx
+        .send(Message::SetFeeModel(
+            Some(btc_usd()),
+            Arc::new(ZeroFeeModel),
+            fee_tx,
+        ))
+        .await
+        .unwrap();
+    fee_rx.recv().await.unwrap();
+
+    let trade = cross(&engine_tx, 3, 4).await;
+    assert_eq!((trade.buy_fee, trade.sell_fee), (0.0, 0.0));
+}
+
+#[tokio::test]
+async fn test_broadcast_applies_to_every_pair() {
+    let engine_tx = start_engine_with_config(EngineConfig::default(), |trading_pair| {
+        Box::new(SimpleOrderBook::new(trading_pair))
+    });
+    let eth_usd = TradingPair::new("ETH".to_string(), "USD".to_string());
+    let eth_order = OrderBuilder::new()
+        .id(10)
+        .pair(eth_usd.clone())
+        .buy_at(2000.0)
+        .quantity(1.0)
+        .build();
+    engine_tx.send(Message::NewOrder(eth_order)).await.unwrap();
+    cross(&engine_tx, 1, 2).await;
+
+    let broadcast = |payload| {
+        let engine_tx = engine_tx.clone();
+        async move {
+            let (count_tx, mut count_rx) = mpsc::channel(1);
+            engine_tx
+                .send(Message::Broadcast(payload, count_tx))
+                .await
+                .unwrap();
+            count_rx.recv().await.unwrap()
+        }
+    };
+
+    assert_eq!(broadcast(BroadcastPayload::HaltAllTrading).await, 2);
+    assert_eq!(broadcast(BroadcastPayload::HaltAllTrading).await, 0);
+    let (ack_tx, mut ack_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::NewOrderWithCallback(
+            order(3, OrderType::Sell, 100.0, 1.0),
+            ack_tx,
+        ))
+        .await
+        .unwrap();
+    assert_eq!(
+        ack_rx.recv().await.unwrap(),
+        OrderAck::Rejected {
+            order_id: 3,
+            reason: OrderBookError::TradingHalted(btc_usd()),
+        }
+    );
+    assert_eq!(broadcast(BroadcastPayload::ResumeAllTrading).await, 2);
+
+    let fee_model = Arc::new(FlatFeeModel::new(10.0, 20.0));
+    assert_eq!(
+        broadcast(BroadcastPayload::UpdateFeeModel(fee_model)).await,
+        2
+    );
+    let trade = cross(&engine_tx, 4, 5).await;
+    assert!((trade.buy_fee - 0.2).abs() < 1e-9);
+}
+
+#[tokio::test]
+async fn test_new_order_rejects_invalid_price_and_quantity() {
+    let engine_tx = start_engine_with_config(EngineConfig::default(), |trading_pair| {
+        Box::new(SimpleOrderBook::new(trading_pair))
+    });
+    let cases = [
+        (order(1, OrderType::Buy, 0.0, 1.0), true),
+        (order(2, OrderType::Buy, -5.0, 1.0), true),
+        (order(3, OrderType::Buy, f64::NAN, 1.0), true),
+        (order(4, OrderType::Sell, 100.0, -1.0), false),
+        (order(5, OrderType::Sell, 100.0, f64::NAN), false),
+    ];
+    for (order, bad_price) in cases {
+        let order_id = order.id;
+        let (ack_tx, mut ack_rx) = mpsc::channel(1);
+        engine_tx
+            .send(Message::NewOrderWithCallback(order, ack_tx))
+            .await
+            .unwrap();
+        match ack_rx.recv().await.unwrap() {
+            OrderAck::Rejected {
+                order_id: rejected,
+                reason: OrderBookError::Invalid(e),
+            } => {
+                assert_eq!(rejected, order_id);
+                if bad_price {
+                    assert!(matches!(e, OrderValidationError::InvalidPrice(_)));
+                } else {
+                    assert!(matches!(e, OrderValidationError::InvalidQuantity(_)));
+                }
+            }
+            ack => panic!("order {} was not rejected: {:?}", order_id, ack),
+        }
+    }
+
+    // Plain `NewOrder` runs the same checks, so nothing reaches the book.
+    engine_tx
+        .send(Message::NewOrder(order(6, OrderType::Buy, -1.0, 1.0)))
+        .await
+        .unwrap();
+    engine_tx
+        .send(Message::NewOrder(order(7, OrderType::Sell, 100.0, -2.0)))
+        .await
+        .unwrap();
+    let (book_tx, mut book_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::GetOrderBook(btc_usd(), book_tx))
+        .await
+        .unwrap();
+    let (bids, asks) = book_rx.recv().await.unwrap();
+    assert!(bids.is_empty() && asks.is_empty());
+}
+
+#[tokio::test]
+async fn test_bbo_stream_publishes_top_of_book_changes() {
+    let engine_tx = start_engine_with_config(EngineConfig::default(), |trading_pair| {
+        Box::new(SimpleOrderBook::new(trading_pair))
+    });
+
+    let (subscribe_tx, mut subscribe_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::SubscribeToBbo(btc_usd(), subscribe_tx))
+        .await
+        .unwrap();
+    let mut bbo = subscribe_rx.recv().await.unwrap();
+
+    // The bid at 98 sits behind the best bid and publishes nothing.
+    for order in [
+        order(1, OrderType::Buy, 99.0, 1.0),
+        order(2, OrderType::Sell, 101.0, 2.0),
+        order(3, OrderType::Buy, 98.0, 3.0),
+    ] {
+        engine_tx.send(Message::NewOrder(order)).await.unwrap();
+    }
+    let (cancel_tx, mut cancel_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::CancelOrder(btc_usd(), 1, cancel_tx))
+        .await
+        .unwrap();
+    cancel_rx.recv().await.unwrap();
+
+    let quotes: Vec<_> = (0..3)
+        .map(|_| {
+            let update = bbo.try_recv().unwrap();
+            (update.seq, update.bid, update.bid_qty, update.ask)
+        })
+        .collect();
+    assert_eq!(
+        quotes,
+        vec![
+            (1, Some(99.0), 1.0, None),
+            (2, Some(99.0), 1.0, Some(101.0)),
+            (3, Some(98.0), 3.0, Some(101.0)),
+        ]
+    );
+    assert!(bbo.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn test_get_top_of_book() {
+    let engine_tx = start_engine_with_config(EngineConfig::default(), |trading_pair| {
+        Box::new(SimpleOrderBook::new(trading_pair))
+    });
+
+    for order in [
+        order(1, OrderType::Buy, 99.0, 1.0),
+        order(2, OrderType::Buy, 99.0, 1.5),
+        order(3, OrderType::Sell, 101.0, 2.0),
+        order(4, OrderType::Sell, 102.0, 5.0),
+    ] {
+        engine_tx.send(Message::NewOrder(order)).await.unwrap();
+    }
+
+    let (top_tx, mut top_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::GetTopOfBook(btc_usd(), top_tx.clone()))
+        .await
+        .unwrap();
+    let top = top_rx.recv().await.unwrap();
+    assert_eq!((top.bid, top.bid_qty), (Some(99.0), 2.5));
+    assert_eq!((top.ask, top.ask_qty), (Some(101.0), 2.0));
+    assert_eq!(top.seq, 0);
+
+    let unknown = TradingPair::new("ETH".to_string(), "USD".to_string());
+    engine_tx
+        .send(Message::GetTopOfBook(unknown, top_tx))
+        .await
+        .unwrap();
+    let top = top_rx.recv().await.unwrap();
+    assert!(top.bid.is_none() && top.ask.is_none());
+}
+
+#[tokio::test]
+async fn test_drain_processes_queued_messages_then_stops() {
+    let config = EngineConfig {
+        close_books_on_shutdown: true,
+        ..EngineConfig::default()
+    };
+    let mut engine = Engine::with_config(config, |trading_pair| {
+        Box::new(SimpleOrderBook::new(trading_pair))
+    });
+    let (engine_tx, engine_rx) = mpsc::channel(10);
+
+    // Everything is queued before the engine starts, so the drain sees the
+    // three orders behind it.
+    let (drain_tx, mut drain_rx) = mpsc::channel(1);
+    engine_tx.send(Message::Drain(drain_tx)).await.unwrap();
+    for id in 1..=3 {
+        engine_tx
+            .send(Message::NewOrder(order(id, OrderType::Buy, 99.0, 1.0)))
+            .await
+            .unwrap();
+    }
+    let handle = tokio::spawn(async move {
+        engine.run(engine_rx).await;
+        engine
+    });
+
+    assert_eq!(drain_rx.recv().await, Some(3));
+    let engine = handle.await.unwrap();
+    assert!(engine_tx
+        .send(Message::NewOrder(order(4, OrderType::Buy, 99.0, 1.0)))
+        .await
+        .is_err());
+    // The drain ends in the same shutdown as `Message::Shutdown`.
+    assert_eq!(engine.metrics().snapshot().shutdown_cancelled_orders, 3);
+}
+
+#[tokio::test]
+async fn test_new_order_with_callback_acks() {
+    let engine_tx = start_engine_with_config(EngineConfig::default(), |trading_pair| {
+        Box::new(SimpleOrderBook::new(trading_pair))
+    });
+    engine_tx
+        .send(Message::SetRiskManager(Box::new(
+            MaxOrderSizeRiskManager::uniform(5.0),
+        )))
+        .await
+        .unwrap();
+
+    let (ack_tx, mut ack_rx) = mpsc::channel(1);
+    for order in [
+        order(1, OrderType::Buy, 99.0, 1.0),
+        order(2, OrderType::Buy, 98.0, 1.0),
+        order(3, OrderType::Buy, 97.0, 10.0),
+    ] {
+        engine_tx
+            .send(Message::NewOrderWithCallback(order, ack_tx.clone()))
+            .await
+            .unwrap();
+    }
+
+    assert_eq!(
+        ack_rx.recv().await.unwrap(),
+        OrderAck::Accepted {
+            order_id: 1,
+            book_seq: 1
+        }
+    );
+    assert_eq!(
+        ack_rx.recv().await.unwrap(),
+        OrderAck::Accepted {
+            order_id: 2,
+            book_seq: 2
+        }
+    );
+    assert_eq!(
+        ack_rx.recv().await.unwrap(),
+        OrderAck::Rejected {
+            order_id: 3,
+            reason: OrderBookError::Risk(RiskError::OrderTooLarge {
+                quantity: 10.0,
+                limit: 5.0
+            }),
+        }
+    );
+}
+
+#[tokio::test]
+async fn test_client_orders_across_pairs() {
+    let engine_tx = start_engine_with_config(EngineConfig::default(), |trading_pair| {
+        Box::new(SimpleOrderBook::new(trading_pair))
+    });
+    let eth_usd = TradingPair::new("ETH".to_string(), "USD".to_string());
+    for (id, trading_pair, client) in [
+        (1, btc_usd(), "alice"),
+        (2, eth_usd.clone(), "alice"),
+        (3, eth_usd.clone(), "bob"),
+    ] {
+        let order = OrderBuilder::new()
+            .id(id)
+            .pair(trading_pair)
+            .buy_at(99.0)
+            .quantity(1.0)
+            .build()
+            .with_client(client);
+        engine_tx.send(Message::NewOrder(order)).await.unwrap();
+    }
+
+    let (orders_tx, mut orders_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::GetClientOrders(
+            "bob".to_string(),
+            eth_usd.clone(),
+            orders_tx,
+        ))
+        .await
+        .unwrap();
+    let orders = orders_rx.recv().await.unwrap();
+    assert_eq!(orders.len(), 1);
+    assert_eq!(orders[0].id, 3);
+
+    let (all_tx, mut all_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::GetClientOrdersAllPairs(
+            "alice".to_string(),
+            all_tx,
+        ))
+        .await
+        .unwrap();
+    let by_pair = all_rx.recv().await.unwrap();
+    assert_eq!(by_pair.len(), 2);
+    assert_eq!(by_pair[&btc_usd()][0].id, 1);
+    assert_eq!(by_pair[&eth_usd][0].id, 2);
+}
+
+#[tokio::test]
+async fn test_active_order_counts() {
+    let engine_tx = start_engine_with_config(EngineConfig::default(), |trading_pair| {
+        Box::new(SimpleOrderBook::new(trading_pair))
+    });
+    let eth_usd = TradingPair::new("ETH".to_string(), "USD".to_string());
+    for (id, trading_pair) in [(1, btc_usd()), (2, btc_usd()), (3, eth_usd.clone())] {
+        let order = OrderBuilder::new()
+            .id(id)
+            .pair(trading_pair)
+            .buy_at(99.0)
+            .quantity(1.0)
+            .build();
+        engine_tx.send(Message::NewOrder(order)).await.unwrap();
+    }
+
+    let (count_tx, mut count_rx) = mpsc::channel(1);
+    for (trading_pair, expected) in [
+        (btc_usd(), 2),
+        (eth_usd.clone(), 1),
+        (TradingPair::new("SOL".to_string(), "USD".to_string()), 0),
+    ] {
+        engine_tx
+            .send(Message::GetActiveOrderCount(trading_pair, count_tx.clone()))
+            .await
+            .unwrap();
+        assert_eq!(count_rx.recv().await, Some(expected));
+    }
+
+    let (all_tx, mut all_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::GetActiveOrderCountAllPairs(all_tx))
+        .await
+        .unwrap();
+    let counts = all_rx.recv().await.unwrap();
+    assert_eq!(counts.len(), 2);
+    assert_eq!(counts[&btc_usd()], 2);
+    assert_eq!(counts[&eth_usd], 1);
+}
+
+#[tokio::test]
+async fn test_all_active_orders_across_pairs() {
+    let engine_tx = start_engine_with_config(EngineConfig::default(), |trading_pair| {
+        Box::new(SimpleOrderBook::new(trading_pair))
+    });
+    let eth_usd = TradingPair::new("ETH".to_string(), "USD".to_string());
+    let base = chrono::Utc::now();
+    for (id, trading_pair, client_id, seconds) in [
+        (1, btc_usd(), "alice", 3),
+        (2, eth_usd.clone(), "bob", 1),
+        (3, eth_usd.clone(), "alice", 2),
+    ] {
+        let order = OrderBuilder::new()
+            .id(id)
+            .pair(trading_pair)
+            .buy_at(99.0)
+            .quantity(1.0)
+            .timestamp(base + chrono::Duration::seconds(seconds))
+            .build()
+            .with_client(client_id);
+        engine_tx.send(Message::NewOrder(order)).await.unwrap();
+    }
+
+    let (all_tx, mut all_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::GetAllActiveOrders(all_tx.clone()))
+        .await
+        .unwrap();
+    let ids: Vec<u64> = all_rx.recv().await.unwrap().iter().map(|o| o.id).collect();
+    assert_eq!(ids, vec![2, 3, 1]);
+
+    engine_tx
+        .send(Message::GetAllActiveOrdersByClient(
+            "alice".to_string(),
+            all_tx,
+        ))
+        .await
+        .unwrap();
+    let ids: Vec<u64> = all_rx.recv().await.unwrap().iter().map(|o| o.id).collect();
+    assert_eq!(ids, vec![3, 1]);
+}
+
+#[tokio::test]
+async fn test_uptime_gauge_updates_in_background() {
+    let metrics = EngineMetrics::new();
+    let started = std::time::Instant::now() - Duration::from_secs(5);
+    metrics.spawn_uptime_updates(started);
+
+    // The first update runs straight away.
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(metrics.snapshot().uptime_seconds >= 5);
+
+    metrics.update_uptime(42);
+    assert_eq!(metrics.snapshot().uptime_seconds, 42);
+}
+
+#[tokio::test]
+async fn test_reset_metrics() {
+    let engine_tx = start_engine_with_config(EngineConfig::default(), |trading_pair| {
+        Box::new(SimpleOrderBook::new(trading_pair))
+    });
+    engine_tx
+        .send(Message::SetRiskManager(Box::new(
+            MaxOrderSizeRiskManager::uniform(5.0),
+        )))
+        .await
+        .unwrap();
+    for order in [
+        order(1, OrderType::Sell, 100.0, 1.0),
+        order(2, OrderType::Buy, 100.0, 1.0),
+        order(3, OrderType::Buy, 99.0, 10.0),
+    ] {
+        engine_tx.send(Message::NewOrder(order)).await.unwrap();
+    }
+    let (match_tx, mut match_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::MatchOrders(btc_usd(), match_tx))
+        .await
+        .unwrap();
+    match_rx.recv().await.unwrap();
+
+    let (metrics_tx, mut metrics_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::GetMetrics(metrics_tx.clone()))
+        .await
+        .unwrap();
+    let metrics = metrics_rx.recv().await.unwrap();
+    assert_eq!(metrics.orders_accepted, 2);
+    assert_eq!(metrics.orders_rejected, 1);
+    assert_eq!(metrics.trades_executed, 1);
+    assert_eq!(metrics.order_books, 1);
+
+    let (reset_tx, mut reset_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::ResetMetrics(reset_tx))
+        .await
+        .unwrap();
+    reset_rx.recv().await.unwrap();
+    engine_tx
+        .send(Message::GetMetrics(metrics_tx))
+        .await
+        .unwrap();
+    // Uptime is kept, and ticks over if the test runs past a second.
+    let metrics = metrics_rx.recv().await.unwrap();
+    assert_eq!(
+        metrics,
+        EngineMetricsSnapshot {
+            uptime_seconds: metrics.uptime_seconds,
+            ..Default::default()
+        }
+    );
+}
+
+#[tokio::test]
+async fn test_shutdown_matches_then_cancels_resting_orders() {
+    let config = EngineConfig {
+        close_books_on_shutdown: true,
+        ..EngineConfig::default()
+    };
+    let mut engine = Engine::with_config(config, |trading_pair| {
+        Box::new(SimpleOrderBook::new(trading_pair))
+    });
+    let (engine_tx, engine_rx) = mpsc::channel(10);
+    for order in [
+        order(1, OrderType::Sell, 100.0, 1.0),
+        order(2, OrderType::Buy, 100.0, 1.0),
+        order(3, OrderType::Buy, 98.0, 1.0),
+        order(4, OrderType::Sell, 102.0, 1.0),
+    ] {
+        engine_tx.send(Message::NewOrder(order)).await.unwrap();
+    }
+    engine_tx.send(Message::Shutdown).await.unwrap();
+    engine.run(engine_rx).await;
+
+    let metrics = engine.metrics().snapshot();
+    assert_eq!(metrics.shutdown_trades, 1);
+    assert_eq!(metrics.shutdown_cancelled_orders, 2);
+    assert_eq!(metrics.orders_cancelled, 2);
+    assert_eq!(metrics.trades_executed, 1);
+}
+
+#[tokio::test]
+async fn test_shutdown_leaves_books_by_default() {
+    let mut engine = Engine::new(|trading_pair| Box::new(SimpleOrderBook::new(trading_pair)));
+    let (engine_tx, engine_rx) = mpsc::channel(10);
+    for order in [
+        order(1, OrderType::Sell, 100.0, 1.0),
+        order(2, OrderType::Buy, 100.0, 1.0),
+    ] {
+        engine_tx.send(Message::NewOrder(order)).await.unwrap();
+    }
+    engine_tx.send(Message::Shutdown).await.unwrap();
+    engine.run(engine_rx).await;
+
+    let metrics = engine.metrics().snapshot();
+    assert_eq!(metrics.shutdown_trades, 0);
+    assert_eq!(metrics.shutdown_cancelled_orders, 0);
+    let order_book = engine.get_order_book_handle(&btc_usd()).unwrap();
+    assert_eq!(order_book.read().await.get_active_orders().await.len(), 2);
+}
+
+#[tokio::test]
+async fn test_dedup_rejects_repeated_submissions() {
+    let config = EngineConfig {
+        dedup_window_seconds: Some(60),
+        dedup_capacity: 1,
+        ..EngineConfig::default()
+    };
+    let engine_tx = start_engine_with_config(config, |trading_pair| {
+        Box::new(SimpleOrderBook::new(trading_pair))
+    });
+    let timestamp = chrono::Utc::now();
+    let submission = |id: u64, quantity: f64| {
+        OrderBuilder::new()
+            .id(id)
+            .pair(btc_usd())
+            .buy_at(99.0)
+            .quantity(quantity)
+            .timestamp(timestamp)
+            .build()
+            .with_client("alice")
+    };
+
+    let (ack_tx, mut ack_rx) = mpsc::channel(1);
+    let mut acks = vec![];
+    // A retry under a new id, a different order, then the retry again once
+    // the different order has pushed the first out of the window.
+    for order in [
+        submission(1, 1.0),
+        submission(2, 1.0),
+        submission(3, 2.0),
+        submission(4, 1.0),
+    ] {
+        engine_tx
+            .send(Message::NewOrderWithCallback(order, ack_tx.clone()))
+            .await
+            .unwrap();
+        acks.push(ack_rx.recv().await.unwrap());
+    }
+
+    assert!(matches!(acks[0], OrderAck::Accepted { order_id: 1, .. }));
+    assert!(matches!(
+        &acks[1],
+        OrderAck::Rejected {
+            order_id: 2,
+            reason: OrderBookError::DuplicateSubmission(fingerprint),
+        } if *fingerprint == submission(1, 1.0).fingerprint()
+    ));
+    assert!(matches!(acks[2], OrderAck::Accepted { order_id: 3, .. }));
+    assert!(matches!(acks[3], OrderAck::Accepted { order_id: 4, .. }));
+}
+
+#[tokio::test]
+async fn test_dedup_forgets_rejected_submissions() {
+    let config = EngineConfig {
+        dedup_window_seconds: Some(60),
+        ..EngineConfig::default()
+    };
+    let engine_tx = start_engine_with_config(config, |trading_pair| {
+        Box::new(SimpleOrderBook::new(trading_pair))
+    });
+    let submission = order(1, OrderType::Buy, 99.0, 1.0).with_client("alice");
+    let (ack_tx, mut ack_rx) = mpsc::channel(1);
+    let submit = |accounts: AccountManager| {
+        let engine_tx = engine_tx.clone();
+        let order = submission.clone();
+        let ack_tx = ack_tx.clone();
+        async move {
+            engine_tx
+                .send(Message::SetAccountManager(accounts))
+                .await
+                .unwrap();
+            engine_tx
+                .send(Message::NewOrderWithCallback(order, ack_tx))
+                .await
+                .unwrap();
+        }
+    };
+
+    // Rejected for want of funds, then retried once alice has deposited.
+    submit(AccountManager::new()).await;
+    assert!(matches!(
+        ack_rx.recv().await.unwrap(),
+        OrderAck::Rejected {
+            reason: OrderBookError::Account(_),
+            ..
+        }
+    ));
+    let mut funded = AccountManager::new();
+    funded.deposit("alice", "USD", 1000.0);
+    submit(funded).await;
+    assert!(matches!(
+        ack_rx.recv().await.unwrap(),
+        OrderAck::Accepted { order_id: 1, .. }
+    ));
+}
+
+#[tokio::test]
+async fn test_side_limits_reject_new_orders_but_keep_matching() {
+    let config = EngineConfig {
+        default_max_orders_per_side: Some(2),
+        ..EngineConfig::default()
+    };
+    let engine_tx = start_engine_with_config(config, |trading_pair| {
+        Box::new(SimpleOrderBook::new(trading_pair))
+    });
+    let eth_usd = TradingPair::new("ETH".to_string(), "USD".to_string());
+    let (loaded_tx, mut loaded_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::LoadReferenceData(
+            vec![TradingPairInfo {
+                max_orders_per_side: Some(1),
+                ..TradingPairInfo::new(eth_usd.clone(), 0.01, 0.01)
+            }],
+            loaded_tx,
+        ))
+        .await
+        .unwrap();
+    loaded_rx.recv().await.unwrap();
+
+    let (ack_tx, mut ack_rx) = mpsc::channel(10);
+    let submit = |order: Order| {
+        let ack_tx = ack_tx.clone();
+        let engine_tx = engine_tx.clone();
+        async move {
+            engine_tx
+                .send(Message::NewOrderWithCallback(order, ack_tx))
+                .await
+                .unwrap();
+        }
+    };
+    for id in 1..=3 {
+        submit(order(id, OrderType::Sell, 100.0 + id as f64, 1.0)).await;
+    }
+    submit(
+        OrderBuilder::new()
+            .id(4)
+            .pair(eth_usd.clone())
+            .sell_at(10.0)
+            .quantity(1.0)
+            .build(),
+    )
+    .await;
+    submit(
+        OrderBuilder::new()
+            .id(5)
+            .pair(eth_usd)
+            .sell_at(11.0)
+            .quantity(1.0)
+            .build(),
+    )
+    .await;
+
+    let mut rejected = vec![];
+    for _ in 0..5 {
+        if let OrderAck::Rejected { order_id, reason } = ack_rx.recv().await.unwrap() {
+            assert!(matches!(reason, OrderBookError::SideFull { limit, .. } if limit <= 2));
+            rejected.push(order_id);
+        }
+    }
+    assert_eq!(rejected, vec![3, 5]);
+
+    // The bid side still has room, and resting asks still trade.
+    submit(order(6, OrderType::Buy, 101.0, 1.0)).await;
+    assert!(matches!(
+        ack_rx.recv().await.unwrap(),
+        OrderAck::Accepted { order_id: 6, .. }
+    ));
+    let (match_tx, mut match_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::MatchOrders(btc_usd(), match_tx))
+        .await
+        .unwrap();
+    assert_eq!(match_rx.recv().await.unwrap().len(), 1);
+    submit(order(7, OrderType::Sell, 103.0, 1.0)).await;
+    assert!(matches!(
+        ack_rx.recv().await.unwrap(),
+        OrderAck::Accepted { order_id: 7, .. }
+    ));
+}
+
+#[tokio::test]
+async fn test_warm_up_order_book_message() {
+    let engine_tx = start_engine_with_config(EngineConfig::default(), |trading_pair| {
+        Box::new(SimpleOrderBook::new(trading_pair))
+    });
+    let (warm_tx, mut warm_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::WarmUpOrderBook(
+            btc_usd(),
+            vec![
+                order(1, OrderType::Buy, 99.0, 1.0),
+                order(2, OrderType::Buy, 98.0, 2.0),
+                order(3, OrderType::Sell, 101.0, 1.5),
+            ],
+            warm_tx,
+        ))
+        .await
+        .unwrap();
+    warm_rx.recv().await.unwrap();
+
+    let (book_tx, mut book_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::GetOrderBook(btc_usd(), book_tx))
+        .await
+        .unwrap();
+    let (bids, asks) = book_rx.recv().await.unwrap();
+    assert_eq!((bids.len(), asks.len()), (2, 1));
+    assert_eq!(asks[0].total_quantity, 1.5);
+}
+
+#[tokio::test]
+async fn test_price_update_triggers_stop_orders() {
+    let engine_tx = start_engine_with_config(EngineConfig::default(), |trading_pair| {
+        Box::new(SimpleOrderBook::new(trading_pair))
+    });
+    let stop = OrderBuilder::new()
+        .id(1)
+        .pair(btc_usd())
+        .sell_at(95.0)
+        .quantity(1.0)
+        .build();
+    engine_tx
+        .send(Message::NewOrder(stop.clone().with_stop_price(96.0)))
+        .await
+        .unwrap();
+    // A sell stop below its limit is rejected.
+    engine_tx
+        .send(Message::NewOrder(Order {
+            id: 2,
+            ..stop.with_stop_price(94.0)
+        }))
+        .await
+        .unwrap();
+
+    for price in [100.0, 96.0] {
+        engine_tx
+            .send(Message::PriceUpdate(PriceUpdate {
+                trading_pair: btc_usd(),
+                price,
+                source: "index".to_string(),
+            }))
+            .await
+            .unwrap();
+        let (book_tx, mut book_rx) = mpsc::channel(1);
+        engine_tx
+            .send(Message::GetOrderBook(btc_usd(), book_tx))
+            .await
+            .unwrap();
+        let (_, asks) = book_rx.recv().await.unwrap();
+        assert_eq!(asks.len(), usize::from(price == 96.0));
+    }
+}
+
+#[tokio::test]
+async fn test_triggered_stop_orders_auto_match() {
+    let config = EngineConfig {
+        auto_match: true,
+        ..EngineConfig::default()
+    };
+    let engine_tx = start_engine_with_config(config, |trading_pair| {
+        Box::new(SimpleOrderBook::new(trading_pair))
+    });
+    for order in [
+        order(1, OrderType::Buy, 95.0, 1.0),
+        order(2, OrderType::Sell, 95.0, 1.0).with_stop_price(96.0),
+    ] {
+        engine_tx.send(Message::NewOrder(order)).await.unwrap();
+    }
+    engine_tx
+        .send(Message::PriceUpdate(PriceUpdate {
+            trading_pair: btc_usd(),
+            price: 96.0,
+            source: "index".to_string(),
+        }))
+        .await
+        .unwrap();
+
+    let (history_tx, mut history_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::GetTradeHistory(btc_usd(), history_tx))
+        .await
+        .unwrap();
+    let trades = history_rx.recv().await.unwrap();
+    assert_eq!(trades.len(), 1);
+    assert_eq!((trades[0].buy_order_id, trades[0].sell_order_id), (1, 2));
+}
+
+#[tokio::test]
+async fn test_stop_orders_count_as_active_once_triggered() {
+    let mut engine = Engine::new(|trading_pair| Box::new(SimpleOrderBook::new(trading_pair)));
+    let (engine_tx, engine_rx) = mpsc::channel(10);
+    for order in [
+        order(1, OrderType::Sell, 95.0, 1.0).with_stop_price(96.0),
+        order(2, OrderType::Buy, 121.0, 1.0).with_stop_price(120.0),
+        order(3, OrderType::Sell, 96.0, 1.0),
+        order(4, OrderType::Buy, 96.0, 1.0),
+    ] {
+        engine_tx.send(Message::NewOrder(order)).await.unwrap();
+    }
+    let (match_tx, _match_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::MatchOrders(btc_usd(), match_tx))
+        .await
+        .unwrap();
+    drop(engine_tx);
+    engine.run(engine_rx).await;
+
+    // The trade at 96 sets off the first stop alone, which then rests.
+    let order_book = engine.get_order_book_handle(&btc_usd()).unwrap();
+    let order_book = order_book.read().await;
+    assert_eq!(order_book.get_active_orders_count().await, 1);
+    assert_eq!(order_book.get_stop_orders().await.len(), 1);
+    assert_eq!(engine.metrics().active_orders(&btc_usd()), 1);
+}
+
+#[tokio::test]
+async fn test_book_depth_gauges_follow_adds_and_matches() {
+    let mut engine = Engine::new(|trading_pair| Box::new(SimpleOrderBook::new(trading_pair)));
+    let (engine_tx, engine_rx) = mpsc::channel(10);
+    for order in [
+        order(1, OrderType::Buy, 99.0, 1.0),
+        order(2, OrderType::Buy, 98.0, 1.0),
+        order(3, OrderType::Sell, 99.0, 1.0),
+    ] {
+        engine_tx.send(Message::NewOrder(order)).await.unwrap();
+    }
+    let (match_tx, _match_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::MatchOrders(btc_usd(), match_tx))
+        .await
+        .unwrap();
+    drop(engine_tx);
+    engine.run(engine_rx).await;
+
+    // Only bids are left once the ask has traded away.
+    assert_eq!(engine.metrics().book_depth(&btc_usd(), "bid"), Some(1));
+    assert_eq!(engine.metrics().book_depth(&btc_usd(), "ask"), Some(0));
+    assert_eq!(
+        engine.metrics().book_depth(
+            &TradingPair::new("ETH".to_string(), "USD".to_string()),
+            "bid"
+        ),
+        None
+    );
+}
+
+/// Rests bids at 99, 98 and 97, then cancels the two lower ones, leaving
+/// their levels empty.
+async fn empty_two_bid_levels(engine_tx: &mpsc::Sender<Message>) {
+    for (id, price) in [(1, 99.0), (2, 98.0), (3, 97.0)] {
+        engine_tx
+            .send(Message::NewOrder(order(id, OrderType::Buy, price, 1.0)))
+            .await
+            .unwrap();
+    }
+    for id in [2, 3] {
+        let (cancel_tx, _cancel_rx) = mpsc::channel(1);
+        engine_tx
+            .send(Message::CancelOrder(btc_usd(), id, cancel_tx))
+            .await
+            .unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_rebalance_order_book_message_drops_empty_levels() {
+    let mut engine = Engine::new(|trading_pair| Box::new(SimpleOrderBook::new(trading_pair)));
+    let (engine_tx, engine_rx) = mpsc::channel(10);
+    empty_two_bid_levels(&engine_tx).await;
+    let (rebalance_tx, mut rebalance_rx) = mpsc::channel(2);
+    for trading_pair in [btc_usd(), btc_usd()] {
+        engine_tx
+            .send(Message::RebalanceOrderBook(
+                trading_pair,
+                rebalance_tx.clone(),
+            ))
+            .await
+            .unwrap();
+    }
+    drop(engine_tx);
+    engine.run(engine_rx).await;
+
+    assert_eq!(rebalance_rx.recv().await, Some(2));
+    assert_eq!(rebalance_rx.recv().await, Some(0));
+    let order_book = engine.get_order_book_handle(&btc_usd()).unwrap();
+    assert_eq!(order_book.read().await.bid_level_count().await, 1);
+}
+
+#[tokio::test]
+async fn test_add_past_rebalance_threshold_drops_empty_levels() {
+    let mut engine = Engine::new(|trading_pair| Box::new(SimpleOrderBook::new(trading_pair)));
+    let (engine_tx, engine_rx) = mpsc::channel(10);
+    let (load_tx, _load_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::LoadReferenceData(
+            vec![TradingPairInfo {
+                rebalance_threshold: Some(0.5),
+                ..TradingPairInfo::new(btc_usd(), 0.01, 0.01)
+            }],
+            load_tx,
+        ))
+        .await
+        .unwrap();
+    empty_two_bid_levels(&engine_tx).await;
+    // Cancels leave the empty levels alone; the next add finds two empty
+    // levels per two holding orders and sweeps them.
+    engine_tx
+        .send(Message::NewOrder(order(4, OrderType::Buy, 96.0, 1.0)))
+        .await
+        .unwrap();
+    drop(engine_tx);
+    engine.run(engine_rx).await;
+
+    let order_book = engine.get_order_book_handle(&btc_usd()).unwrap();
+    let order_book = order_book.read().await;
+    assert_eq!(order_book.bid_level_count().await, 2);
+    assert_eq!(order_book.empty_level_ratio().await, 0.0);
+}
+
+#[tokio::test]
+async fn test_auto_match_trades_on_each_new_order() {
+    let config = EngineConfig {
+        auto_match: true,
+        ..EngineConfig::default()
+    };
+    let engine_tx = start_engine_with_config(config, |trading_pair| {
+        Box::new(SimpleOrderBook::new(trading_pair))
+    });
+    let (subscribe_tx, mut subscribe_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::SubscribeToAllTrades(subscribe_tx))
+        .await
+        .unwrap();
+    let mut trades = subscribe_rx.recv().await.unwrap();
+
+    engine_tx
+        .send(Message::NewOrder(order(1, OrderType::Sell, 100.0, 2.0)))
+        .await
+        .unwrap();
+    let (ack_tx, mut ack_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::NewOrderWithCallback(
+            order(2, OrderType::Buy, 100.0, 1.5),
+            ack_tx,
+        ))
+        .await
+        .unwrap();
+    assert!(matches!(
+        ack_rx.recv().await.unwrap(),
+        OrderAck::Accepted { order_id: 2, .. }
+    ));
+
+    let trade = trades.recv().await.unwrap();
+    assert_eq!(
+        (
+            trade.sell_order_id,
+            trade.buy_order_id,
+            trade.quantity.value()
+        ),
+        (1, 2, 1.5)
+    );
+    // Nothing is left to cross for an explicit match.
+    let (match_tx, mut match_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::MatchOrders(btc_usd(), match_tx))
+        .await
+        .unwrap();
+    assert!(match_rx.recv().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_match_feeds_last_trade_price_back_as_mark() {
+    let engine_tx = start_engine_with_config(EngineConfig::default(), |trading_pair| {
+        Box::new(SimpleOrderBook::new(trading_pair))
+    });
+    let (subscribe_tx, mut subscribe_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::SubscribeToMarketEvents(subscribe_tx))
+        .await
+        .unwrap();
+    let mut events = subscribe_rx.recv().await.unwrap();
+
+    cross(&engine_tx, 1, 2).await;
+    match events.recv().await.unwrap() {
+        MarketEvent::MarkPriceUpdate(update) => {
+            assert_eq!(
+                (update.price, update.source.as_str()),
+                (100.0, "last_trade")
+            );
+        }
+        event => panic!("unexpected event {:?}", event),
+    }
+}
+
+#[cfg(feature = "serde")]
+#[tokio::test]
+async fn test_pairs_differing_only_in_case_share_a_book() {
+    let engine_tx = start_engine_with_config(EngineConfig::default(), |trading_pair| {
+        Box::new(SimpleOrderBook::new(trading_pair))
+    });
+    let lower = TradingPair::new("btc".to_string(), "usd".to_string());
+    let mixed: TradingPair = serde_json::from_str(r#"{"base": "Btc", "quote": "usD"}"#).unwrap();
+    for (id, trading_pair) in [(1, lower), (2, mixed)] {
+        let order = OrderBuilder::new()
+            .id(id)
+            .pair(trading_pair)
+            .buy_at(99.0)
+            .quantity(1.0)
+            .build();
+        engine_tx.send(Message::NewOrder(order)).await.unwrap();
+    }
+
+    let (book_tx, mut book_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::GetOrderBook(btc_usd(), book_tx))
+        .await
+        .unwrap();
+    let (bids, _) = book_rx.recv().await.unwrap();
+    assert_eq!((bids[0].total_quantity, bids[0].order_count), (2.0, 2));
+}
+
+#[tokio::test]
+async fn test_bust_trade_publishes_event() {
+    let engine_tx = start_engine_with_config(EngineConfig::default(), |trading_pair| {
+        Box::new(SimpleOrderBook::new(trading_pair))
+    });
+    let trade = cross(&engine_tx, 1, 2).await;
+    let (subscribe_tx, mut subscribe_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::SubscribeToMarketEvents(subscribe_tx))
+        .await
+        .unwrap();
+    let mut events = subscribe_rx.recv().await.unwrap();
+
+    let (bust_tx, mut bust_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::BustTrade(btc_usd(), trade.id, bust_tx.clone()))
+        .await
+        .unwrap();
+    assert!(bust_rx.recv().await.unwrap().is_ok());
+    loop {
+        match events.recv().await.unwrap() {
+            MarketEvent::TradeBusted {
+                trading_pair,
+                trade_id,
+            } => {
+                assert_eq!((trading_pair, trade_id), (btc_usd(), trade.id));
+                break;
+            }
+            MarketEvent::MarkPriceUpdate(_) => {}
+            event => panic!("unexpected event {:?}", event),
+        }
+    }
+
+    engine_tx
+        .send(Message::BustTrade(btc_usd(), trade.id, bust_tx))
+        .await
+        .unwrap();
+    assert!(matches!(
+        bust_rx.recv().await.unwrap(),
+        Err(OrderBookError::TradeNotFound(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_bust_trade_refused_once_accounts_settle() {
+    let engine_tx = start_engine_with_config(EngineConfig::default(), |trading_pair| {
+        Box::new(SimpleOrderBook::new(trading_pair))
+    });
+    let trade = cross(&engine_tx, 1, 2).await;
+    engine_tx
+        .send(Message::SetAccountManager(AccountManager::new()))
+        .await
+        .unwrap();
+
+    let (bust_tx, mut bust_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::BustTrade(btc_usd(), trade.id, bust_tx))
+        .await
+        .unwrap();
+    assert!(matches!(
+        bust_rx.recv().await.unwrap(),
+        Err(OrderBookError::TradeSettled(id)) if id == trade.id
+    ));
+    let (history_tx, mut history_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::GetTradeHistory(btc_usd(), history_tx))
+        .await
+        .unwrap();
+    assert_eq!(history_rx.recv().await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_benchmark_mode_records_no_metrics() {
+    let config = EngineConfig {
+        benchmark_mode: true,
+        ..EngineConfig::default()
+    };
+    let engine_tx = start_engine_with_config(config.clone(), |trading_pair| {
+        Box::new(SimpleOrderBook::new(trading_pair))
+    });
+    cross(&engine_tx, 1, 2).await;
+
+    let (metrics_tx, mut metrics_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::GetMetrics(metrics_tx))
+        .await
+        .unwrap();
+    assert_eq!(metrics_rx.recv().await.unwrap(), Default::default());
+
+    let (reload_tx, mut reload_rx) = mpsc::channel(1);
+    let reloaded = EngineConfig {
+        benchmark_mode: false,
+        ..config
+    };
+    engine_tx
+        .send(Message::ReloadConfig(reloaded, reload_tx))
+        .await
+        .unwrap();
+    assert!(matches!(
+        reload_rx.recv().await.unwrap(),
+        Err(ConfigError::ImmutableField(field)) if field == "benchmark_mode"
+    ));
+}
+
+/// Counts the events logged while it is the default subscriber.
+struct CountingSubscriber(Arc<AtomicUsize>);
+
+impl Subscriber for CountingSubscriber {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+    fn new_span(&self, _: &span::Attributes<'_>) -> span::Id {
+        span::Id::from_u64(1)
+    }
+    fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+    fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+    fn event(&self, _: &tracing::Event<'_>) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+    fn enter(&self, _: &span::Id) {}
+    fn exit(&self, _: &span::Id) {}
+}
+
+#[tokio::test]
+async fn test_benchmark_mode_silences_only_its_own_engine() {
+    let events = Arc::new(AtomicUsize::new(0));
+    let mut logged = Vec::new();
+    for benchmark_mode in [true, false] {
+        let config = EngineConfig {
+            benchmark_mode,
+            ..EngineConfig::default()
+        };
+        let mut engine = Engine::with_config(config, |trading_pair| {
+            Box::new(SimpleOrderBook::new(trading_pair))
+        });
+        let (engine_tx, engine_rx) = mpsc::channel(8);
+        for order in [
+            order(1, OrderType::Sell, 100.0, 1.0),
+            order(2, OrderType::Buy, 100.0, 1.0),
+        ] {
+            engine_tx.send(Message::NewOrder(order)).await.unwrap();
+        }
+        drop(engine_tx);
+
+        let before = events.load(Ordering::SeqCst);
+        engine
+            .run(engine_rx)
+            .with_subscriber(CountingSubscriber(events.clone()))
+            .await;
+        logged.push(events.load(Ordering::SeqCst) - before);
+    }
+    assert_eq!(logged[0], 0);
+    assert!(logged[1] > 0);
+}
+
+#[tokio::test]
+async fn test_get_stale_orders_uses_time_in_book() {
+    let engine_tx = start_engine_with_config(EngineConfig::default(), |trading_pair| {
+        Box::new(SimpleOrderBook::new(trading_pair))
+    });
+    let (stale_tx, mut stale_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::NewOrder(order(1, OrderType::Buy, 99.0, 1.0)))
+        .await
+        .unwrap();
+    // The engine stamps each order as it accepts it.
+    engine_tx
+        .send(Message::GetStaleOrders(
+            btc_usd(),
+            chrono::Duration::zero(),
+            stale_tx.clone(),
+        ))
+        .await
+        .unwrap();
+    let resting = stale_rx.recv().await.unwrap();
+    assert!(resting[0].received_at.is_some());
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    engine_tx
+        .send(Message::NewOrder(order(2, OrderType::Buy, 98.0, 1.0)))
+        .await
+        .unwrap();
+    engine_tx
+        .send(Message::GetStaleOrders(
+            btc_usd(),
+            chrono::Duration::milliseconds(50),
+            stale_tx,
+        ))
+        .await
+        .unwrap();
+    let ids: Vec<u64> = stale_rx
+        .recv()
+        .await
+        .unwrap()
+        .iter()
+        .map(|o| o.id)
+        .collect();
+    assert_eq!(ids, vec![1]);
+}
+
+#[test]
+fn test_synchronous_calls_without_runtime() {
+    let config = EngineConfig {
+        auto_match: true,
+        ..EngineConfig::default()
+    };
+    let mut engine = Engine::with_config(config, |trading_pair| {
+        Box::new(SimpleOrderBook::new(trading_pair))
+    });
+
+    let trades = engine
+        .process_order_synchronously(order(1, OrderType::Sell, 100.0, 2.0))
+        .unwrap();
+    assert!(trades.is_empty());
+    engine
+        .process_order_synchronously(order(2, OrderType::Sell, 101.0, 1.0))
+        .unwrap();
+    let trades = engine
+        .process_order_synchronously(order(3, OrderType::Buy, 100.0, 1.5))
+        .unwrap();
+    assert_eq!(
+        trades
+            .iter()
+            .map(|trade| (trade.sell_order_id, trade.quantity.value()))
+            .collect::<Vec<_>>(),
+        [(1, 1.5)]
+    );
+
+    let stop = order(4, OrderType::Buy, 99.0, 1.0).with_stop_price(f64::NAN);
+    assert!(matches!(
+        engine.process_order_synchronously(stop),
+        Err(OrderBookError::Invalid(_))
+    ));
+    assert_eq!(
+        engine
+            .cancel_order_synchronously(&btc_usd(), 1)
+            .map(|order| order.quantity.value()),
+        Some(0.5)
+    );
+    assert!(engine.cancel_order_synchronously(&btc_usd(), 1).is_none());
+    assert_eq!(engine.get_price_synchronously(&btc_usd()), Some(101.0));
+    assert_eq!(engine.metrics().snapshot().orders_rejected, 1);
+}
+
+#[tokio::test]
+async fn test_get_trade_history_paginated() {
+    let engine_tx = start_engine_with_config(EngineConfig::default(), |trading_pair| {
+        Box::new(SimpleOrderBook::new(trading_pair))
+    });
+    for id in 0..5 {
+        cross(&engine_tx, 2 * id + 1, 2 * id + 2).await;
+    }
+
+    let (page_tx, mut page_rx) = mpsc::channel(1);
+    for (offset, limit) in [(0, 2), (4, 2), (9, 2)] {
+        engine_tx
+            .send(Message::GetTradeHistoryPaginated(
+                btc_usd(),
+                offset,
+                limit,
+                page_tx.clone(),
+            ))
+            .await
+            .unwrap();
+    }
+    let first = page_rx.recv().await.unwrap();
+    let sell_ids: Vec<u64> = first.trades.iter().map(|t| t.sell_order_id).collect();
+    assert_eq!(sell_ids, [1, 3]);
+    assert_eq!((first.total_count, first.has_more), (5, true));
+    let last = page_rx.recv().await.unwrap();
+    assert_eq!(last.trades[0].sell_order_id, 9);
+    assert_eq!((last.trades.len(), last.has_more), (1, false));
+    assert!(page_rx.recv().await.unwrap().trades.is_empty());
+
+    let unknown = TradingPair::new("ETH".to_string(), "USD".to_string());
+    engine_tx
+        .send(Message::GetTradeHistoryPaginated(unknown, 0, 10, page_tx))
+        .await
+        .unwrap();
+    assert_eq!(page_rx.recv().await.unwrap(), Default::default());
+}
+
+#[tokio::test]
+async fn test_diagnose_engine_reports_crossed_books() {
+    let engine_tx = start_engine_with_config(EngineConfig::default(), |trading_pair| {
+        Box::new(SimpleOrderBook::new(trading_pair))
+    });
+    let eth_usd = TradingPair::new("ETH".to_string(), "USD".to_string());
+    // Without auto_match the book stays crossed until asked to match.
+    engine_tx
+        .send(Message::NewOrder(
+            order(1, OrderType::Buy, 101.0, 1.0).with_client("alice"),
+        ))
+        .await
+        .unwrap();
+    engine_tx
+        .send(Message::NewOrder(order(2, OrderType::Sell, 100.0, 1.0)))
+        .await
+        .unwrap();
+    let resting = OrderBuilder::new()
+        .id(3)
+        .pair(eth_usd)
+        .buy_at(2000.0)
+        .quantity(1.0)
+        .build();
+    engine_tx.send(Message::NewOrder(resting)).await.unwrap();
+
+    let (report_tx, mut report_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::DiagnoseEngine(report_tx.clone()))
+        .await
+        .unwrap();
+    let report = report_rx.recv().await.unwrap();
+    assert!(report.is_healthy());
+    assert_eq!(
+        report,
+        DiagnosticReport {
+            crossed_books: vec![btc_usd()],
+            total_pairs: 2,
+            total_orders: 3,
+            ..DiagnosticReport::default()
+        }
+    );
+
+    let (match_tx, mut match_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::MatchOrders(btc_usd(), match_tx))
+        .await
+        .unwrap();
+    match_rx.recv().await.unwrap();
+    engine_tx
+        .send(Message::DiagnoseEngine(report_tx))
+        .await
+        .unwrap();
+    let report = report_rx.recv().await.unwrap();
+    assert!(report.crossed_books.is_empty());
+    assert_eq!(report.total_orders, 1);
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn test_per_pair_metrics_through_mock() {
+    let config = EngineConfig {
+        auto_match: true,
+        ..EngineConfig::default()
+    };
+    let mock = Arc::new(MockEngineMetrics::new());
+    let mut engine = Engine::with_config(config.clone(), |trading_pair| {
+        Box::new(SimpleOrderBook::new(trading_pair))
+    })
+    .with_metrics(mock.clone());
+
+    for order in [
+        order(1, OrderType::Sell, 101.0, 1.0),
+        order(2, OrderType::Buy, 100.0, 1.0),
+        order(3, OrderType::Buy, 101.0, 1.0),
+    ] {
+        engine.process_order_synchronously(order).unwrap();
+    }
+    engine.cancel_order_synchronously(&btc_usd(), 2);
+
+    let per_pair: Vec<MetricCall> = mock
+        .calls()
+        .into_iter()
+        .filter(|call| {
+            matches!(
+                call,
+                MetricCall::OrderSubmitted(_)
+                    | MetricCall::ActiveOrdersIncremented(..)
+                    | MetricCall::ActiveOrdersDecremented(..)
+                    | MetricCall::Spread(..)
+            )
+        })
+        .collect();
+    let decremented = |n| MetricCall::ActiveOrdersDecremented(btc_usd(), n);
+    assert_eq!(
+        per_pair,
+        [
+            MetricCall::OrderSubmitted(btc_usd()),
+            MetricCall::ActiveOrdersIncremented(btc_usd(), 1),
+            decremented(0),
+            MetricCall::OrderSubmitted(btc_usd()),
+            MetricCall::Spread(btc_usd(), 1.0),
+            MetricCall::ActiveOrdersIncremented(btc_usd(), 1),
+            MetricCall::Spread(btc_usd(), 1.0),
+            decremented(0),
+            MetricCall::OrderSubmitted(btc_usd()),
+            MetricCall::Spread(btc_usd(), 0.0),
+            MetricCall::ActiveOrdersIncremented(btc_usd(), 1),
+            decremented(2),
+            decremented(1),
+        ]
+    );
+
+    // The same calls on the real metrics leave the book empty.
+    let mut engine = Engine::with_config(config, |trading_pair| {
+        Box::new(SimpleOrderBook::new(trading_pair))
+    });
+    for order in [
+        order(1, OrderType::Sell, 101.0, 1.0),
+        order(2, OrderType::Buy, 100.0, 1.0),
+        order(3, OrderType::Buy, 101.0, 1.0),
+    ] {
+        engine.process_order_synchronously(order).unwrap();
+    }
+    engine.cancel_order_synchronously(&btc_usd(), 2);
+    assert_eq!(engine.metrics().orders_submitted(&btc_usd()), 3);
+    assert_eq!(engine.metrics().active_orders(&btc_usd()), 0);
+    assert_eq!(engine.metrics().spread(&btc_usd()), Some(0.0));
+}
+
+#[tokio::test]
+async fn test_merge_trading_pairs_moves_orders_and_removes_source() {
+    let engine_tx = start_engine_with_config(EngineConfig::default(), |trading_pair| {
+        Box::new(SimpleOrderBook::new(trading_pair))
+    });
+    let luna_usdt = TradingPair::new("LUNA".to_string(), "USDT".to_string());
+    let lunc_usdt = TradingPair::new("LUNC".to_string(), "USDT".to_string());
+    let order = |id, pair: &TradingPair, side, price| {
+        OrderBuilder::new()
+            .id(id)
+            .pair(pair.clone())
+            .side(side)
+            .price(price)
+            .quantity(1.0)
+            .build()
+    };
+    for new_order in [
+        order(1, &luna_usdt, OrderType::Buy, 1.0),
+        order(2, &luna_usdt, OrderType::Sell, 1.2),
+        order(3, &lunc_usdt, OrderType::Buy, 1.1),
+    ] {
+        engine_tx.send(Message::NewOrder(new_order)).await.unwrap();
+    }
+    let (subscribe_tx, mut subscribe_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::SubscribeToPair(luna_usdt.clone(), subscribe_tx))
+        .await
+        .unwrap();
+    let mut luna_events = subscribe_rx.recv().await.unwrap();
+
+    let (merge_tx, mut merge_rx) = mpsc::channel(1);
+    let (count_tx, mut count_rx) = mpsc::channel(1);
+    let merge = Message::MergeTradingPairs(luna_usdt.clone(), lunc_usdt.clone(), merge_tx.clone());
+    // A halted source is not merged.
+    for message in [
+        Message::Broadcast(BroadcastPayload::HaltAllTrading, count_tx.clone()),
+        Message::MergeTradingPairs(luna_usdt.clone(), lunc_usdt.clone(), merge_tx.clone()),
+        Message::Broadcast(BroadcastPayload::ResumeAllTrading, count_tx),
+        merge,
+    ] {
+        engine_tx.send(message).await.unwrap();
+    }
+    count_rx.recv().await.unwrap();
+    assert_eq!(merge_rx.recv().await.unwrap(), 0);
+    count_rx.recv().await.unwrap();
+    assert_eq!(merge_rx.recv().await.unwrap(), 2);
+
+    // The source's channel is dropped with its book.
+    loop {
+        match luna_events.recv().await {
+            Ok(_) => continue,
+            Err(e) => {
+                assert_eq!(e, tokio::sync::broadcast::error::RecvError::Closed);
+                break;
+            }
+        }
+    }
+
+    let (counts_tx, mut counts_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::GetActiveOrderCountAllPairs(counts_tx))
+        .await
+        .unwrap();
+    let counts = counts_rx.recv().await.unwrap();
+    assert_eq!(counts.len(), 1);
+    assert_eq!(counts[&lunc_usdt], 3);
+
+    // The source is gone, so merging it again moves nothing.
+    engine_tx
+        .send(Message::MergeTradingPairs(luna_usdt, lunc_usdt, merge_tx))
+        .await
+        .unwrap();
+    assert_eq!(merge_rx.recv().await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn test_get_all_pair_stats() {
+    let engine_tx = start_engine_with_config(EngineConfig::default(), |trading_pair| {
+        Box::new(SimpleOrderBook::new(trading_pair))
+    });
+    cross(&engine_tx, 1, 2).await;
+    cross(&engine_tx, 3, 4).await;
+    for resting in [
+        order(5, OrderType::Buy, 99.0, 1.0),
+        order(6, OrderType::Buy, 98.0, 1.0),
+        order(7, OrderType::Sell, 101.5, 1.0),
+    ] {
+        engine_tx.send(Message::NewOrder(resting)).await.unwrap();
+    }
+    let eth_usd = TradingPair::new("ETH".to_string(), "USD".to_string());
+    let eth = OrderBuilder::new()
+        .id(8)
+        .pair(eth_usd.clone())
+        .sell_at(2000.0)
+        .quantity(1.0)
+        .build();
+    engine_tx.send(Message::NewOrder(eth)).await.unwrap();
+
+    let (stats_tx, mut stats_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::GetAllPairStats(stats_tx))
+        .await
+        .unwrap();
+    let stats = stats_rx.recv().await.unwrap();
+    assert_eq!(stats.len(), 2);
+    assert_eq!(
+        stats[&btc_usd()],
+        PairStats {
+            last_price: Some(100.0),
+            spread: Some(2.5),
+            daily_volume: 2.0,
+            active_orders: 3,
+            bid_levels: 2,
+            ask_levels: 1,
+        }
+    );
+    assert_eq!(
+        stats[&eth_usd],
+        PairStats {
+            active_orders: 1,
+            ask_levels: 1,
+            ..PairStats::default()
+        }
+    );
+}
+
+#[tokio::test]
+async fn test_get_allocation_stats() {
+    let engine_tx = start_engine_with_config(EngineConfig::default(), |trading_pair| {
+        Box::new(SimpleOrderBook::new(trading_pair))
+    });
+    engine_tx
+        .send(Message::NewOrder(
+            order(1, OrderType::Buy, 99.0, 1.0).with_client("alice"),
+        ))
+        .await
+        .unwrap();
+
+    let (stats_tx, mut stats_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::GetAllocationStats(btc_usd(), stats_tx.clone()))
+        .await
+        .unwrap();
+    let stats = stats_rx.recv().await.unwrap().unwrap();
+    assert_eq!((stats.bid_entries, stats.id_index_entries), (1, 1));
+    assert!(!stats.index_is_leaking());
+
+    let unknown = TradingPair::new("ETH".to_string(), "USD".to_string());
+    engine_tx
+        .send(Message::GetAllocationStats(unknown, stats_tx))
+        .await
+        .unwrap();
+    assert_eq!(stats_rx.recv().await.unwrap(), None);
+}
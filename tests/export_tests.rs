@@ -0,0 +1,57 @@
+#![cfg(feature = "export")]
+
+use engine::engine::core::{start_engine, Message};
+use engine::engine::models::OrderType;
+use engine::engine::order_book::SimpleOrderBook;
+use engine::engine::testing::{btc_usd, OrderBuilder};
+use tokio::sync::mpsc;
+
+#[tokio::test]
+async fn test_export_order_book_csv() {
+    let engine_tx = start_engine(|trading_pair| Box::new(SimpleOrderBook::new(trading_pair)));
+    let base = chrono::Utc::now();
+    for (id, side, price, quantity) in [
+        (1, OrderType::Buy, 98.0, 1.0),
+        (2, OrderType::Buy, 99.0, 2.0),
+        (3, OrderType::Buy, 99.0, 0.5),
+        (4, OrderType::Sell, 102.0, 1.0),
+        (5, OrderType::Sell, 101.0, 3.0),
+    ] {
+        let order = OrderBuilder::new()
+            .id(id)
+            .pair(btc_usd())
+            .side(side)
+            .price(price)
+            .quantity(quantity)
+            .timestamp(base + chrono::Duration::seconds(id as i64))
+            .build();
+        engine_tx.send(Message::NewOrder(order)).await.unwrap();
+    }
+
+    let path = std::env::temp_dir().join(format!("engine-book-{}.csv", std::process::id()));
+    let (export_tx, mut export_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::ExportOrderBookCsv(
+            btc_usd(),
+            path.clone(),
+            export_tx,
+        ))
+        .await
+        .unwrap();
+    export_rx.recv().await.unwrap().unwrap();
+
+    let at = |id: i64| (base + chrono::Duration::seconds(id)).to_rfc3339();
+    let csv = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(
+        csv.lines().collect::<Vec<_>>(),
+        vec![
+            "side,price,quantity,order_count,top_order_id,top_order_timestamp".to_string(),
+            format!("bid,99,2.5,2,2,{}", at(2)),
+            format!("bid,98,1,1,1,{}", at(1)),
+            ",,,,,".to_string(),
+            format!("ask,101,3,1,5,{}", at(5)),
+            format!("ask,102,1,1,4,{}", at(4)),
+        ]
+    );
+    let _ = std::fs::remove_file(path);
+}
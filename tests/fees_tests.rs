@@ -0,0 +1,42 @@
+use engine::engine::fees::{FeeModel, FlatFeeModel};
+use engine::engine::models::{OrderType, Trade, TradingPair};
+
+fn trade(trading_pair: TradingPair, price: f64, quantity: f64) -> Trade {
+    Trade {
+        id: 0,
+        trading_pair,
+        buy_order_id: 1,
+        sell_order_id: 2,
+        price: price.into(),
+        quantity: quantity.into(),
+        aggressor_side: OrderType::Buy,
+        timestamp: chrono::Utc::now(),
+        buy_client_id: None,
+        sell_client_id: None,
+        buy_fee: 0.0,
+        sell_fee: 0.0,
+    }
+}
+
+#[test]
+fn test_fees_follow_the_stablecoin_heuristic() {
+    let btc_usdt = TradingPair::new("BTC".to_string(), "USDT".to_string());
+    let eth_btc = TradingPair::new("ETH".to_string(), "BTC".to_string());
+    let fee_model = FlatFeeModel::new(10.0, 20.0).with_charge_in_quote(false);
+    assert!(fee_model.fee_in_quote(&btc_usdt));
+    assert!(!fee_model.fee_in_quote(&eth_btc));
+
+    // 20 bps of 2 * 50_000 USDT for the taker, 10 bps for the maker.
+    let mut quoted = trade(btc_usdt, 50_000.0, 2.0);
+    fee_model.charge(&mut quoted);
+    assert_eq!((quoted.buy_fee, quoted.sell_fee), (200.0, 100.0));
+
+    // Charged on the 4 ETH traded instead of the BTC notional.
+    let mut based = trade(eth_btc.clone(), 0.05, 4.0);
+    fee_model.charge(&mut based);
+    assert_eq!((based.buy_fee, based.sell_fee), (0.008, 0.004));
+
+    let mut always_quote = trade(eth_btc, 0.05, 4.0);
+    FlatFeeModel::new(10.0, 20.0).charge(&mut always_quote);
+    assert!((always_quote.buy_fee - 0.0004).abs() < 1e-12);
+}
@@ -0,0 +1,76 @@
+use engine::engine::core::{start_engine, Engine, Message};
+use engine::engine::models::{OrderType, TradingPair};
+use engine::engine::order_book::SimpleOrderBook;
+use engine::engine::testing::{btc_usd, OrderBuilder};
+use tokio::sync::mpsc;
+
+fn spawn() -> mpsc::Sender<Message> {
+    start_engine(|trading_pair| Box::new(SimpleOrderBook::new(trading_pair)))
+}
+
+async fn new_order(engine_tx: &mpsc::Sender<Message>, id: u64, trading_pair: TradingPair) {
+    let order = OrderBuilder::new()
+        .id(id)
+        .pair(trading_pair)
+        .side(OrderType::Buy)
+        .price(100.0)
+        .quantity(1.0)
+        .build();
+    engine_tx.send(Message::NewOrder(order)).await.unwrap();
+}
+
+async fn order_count(engine_tx: &mpsc::Sender<Message>, trading_pair: TradingPair) -> usize {
+    let (count_tx, mut count_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::GetActiveOrderCount(trading_pair, count_tx))
+        .await
+        .unwrap();
+    count_rx.recv().await.unwrap()
+}
+
+#[tokio::test]
+async fn test_link_copies_linked_pairs_to_the_other_engine() {
+    let (engine_a, engine_b) = (spawn(), spawn());
+    let link = Engine::connect_engines(engine_a.clone(), engine_b.clone(), vec![btc_usd()]);
+    let eth_usd = TradingPair::new("ETH".to_string(), "USD".to_string());
+
+    // Each count is asked through the door just used, so every copy that
+    // door made has been sent before the next assertion.
+    new_order(&link.a_tx, 1, btc_usd()).await;
+    new_order(&link.a_tx, 2, eth_usd.clone()).await;
+    assert_eq!(order_count(&link.a_tx, btc_usd()).await, 1);
+    new_order(&link.b_tx, 3, btc_usd()).await;
+    assert_eq!(order_count(&link.b_tx, btc_usd()).await, 2);
+    assert_eq!(order_count(&engine_a, btc_usd()).await, 2);
+    assert_eq!(order_count(&engine_b, eth_usd).await, 0);
+
+    // The caller hears back from its own engine only.
+    let (cancel_tx, mut cancel_rx) = mpsc::channel(1);
+    link.b_tx
+        .send(Message::CancelOrder(btc_usd(), 1, cancel_tx))
+        .await
+        .unwrap();
+    assert_eq!(cancel_rx.recv().await.unwrap().unwrap().id, 1);
+    assert_eq!(order_count(&link.b_tx, btc_usd()).await, 1);
+    assert!(cancel_rx.try_recv().is_err());
+    assert_eq!(order_count(&engine_a, btc_usd()).await, 1);
+
+    link.cancel_token().cancel();
+    new_order(&link.a_tx, 4, btc_usd()).await;
+    assert_eq!(order_count(&link.a_tx, btc_usd()).await, 2);
+    assert_eq!(order_count(&engine_b, btc_usd()).await, 1);
+}
+
+#[tokio::test]
+async fn test_forwarded_messages_are_not_forwarded_again() {
+    let (engine_a, engine_b) = (spawn(), spawn());
+    let inner = Engine::connect_engines(engine_a.clone(), engine_b.clone(), vec![btc_usd()]);
+    let outer = Engine::connect_engines(inner.a_tx.clone(), inner.b_tx.clone(), vec![btc_usd()]);
+
+    // Outer copies the order into inner's B door, which must not send it
+    // back to A.
+    new_order(&outer.a_tx, 1, btc_usd()).await;
+    assert_eq!(order_count(&outer.a_tx, btc_usd()).await, 1);
+    assert_eq!(order_count(&inner.b_tx, btc_usd()).await, 2);
+    assert_eq!(order_count(&engine_a, btc_usd()).await, 1);
+}
@@ -0,0 +1,196 @@
+use chrono::Utc;
+use engine::engine::accounts::AccountManager;
+use engine::engine::core::{start_engine, Message};
+use engine::engine::margin::MarginCalculator;
+use engine::engine::models::{MarketEvent, OrderType, PriceUpdate, Trade, TradingPair};
+use engine::engine::order_book::SimpleOrderBook;
+use engine::engine::testing::OrderBuilder;
+use tokio::sync::mpsc;
+
+fn btc_perp() -> TradingPair {
+    TradingPair::new("BTC-PERP".to_string(), "USD".to_string())
+}
+
+fn margin_accounts() -> AccountManager {
+    let mut accounts = AccountManager::new();
+    accounts.enable_isolated_margin(btc_perp(), MarginCalculator::new(10.0, 5.0));
+    accounts.deposit("alice", "USD", 1000.0);
+    accounts.deposit("bob", "USD", 1000.0);
+    accounts
+}
+
+fn trade(buy_order_id: u64, sell_order_id: u64, price: f64, quantity: f64) -> Trade {
+    Trade {
+        id: buy_order_id.max(sell_order_id),
+        trading_pair: btc_perp(),
+        buy_order_id,
+        sell_order_id,
+        price: price.into(),
+        quantity: quantity.into(),
+        aggressor_side: OrderType::Buy,
+        timestamp: Utc::now(),
+        buy_client_id: None,
+        sell_client_id: None,
+        buy_fee: 0.0,
+        sell_fee: 0.0,
+    }
+}
+
+#[test]
+fn test_isolated_margin_open_and_close() {
+    let mut accounts = margin_accounts();
+    let calculator = *accounts.margin_calculator(&btc_perp()).unwrap();
+    assert_eq!(calculator.initial_margin(100.0, 2.0), 20.0);
+    assert_eq!(calculator.maintenance_margin(100.0, -2.0), 10.0);
+
+    let open_long = OrderBuilder::new()
+        .id(1)
+        .pair(btc_perp())
+        .client_id("alice")
+        .buy_at(100.0)
+        .quantity(1.0)
+        .build();
+    let open_short = OrderBuilder::new()
+        .id(2)
+        .pair(btc_perp())
+        .client_id("bob")
+        .sell_at(100.0)
+        .quantity(1.0)
+        .build();
+    accounts.lock_for_order(&open_long).unwrap();
+    accounts.lock_for_order(&open_short).unwrap();
+    assert_eq!(accounts.locked_balance("alice", "USD"), 10.0);
+    assert_eq!(accounts.account("alice").unwrap().balance("USD"), 990.0);
+
+    accounts.settle_trade(&Trade {
+        buy_client_id: Some("alice".to_string()),
+        sell_client_id: Some("bob".to_string()),
+        ..trade(1, 2, 100.0, 1.0)
+    });
+    let alice = accounts.account("alice").unwrap();
+    let position = &alice.positions[&btc_perp()];
+    assert_eq!(position.quantity, 1.0);
+    assert_eq!(position.margin, 10.0);
+    assert_eq!(accounts.locked_balance("alice", "USD"), 0.0);
+    assert_eq!(
+        accounts.account("bob").unwrap().positions[&btc_perp()].quantity,
+        -1.0
+    );
+
+    // Equity 10 - 6 = 4 is under 5% of 94; at 96 it is 6 against 4.8.
+    assert!(calculator.check_liquidation(&btc_perp(), 94.0, alice));
+    assert!(!calculator.check_liquidation(&btc_perp(), 96.0, alice));
+    let long_liquidation = accounts.liquidation_price("alice", &btc_perp()).unwrap();
+    assert!((long_liquidation - 90.0 / 0.95).abs() < 1e-9);
+    let short_liquidation = accounts.liquidation_price("bob", &btc_perp()).unwrap();
+    assert!((short_liquidation - 110.0 / 1.05).abs() < 1e-9);
+    assert_eq!(
+        accounts.liquidation_candidates(&btc_perp(), 94.0),
+        vec!["alice".to_string()]
+    );
+
+    // Alice closes at 110: her margin and 10 USD of profit come back.
+    let close_long = OrderBuilder::new()
+        .id(3)
+        .pair(btc_perp())
+        .client_id("alice")
+        .sell_at(110.0)
+        .quantity(1.0)
+        .build();
+    let close_short = OrderBuilder::new()
+        .id(4)
+        .pair(btc_perp())
+        .client_id("bob")
+        .buy_at(110.0)
+        .quantity(1.0)
+        .build();
+    accounts.lock_for_order(&close_long).unwrap();
+    accounts.lock_for_order(&close_short).unwrap();
+    accounts.settle_trade(&Trade {
+        buy_client_id: Some("bob".to_string()),
+        sell_client_id: Some("alice".to_string()),
+        ..trade(4, 3, 110.0, 1.0)
+    });
+
+    let alice = accounts.account("alice").unwrap();
+    let bob = accounts.account("bob").unwrap();
+    assert!(alice.positions.is_empty() && bob.positions.is_empty());
+    assert_eq!(alice.balance("USD"), 1010.0);
+    assert_eq!(bob.balance("USD"), 990.0);
+    assert_eq!(accounts.liquidation_price("alice", &btc_perp()), None);
+}
+
+#[tokio::test]
+async fn test_engine_reports_liquidation() {
+    let engine_tx = start_engine(|trading_pair| Box::new(SimpleOrderBook::new(trading_pair)));
+    engine_tx
+        .send(Message::SetAccountManager(margin_accounts()))
+        .await
+        .unwrap();
+
+    for order in [
+        OrderBuilder::new().id(1).client_id("bob").sell_at(100.0),
+        OrderBuilder::new().id(2).client_id("alice").buy_at(100.0),
+    ] {
+        engine_tx
+            .send(Message::NewOrder(
+                order.pair(btc_perp()).quantity(2.0).build(),
+            ))
+            .await
+            .unwrap();
+    }
+    let (match_tx, mut match_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::MatchOrders(btc_perp(), match_tx))
+        .await
+        .unwrap();
+    assert_eq!(match_rx.recv().await.unwrap().len(), 1);
+
+    let (liquidation_tx, mut liquidation_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::GetLiquidationPrice(
+            "alice".to_string(),
+            btc_perp(),
+            liquidation_tx.clone(),
+        ))
+        .await
+        .unwrap();
+    let price = liquidation_rx.recv().await.unwrap().unwrap();
+    assert!((price - 90.0 / 0.95).abs() < 1e-9);
+    engine_tx
+        .send(Message::GetLiquidationPrice(
+            "carol".to_string(),
+            btc_perp(),
+            liquidation_tx,
+        ))
+        .await
+        .unwrap();
+    assert_eq!(liquidation_rx.recv().await.unwrap(), None);
+
+    let (subscribe_tx, mut subscribe_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::SubscribeToMarketEvents(subscribe_tx))
+        .await
+        .unwrap();
+    let mut events = subscribe_rx.recv().await.unwrap();
+    engine_tx
+        .send(Message::PriceUpdate(PriceUpdate {
+            trading_pair: btc_perp(),
+            price: 94.0,
+            source: "index".to_string(),
+        }))
+        .await
+        .unwrap();
+    assert_eq!(
+        events.recv().await.unwrap(),
+        MarketEvent::LiquidationTriggered {
+            client_id: "alice".to_string(),
+            trading_pair: btc_perp(),
+            mark_price: 94.0,
+        }
+    );
+    assert!(matches!(
+        events.recv().await.unwrap(),
+        MarketEvent::MarkPriceUpdate(_)
+    ));
+}
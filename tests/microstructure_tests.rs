@@ -0,0 +1,268 @@
+use engine::engine::microstructure::{
+    effective_spread_bps, kyle_lambda, price_impact, PriceImpact, VpinCalculator,
+};
+use engine::engine::models::{OrderType, Trade};
+use engine::engine::order_book::{OrderBook, SimpleOrderBook};
+use engine::engine::testing::{btc_usd, OrderBuilder};
+
+fn trade(price: f64, quantity: f64, aggressor_side: OrderType) -> Trade {
+    Trade {
+        id: 0,
+        trading_pair: btc_usd(),
+        buy_order_id: 1,
+        sell_order_id: 2,
+        price: price.into(),
+        quantity: quantity.into(),
+        aggressor_side,
+        timestamp: chrono::Utc::now(),
+        buy_client_id: None,
+        sell_client_id: None,
+        buy_fee: 0.0,
+        sell_fee: 0.0,
+    }
+}
+
+#[test]
+fn test_kyle_lambda_recovers_linear_impact() {
+    // Each price change is exactly half the signed flow of the trade.
+    let trades = vec![
+        trade(100.0, 1.0, OrderType::Buy),
+        trade(101.0, 2.0, OrderType::Buy),
+        trade(100.5, 1.0, OrderType::Sell),
+        trade(102.0, 3.0, OrderType::Buy),
+        trade(100.0, 4.0, OrderType::Sell),
+    ];
+
+    let lambda = kyle_lambda(&trades).unwrap();
+    assert!((lambda - 0.5).abs() < 1e-12);
+}
+
+#[test]
+fn test_kyle_lambda_needs_varying_flow() {
+    assert_eq!(kyle_lambda(&[]), None);
+    assert_eq!(
+        kyle_lambda(&[
+            trade(100.0, 1.0, OrderType::Buy),
+            trade(101.0, 1.0, OrderType::Buy)
+        ]),
+        None
+    );
+    assert_eq!(
+        kyle_lambda(&[
+            trade(100.0, 1.0, OrderType::Buy),
+            trade(101.0, 1.0, OrderType::Buy),
+            trade(102.0, 1.0, OrderType::Buy),
+        ]),
+        None
+    );
+}
+
+#[tokio::test]
+async fn test_order_book_kyle_lambda_uses_recent_window() {
+    let order_book = SimpleOrderBook::new(btc_usd());
+    let base = chrono::Utc::now();
+
+    // Resting asks walk up the book; each aggressive buy lifts the next one.
+    for (i, price) in [100.0, 101.0, 103.0].iter().enumerate() {
+        order_book
+            .add_order(
+                OrderBuilder::new()
+                    .id(i as u64 + 1)
+                    .pair(btc_usd())
+                    .sell_at(*price)
+                    .quantity(i as f64 + 1.0)
+                    .timestamp(base)
+                    .build(),
+            )
+            .await
+            .unwrap();
+        order_book
+            .add_order(
+                OrderBuilder::new()
+                    .id(i as u64 + 10)
+                    .pair(btc_usd())
+                    .buy_at(*price)
+                    .quantity(i as f64 + 1.0)
+                    .timestamp(base + chrono::Duration::seconds(1))
+                    .build(),
+            )
+            .await
+            .unwrap();
+        order_book.match_orders().await;
+    }
+
+    let history = order_book.get_trade_history().await;
+    assert!(history
+        .iter()
+        .all(|trade| trade.aggressor_side == OrderType::Buy));
+
+    // Flows 2 and 3 move the price by 1 and 2.
+    let lambda = order_book.kyle_lambda(3).await.unwrap();
+    assert!((lambda - 1.0).abs() < 1e-12);
+    assert_eq!(order_book.kyle_lambda(2).await, None);
+}
+
+#[test]
+fn test_vpin_buckets_by_volume() {
+    let mut calculator = VpinCalculator::new(2.0, 10);
+    assert_eq!(calculator.vpin(), None);
+
+    // A 3-lot buy fills the first bucket and spills one lot into the second.
+    calculator.add_trade(&trade(100.0, 3.0, OrderType::Buy));
+    assert_eq!(calculator.vpin(), Some(1.0));
+
+    calculator.add_trade(&trade(100.0, 1.0, OrderType::Sell));
+    assert_eq!(calculator.vpin(), Some(0.5));
+}
+
+#[test]
+fn test_vpin_rolls_over_last_buckets() {
+    let mut calculator = VpinCalculator::new(1.0, 2);
+    calculator.add_trades(&[
+        trade(100.0, 1.0, OrderType::Buy),
+        trade(100.0, 0.5, OrderType::Buy),
+        trade(100.0, 0.5, OrderType::Sell),
+        trade(100.0, 0.5, OrderType::Sell),
+        trade(100.0, 0.5, OrderType::Buy),
+    ]);
+
+    // The fully one-sided first bucket has aged out of the window.
+    assert_eq!(calculator.vpin(), Some(0.0));
+}
+
+#[tokio::test]
+async fn test_depth_imbalance_at_level() {
+    let bids_only = SimpleOrderBook::new(btc_usd());
+    bids_only
+        .add_order(
+            OrderBuilder::new()
+                .id(1)
+                .pair(btc_usd())
+                .buy_at(99.0)
+                .quantity(1.0)
+                .build(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(bids_only.get_depth_imbalance_at_level(0).await, Some(1.0));
+    assert_eq!(bids_only.get_depth_imbalance_at_level(1).await, None);
+
+    let asks_only = SimpleOrderBook::new(btc_usd());
+    asks_only
+        .add_order(
+            OrderBuilder::new()
+                .id(1)
+                .pair(btc_usd())
+                .sell_at(101.0)
+                .quantity(1.0)
+                .build(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(asks_only.get_depth_imbalance_at_level(0).await, Some(-1.0));
+
+    // Level 1 holds 3 bid and 1 ask.
+    let order_book = SimpleOrderBook::new(btc_usd());
+    for (id, side, price, quantity) in [
+        (1, OrderType::Buy, 99.0, 1.0),
+        (2, OrderType::Buy, 98.0, 2.0),
+        (3, OrderType::Buy, 98.0, 1.0),
+        (4, OrderType::Sell, 101.0, 1.0),
+        (5, OrderType::Sell, 102.0, 1.0),
+    ] {
+        order_book
+            .add_order(
+                OrderBuilder::new()
+                    .id(id)
+                    .pair(btc_usd())
+                    .side(side)
+                    .price(price)
+                    .quantity(quantity)
+                    .build(),
+            )
+            .await
+            .unwrap();
+    }
+    assert_eq!(order_book.get_depth_imbalance_at_level(0).await, Some(0.0));
+    assert_eq!(order_book.get_depth_imbalance_at_level(1).await, Some(0.5));
+}
+
+#[tokio::test]
+async fn test_effective_spread_walks_the_book() {
+    let order_book = SimpleOrderBook::new(btc_usd());
+    for (id, side, price, quantity) in [
+        (1, OrderType::Buy, 99.0, 1.0),
+        (2, OrderType::Buy, 98.0, 2.0),
+        (3, OrderType::Sell, 101.0, 1.0),
+        (4, OrderType::Sell, 102.0, 2.0),
+    ] {
+        order_book
+            .add_order(
+                OrderBuilder::new()
+                    .id(id)
+                    .pair(btc_usd())
+                    .side(side)
+                    .price(price)
+                    .quantity(quantity)
+                    .build(),
+            )
+            .await
+            .unwrap();
+    }
+
+    // One lot trades at the touch; two average 101.5 against 98.5.
+    assert_eq!(order_book.get_effective_spread(1.0).await, Some(400.0));
+    assert_eq!(order_book.get_effective_spread(2.0).await, Some(600.0));
+    assert_eq!(order_book.get_effective_spread(4.0).await, None);
+    assert_eq!(order_book.get_effective_spread(0.0).await, None);
+
+    assert_eq!(
+        effective_spread_bps([(99.0, 1.0)], Vec::<(f64, f64)>::new(), 1.0),
+        None
+    );
+}
+
+#[tokio::test]
+async fn test_price_impact_simulates_fills_without_trading() {
+    let order_book = SimpleOrderBook::new(btc_usd());
+    for (id, side, price, quantity) in [
+        (1, OrderType::Buy, 99.0, 1.0),
+        (2, OrderType::Buy, 98.0, 2.0),
+        (3, OrderType::Sell, 101.0, 1.0),
+        (4, OrderType::Sell, 102.0, 2.0),
+    ] {
+        order_book
+            .add_order(
+                OrderBuilder::new()
+                    .id(id)
+                    .pair(btc_usd())
+                    .side(side)
+                    .price(price)
+                    .quantity(quantity)
+                    .build(),
+            )
+            .await
+            .unwrap();
+    }
+
+    // Emptying the 101 ask moves the mid from 100 to (99 + 102) / 2.
+    let buy = order_book.get_price_impact(OrderType::Buy, 2.0).await;
+    assert_eq!(
+        buy,
+        PriceImpact {
+            estimated_avg_price: 101.5,
+            expected_mid_price_after: 100.5,
+            impact_bps: 50.0,
+        }
+    );
+    let sell = order_book.get_price_impact(OrderType::Sell, 0.5).await;
+    assert_eq!((sell.estimated_avg_price, sell.impact_bps), (99.0, 0.0));
+    let sweep = order_book.get_price_impact(OrderType::Sell, 10.0).await;
+    assert!((sweep.estimated_avg_price - 295.0 / 3.0).abs() < 1e-9);
+    assert_eq!(sweep.expected_mid_price_after, 99.5);
+    assert_eq!(order_book.get_active_orders_count().await, 4);
+
+    let empty = price_impact(&OrderType::Buy, [], [], 1.0);
+    assert!(empty.estimated_avg_price.is_nan());
+    assert_eq!(empty.impact_bps, 0.0);
+}
@@ -0,0 +1,380 @@
+use engine::engine::api::{merge_order_books, PriceLevel};
+use engine::engine::models::{
+    Notional, Order, OrderType, OrderValidationError, Price, Quantity, Trade, TradingPair,
+    TradingPairInfo, COMMON_STABLECOINS,
+};
+use engine::engine::testing::OrderBuilder;
+
+#[test]
+fn test_validate_order_precision() {
+    let info = TradingPairInfo::new(
+        TradingPair::new("BTC".to_string(), "USDT".to_string()),
+        0.01,
+        0.001,
+    );
+
+    let valid = OrderBuilder::new().buy_at(100.01).quantity(0.25).build();
+    assert!(TradingPair::validate_order(&valid, &info).is_empty());
+
+    let invalid = OrderBuilder::new()
+        .buy_at(100.000000001)
+        .quantity(0.0005)
+        .build();
+    assert_eq!(
+        TradingPair::validate_order(&invalid, &info),
+        vec![
+            OrderValidationError::PricePrecision {
+                price: 100.000000001,
+                tick_size: 0.01,
+            },
+            OrderValidationError::QuantityPrecision {
+                quantity: 0.0005,
+                lot_size: 0.001,
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_fill_tracks_running_average() {
+    let mut order = OrderBuilder::new().buy_at(101.0).quantity(6.0).build();
+    assert_eq!(order.average_fill_price, None);
+    // Zero and NaN fills leave the order alone.
+    for qty in [0.0, f64::NAN] {
+        order.fill(Quantity::from(qty), Price::from(100.0));
+    }
+    assert_eq!(order.average_fill_price, None);
+    assert_eq!(order.quantity.value(), 6.0);
+
+    let fills = [(1.5, 100.1), (2.5, 100.7), (1.0, 99.9)];
+    for (quantity, price) in fills {
+        order.fill(Quantity::from(quantity), Price::from(price));
+    }
+
+    let exact = (1.5 * 100.1 + 2.5 * 100.7 + 99.9) / 5.0;
+    assert!((order.average_fill_price.unwrap() - exact).abs() < 1e-9);
+    assert!((order.cumulative_filled_quantity - 5.0).abs() < 1e-12);
+    assert!((order.quantity.value() - 1.0).abs() < 1e-12);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_order_builder_from_json_and_fluent_setters() {
+    let order = OrderBuilder::from_json(
+        r#"{"id": 7, "trading_pair": {"base": "ETH", "quote": "USD"}, "order_type": "Sell", "price": 2500.0}"#,
+    )
+    .unwrap()
+    .quantity(2.0)
+    .build()
+    .with_client("alice")
+    .with_session("session-1");
+
+    assert_eq!(order.id, 7);
+    assert_eq!(
+        order.trading_pair,
+        TradingPair::new("ETH".to_string(), "USD".to_string())
+    );
+    assert_eq!(order.order_type, OrderType::Sell);
+    assert_eq!((order.price.value(), order.quantity.value()), (2500.0, 2.0));
+    assert_eq!(order.client_id.as_deref(), Some("alice"));
+    assert_eq!(order.session_id.as_deref(), Some("session-1"));
+
+    let defaults = OrderBuilder::from_json("{}").unwrap().build();
+    assert_eq!(defaults.order_type, OrderType::Buy);
+    assert!(defaults.client_id.is_none());
+    assert!(OrderBuilder::from_json(r#"{"price": "high"}"#).is_err());
+}
+
+#[test]
+fn test_inverse_pairs() {
+    let btc_usdt = TradingPair::new("BTC".to_string(), "USDT".to_string());
+    let usdt_btc = TradingPair::new("USDT".to_string(), "BTC".to_string());
+
+    assert_eq!(btc_usdt.inverse(), usdt_btc);
+    assert_eq!(btc_usdt.inverse().inverse(), btc_usdt);
+    assert!(btc_usdt.is_inverse(&usdt_btc));
+    assert!(usdt_btc.is_inverse(&btc_usdt));
+    assert!(!btc_usdt.is_inverse(&btc_usdt));
+    assert!(!btc_usdt.is_inverse(&TradingPair::new("USDT".to_string(), "ETH".to_string())));
+}
+
+#[test]
+fn test_trading_pair_normalizes_case() {
+    let btc_usdt = TradingPair::new("BTC".to_string(), "USDT".to_string());
+    assert_eq!(
+        TradingPair::new("btc".to_string(), "Usdt".to_string()),
+        btc_usdt
+    );
+    assert_eq!("btc/usdt".parse::<TradingPair>().unwrap(), btc_usdt);
+    #[cfg(feature = "serde")]
+    {
+        let parsed: TradingPair =
+            serde_json::from_str(r#"{"base": "btc", "quote": "usdt"}"#).unwrap();
+        assert_eq!(parsed, btc_usdt);
+    }
+}
+
+#[test]
+fn test_order_notional_and_side() {
+    let buy = OrderBuilder::new().buy_at(250.0).quantity(0.4).build();
+    assert_eq!(buy.notional_value(), 100.0);
+    assert!(buy.is_buy() && !buy.is_sell());
+
+    let sell = OrderBuilder::new().sell_at(0.0).quantity(3.0).build();
+    assert_eq!(sell.notional_value(), 0.0);
+    assert!(sell.is_sell() && !sell.is_buy());
+
+    let empty = OrderBuilder::new().buy_at(250.0).quantity(0.0).build();
+    assert_eq!(empty.notional_value(), 0.0);
+}
+
+#[test]
+fn test_order_fingerprint_ignores_id() {
+    let order = OrderBuilder::new()
+        .id(1)
+        .buy_at(100.0)
+        .quantity(1.0)
+        .build()
+        .with_client("alice");
+    let retry = Order {
+        id: 2,
+        ..order.clone()
+    };
+    assert_eq!(order.fingerprint(), retry.fingerprint());
+
+    let larger = Order {
+        quantity: Quantity::from(1.5),
+        ..order.clone()
+    };
+    let other_client = order.clone().with_client("bob");
+    let other_side = Order {
+        order_type: OrderType::Sell,
+        ..order.clone()
+    };
+    let later = Order {
+        timestamp: order.timestamp + chrono::Duration::nanoseconds(1),
+        ..order.clone()
+    };
+    for changed in [larger, other_client, other_side, later] {
+        assert_ne!(order.fingerprint(), changed.fingerprint());
+    }
+}
+
+#[test]
+fn test_price_level_from_orders() {
+    let mut order = OrderBuilder::new().buy_at(99.5).quantity(3.0).build();
+    order.fill(Quantity::from(1.0), Price::from(99.5));
+    let single = PriceLevel {
+        price: 99.5,
+        total_quantity: 2.0,
+        order_count: 1,
+    };
+    assert_eq!(PriceLevel::from(&order), single);
+    assert_eq!(PriceLevel::from(order.clone()), single);
+
+    let other = OrderBuilder::new().buy_at(99.5).quantity(0.5).build();
+    assert_eq!(
+        PriceLevel::from(vec![order, other]),
+        PriceLevel {
+            price: 99.5,
+            total_quantity: 2.5,
+            order_count: 2,
+        }
+    );
+    assert_eq!(PriceLevel::from(Vec::new()).order_count, 0);
+}
+
+#[test]
+fn test_order_split_resets_fills() {
+    let mut order = OrderBuilder::new()
+        .id(1)
+        .sell_at(101.0)
+        .quantity(10.0)
+        .build()
+        .with_client("alice");
+    order.fill(Quantity::from(2.0), Price::from(101.0));
+
+    let (first, second) = order.split(0.25);
+    assert_eq!(
+        (first.quantity.value(), second.quantity.value()),
+        (2.0, 6.0)
+    );
+    assert!(first.id != order.id && second.id != order.id && first.id != second.id);
+    for child in [&first, &second] {
+        assert_eq!(child.average_fill_price, None);
+        assert_eq!(child.cumulative_filled_quantity, 0.0);
+        assert_eq!(child.price.value(), 101.0);
+        assert_eq!(child.client_id.as_deref(), Some("alice"));
+    }
+}
+
+#[test]
+#[should_panic(expected = "split ratio must be strictly between 0 and 1")]
+fn test_order_split_rejects_whole_ratio() {
+    OrderBuilder::new().quantity(1.0).build().split(1.0);
+}
+
+#[test]
+fn test_validate_stop_limit_orders() {
+    let buy = OrderBuilder::new().buy_at(101.0).quantity(1.0).build();
+    assert!(buy.validate().is_empty());
+    assert!(buy.clone().with_stop_price(100.0).validate().is_empty());
+    assert_eq!(
+        buy.clone().with_stop_price(102.0).validate(),
+        vec![OrderValidationError::StopPrice {
+            stop_price: 102.0,
+            price: 101.0,
+        }]
+    );
+    assert_eq!(buy.with_stop_price(f64::NAN).validate().len(), 1);
+
+    let sell = OrderBuilder::new().sell_at(99.0).quantity(1.0).build();
+    assert!(sell.clone().with_stop_price(100.0).validate().is_empty());
+    assert_eq!(sell.with_stop_price(98.0).validate().len(), 1);
+}
+
+#[test]
+fn test_quote_is_stablecoin() {
+    let btc_usdt = TradingPair::new("BTC".to_string(), "USDT".to_string());
+    let eth_btc = TradingPair::new("ETH".to_string(), "BTC".to_string());
+    assert!(btc_usdt.quote_is_stablecoin(COMMON_STABLECOINS));
+    assert!(!eth_btc.quote_is_stablecoin(COMMON_STABLECOINS));
+    assert!(!btc_usdt.inverse().quote_is_stablecoin(COMMON_STABLECOINS));
+    assert!(TradingPair::new("BTC".to_string(), "usdc".to_string())
+        .quote_is_stablecoin(COMMON_STABLECOINS));
+    assert!(eth_btc.quote_is_stablecoin(&["BTC"]));
+}
+
+#[test]
+fn test_merge_order_books_by_price() {
+    let level = |price, total_quantity, order_count| PriceLevel {
+        price,
+        total_quantity,
+        order_count,
+    };
+    assert_eq!(
+        level(100.0, 1.0, 1).merge(&level(100.0, 2.5, 3)),
+        level(100.0, 3.5, 4)
+    );
+
+    let bids = merge_order_books(
+        vec![level(101.0, 1.0, 1), level(99.0, 1.0, 1)],
+        vec![
+            level(100.0, 2.0, 1),
+            level(99.0, 3.0, 2),
+            level(98.0, 1.0, 1),
+        ],
+        true,
+    );
+    assert_eq!(
+        bids,
+        vec![
+            level(101.0, 1.0, 1),
+            level(100.0, 2.0, 1),
+            level(99.0, 4.0, 3),
+            level(98.0, 1.0, 1),
+        ]
+    );
+    let asks = merge_order_books(
+        vec![level(102.0, 1.0, 1)],
+        vec![level(101.0, 1.0, 1)],
+        false,
+    );
+    assert_eq!(asks, vec![level(101.0, 1.0, 1), level(102.0, 1.0, 1)]);
+    assert!(merge_order_books(Vec::new(), Vec::new(), false).is_empty());
+}
+
+#[test]
+#[should_panic(expected = "cannot merge levels at different prices")]
+fn test_merge_rejects_different_prices() {
+    let level = |price| PriceLevel {
+        price,
+        total_quantity: 1.0,
+        order_count: 1,
+    };
+    level(100.0).merge(&level(101.0));
+}
+
+#[test]
+fn test_price_and_quantity_newtypes() {
+    let price = Price::new(100.0).unwrap();
+    let quantity = Quantity::new(2.5).unwrap();
+    assert_eq!(price * quantity, Notional(250.0));
+    assert_eq!(quantity * price, price * quantity);
+    assert_eq!((price + Price::new(1.0).unwrap()).value(), 101.0);
+    assert_eq!(f64::from(quantity - Quantity::new(0.5).unwrap()), 2.0);
+
+    assert_eq!(
+        Price::new(0.0),
+        Err(OrderValidationError::InvalidPrice(0.0))
+    );
+    assert!(Price::new(f64::NAN).is_err());
+    assert!(Quantity::new(0.0).is_ok());
+    assert_eq!(
+        Quantity::new(-1.0),
+        Err(OrderValidationError::InvalidQuantity(-1.0))
+    );
+
+    #[cfg(feature = "serde")]
+    {
+        assert_eq!(serde_json::to_string(&price).unwrap(), "100.0");
+        let parsed: Quantity = serde_json::from_str("2.5").unwrap();
+        assert_eq!(parsed, quantity);
+    }
+}
+
+#[test]
+fn test_order_age_and_time_in_book() {
+    let now = chrono::Utc::now();
+    let mut order = OrderBuilder::new()
+        .timestamp(now - chrono::Duration::seconds(90))
+        .build();
+    assert_eq!(order.age(now), chrono::Duration::seconds(90));
+    // Unstamped orders have been in the book as long as they have existed.
+    assert_eq!(order.time_in_book(now), chrono::Duration::seconds(90));
+
+    order.received_at = Some(now - chrono::Duration::seconds(30));
+    assert_eq!(order.time_in_book(now), chrono::Duration::seconds(30));
+    assert_eq!(order.age(now), chrono::Duration::seconds(90));
+}
+
+#[test]
+fn test_order_and_trade_display() {
+    let btc_usd = TradingPair::new("BTC".to_string(), "USD".to_string());
+    assert_eq!(btc_usd.to_string(), "BTC/USD");
+    assert_eq!(btc_usd.to_string().parse::<TradingPair>().unwrap(), btc_usd);
+
+    let mut order = OrderBuilder::new()
+        .id(7)
+        .pair(btc_usd.clone())
+        .buy_at(100.0)
+        .quantity(2.0)
+        .build();
+    assert_eq!(order.to_string(), "Order#7 Buy 2 BTC/USD @ 100 [Open]");
+    order.fill(Quantity::from(0.5), Price::from(100.0));
+    assert_eq!(
+        order.to_string(),
+        "Order#7 Buy 1.5 BTC/USD @ 100 [PartiallyFilled]"
+    );
+    order.fill(Quantity::from(1.5), Price::from(100.0));
+    assert_eq!(order.to_string(), "Order#7 Buy 0 BTC/USD @ 100 [Filled]");
+
+    let trade = Trade {
+        id: 3,
+        trading_pair: btc_usd,
+        buy_order_id: 7,
+        sell_order_id: 8,
+        price: Price::from(100.5),
+        quantity: Quantity::from(0.5),
+        aggressor_side: OrderType::Sell,
+        timestamp: "2024-01-01T00:00:00Z".parse().unwrap(),
+        buy_client_id: None,
+        sell_client_id: None,
+        buy_fee: 0.0,
+        sell_fee: 0.0,
+    };
+    assert_eq!(
+        trade.to_string(),
+        "Trade#3 0.5 BTC/USD @ 100.5 at 2024-01-01 00:00:00 UTC"
+    );
+}
@@ -1,6 +1,13 @@
+use chrono::Utc;
+use engine::engine::backtest::SimulatedClock;
 use engine::engine::concurrent::ConcurrentOrderBook;
-use engine::engine::models::{Order, OrderType, TradingPair};
-use engine::engine::order_book::{OrderBook, SimpleOrderBook};
+use engine::engine::models::{OrderStatus, OrderType, PriceUpdate, TradingPair};
+use engine::engine::order_book::{
+    AllocationStats, OrderBook, OrderBookError, SimpleOrderBook, LAST_TRADE_SOURCE,
+};
+use engine::engine::persistence::OrderBookSnapshot;
+use engine::engine::testing::OrderBuilder;
+use std::sync::{Arc, Mutex};
 use tokio::time::Duration;
 use tracing::info;
 
@@ -8,30 +15,57 @@ use tracing::info;
 async fn test_add_and_match_orders() {
     let order_book = SimpleOrderBook::new(TradingPair::new("BTC".to_string(), "USD".to_string()));
 
-    let buy_order = Order {
-        id: 1,
-        trading_pair: TradingPair::new("BTC".to_string(), "USD".to_string()),
-        order_type: OrderType::Buy,
-        price: 50000.0,
-        quantity: 1.0,
-        timestamp: chrono::Utc::now(),
-    };
-    order_book.add_order(buy_order).await;
-
-    let sell_order = Order {
-        id: 2,
-        trading_pair: TradingPair::new("BTC".to_string(), "USD".to_string()),
-        order_type: OrderType::Sell,
-        price: 50000.0,
-        quantity: 1.0,
-        timestamp: chrono::Utc::now(),
-    };
-    order_book.add_order(sell_order).await;
+    let buy_order = OrderBuilder::new()
+        .id(1)
+        .pair(TradingPair::new("BTC".to_string(), "USD".to_string()))
+        .buy_at(50000.0)
+        .quantity(1.0)
+        .build();
+    order_book.add_order(buy_order).await.unwrap();
+
+    let sell_order = OrderBuilder::new()
+        .id(2)
+        .pair(TradingPair::new("BTC".to_string(), "USD".to_string()))
+        .sell_at(50000.0)
+        .quantity(1.0)
+        .build();
+    order_book.add_order(sell_order).await.unwrap();
 
-    let trades = order_book.match_orders().await;
+    let trades = order_book.match_orders().await.trades;
     assert_eq!(trades.len(), 1);
-    assert_eq!(trades[0].quantity, 1.0);
-    assert_eq!(trades[0].price, 50000.0);
+    assert_eq!(trades[0].quantity.value(), 1.0);
+    assert_eq!(trades[0].price.value(), 50000.0);
+}
+
+#[tokio::test]
+async fn test_match_drops_orders_without_positive_quantity() {
+    let btc_usd = TradingPair::new("BTC".to_string(), "USD".to_string());
+    let order_book = SimpleOrderBook::new(btc_usd.clone());
+    // `add_order` does not validate, as a restored snapshot would not.
+    for (id, quantity) in [(1, -1.0), (2, f64::NAN), (3, 1.0)] {
+        let order = OrderBuilder::new()
+            .id(id)
+            .pair(btc_usd.clone())
+            .sell_at(100.0)
+            .quantity(quantity)
+            .build();
+        order_book.add_order(order).await.unwrap();
+    }
+    let buy = OrderBuilder::new()
+        .id(4)
+        .pair(btc_usd)
+        .buy_at(100.0)
+        .quantity(1.0)
+        .build();
+    order_book.add_order(buy).await.unwrap();
+
+    let result = tokio::time::timeout(Duration::from_secs(5), order_book.match_orders())
+        .await
+        .expect("matching a non-positive quantity must not loop forever");
+    assert_eq!(result.trades.len(), 1);
+    assert_eq!(result.trades[0].sell_order_id, 3);
+    assert_eq!(result.trades[0].quantity.value(), 1.0);
+    assert_eq!(order_book.get_active_orders_count().await, 0);
 }
 
 #[tokio::test]
@@ -39,28 +73,24 @@ async fn test_order_matching() {
     let (book, mut trade_rx) =
         ConcurrentOrderBook::new(TradingPair::new("BTC".to_string(), "USD".to_string()));
 
-    let buy_order = Order {
-        id: 1,
-        trading_pair: TradingPair::new("BTC".to_string(), "USD".to_string()),
-        order_type: OrderType::Buy,
-        price: 50000.0,
-        quantity: 1.0,
-        timestamp: chrono::Utc::now(),
-    };
+    let buy_order = OrderBuilder::new()
+        .id(1)
+        .pair(TradingPair::new("BTC".to_string(), "USD".to_string()))
+        .buy_at(50000.0)
+        .quantity(1.0)
+        .build();
 
-    let sell_order = Order {
-        id: 2,
-        trading_pair: TradingPair::new("BTC".to_string(), "USD".to_string()),
-        order_type: OrderType::Sell,
-        price: 50000.0,
-        quantity: 1.0,
-        timestamp: chrono::Utc::now(),
-    };
+    let sell_order = OrderBuilder::new()
+        .id(2)
+        .pair(TradingPair::new("BTC".to_string(), "USD".to_string()))
+        .sell_at(50000.0)
+        .quantity(1.0)
+        .build();
 
     info!("Adding buy order: {:?}", buy_order);
-    book.add_order(buy_order).await;
+    book.add_order(buy_order).await.unwrap();
     info!("Adding sell order: {:?}", sell_order);
-    book.add_order(sell_order).await;
+    book.add_order(sell_order).await.unwrap();
 
     match tokio::time::timeout(Duration::from_secs(1), trade_rx.recv()).await {
         Ok(Some(trade)) => {
@@ -74,3 +104,1067 @@ async fn test_order_matching() {
         }
     }
 }
+
+#[tokio::test]
+async fn test_liquidity_within_range() {
+    let trading_pair = TradingPair::new("BTC".to_string(), "USD".to_string());
+    let order_book = SimpleOrderBook::new(trading_pair.clone());
+
+    for (id, order_type, price, quantity) in [
+        (1, OrderType::Buy, 99.0, 1.0),
+        (2, OrderType::Buy, 99.0, 2.0),
+        (3, OrderType::Buy, 98.0, 4.0),
+        (4, OrderType::Sell, 101.0, 3.0),
+        (5, OrderType::Sell, 102.0, 5.0),
+    ] {
+        let order = OrderBuilder::new()
+            .id(id)
+            .pair(trading_pair.clone())
+            .side(order_type)
+            .price(price)
+            .quantity(quantity)
+            .build();
+        order_book.add_order(order).await.unwrap();
+    }
+
+    assert_eq!(
+        order_book
+            .get_liquidity_within_range(100.0, 1.0, OrderType::Buy)
+            .await,
+        3.0
+    );
+    assert_eq!(
+        order_book
+            .get_liquidity_within_range(100.0, 2.0, OrderType::Buy)
+            .await,
+        7.0
+    );
+    assert_eq!(
+        order_book
+            .get_liquidity_within_range(100.0, 1.0, OrderType::Sell)
+            .await,
+        3.0
+    );
+    assert_eq!(
+        order_book
+            .get_liquidity_within_range(100.0, 0.5, OrderType::Sell)
+            .await,
+        0.0
+    );
+}
+
+#[tokio::test]
+async fn test_fill_reports() {
+    let btc_usd = TradingPair::new("BTC".to_string(), "USD".to_string());
+    let order_book = SimpleOrderBook::new(btc_usd.clone());
+    let order = |id: u64| OrderBuilder::new().id(id).pair(btc_usd.clone());
+
+    order_book
+        .add_order(order(1).sell_at(100.0).quantity(3.0).build())
+        .await
+        .unwrap();
+    order_book
+        .add_order(order(2).buy_at(101.0).quantity(1.0).build())
+        .await
+        .unwrap();
+    order_book.match_orders().await;
+    order_book
+        .add_order(order(3).buy_at(100.0).quantity(1.0).build())
+        .await
+        .unwrap();
+    order_book.match_orders().await;
+    order_book.cancel_order(1).await;
+
+    let resting = order_book.get_fill_report(1).await.unwrap();
+    assert_eq!(resting.status, OrderStatus::Cancelled);
+    assert_eq!(resting.fills.len(), 2);
+    assert_eq!(resting.total_quantity, 2.0);
+    assert_eq!(resting.average_price, 100.0);
+    let aggressor = order_book.get_fill_report(2).await.unwrap();
+    assert_eq!(aggressor.status, OrderStatus::Filled);
+    assert_eq!(aggressor.fills[0].trade_id, 1);
+    assert!(order_book.get_fill_report(4).await.is_none());
+
+    let later = chrono::Utc::now() + chrono::Duration::seconds(1);
+    assert_eq!(order_book.prune_fill_reports(later).await, 3);
+    assert!(order_book.get_fill_report(1).await.is_none());
+}
+
+#[tokio::test]
+async fn test_bust_trade_restores_orders() {
+    let btc_usd = TradingPair::new("BTC".to_string(), "USD".to_string());
+    let order_book = SimpleOrderBook::new(btc_usd.clone());
+    let start = chrono::Utc::now();
+    let order = |id: u64, seconds: i64| {
+        OrderBuilder::new()
+            .id(id)
+            .pair(btc_usd.clone())
+            .quantity(2.0)
+            .timestamp(start + chrono::Duration::seconds(seconds))
+    };
+
+    order_book
+        .add_order(order(1, 0).sell_at(100.0).build())
+        .await
+        .unwrap();
+    order_book
+        .add_order(order(2, 1).buy_at(100.0).build())
+        .await
+        .unwrap();
+    let trade_id = order_book.match_orders().await.trades[0].id;
+    order_book
+        .add_order(order(3, 2).sell_at(100.0).quantity(1.0).build())
+        .await
+        .unwrap();
+
+    assert_eq!(order_book.bust_trade(trade_id).await.unwrap().id, trade_id);
+    assert!(order_book.get_trade_history().await.is_empty());
+    assert!(order_book.get_fill_report(1).await.is_none());
+    let resting: Vec<(u64, f64, f64)> = order_book
+        .get_active_orders()
+        .await
+        .iter()
+        .map(|order| {
+            (
+                order.id,
+                order.quantity.value(),
+                order.cumulative_filled_quantity,
+            )
+        })
+        .collect();
+    // Order 1 goes back ahead of order 3, which arrived after it.
+    assert_eq!(resting, [(2, 2.0, 0.0), (1, 2.0, 0.0), (3, 1.0, 0.0)]);
+    assert!(matches!(
+        order_book.bust_trade(trade_id).await,
+        Err(OrderBookError::TradeNotFound(id)) if id == trade_id
+    ));
+
+    // IDs are not reused by later matches.
+    let trades = order_book.match_orders().await.trades;
+    assert_eq!(trades[0].sell_order_id, 1);
+    assert!(trades.iter().all(|trade| trade.id > trade_id));
+}
+
+#[test]
+fn test_price_level_locks_stack() {
+    let order_book = SimpleOrderBook::new(TradingPair::new("BTC".to_string(), "USD".to_string()));
+    let first = order_book.lock_price_level(100.0).unwrap();
+    let second = order_book.lock_price_level(100.0).unwrap();
+    assert_eq!(first.price(), 100.0);
+    assert!(order_book.is_price_level_locked(100.0));
+    assert!(!order_book.is_price_level_locked(100.5));
+
+    drop(first);
+    assert!(order_book.is_price_level_locked(100.0));
+    drop(second);
+    assert!(!order_book.is_price_level_locked(100.0));
+}
+
+#[tokio::test]
+async fn test_price_level_lock_refuses_orders_on_every_add_path() {
+    let btc_usd = TradingPair::new("BTC".to_string(), "USD".to_string());
+    let order = |id: u64, price: f64| {
+        OrderBuilder::new()
+            .id(id)
+            .pair(btc_usd.clone())
+            .buy_at(price)
+            .quantity(1.0)
+            .build()
+    };
+    let mut order_book = SimpleOrderBook::new(btc_usd.clone());
+    let lock = order_book.lock_price_level(100.0).unwrap();
+
+    assert!(matches!(
+        order_book.add_order(order(1, 100.0)).await,
+        Err(OrderBookError::PriceLevelLocked(price)) if price == 100.0
+    ));
+    order_book.add_order(order(2, 99.0)).await.unwrap();
+    order_book
+        .warm_up(vec![order(3, 100.0), order(4, 98.0)])
+        .await;
+    let moved = order_book.merge_with(SimpleOrderBook::from_orders(
+        btc_usd.clone(),
+        [order(5, 100.0)],
+    ));
+    assert_eq!(moved, 0);
+
+    let mut ids: Vec<u64> = order_book
+        .get_active_orders()
+        .await
+        .iter()
+        .map(|order| order.id)
+        .collect();
+    ids.sort();
+    assert_eq!(ids, [2, 4]);
+
+    drop(lock);
+    order_book.add_order(order(1, 100.0)).await.unwrap();
+    assert_eq!(order_book.get_active_orders_count().await, 3);
+}
+
+#[tokio::test]
+async fn test_match_result_reports_fills() {
+    let btc_usd = TradingPair::new("BTC".to_string(), "USD".to_string());
+    let order_book = SimpleOrderBook::new(btc_usd.clone());
+    let order = |id: u64| OrderBuilder::new().id(id).pair(btc_usd.clone());
+
+    let result = order_book.match_orders().await;
+    assert_eq!(
+        result.no_match_reason.as_deref(),
+        Some("order book is empty")
+    );
+
+    order_book
+        .add_order(order(1).sell_at(100.0).quantity(3.0).build())
+        .await
+        .unwrap();
+    order_book
+        .add_order(order(2).buy_at(99.0).quantity(1.0).build())
+        .await
+        .unwrap();
+    let result = order_book.match_orders().await;
+    assert!(result.trades.is_empty());
+    assert_eq!(
+        result.no_match_reason.as_deref(),
+        Some("best bid 99 is below best ask 100")
+    );
+
+    order_book
+        .add_order(order(3).buy_at(100.0).quantity(2.0).build())
+        .await
+        .unwrap();
+    let result = order_book.match_orders().await;
+    assert_eq!(result.trades.len(), 1);
+    assert_eq!(result.fully_filled, vec![3]);
+    assert_eq!(result.partially_filled, vec![1]);
+    assert_eq!(result.no_match_reason, None);
+}
+
+#[tokio::test]
+async fn test_active_orders_by_client() {
+    let btc_usd = TradingPair::new("BTC".to_string(), "USD".to_string());
+    let order_book = SimpleOrderBook::new(btc_usd.clone());
+    let order = |id: u64| OrderBuilder::new().id(id).pair(btc_usd.clone());
+    let ids = |mut orders: Vec<engine::engine::models::Order>| {
+        orders.sort_by_key(|order| order.id);
+        orders.into_iter().map(|order| order.id).collect::<Vec<_>>()
+    };
+
+    for (id, client) in [(1, "alice"), (2, "alice"), (3, "bob")] {
+        order_book
+            .add_order(
+                order(id)
+                    .sell_at(100.0 + id as f64)
+                    .quantity(2.0)
+                    .build()
+                    .with_client(client),
+            )
+            .await
+            .unwrap();
+    }
+    order_book
+        .add_order(order(4).sell_at(110.0).quantity(1.0).build())
+        .await
+        .unwrap();
+    assert_eq!(
+        ids(order_book.get_active_orders_by_client("alice").await),
+        vec![1, 2]
+    );
+
+    // A partial fill leaves order 1 listed with its remaining quantity.
+    order_book
+        .add_order(
+            order(5)
+                .buy_at(101.0)
+                .quantity(1.5)
+                .build()
+                .with_client("bob"),
+        )
+        .await
+        .unwrap();
+    order_book.match_orders().await;
+    let alice = order_book.get_active_orders_by_client("alice").await;
+    let partial = alice.iter().find(|order| order.id == 1).unwrap();
+    assert_eq!(partial.quantity.value(), 0.5);
+
+    order_book
+        .add_order(order(6).buy_at(101.0).quantity(0.5).build())
+        .await
+        .unwrap();
+    order_book.match_orders().await;
+    order_book.cancel_order(3).await;
+    assert_eq!(
+        ids(order_book.get_active_orders_by_client("alice").await),
+        vec![2]
+    );
+    assert!(order_book
+        .get_active_orders_by_client("bob")
+        .await
+        .is_empty());
+    assert!(order_book
+        .get_active_orders_by_client("carol")
+        .await
+        .is_empty());
+}
+
+#[tokio::test]
+async fn test_order_book_levels_count_orders() {
+    let btc_usd = TradingPair::new("BTC".to_string(), "USD".to_string());
+    let order_book = SimpleOrderBook::new(btc_usd.clone());
+    for (id, price, quantity) in [(1, 99.0, 1.0), (2, 99.0, 2.5), (3, 98.0, 1.0)] {
+        order_book
+            .add_order(
+                OrderBuilder::new()
+                    .id(id)
+                    .pair(btc_usd.clone())
+                    .buy_at(price)
+                    .quantity(quantity)
+                    .build(),
+            )
+            .await
+            .unwrap();
+    }
+
+    let (bids, asks) = order_book.get_order_book().await;
+    assert!(asks.is_empty());
+    assert_eq!((bids[0].total_quantity, bids[0].order_count), (3.5, 2));
+    assert_eq!((bids[1].total_quantity, bids[1].order_count), (1.0, 1));
+}
+
+#[tokio::test]
+async fn test_warm_up_adds_to_existing_book_without_matching() {
+    let btc_usd = TradingPair::new("BTC".to_string(), "USD".to_string());
+    let order_book = SimpleOrderBook::new(btc_usd.clone());
+    let order = |id: u64| OrderBuilder::new().id(id).pair(btc_usd.clone());
+    order_book
+        .add_order(order(1).buy_at(99.0).quantity(1.0).build())
+        .await
+        .unwrap();
+
+    order_book
+        .warm_up(vec![
+            order(2).sell_at(101.0).quantity(1.0).build(),
+            order(3).sell_at(99.0).quantity(0.5).build(),
+        ])
+        .await;
+    assert_eq!(order_book.get_active_orders_count().await, 3);
+    assert!(order_book.get_trade_history().await.is_empty());
+
+    let snapshot = OrderBookSnapshot {
+        trading_pair: btc_usd.clone(),
+        orders: vec![order(4).sell_at(102.0).quantity(2.0).build()],
+        trades: vec![],
+    };
+    order_book.warm_up_from_snapshot(&snapshot).await;
+    assert_eq!(order_book.get_active_orders_count().await, 4);
+
+    assert_eq!(order_book.match_orders().await.trades.len(), 1);
+}
+
+#[tokio::test]
+async fn test_side_iterators_walk_best_price_first() {
+    let btc_usd = TradingPair::new("BTC".to_string(), "USD".to_string());
+    let order_book = SimpleOrderBook::new(btc_usd.clone());
+    for (id, side, price) in [
+        (1, OrderType::Buy, 98.0),
+        (2, OrderType::Buy, 99.0),
+        (3, OrderType::Buy, 99.0),
+        (4, OrderType::Sell, 102.0),
+        (5, OrderType::Sell, 101.0),
+    ] {
+        order_book
+            .add_order(
+                OrderBuilder::new()
+                    .id(id)
+                    .pair(btc_usd.clone())
+                    .side(side)
+                    .price(price)
+                    .quantity(1.0)
+                    .build(),
+            )
+            .await
+            .unwrap();
+    }
+
+    let bids = order_book.bids().await;
+    let levels: Vec<(f64, Vec<u64>)> = bids
+        .iter()
+        .map(|(price, orders)| (price, orders.iter().map(|order| order.id).collect()))
+        .collect();
+    assert_eq!(levels, vec![(99.0, vec![2, 3]), (98.0, vec![1])]);
+    drop(bids);
+
+    let asks = order_book.asks().await;
+    let prices: Vec<f64> = asks.iter().map(|(price, _)| price).collect();
+    assert_eq!(prices, vec![101.0, 102.0]);
+}
+
+#[tokio::test]
+async fn test_volume_traded_since() {
+    let pair = TradingPair::new("BTC".to_string(), "USD".to_string());
+    let order_book = SimpleOrderBook::new(pair.clone());
+    let cross = |id: u64, quantity: f64| {
+        let order_book = &order_book;
+        let pair = pair.clone();
+        async move {
+            for (id, side) in [(id, OrderType::Sell), (id + 1, OrderType::Buy)] {
+                order_book
+                    .add_order(
+                        OrderBuilder::new()
+                            .id(id)
+                            .pair(pair.clone())
+                            .side(side)
+                            .price(100.0)
+                            .quantity(quantity)
+                            .build(),
+                    )
+                    .await
+                    .unwrap();
+            }
+            order_book.match_orders().await;
+        }
+    };
+
+    let start = chrono::Utc::now();
+    cross(1, 2.0).await;
+    tokio::time::sleep(Duration::from_millis(5)).await;
+    let cutoff = chrono::Utc::now();
+    cross(3, 0.5).await;
+    cross(5, 1.0).await;
+
+    assert_eq!(order_book.get_volume_traded_since(start).await, 3.5);
+    assert_eq!(order_book.get_volume_traded_since(cutoff).await, 1.5);
+    assert_eq!(
+        order_book
+            .get_volume_traded_since(chrono::Utc::now() + chrono::Duration::seconds(1))
+            .await,
+        0.0
+    );
+}
+
+#[tokio::test]
+async fn test_from_orders_and_extend_rest_without_matching() {
+    let pair = TradingPair::new("BTC".to_string(), "USD".to_string());
+    let order = |id: u64, side: OrderType, price: f64| {
+        OrderBuilder::new()
+            .id(id)
+            .pair(pair.clone())
+            .side(side)
+            .price(price)
+            .quantity(1.0)
+            .build()
+            .with_client("alice")
+    };
+
+    let mut order_book = SimpleOrderBook::from_orders(
+        pair.clone(),
+        [
+            order(1, OrderType::Buy, 100.0),
+            order(2, OrderType::Sell, 100.0),
+        ],
+    );
+    order_book.extend([
+        order(3, OrderType::Buy, 100.0),
+        order(4, OrderType::Sell, 105.0),
+    ]);
+
+    assert!(order_book.get_trade_history().await.is_empty());
+    let (bids, asks) = order_book.get_order_book().await;
+    assert_eq!((bids[0].total_quantity, bids[0].order_count), (2.0, 2));
+    assert_eq!(asks.len(), 2);
+    assert_eq!(
+        order_book.get_active_orders_by_client("alice").await.len(),
+        4
+    );
+    assert!(order_book.take_diff().await.is_some());
+
+    // Queue order follows the iterator, so order 1 fills before order 3.
+    let result = order_book.match_orders().await;
+    assert_eq!(result.trades.len(), 1);
+    assert_eq!(result.trades[0].buy_order_id, 1);
+}
+
+/// Collects `name: field=value` for every span field set on creation or
+/// recorded later.
+#[derive(Clone, Default)]
+struct SpanFields(std::sync::Arc<std::sync::Mutex<Vec<String>>>);
+
+impl<S> tracing_subscriber::Layer<S> for SpanFields
+where
+    S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+{
+    fn on_new_span(
+        &self,
+        attrs: &tracing::span::Attributes<'_>,
+        _id: &tracing::span::Id,
+        _ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        attrs.record(&mut FieldVisitor(attrs.metadata().name(), &self.0));
+    }
+
+    fn on_record(
+        &self,
+        id: &tracing::span::Id,
+        values: &tracing::span::Record<'_>,
+        ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        let name = ctx.span(id).unwrap().name();
+        values.record(&mut FieldVisitor(name, &self.0));
+    }
+}
+
+struct FieldVisitor<'a>(&'static str, &'a std::sync::Mutex<Vec<String>>);
+
+impl tracing::field::Visit for FieldVisitor<'_> {
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        self.1
+            .lock()
+            .unwrap()
+            .push(format!("{}: {}={:?}", self.0, field.name(), value));
+    }
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_order_book_methods_record_span_fields() {
+    use tracing_subscriber::layer::SubscriberExt;
+
+    let fields = SpanFields::default();
+    let subscriber = tracing_subscriber::registry()
+        .with(fields.clone())
+        .with(tracing_subscriber::filter::LevelFilter::DEBUG);
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let pair = TradingPair::new("BTC".to_string(), "USD".to_string());
+    let order_book = SimpleOrderBook::new(pair.clone());
+    for (id, side) in [(1, OrderType::Sell), (2, OrderType::Buy)] {
+        order_book
+            .add_order(
+                OrderBuilder::new()
+                    .id(id)
+                    .pair(pair.clone())
+                    .side(side)
+                    .price(100.0)
+                    .quantity(1.0)
+                    .build(),
+            )
+            .await
+            .unwrap();
+    }
+    order_book.match_orders().await;
+    order_book.cancel_order(7).await;
+    order_book.get_order_book().await;
+
+    let fields = fields.0.lock().unwrap();
+    let has = |field: &str| fields.iter().any(|recorded| recorded == field);
+    assert!(has(&format!("add_order: pair={:?}", pair)));
+    assert!(has("add_order: order_id=2"));
+    assert!(has(&format!("match_orders: pair={:?}", pair)));
+    assert!(has("match_orders: trades=1"));
+    assert!(has("cancel_order: order_id=7"));
+    assert!(has("cancel_order: cancelled=false"));
+    assert!(has("get_order_book: bids=0"));
+}
+
+#[tokio::test]
+async fn test_price_ladder_buckets_by_nearest_rung() {
+    let btc_usd = TradingPair::new("BTC".to_string(), "USD".to_string());
+    let order_book = SimpleOrderBook::new(btc_usd.clone());
+    for (id, side, price, quantity) in [
+        (1, OrderType::Buy, 99.0, 1.0),
+        (2, OrderType::Buy, 98.6, 2.0),
+        (3, OrderType::Buy, 95.0, 5.0),
+        (4, OrderType::Sell, 100.2, 1.5),
+        (5, OrderType::Sell, 101.0, 1.0),
+    ] {
+        order_book
+            .add_order(
+                OrderBuilder::new()
+                    .id(id)
+                    .pair(btc_usd.clone())
+                    .side(side)
+                    .price(price)
+                    .quantity(quantity)
+                    .build(),
+            )
+            .await
+            .unwrap();
+    }
+
+    let ladder = order_book.price_ladder(100.0, 1.0, 2).await;
+    let rungs: Vec<(f64, f64, usize, f64, usize)> = ladder
+        .iter()
+        .map(|entry| {
+            (
+                entry.price,
+                entry.bid_qty,
+                entry.bid_order_count,
+                entry.ask_qty,
+                entry.ask_order_count,
+            )
+        })
+        .collect();
+    // The bid at 95 is beyond the bottom rung.
+    assert_eq!(
+        rungs,
+        vec![
+            (102.0, 0.0, 0, 0.0, 0),
+            (101.0, 0.0, 0, 1.0, 1),
+            (100.0, 0.0, 0, 1.5, 1),
+            (99.0, 3.0, 2, 0.0, 0),
+            (98.0, 0.0, 0, 0.0, 0),
+        ]
+    );
+    assert!(order_book.price_ladder(100.0, 0.0, 2).await.is_empty());
+}
+
+#[tokio::test]
+async fn test_stats_are_cached_until_the_book_changes() {
+    let btc_usd = TradingPair::new("BTC".to_string(), "USD".to_string());
+    let order_book = SimpleOrderBook::new(btc_usd.clone());
+    let order = |id, side, price, quantity| {
+        OrderBuilder::new()
+            .id(id)
+            .pair(btc_usd.clone())
+            .side(side)
+            .price(price)
+            .quantity(quantity)
+            .build()
+    };
+    order_book
+        .add_order(order(1, OrderType::Buy, 99.0, 1.0))
+        .await
+        .unwrap();
+    order_book
+        .add_order(order(2, OrderType::Sell, 101.0, 1.0))
+        .await
+        .unwrap();
+
+    let stats = order_book.stats().await;
+    assert_eq!((stats.best_bid, stats.best_ask), (Some(99.0), Some(101.0)));
+    assert_eq!((stats.spread, stats.mid_price), (Some(2.0), Some(100.0)));
+    assert_eq!(stats.vwap_100, None);
+    assert_eq!(order_book.stats().await.last_updated, stats.last_updated);
+
+    order_book
+        .add_order(order(3, OrderType::Buy, 101.0, 0.5))
+        .await
+        .unwrap();
+    order_book
+        .add_order(order(4, OrderType::Sell, 100.5, 1.0))
+        .await
+        .unwrap();
+    // Crossed until matched.
+    assert_eq!(order_book.get_spread().await, Some(-0.5));
+    order_book.match_orders().await;
+    assert_eq!(order_book.get_vwap().await, Some(101.0));
+    assert_eq!(order_book.get_mid_price().await, Some(99.75));
+
+    order_book.cancel_order(1).await;
+    let stats = order_book.stats().await;
+    assert_eq!(
+        (stats.best_bid, stats.spread, stats.mid_price),
+        (None, None, None)
+    );
+}
+
+#[tokio::test]
+async fn test_resting_side_follows_arrival_not_timestamp() {
+    let btc_usd = TradingPair::new("BTC".to_string(), "USD".to_string());
+    let timestamp = chrono::Utc::now();
+    let order = |id| {
+        OrderBuilder::new()
+            .id(id)
+            .pair(btc_usd.clone())
+            .quantity(1.0)
+            .timestamp(timestamp)
+    };
+
+    // Equal timestamps: the buy arrived first, so it rests and sets the price.
+    let order_book = SimpleOrderBook::new(btc_usd.clone());
+    order_book
+        .add_order(order(1).buy_at(101.0).build())
+        .await
+        .unwrap();
+    order_book
+        .add_order(order(2).sell_at(100.0).build())
+        .await
+        .unwrap();
+    let trade = order_book.match_orders().await.trades.remove(0);
+    assert_eq!(
+        (trade.price.value(), trade.aggressor_side),
+        (101.0, OrderType::Sell)
+    );
+
+    // A submitter's earlier timestamp does not jump the queue.
+    let order_book = SimpleOrderBook::new(btc_usd.clone());
+    order_book
+        .add_order(order(3).sell_at(100.0).build())
+        .await
+        .unwrap();
+    let backdated = order(4)
+        .buy_at(101.0)
+        .timestamp(timestamp - chrono::Duration::seconds(60))
+        .build();
+    order_book.add_order(backdated).await.unwrap();
+    let trade = order_book.match_orders().await.trades.remove(0);
+    assert_eq!(
+        (trade.price.value(), trade.aggressor_side),
+        (100.0, OrderType::Buy)
+    );
+}
+
+#[tokio::test]
+async fn test_stop_orders_trigger_on_last_trade() {
+    let btc_usd = TradingPair::new("BTC".to_string(), "USD".to_string());
+    let order_book = SimpleOrderBook::new(btc_usd.clone());
+    let order = |id| {
+        OrderBuilder::new()
+            .id(id)
+            .pair(btc_usd.clone())
+            .quantity(1.0)
+    };
+
+    order_book
+        .add_order(order(1).buy_at(106.0).build().with_stop_price(105.0))
+        .await
+        .unwrap();
+    order_book
+        .add_order(order(2).buy_at(111.0).build().with_stop_price(110.0))
+        .await
+        .unwrap();
+    order_book
+        .add_order(order(3).sell_at(90.0).build().with_stop_price(95.0))
+        .await
+        .unwrap();
+    assert_eq!(order_book.get_active_orders_count().await, 0);
+    assert_eq!(order_book.get_stop_orders().await.len(), 3);
+
+    order_book
+        .add_order(order(4).sell_at(105.0).build())
+        .await
+        .unwrap();
+    order_book
+        .add_order(order(5).buy_at(105.0).build())
+        .await
+        .unwrap();
+    order_book
+        .add_order(order(6).sell_at(106.0).build())
+        .await
+        .unwrap();
+
+    // The trade at 105 sets off only the first buy stop, which trades in
+    // the same match.
+    let result = order_book.match_orders().await;
+    let trades: Vec<(u64, f64)> = result
+        .trades
+        .iter()
+        .map(|trade| (trade.buy_order_id, trade.price.value()))
+        .collect();
+    assert_eq!(trades, vec![(5, 105.0), (1, 106.0)]);
+    assert_eq!(result.fully_filled.len(), 4);
+    let mut waiting: Vec<u64> = order_book
+        .get_stop_orders()
+        .await
+        .iter()
+        .map(|order| order.id)
+        .collect();
+    waiting.sort();
+    assert_eq!(waiting, vec![2, 3]);
+
+    assert_eq!(
+        order_book.cancel_order(3).await.unwrap().stop_price,
+        Some(95.0)
+    );
+    assert_eq!(order_book.trigger_stop_orders(110.0).await, 1);
+    let resting = order_book.get_active_orders().await;
+    assert_eq!((resting[0].id, resting[0].stop_price), (2, None));
+}
+
+#[tokio::test]
+async fn test_match_publishes_last_trade_price() {
+    let btc_usd = TradingPair::new("BTC".to_string(), "USD".to_string());
+    let updates = Arc::new(Mutex::new(Vec::new()));
+    let seen = updates.clone();
+    let order_book = SimpleOrderBook::new_with_callback(
+        btc_usd.clone(),
+        Arc::new(move |update: PriceUpdate| seen.lock().unwrap().push(update)),
+    );
+    let order = |id| {
+        OrderBuilder::new()
+            .id(id)
+            .pair(btc_usd.clone())
+            .quantity(1.0)
+    };
+
+    order_book
+        .add_order(order(1).sell_at(100.0).build())
+        .await
+        .unwrap();
+    order_book
+        .add_order(order(2).sell_at(101.0).build())
+        .await
+        .unwrap();
+    order_book
+        .add_order(order(3).buy_at(101.0).quantity(2.0).build())
+        .await
+        .unwrap();
+    assert_eq!(order_book.match_orders().await.trades.len(), 2);
+    // Nothing trades, so nothing is published.
+    order_book.match_orders().await;
+
+    let updates = updates.lock().unwrap();
+    assert_eq!(updates.len(), 1);
+    assert_eq!(updates[0].price, 101.0);
+    assert_eq!(updates[0].source, LAST_TRADE_SOURCE);
+}
+
+#[tokio::test]
+async fn test_integrity_check_after_fills_and_cancels() {
+    let btc_usd = TradingPair::new("BTC".to_string(), "USD".to_string());
+    let order_book = SimpleOrderBook::new(btc_usd.clone());
+    let order = |id: u64| OrderBuilder::new().id(id).pair(btc_usd.clone());
+    for (id, price) in [(1, 99.0), (2, 100.0)] {
+        order_book
+            .add_order(
+                order(id)
+                    .buy_at(price)
+                    .quantity(2.0)
+                    .build()
+                    .with_client("alice"),
+            )
+            .await
+            .unwrap();
+    }
+    order_book
+        .add_order(
+            order(3)
+                .sell_at(100.0)
+                .quantity(3.0)
+                .build()
+                .with_client("bob"),
+        )
+        .await
+        .unwrap();
+    order_book.match_orders().await;
+    order_book.cancel_order(1).await;
+
+    let report = order_book.check_book_integrity().await;
+    assert!(report.inconsistencies.is_empty(), "{:?}", report);
+    assert!(report.orphaned_order_ids.is_empty());
+}
+
+#[tokio::test]
+async fn test_merge_with_retags_and_indexes_orders() {
+    let lunc_usdt = TradingPair::new("LUNC".to_string(), "USDT".to_string());
+    let luna_usdt = TradingPair::new("LUNA".to_string(), "USDT".to_string());
+    let order = |id: u64, pair: &TradingPair| OrderBuilder::new().id(id).pair(pair.clone());
+    let mut target = SimpleOrderBook::from_orders(
+        lunc_usdt.clone(),
+        [order(1, &lunc_usdt).buy_at(1.0).quantity(5.0).build()],
+    );
+    let source = SimpleOrderBook::from_orders(
+        luna_usdt.clone(),
+        [
+            order(2, &luna_usdt)
+                .buy_at(1.0)
+                .quantity(2.0)
+                .build()
+                .with_client("alice"),
+            order(3, &luna_usdt).sell_at(1.2).quantity(1.0).build(),
+            order(4, &luna_usdt)
+                .sell_at(0.9)
+                .quantity(1.0)
+                .build()
+                .with_stop_price(0.95),
+        ],
+    );
+
+    assert_eq!(target.merge_with(source), 3);
+    let active = target.get_active_orders().await;
+    assert_eq!(
+        active.iter().map(|order| order.id).collect::<Vec<_>>(),
+        [1, 2, 3]
+    );
+    assert!(active.iter().all(|order| order.trading_pair == lunc_usdt));
+    assert_eq!(target.get_stop_orders().await[0].trading_pair, lunc_usdt);
+    let alice = target.get_active_orders_by_client("alice").await;
+    assert_eq!(alice.len(), 1);
+    assert_eq!(alice[0].id, 2);
+    assert_eq!(target.check_book_integrity().await, Default::default());
+}
+
+#[tokio::test]
+async fn test_allocation_stats_track_each_collection() {
+    let btc_usd = TradingPair::new("BTC".to_string(), "USD".to_string());
+    let order_book = SimpleOrderBook::new(btc_usd.clone());
+    let order = |id: u64| OrderBuilder::new().id(id).pair(btc_usd.clone());
+    let resting = [
+        order(1)
+            .buy_at(99.0)
+            .quantity(1.0)
+            .build()
+            .with_client("alice"),
+        order(2).buy_at(99.0).quantity(1.0).build(),
+        order(3)
+            .sell_at(100.0)
+            .quantity(1.0)
+            .build()
+            .with_client("bob"),
+        order(4)
+            .buy_at(100.0)
+            .quantity(0.5)
+            .build()
+            .with_client("carol"),
+        order(5)
+            .sell_at(98.0)
+            .quantity(1.0)
+            .build()
+            .with_stop_price(98.5),
+    ];
+    for order in resting {
+        order_book
+            .register_submission(&order, Duration::from_secs(60), 100)
+            .await
+            .unwrap();
+        order_book.add_order(order).await.unwrap();
+    }
+    order_book.match_orders().await;
+    order_book.cancel_order(1).await;
+
+    let stats = order_book.allocation_stats().await;
+    assert_eq!(
+        stats,
+        AllocationStats {
+            bid_entries: 1,
+            ask_entries: 1,
+            id_index_entries: 1,
+            trade_history_len: 1,
+            stop_orders: 1,
+            fingerprint_cache_size: 5,
+        }
+    );
+    assert!(!stats.index_is_leaking());
+    assert_eq!(order_book.get_allocation_stats().await, Some(stats));
+}
+
+#[tokio::test]
+async fn test_submission_window_follows_the_book_clock() {
+    let btc_usd = TradingPair::new("BTC".to_string(), "USD".to_string());
+    let order_book = SimpleOrderBook::new(btc_usd.clone());
+    let clock = Arc::new(SimulatedClock::new(Utc::now()));
+    order_book.set_clock(clock.clone());
+    let order = OrderBuilder::new()
+        .id(1)
+        .pair(btc_usd)
+        .buy_at(99.0)
+        .quantity(1.0)
+        .build();
+    let window = Duration::from_secs(60);
+
+    order_book
+        .register_submission(&order, window, 100)
+        .await
+        .unwrap();
+    clock.advance(Duration::from_secs(59));
+    assert!(order_book.is_duplicate_submission(&order, window).await);
+    assert!(matches!(
+        order_book.register_submission(&order, window, 100).await,
+        Err(OrderBookError::DuplicateSubmission(_))
+    ));
+
+    // No wall time passes, only the book's clock.
+    clock.advance(Duration::from_secs(1));
+    assert!(!order_book.is_duplicate_submission(&order, window).await);
+    order_book
+        .register_submission(&order, window, 100)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_client_index_matches_resting_orders_through_adds_cancels_and_fills() {
+    let btc_usd = TradingPair::new("BTC".to_string(), "USD".to_string());
+    let order_book = SimpleOrderBook::new(btc_usd.clone());
+    let order = |id: u64, side: OrderType, price: f64, quantity: f64, client: &str| {
+        OrderBuilder::new()
+            .id(id)
+            .pair(btc_usd.clone())
+            .side(side)
+            .price(price)
+            .quantity(quantity)
+            .build()
+            .with_client(client)
+    };
+    let assert_indexed = |stats: AllocationStats| {
+        assert_eq!(
+            stats.id_index_entries,
+            stats.bid_entries + stats.ask_entries
+        );
+    };
+
+    for order in [
+        order(1, OrderType::Buy, 99.0, 1.0, "alice"),
+        order(2, OrderType::Sell, 100.0, 0.5, "bob"),
+        order(3, OrderType::Buy, 100.0, 1.0, "carol"),
+    ] {
+        order_book.add_order(order).await.unwrap();
+    }
+    let stats = order_book.allocation_stats().await;
+    assert_eq!(stats.id_index_entries, 3);
+    assert_indexed(stats);
+
+    order_book.cancel_order(1).await.unwrap();
+    assert_indexed(order_book.allocation_stats().await);
+
+    // Fills the ask and leaves half the bid resting.
+    order_book.match_orders().await;
+    let stats = order_book.allocation_stats().await;
+    assert_eq!(stats.id_index_entries, 1);
+    assert_indexed(stats);
+}
+
+#[tokio::test]
+async fn test_rebalance_price_levels_drops_levels_left_empty_by_cancels() {
+    let btc_usd = TradingPair::new("BTC".to_string(), "USD".to_string());
+    let mut order_book = SimpleOrderBook::new(btc_usd.clone());
+    let order = |id: u64, side: OrderType, price: f64| {
+        OrderBuilder::new()
+            .id(id)
+            .pair(btc_usd.clone())
+            .side(side)
+            .price(price)
+            .quantity(1.0)
+            .build()
+    };
+    for (id, side, price) in [
+        (1, OrderType::Buy, 99.0),
+        (2, OrderType::Buy, 98.0),
+        (3, OrderType::Buy, 97.0),
+        (4, OrderType::Sell, 101.0),
+    ] {
+        order_book.add_order(order(id, side, price)).await.unwrap();
+    }
+    assert_eq!(order_book.empty_level_ratio().await, 0.0);
+    assert_eq!(order_book.rebalance_price_levels(), 0);
+
+    for id in [2, 3, 4] {
+        assert!(order_book.cancel_order(id).await.is_some());
+    }
+    // The emptied levels linger but are not shown.
+    assert_eq!(order_book.empty_level_ratio().await, 3.0);
+    assert_eq!(order_book.bid_level_count().await, 3);
+    let (bids, asks) = order_book.get_order_book().await;
+    assert_eq!(bids.len(), 1);
+    assert!(asks.is_empty());
+    assert_eq!(order_book.get_current_price().await, Some(99.0));
+
+    assert_eq!(order_book.rebalance_price_levels(), 3);
+    assert_eq!(order_book.empty_level_ratio().await, 0.0);
+    assert_eq!(
+        (
+            order_book.bid_level_count().await,
+            order_book.ask_level_count().await
+        ),
+        (1, 0)
+    );
+}
@@ -0,0 +1,251 @@
+#[cfg(feature = "serde")]
+use engine::engine::config::SerializationFormat;
+#[cfg(feature = "serde")]
+use engine::engine::core::start_engine_from_state;
+use engine::engine::core::{start_engine, Message};
+use engine::engine::models::{Order, OrderType, TradingPair};
+use engine::engine::order_book::SimpleOrderBook;
+#[cfg(feature = "serde")]
+use engine::engine::persistence::{decode, encode, EngineState, STATE_MAGIC, STATE_VERSION};
+use engine::engine::persistence::{
+    InMemoryPersistenceBackend, OrderBookSnapshot, PersistenceBackend,
+};
+use engine::engine::testing::{btc_usd, OrderBuilder};
+#[cfg(feature = "serde")]
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+fn order(id: u64, order_type: OrderType, price: f64, quantity: f64) -> Order {
+    OrderBuilder::new()
+        .id(id)
+        .pair(btc_usd())
+        .side(order_type)
+        .price(price)
+        .quantity(quantity)
+        .build()
+}
+
+#[cfg(feature = "serde")]
+fn state_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("engine-{}-{}.json", name, std::process::id()))
+}
+
+#[cfg(feature = "serde")]
+#[tokio::test]
+async fn test_save_and_load_state_round_trip() {
+    let path = state_path("round-trip");
+    let engine_tx = start_engine(|trading_pair| Box::new(SimpleOrderBook::new(trading_pair)));
+
+    engine_tx
+        .send(Message::NewOrder(order(1, OrderType::Buy, 100.0, 2.0)))
+        .await
+        .unwrap();
+    engine_tx
+        .send(Message::NewOrder(order(2, OrderType::Sell, 100.0, 1.0)))
+        .await
+        .unwrap();
+    engine_tx
+        .send(Message::NewOrder(order(3, OrderType::Sell, 105.0, 4.0)))
+        .await
+        .unwrap();
+    let (match_tx, mut match_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::MatchOrders(btc_usd(), match_tx))
+        .await
+        .unwrap();
+    assert_eq!(match_rx.recv().await.unwrap().len(), 1);
+
+    let (save_tx, mut save_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::SaveState(path.clone(), save_tx))
+        .await
+        .unwrap();
+    save_rx.recv().await.unwrap().unwrap();
+    engine_tx.send(Message::Shutdown).await.unwrap();
+
+    let state = EngineState::read_from(&path).unwrap();
+    assert_eq!(state.version, STATE_VERSION);
+
+    let restored_tx = start_engine_from_state(path.clone(), |trading_pair| {
+        Box::new(SimpleOrderBook::new(trading_pair))
+    })
+    .await
+    .unwrap();
+
+    let (book_tx, mut book_rx) = mpsc::channel(1);
+    restored_tx
+        .send(Message::GetOrderBook(btc_usd(), book_tx))
+        .await
+        .unwrap();
+    let (bids, asks) = book_rx.recv().await.unwrap();
+    assert_eq!(bids.len(), 1);
+    assert_eq!(bids[0].total_quantity, 1.0);
+    assert_eq!(asks[0].price, 105.0);
+
+    let (history_tx, mut history_rx) = mpsc::channel(1);
+    restored_tx
+        .send(Message::GetTradeHistory(btc_usd(), history_tx))
+        .await
+        .unwrap();
+    assert_eq!(history_rx.recv().await.unwrap().len(), 1);
+
+    let _ = std::fs::remove_file(path);
+}
+
+#[cfg(feature = "serde")]
+#[tokio::test]
+async fn test_load_state_rejects_foreign_files() {
+    let path = state_path("foreign");
+    std::fs::write(
+        &path,
+        r#"{"magic":1,"version":1,"config":{"channel_capacity":100,"concurrent_books":false},"order_books":[],"next_child_order_id":0}"#,
+    )
+    .unwrap();
+
+    let result = start_engine_from_state(path.clone(), |trading_pair| {
+        Box::new(SimpleOrderBook::new(trading_pair))
+    })
+    .await;
+    assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+
+    let _ = std::fs::remove_file(path);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_read_state_checks_version_before_body() {
+    let path = state_path("newer");
+    // A body this engine cannot parse, from a version it does not know.
+    std::fs::write(
+        &path,
+        format!(
+            r#"{{"magic":{},"version":{},"order_books":"elsewhere"}}"#,
+            STATE_MAGIC,
+            STATE_VERSION + 1
+        ),
+    )
+    .unwrap();
+
+    let error = EngineState::read_from(&path).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    assert_eq!(
+        error.to_string(),
+        format!("unsupported engine state version {}", STATE_VERSION + 1)
+    );
+
+    let _ = std::fs::remove_file(path);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_json_encoding_round_trip() {
+    let snapshot = OrderBookSnapshot {
+        trading_pair: btc_usd(),
+        orders: vec![order(1, OrderType::Buy, 100.0, 1.0)],
+        trades: vec![],
+    };
+    let bytes = encode(&snapshot, SerializationFormat::Json).unwrap();
+    let decoded: OrderBookSnapshot = decode(&bytes, SerializationFormat::Json).unwrap();
+    assert_eq!(decoded.trading_pair, snapshot.trading_pair);
+    assert_eq!(decoded.orders.len(), 1);
+}
+
+#[cfg(all(feature = "serde", not(feature = "bincode-serde")))]
+#[test]
+fn test_bincode_needs_feature() {
+    let result = encode(&btc_usd(), SerializationFormat::Bincode);
+    assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::Unsupported);
+}
+
+#[cfg(feature = "bincode-serde")]
+#[tokio::test]
+async fn test_bincode_state_round_trip() {
+    use engine::engine::config::EngineConfig;
+    use engine::engine::core::start_engine_with_config;
+
+    let path = state_path("bincode");
+    let config = EngineConfig {
+        serialization_format: SerializationFormat::Bincode,
+        ..EngineConfig::default()
+    };
+    let engine_tx = start_engine_with_config(config, |trading_pair| {
+        Box::new(SimpleOrderBook::new(trading_pair))
+    });
+    engine_tx
+        .send(Message::NewOrder(order(1, OrderType::Sell, 101.0, 3.0)))
+        .await
+        .unwrap();
+
+    let (save_tx, mut save_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::SaveState(path.clone(), save_tx))
+        .await
+        .unwrap();
+    save_rx.recv().await.unwrap().unwrap();
+    assert_ne!(std::fs::read(&path).unwrap()[0], b'{');
+
+    let state = EngineState::read_from(&path).unwrap();
+    assert_eq!(
+        state.config.serialization_format,
+        SerializationFormat::Bincode
+    );
+    assert_eq!(state.order_books[0].orders[0].quantity.value(), 3.0);
+
+    let _ = std::fs::remove_file(path);
+}
+
+#[tokio::test]
+async fn test_in_memory_backend_round_trip() {
+    let backend = InMemoryPersistenceBackend::new();
+    let engine_tx = start_engine(|trading_pair| Box::new(SimpleOrderBook::new(trading_pair)));
+    for order in [
+        order(1, OrderType::Buy, 99.0, 1.0),
+        order(2, OrderType::Sell, 101.0, 2.0),
+    ] {
+        engine_tx.send(Message::NewOrder(order)).await.unwrap();
+    }
+    let (save_tx, mut save_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::SaveSnapshots(Arc::new(backend.clone()), save_tx))
+        .await
+        .unwrap();
+    save_rx.recv().await.unwrap().unwrap();
+
+    let stored = backend.load_snapshot(&btc_usd()).unwrap().unwrap();
+    assert_eq!(stored.orders.len(), 2);
+    let eth_usd = TradingPair::new("ETH".to_string(), "USD".to_string());
+    assert!(backend.load_snapshot(&eth_usd).unwrap().is_none());
+
+    // Saving again replaces the pair's snapshot.
+    backend
+        .save_snapshot(OrderBookSnapshot {
+            orders: stored.orders[..1].to_vec(),
+            ..stored
+        })
+        .unwrap();
+
+    let restored_tx = start_engine(|trading_pair| Box::new(SimpleOrderBook::new(trading_pair)));
+    let (restore_tx, mut restore_rx) = mpsc::channel(1);
+    for (trading_pair, found) in [(btc_usd(), true), (eth_usd, false)] {
+        restored_tx
+            .send(Message::RestoreSnapshot(
+                trading_pair,
+                Arc::new(backend.clone()),
+                restore_tx.clone(),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(restore_rx.recv().await.unwrap().unwrap(), found);
+    }
+
+    let (book_tx, mut book_rx) = mpsc::channel(1);
+    restored_tx
+        .send(Message::GetOrderBook(btc_usd(), book_tx))
+        .await
+        .unwrap();
+    let (bids, asks) = book_rx.recv().await.unwrap();
+    assert_eq!(bids.len(), 1);
+    assert_eq!(bids[0].price, 99.0);
+    assert!(asks.is_empty());
+}
@@ -0,0 +1,137 @@
+use chrono::Utc;
+use engine::engine::core::{Engine, Message};
+use engine::engine::margin::MarginCalculator;
+use engine::engine::models::{OrderType, Trade, TradingPair};
+use engine::engine::order_book::SimpleOrderBook;
+use engine::engine::position::{LiquidationHook, PositionChangeHook, PositionTracker};
+use engine::engine::testing::OrderBuilder;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+
+fn btc_perp() -> TradingPair {
+    TradingPair::new("BTC-PERP".to_string(), "USD".to_string())
+}
+
+fn trade(buyer: Option<&str>, seller: Option<&str>, price: f64, quantity: f64) -> Trade {
+    Trade {
+        id: 1,
+        trading_pair: btc_perp(),
+        buy_order_id: 1,
+        sell_order_id: 2,
+        price: price.into(),
+        quantity: quantity.into(),
+        aggressor_side: OrderType::Buy,
+        timestamp: Utc::now(),
+        buy_client_id: buyer.map(str::to_string),
+        sell_client_id: seller.map(str::to_string),
+        buy_fee: 0.0,
+        sell_fee: 0.0,
+    }
+}
+
+/// Records every change it is told about.
+#[derive(Default)]
+struct Recorder {
+    changes: Mutex<Vec<(String, f64, f64)>>,
+}
+
+impl PositionChangeHook for Recorder {
+    fn on_position_change(
+        &self,
+        client_id: &str,
+        _pair: &TradingPair,
+        old_qty: f64,
+        new_qty: f64,
+        _trade: &Trade,
+    ) {
+        self.changes
+            .lock()
+            .unwrap()
+            .push((client_id.to_string(), old_qty, new_qty));
+    }
+}
+
+#[test]
+fn test_tracker_nets_both_sides_and_runs_hooks() {
+    let recorder = Arc::new(Recorder::default());
+    let mut tracker = PositionTracker::new();
+    tracker.add_hook(recorder.clone());
+
+    tracker.apply_trade(&trade(Some("alice"), Some("bob"), 100.0, 2.0));
+    tracker.apply_trade(&trade(Some("bob"), None, 101.0, 0.5));
+
+    assert_eq!(tracker.position("alice", &btc_perp()), 2.0);
+    assert_eq!(tracker.position("bob", &btc_perp()), -1.5);
+    assert_eq!(tracker.position("carol", &btc_perp()), 0.0);
+    assert_eq!(
+        *recorder.changes.lock().unwrap(),
+        vec![
+            ("alice".to_string(), 0.0, 2.0),
+            ("bob".to_string(), 0.0, -2.0),
+            ("bob".to_string(), -2.0, -1.5),
+        ]
+    );
+}
+
+#[test]
+fn test_liquidation_hook_flags_underwater_positions() {
+    let hook = Arc::new(LiquidationHook::new(MarginCalculator::new(10.0, 5.0)));
+    let mut tracker = PositionTracker::new();
+    tracker.add_hook(hook.clone());
+
+    tracker.apply_trade(&trade(Some("alice"), None, 100.0, 1.0));
+    assert_eq!(hook.flagged("alice", &btc_perp()), None);
+
+    // Averaging down to 94.5 leaves 18.9 of margin against 11 of losses.
+    tracker.apply_trade(&trade(Some("alice"), None, 89.0, 1.0));
+    let liquidation_price = hook.flagged("alice", &btc_perp()).unwrap();
+    assert!(liquidation_price > 89.0);
+
+    tracker.apply_trade(&trade(None, Some("alice"), 89.0, 2.0));
+    assert_eq!(hook.flagged("alice", &btc_perp()), None);
+}
+
+#[tokio::test]
+async fn test_engine_runs_position_hooks_after_matching() {
+    let recorder = Arc::new(Recorder::default());
+    let mut engine = Engine::new(|trading_pair| Box::new(SimpleOrderBook::new(trading_pair)))
+        .with_position_tracker_hook(recorder.clone());
+    let (engine_tx, engine_rx) = mpsc::channel(10);
+    tokio::spawn(async move { engine.run(engine_rx).await });
+
+    for (id, side, client) in [(1, OrderType::Sell, "bob"), (2, OrderType::Buy, "alice")] {
+        let order = OrderBuilder::new()
+            .id(id)
+            .pair(btc_perp())
+            .side(side)
+            .price(100.0)
+            .quantity(1.0)
+            .build()
+            .with_client(client);
+        engine_tx.send(Message::NewOrder(order)).await.unwrap();
+    }
+    let (match_tx, mut match_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::MatchOrders(btc_perp(), match_tx))
+        .await
+        .unwrap();
+    let trade_id = match_rx.recv().await.unwrap()[0].id;
+
+    // Busting the trade unwinds both positions, seller first.
+    let (bust_tx, mut bust_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::BustTrade(btc_perp(), trade_id, bust_tx))
+        .await
+        .unwrap();
+    bust_rx.recv().await.unwrap().unwrap();
+
+    assert_eq!(
+        *recorder.changes.lock().unwrap(),
+        vec![
+            ("alice".to_string(), 0.0, 1.0),
+            ("bob".to_string(), 0.0, -1.0),
+            ("bob".to_string(), -1.0, 0.0),
+            ("alice".to_string(), 1.0, 0.0),
+        ]
+    );
+}
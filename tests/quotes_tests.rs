@@ -0,0 +1,121 @@
+use engine::engine::api::PriceLevel;
+use engine::engine::core::{start_engine, Message};
+use engine::engine::order_book::{OrderBookError, SimpleOrderBook};
+use engine::engine::quotes::{QuoteAck, QuoteError, QuoteRequest};
+use engine::engine::risk::{MaxOrderSizeRiskManager, RiskError};
+use engine::engine::testing::btc_usd;
+use tokio::sync::mpsc;
+
+fn quote(bid_price: f64, ask_price: f64, previous: Option<QuoteAck>) -> QuoteRequest {
+    QuoteRequest {
+        client_id: "maker".to_string(),
+        bid_price,
+        bid_qty: 1.0,
+        ask_price,
+        ask_qty: 2.0,
+        previous_bid_id: previous.map(|ack| ack.new_bid_id),
+        previous_ask_id: previous.map(|ack| ack.new_ask_id),
+    }
+}
+
+async fn update(
+    engine_tx: &mpsc::Sender<Message>,
+    request: QuoteRequest,
+) -> Result<QuoteAck, QuoteError> {
+    let (ack_tx, mut ack_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::UpdateQuote(btc_usd(), request, ack_tx))
+        .await
+        .unwrap();
+    ack_rx.recv().await.unwrap()
+}
+
+async fn book(engine_tx: &mpsc::Sender<Message>) -> (Vec<(f64, f64)>, Vec<(f64, f64)>) {
+    let (book_tx, mut book_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::GetOrderBook(btc_usd(), book_tx))
+        .await
+        .unwrap();
+    let (bids, asks) = book_rx.recv().await.unwrap();
+    let levels = |levels: Vec<PriceLevel>| {
+        levels
+            .into_iter()
+            .map(|level| (level.price, level.total_quantity))
+            .collect()
+    };
+    (levels(bids), levels(asks))
+}
+
+#[tokio::test]
+async fn test_update_quote_replaces_both_sides() {
+    let engine_tx = start_engine(|trading_pair| Box::new(SimpleOrderBook::new(trading_pair)));
+
+    let first = update(&engine_tx, quote(99.0, 101.0, None)).await.unwrap();
+    assert_ne!(first.new_bid_id, first.new_ask_id);
+    assert_eq!(
+        book(&engine_tx).await,
+        (vec![(99.0, 1.0)], vec![(101.0, 2.0)])
+    );
+
+    let second = update(&engine_tx, quote(98.5, 100.5, Some(first)))
+        .await
+        .unwrap();
+    assert_eq!(
+        book(&engine_tx).await,
+        (vec![(98.5, 1.0)], vec![(100.5, 2.0)])
+    );
+
+    // The first quote is gone, so replacing it again fails and leaves the
+    // second in place.
+    let stale = update(&engine_tx, quote(97.0, 99.0, Some(first))).await;
+    assert!(matches!(stale, Err(QuoteError::UnknownOrder(id)) if id == first.new_bid_id));
+    assert_eq!(
+        book(&engine_tx).await,
+        (vec![(98.5, 1.0)], vec![(100.5, 2.0)])
+    );
+
+    let crossed = update(&engine_tx, quote(101.0, 100.0, Some(second))).await;
+    assert!(matches!(crossed, Err(QuoteError::Crossed { .. })));
+    assert_eq!(
+        book(&engine_tx).await,
+        (vec![(98.5, 1.0)], vec![(100.5, 2.0)])
+    );
+}
+
+#[tokio::test]
+async fn test_update_quote_keeps_old_quote_when_second_leg_is_rejected() {
+    let engine_tx = start_engine(|trading_pair| Box::new(SimpleOrderBook::new(trading_pair)));
+
+    let first = update(&engine_tx, quote(99.0, 101.0, None)).await.unwrap();
+    engine_tx
+        .send(Message::SetRiskManager(Box::new(
+            MaxOrderSizeRiskManager::uniform(3.0),
+        )))
+        .await
+        .unwrap();
+
+    // The bid passes but the ask is too large. Both are checked before the
+    // first quote is cancelled, so it stays in place.
+    let mut request = quote(98.0, 102.0, Some(first));
+    request.ask_qty = 5.0;
+    let rejected = update(&engine_tx, request).await;
+    assert!(matches!(
+        rejected,
+        Err(QuoteError::Rejected(OrderBookError::Risk(
+            RiskError::OrderTooLarge { .. }
+        )))
+    ));
+    assert_eq!(
+        book(&engine_tx).await,
+        (vec![(99.0, 1.0)], vec![(101.0, 2.0)])
+    );
+
+    // The first quote is still live, so it can be replaced.
+    update(&engine_tx, quote(98.0, 102.0, Some(first)))
+        .await
+        .unwrap();
+    assert_eq!(
+        book(&engine_tx).await,
+        (vec![(98.0, 1.0)], vec![(102.0, 2.0)])
+    );
+}
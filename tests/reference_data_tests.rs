@@ -0,0 +1,126 @@
+use engine::engine::core::{start_engine, Engine, Message};
+use engine::engine::models::{Order, OrderValidationError, TradingPair, TradingPairInfo};
+use engine::engine::order_book::{OrderBook, OrderBookError, SimpleOrderBook};
+use engine::engine::reference_data::{ReferenceDataManager, SharedReferenceData};
+use engine::engine::testing::{btc_usd, OrderBuilder};
+use tokio::sync::mpsc;
+
+fn eth_usd() -> TradingPair {
+    TradingPair::new("ETH".to_string(), "USD".to_string())
+}
+
+fn order_at(id: u64, price: f64) -> Order {
+    OrderBuilder::new()
+        .id(id)
+        .pair(btc_usd())
+        .buy_at(price)
+        .quantity(1.0)
+        .build()
+}
+
+#[test]
+fn test_register_and_lookup() {
+    let mut reference_data = ReferenceDataManager::new();
+    reference_data.register(TradingPairInfo::new(btc_usd(), 0.01, 0.001));
+    reference_data.register(TradingPairInfo::new(eth_usd(), 0.01, 0.01));
+    reference_data.register(TradingPairInfo {
+        name: Some("Bitcoin / US Dollar".to_string()),
+        ..TradingPairInfo::new(btc_usd(), 0.5, 0.001)
+    });
+
+    let btc = reference_data.get(&btc_usd()).unwrap();
+    assert_eq!(btc.tick_size, 0.5);
+    assert_eq!(btc.name.as_deref(), Some("Bitcoin / US Dollar"));
+    assert_eq!(reference_data.list_all().len(), 2);
+    assert!(reference_data
+        .get(&TradingPair::new("SOL".to_string(), "USD".to_string()))
+        .is_none());
+}
+
+#[tokio::test]
+async fn test_engine_rejects_orders_off_tick() {
+    let engine_tx = start_engine(|trading_pair| Box::new(SimpleOrderBook::new(trading_pair)));
+
+    let (load_tx, mut load_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::LoadReferenceData(
+            vec![TradingPairInfo::new(btc_usd(), 0.5, 0.1)],
+            load_tx,
+        ))
+        .await
+        .unwrap();
+    load_rx.recv().await.unwrap();
+
+    for (id, price) in [(1, 100.5), (2, 100.25)] {
+        let order = OrderBuilder::new()
+            .id(id)
+            .pair(btc_usd())
+            .buy_at(price)
+            .quantity(1.0)
+            .build();
+        engine_tx.send(Message::NewOrder(order)).await.unwrap();
+    }
+
+    let (book_tx, mut book_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::GetOrderBook(btc_usd(), book_tx))
+        .await
+        .unwrap();
+    let (bids, _) = book_rx.recv().await.unwrap();
+    assert_eq!(bids.len(), 1);
+    assert_eq!(bids[0].price, 100.5);
+}
+
+#[tokio::test]
+async fn test_loads_reach_existing_handles_and_books() {
+    let mut engine = Engine::new(|trading_pair| Box::new(SimpleOrderBook::new(trading_pair)));
+    let reference_data = engine.reference_data();
+    let (engine_tx, engine_rx) = mpsc::channel(10);
+    // Creates the book before any specs are loaded.
+    engine_tx
+        .send(Message::NewOrder(order_at(1, 100.25)))
+        .await
+        .unwrap();
+    let (load_tx, _load_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::LoadReferenceData(
+            vec![TradingPairInfo::new(btc_usd(), 0.5, 0.1)],
+            load_tx,
+        ))
+        .await
+        .unwrap();
+    engine_tx.send(Message::Shutdown).await.unwrap();
+    engine.run(engine_rx).await;
+
+    assert_eq!(
+        reference_data.read().get(&btc_usd()).unwrap().tick_size,
+        0.5
+    );
+    let order_book = engine.get_order_book_handle(&btc_usd()).unwrap();
+    assert!(matches!(
+        order_book.read().await.add_order(order_at(2, 100.25)).await,
+        Err(OrderBookError::Invalid(
+            OrderValidationError::PricePrecision { .. }
+        ))
+    ));
+}
+
+#[tokio::test]
+async fn test_order_book_validates_against_shared_reference_data() {
+    let reference_data = SharedReferenceData::default();
+    let order_book = SimpleOrderBook::new(btc_usd());
+    order_book.set_reference_data(reference_data.clone());
+    order_book.add_order(order_at(1, 100.25)).await.unwrap();
+
+    reference_data
+        .write()
+        .register(TradingPairInfo::new(btc_usd(), 0.5, 0.1));
+    assert!(matches!(
+        order_book.add_order(order_at(2, 100.25)).await,
+        Err(OrderBookError::Invalid(
+            OrderValidationError::PricePrecision { .. }
+        ))
+    ));
+    order_book.add_order(order_at(3, 100.5)).await.unwrap();
+    assert_eq!(order_book.get_active_orders_count().await, 2);
+}
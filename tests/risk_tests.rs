@@ -0,0 +1,286 @@
+use chrono::{NaiveTime, TimeZone, Utc, Weekday};
+use engine::engine::core::{start_engine, Message, OrderAck};
+use engine::engine::models::{Order, OrderType, Trade, TradingPair};
+use engine::engine::order_book::{OrderBookError, SimpleOrderBook};
+use engine::engine::risk::{
+    CompositeRiskManager, DailyVolumeLimitManager, MaxOrderSizeRiskManager, MinNotionalRiskManager,
+    RiskError, RiskManager, TradingHoursManager, TradingSession,
+};
+use engine::engine::testing::{btc_usd, OrderBuilder};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+/// Rejects orders above `max_price` and counts how often it ran.
+struct PriceCap {
+    max_price: f64,
+    calls: Arc<AtomicUsize>,
+}
+
+impl RiskManager for PriceCap {
+    fn check_order(&self, order: &Order) -> Result<(), RiskError> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        if order.price.value() > self.max_price {
+            return Err(RiskError::Rejected(format!(
+                "price above {}",
+                self.max_price
+            )));
+        }
+        Ok(())
+    }
+}
+
+fn price_cap(max_price: f64) -> (Box<dyn RiskManager>, Arc<AtomicUsize>) {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let check = PriceCap {
+        max_price,
+        calls: calls.clone(),
+    };
+    (Box::new(check), calls)
+}
+
+#[test]
+fn test_composite_short_circuits_on_first_rejection() {
+    let (loose, loose_calls) = price_cap(200.0);
+    let (tight, tight_calls) = price_cap(100.0);
+    let (never_reached, last_calls) = price_cap(50.0);
+    let mut composite = CompositeRiskManager::new(vec![loose, tight]);
+    composite.add_check(never_reached);
+
+    let order = OrderBuilder::new().pair(btc_usd()).buy_at(150.0).build();
+    assert_eq!(
+        composite.check_order(&order),
+        Err(RiskError::Rejected("price above 100".to_string()))
+    );
+    assert_eq!(loose_calls.load(Ordering::SeqCst), 1);
+    assert_eq!(tight_calls.load(Ordering::SeqCst), 1);
+    assert_eq!(last_calls.load(Ordering::SeqCst), 0);
+
+    let order = OrderBuilder::new().pair(btc_usd()).buy_at(10.0).build();
+    assert_eq!(composite.check_order(&order), Ok(()));
+    assert_eq!(CompositeRiskManager::default().check_order(&order), Ok(()));
+}
+
+#[tokio::test]
+async fn test_engine_drops_orders_failing_risk_checks() {
+    let engine_tx = start_engine(|trading_pair| Box::new(SimpleOrderBook::new(trading_pair)));
+    let (check, _) = price_cap(100.0);
+    engine_tx
+        .send(Message::SetRiskManager(Box::new(
+            CompositeRiskManager::new(vec![check]),
+        )))
+        .await
+        .unwrap();
+
+    for (id, price) in [(1, 99.0), (2, 101.0)] {
+        let order = OrderBuilder::new()
+            .id(id)
+            .pair(btc_usd())
+            .buy_at(price)
+            .quantity(1.0)
+            .build();
+        engine_tx.send(Message::NewOrder(order)).await.unwrap();
+    }
+
+    let (book_tx, mut book_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::GetOrderBook(btc_usd(), book_tx))
+        .await
+        .unwrap();
+    let (bids, _) = book_rx.recv().await.unwrap();
+    assert_eq!(bids.len(), 1);
+    assert_eq!(bids[0].price, 99.0);
+}
+
+#[test]
+fn test_max_order_size_limits() {
+    let eth_usd = TradingPair::new("ETH".to_string(), "USD".to_string());
+    let order = |pair: TradingPair, quantity: f64| {
+        OrderBuilder::new()
+            .pair(pair)
+            .buy_at(100.0)
+            .quantity(quantity)
+            .build()
+    };
+
+    let uniform = MaxOrderSizeRiskManager::uniform(5.0);
+    assert_eq!(uniform.check_order(&order(btc_usd(), 5.0)), Ok(()));
+    assert_eq!(
+        uniform.check_order(&order(eth_usd.clone(), 6.0)),
+        Err(RiskError::OrderTooLarge {
+            quantity: 6.0,
+            limit: 5.0
+        })
+    );
+
+    let per_pair = MaxOrderSizeRiskManager::per_pair(HashMap::from([(btc_usd(), 1.0)]));
+    assert!(per_pair.check_order(&order(btc_usd(), 2.0)).is_err());
+    assert_eq!(per_pair.check_order(&order(eth_usd, 1000.0)), Ok(()));
+}
+
+#[test]
+fn test_min_notional_composes_with_max_size() {
+    let composite = CompositeRiskManager::new(vec![
+        Box::new(MaxOrderSizeRiskManager::uniform(10.0)),
+        Box::new(MinNotionalRiskManager::new(50.0)),
+    ]);
+    let order = |price: f64, quantity: f64| {
+        OrderBuilder::new()
+            .pair(btc_usd())
+            .sell_at(price)
+            .quantity(quantity)
+            .build()
+    };
+
+    assert_eq!(composite.check_order(&order(10.0, 5.0)), Ok(()));
+    assert_eq!(
+        composite.check_order(&order(10.0, 4.0)),
+        Err(RiskError::BelowMinNotional {
+            notional: 40.0,
+            min_notional: 50.0
+        })
+    );
+    assert!(matches!(
+        composite.check_order(&order(0.0, 11.0)),
+        Err(RiskError::OrderTooLarge { .. })
+    ));
+}
+
+fn client_trade(client_id: &str, price: f64, quantity: f64, day_offset: i64) -> Trade {
+    Trade {
+        id: 1,
+        trading_pair: btc_usd(),
+        buy_order_id: 1,
+        sell_order_id: 2,
+        price: price.into(),
+        quantity: quantity.into(),
+        aggressor_side: OrderType::Buy,
+        timestamp: chrono::Utc::now() + chrono::Duration::days(day_offset),
+        buy_client_id: Some(client_id.to_string()),
+        sell_client_id: Some("maker".to_string()),
+        buy_fee: 0.0,
+        sell_fee: 0.0,
+    }
+}
+
+#[test]
+fn test_daily_volume_limit_per_client() {
+    let manager = DailyVolumeLimitManager::new(1000.0);
+    let order = |client_id: &str, quantity: f64| {
+        OrderBuilder::new()
+            .pair(btc_usd())
+            .client_id(client_id)
+            .buy_at(100.0)
+            .quantity(quantity)
+            .build()
+    };
+
+    manager.apply_trade(&client_trade("alice", 100.0, 8.0, 0));
+    assert_eq!(manager.traded_today("alice"), 800.0);
+    assert_eq!(manager.traded_today("maker"), 800.0);
+
+    assert_eq!(manager.check_order(&order("alice", 2.0)), Ok(()));
+    assert_eq!(
+        manager.check_order(&order("alice", 3.0)),
+        Err(RiskError::DailyLimitExceeded {
+            client_id: "alice".to_string(),
+            traded: 800.0,
+            limit: 1000.0
+        })
+    );
+    assert_eq!(manager.check_order(&order("bob", 3.0)), Ok(()));
+
+    let anonymous = OrderBuilder::new().buy_at(1e9).quantity(1.0).build();
+    assert_eq!(manager.check_order(&anonymous), Ok(()));
+}
+
+#[test]
+fn test_daily_volume_limit_resets_at_midnight() {
+    let manager = DailyVolumeLimitManager::new(1000.0);
+    manager.apply_trade(&client_trade("alice", 100.0, 9.0, 0));
+
+    // A trade stamped tomorrow starts a new day and only it counts.
+    manager.apply_trade(&client_trade("alice", 100.0, 1.0, 1));
+    assert_eq!(manager.traded_today("alice"), 100.0);
+
+    // Going back to today's clock does not restore the old counters.
+    manager.roll_day(chrono::Utc::now());
+    assert_eq!(manager.traded_today("alice"), 100.0);
+}
+
+#[test]
+fn test_trading_hours_sessions() {
+    let eth_usd = TradingPair::new("ETH".to_string(), "USD".to_string());
+    let mut hours = TradingHoursManager::new();
+    hours.register(
+        btc_usd(),
+        vec![
+            TradingSession {
+                open: NaiveTime::from_hms_opt(9, 30, 0).unwrap(),
+                close: NaiveTime::from_hms_opt(16, 0, 0).unwrap(),
+                days: vec![Weekday::Mon, Weekday::Tue],
+            },
+            TradingSession {
+                open: NaiveTime::from_hms_opt(22, 0, 0).unwrap(),
+                close: NaiveTime::from_hms_opt(2, 0, 0).unwrap(),
+                days: vec![Weekday::Fri],
+            },
+        ],
+    );
+
+    // 2024-01-01 was a Monday.
+    let at = |day: u32, hour: u32, minute: u32| {
+        Utc.with_ymd_and_hms(2024, 1, day, hour, minute, 0).unwrap()
+    };
+    assert!(hours.is_open(&btc_usd(), at(1, 9, 30)));
+    assert!(!hours.is_open(&btc_usd(), at(1, 16, 0)));
+    assert!(!hours.is_open(&btc_usd(), at(3, 12, 0)));
+    assert!(hours.is_open(&btc_usd(), at(5, 23, 0)));
+    assert!(hours.is_open(&btc_usd(), at(6, 1, 59)));
+    assert!(!hours.is_open(&btc_usd(), at(5, 1, 0)));
+    assert!(hours.is_open(&eth_usd, at(6, 12, 0)));
+}
+
+#[tokio::test]
+async fn test_engine_rejects_orders_outside_trading_hours() {
+    let engine_tx = start_engine(|trading_pair| Box::new(SimpleOrderBook::new(trading_pair)));
+    let order = |id: u64| {
+        OrderBuilder::new()
+            .id(id)
+            .pair(btc_usd())
+            .buy_at(99.0)
+            .quantity(1.0)
+            .build()
+    };
+    engine_tx.send(Message::NewOrder(order(1))).await.unwrap();
+
+    // No trading days at all keeps the pair closed.
+    let (hours_tx, mut hours_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::RegisterTradingHours(
+            btc_usd(),
+            vec![TradingSession {
+                open: NaiveTime::MIN,
+                close: NaiveTime::from_hms_opt(23, 59, 59).unwrap(),
+                days: vec![],
+            }],
+            hours_tx,
+        ))
+        .await
+        .unwrap();
+    hours_rx.recv().await.unwrap();
+
+    let (ack_tx, mut ack_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::NewOrderWithCallback(order(2), ack_tx))
+        .await
+        .unwrap();
+    assert_eq!(
+        ack_rx.recv().await.unwrap(),
+        OrderAck::Rejected {
+            order_id: 2,
+            reason: OrderBookError::Risk(RiskError::MarketClosed(btc_usd())),
+        }
+    );
+}
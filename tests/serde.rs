@@ -0,0 +1,236 @@
+#![cfg(feature = "serde")]
+
+use chrono::{DateTime, TimeZone, Utc};
+use engine::engine::api::{
+    OrderBookResponse, PlaceOrderRequest, PlaceOrderResponse, PriceLevel, PriceResponse,
+    TradeHistoryResponse, TradeResponse,
+};
+use engine::engine::models::{
+    MarketEvent, OhlcvBar, Order, OrderType, PriceUpdate, Trade, TradingPair,
+};
+use engine::engine::testing::btc_usd;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::fmt::Debug;
+
+// Timestamps are serialized as whole seconds.
+fn now() -> DateTime<Utc> {
+    Utc.timestamp_opt(Utc::now().timestamp(), 0).unwrap()
+}
+
+fn far_future() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(9999, 12, 31, 23, 59, 59).unwrap()
+}
+
+fn assert_round_trip<T>(value: &T)
+where
+    T: Serialize + DeserializeOwned + PartialEq + Debug,
+{
+    let json = serde_json::to_string(value).unwrap();
+    let decoded: T = serde_json::from_str(&json).unwrap();
+    assert_eq!(&decoded, value);
+}
+
+/// For API types whose fields are private: decode the JSON, encode it again
+/// and compare documents.
+fn assert_json_round_trip<T>(value: Value)
+where
+    T: Serialize + DeserializeOwned + PartialEq + Debug,
+{
+    let decoded: T = serde_json::from_value(value.clone()).unwrap();
+    let json = serde_json::to_string(&decoded).unwrap();
+    assert_eq!(serde_json::from_str::<Value>(&json).unwrap(), value);
+    assert_round_trip(&decoded);
+}
+
+fn order(price: f64, quantity: f64, timestamp: DateTime<Utc>) -> Order {
+    Order {
+        id: u64::MAX,
+        trading_pair: btc_usd(),
+        order_type: OrderType::Sell,
+        price: price.into(),
+        quantity: quantity.into(),
+        timestamp,
+        client_id: None,
+        session_id: None,
+        average_fill_price: None,
+        cumulative_filled_quantity: 0.0,
+        stop_price: None,
+        received_at: None,
+        arrival_seq: 0,
+    }
+}
+
+fn trade(price: f64, quantity: f64, timestamp: DateTime<Utc>) -> Trade {
+    Trade {
+        id: 7,
+        trading_pair: btc_usd(),
+        buy_order_id: 1,
+        sell_order_id: 2,
+        price: price.into(),
+        quantity: quantity.into(),
+        aggressor_side: OrderType::Buy,
+        timestamp,
+        buy_client_id: Some("alice".to_string()),
+        sell_client_id: None,
+        buy_fee: 0.0,
+        sell_fee: 0.0,
+    }
+}
+
+#[test]
+fn test_order_type_round_trip() {
+    assert_round_trip(&OrderType::Buy);
+    assert_round_trip(&OrderType::Sell);
+}
+
+#[test]
+fn test_trading_pair_round_trip() {
+    assert_round_trip(&btc_usd());
+    assert_round_trip(&TradingPair::new(String::new(), "ÜSD".to_string()));
+}
+
+#[test]
+fn test_order_round_trip() {
+    assert_round_trip(&order(50000.0, 1.5, now()));
+    assert_round_trip(&order(
+        f64::MAX,
+        f64::MIN_POSITIVE,
+        DateTime::<Utc>::default(),
+    ));
+    assert_round_trip(&order(0.0, f64::MAX, far_future()));
+    assert_round_trip(&Order::default());
+}
+
+#[test]
+fn test_trade_round_trip() {
+    assert_round_trip(&trade(50000.0, 0.25, now()));
+    assert_round_trip(&trade(
+        f64::MAX,
+        f64::MIN_POSITIVE,
+        DateTime::<Utc>::default(),
+    ));
+    assert_round_trip(&trade(1e-12, 1e12, far_future()));
+}
+
+#[test]
+fn test_ohlcv_bar_round_trip() {
+    for open_time in [DateTime::<Utc>::default(), far_future()] {
+        assert_round_trip(&OhlcvBar {
+            open_time,
+            open: f64::MIN_POSITIVE,
+            high: f64::MAX,
+            low: 0.0,
+            close: 101.5,
+            volume: 42.0,
+        });
+    }
+}
+
+#[test]
+fn test_market_event_round_trip() {
+    let update = PriceUpdate {
+        trading_pair: btc_usd(),
+        price: f64::MAX,
+        source: "index".to_string(),
+    };
+    assert_round_trip(&update);
+    assert_round_trip(&MarketEvent::MarkPriceUpdate(update));
+    assert_round_trip(&MarketEvent::CircuitBreakerTripped {
+        trading_pair: btc_usd(),
+        reference_price: 100.0,
+        price: f64::MIN_POSITIVE,
+    });
+}
+
+#[test]
+fn test_price_level_round_trip() {
+    assert_round_trip(&PriceLevel {
+        price: f64::MAX,
+        total_quantity: f64::MIN_POSITIVE,
+        order_count: usize::MAX,
+    });
+
+    // Levels written before `PriceLevel` carried only price and quantity.
+    let legacy: PriceLevel =
+        serde_json::from_value(json!({ "price": 99.5, "quantity": 2.0 })).unwrap();
+    assert_eq!(
+        legacy,
+        PriceLevel {
+            price: 99.5,
+            total_quantity: 2.0,
+            order_count: 0,
+        }
+    );
+}
+
+#[test]
+fn test_api_request_and_responses_round_trip() {
+    assert_json_round_trip::<PlaceOrderRequest>(json!({
+        "trading_pair": "BTC/USD",
+        "order_type": "buy",
+        "price": f64::MAX,
+        "quantity": f64::MIN_POSITIVE,
+    }));
+    assert_json_round_trip::<PlaceOrderResponse>(json!({
+        "order_id": u64::MAX,
+        "status": "accepted",
+    }));
+    assert_json_round_trip::<PriceResponse>(json!({
+        "trading_pair": "BTC/USD",
+        "price": null,
+        "timestamp": "1970-01-01T00:00:00+00:00",
+    }));
+    assert_json_round_trip::<OrderBookResponse>(json!({
+        "trading_pair": "BTC/USD",
+        "bids": [{ "price": 99.5, "total_quantity": 1.0, "order_count": 1 }],
+        "asks": [],
+        "timestamp": "9999-12-31T23:59:59+00:00",
+    }));
+    assert_json_round_trip::<TradeHistoryResponse>(json!({
+        "trading_pair": "BTC/USD",
+        "trades": [{
+            "id": 1,
+            "trading_pair": "BTC/USD",
+            "price": 100.0,
+            "quantity": 2.0,
+            "timestamp": "2024-01-01T00:00:00+00:00",
+        }],
+    }));
+
+    let trade: TradeResponse = serde_json::from_value(json!({
+        "id": 1,
+        "trading_pair": "BTC/USD",
+        "price": f64::MAX,
+        "quantity": f64::MIN_POSITIVE,
+        "timestamp": "2024-01-01T00:00:00+00:00",
+    }))
+    .unwrap();
+    assert_round_trip(&trade);
+}
+
+#[test]
+fn test_unknown_fields_are_ignored() {
+    let order: Order = serde_json::from_value(json!({
+        "id": 1,
+        "trading_pair": { "base": "BTC", "quote": "USD", "venue": "x" },
+        "order_type": "Buy",
+        "price": 100.0,
+        "quantity": 1.0,
+        "timestamp": 0,
+        "client_tag": "abc",
+    }))
+    .unwrap();
+    assert_eq!(order.trading_pair, btc_usd());
+    assert_eq!(order.timestamp, DateTime::<Utc>::default());
+
+    let request: Result<PlaceOrderRequest, _> = serde_json::from_value(json!({
+        "trading_pair": "BTC/USD",
+        "order_type": "sell",
+        "price": 1.0,
+        "quantity": 1.0,
+        "time_in_force": "GTC",
+    }));
+    assert!(request.is_ok());
+}
@@ -0,0 +1,69 @@
+#![cfg(feature = "bincode-serde")]
+
+use engine::engine::config::SerializationFormat;
+use engine::engine::models::{Order, TradingPair};
+use engine::engine::persistence::{decode, encode};
+use engine::engine::testing::OrderBuilder;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+const ORDER_COUNT: usize = 100_000;
+
+fn round_trip(orders: &[Order], format: SerializationFormat) -> (Duration, usize) {
+    let start = Instant::now();
+    let mut bytes_written = 0;
+    for order in orders {
+        let bytes = encode(order, format).unwrap();
+        bytes_written += bytes.len();
+        let decoded: Order = decode(&bytes, format).unwrap();
+        assert_eq!(decoded.id, order.id);
+    }
+    (start.elapsed(), bytes_written)
+}
+
+#[test]
+fn benchmark_json_vs_bincode() {
+    let trading_pair = TradingPair::new("BTC".to_string(), "USD".to_string());
+    let orders: Vec<Order> = (0..ORDER_COUNT as u64)
+        .map(|id| {
+            OrderBuilder::new()
+                .id(id)
+                .pair(trading_pair.clone())
+                .buy_at(50000.0 + id as f64 * 0.01)
+                .quantity(1.5)
+                .build()
+        })
+        .collect();
+
+    let mut results = String::new();
+    results.push_str("=== Serialization Benchmarks ===\n\n");
+    for (name, format) in [
+        ("JSON", SerializationFormat::Json),
+        ("bincode", SerializationFormat::Bincode),
+    ] {
+        let (elapsed, bytes) = round_trip(&orders, format);
+        results.push_str(&format!(
+            "{}: {} order round-trips in {:?}, {} bytes, {:.0} orders/s\n",
+            name,
+            ORDER_COUNT,
+            elapsed,
+            bytes,
+            ORDER_COUNT as f64 / elapsed.as_secs_f64()
+        ));
+    }
+    println!("{}", results);
+
+    let file_path = format!(
+        "{}/serialization_benchmark_results.txt",
+        std::env::var("CARGO_TARGET_DIR").unwrap_or_else(|_| "target".to_string())
+    );
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(file_path)
+        .expect("Failed to open serialization_benchmark_results.txt");
+    file.write_all(results.as_bytes())
+        .expect("Failed to write benchmark results");
+}
@@ -1,7 +1,8 @@
 use super::TestMetrics;
 use crate::stress_tests::{ORDERS_PER_TRADER, TEST_DURATION_SECS};
 use engine::engine::core::Message;
-use engine::engine::models::{Order, OrderType, TradingPair};
+use engine::engine::models::{OrderType, TradingPair};
+use engine::engine::testing::OrderBuilder;
 use rand::Rng;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
@@ -25,23 +26,19 @@ pub async fn simulate_market_maker(
         let sell_quantity = rand::thread_rng().gen_range(0.1..1.0);
         let sleep_duration = rand::thread_rng().gen_range(10..50);
 
-        let buy_order = Order {
-            id: order_id,
-            trading_pair: trading_pair.clone(),
-            order_type: OrderType::Buy,
-            price: mid_price - spread,
-            quantity: buy_quantity,
-            timestamp: chrono::Utc::now(),
-        };
+        let buy_order = OrderBuilder::new()
+            .id(order_id)
+            .pair(trading_pair.clone())
+            .buy_at(mid_price - spread)
+            .quantity(buy_quantity)
+            .build();
 
-        let sell_order = Order {
-            id: order_id + 1,
-            trading_pair: trading_pair.clone(),
-            order_type: OrderType::Sell,
-            price: mid_price + spread,
-            quantity: sell_quantity,
-            timestamp: chrono::Utc::now(),
-        };
+        let sell_order = OrderBuilder::new()
+            .id(order_id + 1)
+            .pair(trading_pair.clone())
+            .sell_at(mid_price + spread)
+            .quantity(sell_quantity)
+            .build();
 
         let start_time = Instant::now();
 
@@ -65,9 +62,8 @@ pub async fn simulate_trader(
     trading_pair: TradingPair,
 ) {
     barrier.wait().await;
-    let mut order_id = 0;
 
-    for _ in 0..ORDERS_PER_TRADER {
+    for order_id in 0..ORDERS_PER_TRADER as u64 {
         // Generate all random values before any await points
         let base_price = 50000.0;
         let price_offset = rand::thread_rng().gen_range(-0.5..0.5);
@@ -81,14 +77,13 @@ pub async fn simulate_trader(
             OrderType::Sell
         };
 
-        let order = Order {
-            id: order_id,
-            trading_pair: trading_pair.clone(),
-            order_type,
-            price: base_price + price_offset,
-            quantity,
-            timestamp: chrono::Utc::now(),
-        };
+        let order = OrderBuilder::new()
+            .id(order_id)
+            .pair(trading_pair.clone())
+            .side(order_type)
+            .price(base_price + price_offset)
+            .quantity(quantity)
+            .build();
 
         let start_time = Instant::now();
         let _ = engine_tx.send(Message::NewOrder(order)).await;
@@ -97,7 +92,6 @@ pub async fn simulate_trader(
             .orders_processed
             .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
 
-        order_id += 1;
         sleep(Duration::from_millis(sleep_duration)).await;
     }
 }
@@ -0,0 +1,97 @@
+use engine::engine::config::EngineConfig;
+use engine::engine::core::{start_engine_with_config, Message};
+use engine::engine::models::{MarketEvent, Order, TradingPair};
+use engine::engine::order_book::{OrderBook, SimpleOrderBook};
+use engine::engine::surveillance::ArbitrageDetector;
+use engine::engine::testing::OrderBuilder;
+use std::collections::HashMap;
+use tokio::sync::mpsc;
+
+fn pair(base: &str, quote: &str) -> TradingPair {
+    TradingPair::new(base.to_string(), quote.to_string())
+}
+
+/// BTC bought for USDT, sold for ETH, and the ETH sold back for USDT returns
+/// 15.5 * 2000 / 30010 per USDT.
+fn mispriced_quotes() -> Vec<Order> {
+    let quotes = [
+        (pair("BTC", "USDT"), 30000.0, 30010.0),
+        (pair("ETH", "USDT"), 2000.0, 2001.0),
+        (pair("BTC", "ETH"), 15.5, 15.6),
+    ];
+    let mut orders = Vec::new();
+    for (index, (trading_pair, bid, ask)) in quotes.into_iter().enumerate() {
+        let order = OrderBuilder::new().pair(trading_pair).quantity(1.0);
+        orders.push(order.clone().id(2 * index as u64).buy_at(bid).build());
+        orders.push(order.id(2 * index as u64 + 1).sell_at(ask).build());
+    }
+    orders
+}
+
+fn expected_profit_bps() -> f64 {
+    (15.5 * 2000.0 / 30010.0 - 1.0) * 10_000.0
+}
+
+#[tokio::test]
+async fn test_check_triangle_finds_one_cycle() {
+    let mut order_books = HashMap::new();
+    for order in mispriced_quotes() {
+        order_books
+            .entry(order.trading_pair.clone())
+            .or_insert_with(|| SimpleOrderBook::new(order.trading_pair.clone()))
+            .add_order(order)
+            .await
+            .unwrap();
+    }
+    let books: HashMap<TradingPair, &dyn OrderBook> = order_books
+        .iter()
+        .map(|(trading_pair, book)| (trading_pair.clone(), book as &dyn OrderBook))
+        .collect();
+
+    let opportunities = ArbitrageDetector::default().check_triangle(&books).await;
+    assert_eq!(opportunities.len(), 1);
+    assert_eq!(
+        opportunities[0].legs,
+        vec![pair("BTC", "ETH"), pair("ETH", "USDT"), pair("BTC", "USDT")]
+    );
+    assert!((opportunities[0].implied_profit_bps - expected_profit_bps()).abs() < 1e-6);
+
+    let strict = ArbitrageDetector::new(1000.0);
+    assert!(strict.check_triangle(&books).await.is_empty());
+}
+
+#[tokio::test]
+async fn test_engine_publishes_arbitrage_after_match() {
+    let config = EngineConfig {
+        detect_arbitrage: true,
+        ..EngineConfig::default()
+    };
+    let engine_tx = start_engine_with_config(config, |trading_pair| {
+        Box::new(SimpleOrderBook::new(trading_pair))
+    });
+    let (subscribe_tx, mut subscribe_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::SubscribeToMarketEvents(subscribe_tx))
+        .await
+        .unwrap();
+    let mut events = subscribe_rx.recv().await.unwrap();
+
+    for order in mispriced_quotes() {
+        engine_tx.send(Message::NewOrder(order)).await.unwrap();
+    }
+    let (match_tx, mut match_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::MatchOrders(pair("BTC", "USDT"), match_tx))
+        .await
+        .unwrap();
+    assert!(match_rx.recv().await.unwrap().is_empty());
+
+    match events.try_recv().unwrap() {
+        MarketEvent::ArbitrageDetected(opportunity) => {
+            assert_eq!(opportunity.legs.len(), 3);
+            assert!((opportunity.implied_profit_bps - expected_profit_bps()).abs() < 1e-6);
+        }
+        event => panic!("unexpected event {:?}", event),
+    }
+    assert!(events.try_recv().is_err());
+}
@@ -0,0 +1,76 @@
+#![cfg(feature = "sync-channel")]
+
+use engine::engine::core::Message;
+use engine::engine::models::{Order, OrderType};
+use engine::engine::order_book::SimpleOrderBook;
+use engine::engine::sync::{start_engine_sync, SyncOrderBook};
+use engine::engine::testing::{btc_usd, OrderBuilder};
+use tokio::sync::mpsc;
+
+fn order(id: u64, order_type: OrderType, price: f64, quantity: f64) -> Order {
+    OrderBuilder::new()
+        .id(id)
+        .pair(btc_usd())
+        .side(order_type)
+        .price(price)
+        .quantity(quantity)
+        .build()
+}
+
+#[test]
+fn test_sync_order_book_blocking_calls() {
+    let order_book = SimpleOrderBook::new(btc_usd());
+    order_book
+        .add_order_blocking(order(1, OrderType::Buy, 100.0, 1.0))
+        .unwrap();
+    order_book
+        .add_order_blocking(order(2, OrderType::Sell, 100.0, 1.0))
+        .unwrap();
+
+    assert_eq!(order_book.get_active_orders_count_blocking(), 2);
+    assert_eq!(order_book.match_orders_blocking().trades.len(), 1);
+    assert_eq!(order_book.get_trade_history_blocking().len(), 1);
+    assert_eq!(order_book.get_active_orders_count_blocking(), 0);
+}
+
+#[test]
+fn test_sync_engine_without_runtime() {
+    let engine_tx = start_engine_sync(|trading_pair| Box::new(SimpleOrderBook::new(trading_pair)));
+
+    engine_tx
+        .send(Message::NewOrder(order(1, OrderType::Buy, 99.0, 1.0)))
+        .unwrap();
+    engine_tx
+        .send(Message::NewOrder(order(2, OrderType::Sell, 101.0, 1.0)))
+        .unwrap();
+
+    let (price_tx, mut price_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::GetPrice(btc_usd(), price_tx.clone()))
+        .unwrap();
+    assert_eq!(price_rx.blocking_recv().unwrap(), Some(100.0));
+    // Wrapped messages are handled as the message inside.
+    engine_tx
+        .send(Message::Forwarded(Box::new(Message::GetPrice(
+            btc_usd(),
+            price_tx,
+        ))))
+        .unwrap();
+    assert_eq!(price_rx.blocking_recv().unwrap(), Some(100.0));
+
+    let (cancel_tx, mut cancel_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::CancelOrder(btc_usd(), 2, cancel_tx))
+        .unwrap();
+    assert_eq!(cancel_rx.blocking_recv().unwrap().unwrap().id, 2);
+
+    let (book_tx, mut book_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::GetOrderBook(btc_usd(), book_tx))
+        .unwrap();
+    let (bids, asks) = book_rx.blocking_recv().unwrap();
+    assert_eq!(bids.len(), 1);
+    assert!(asks.is_empty());
+
+    engine_tx.send(Message::Shutdown).unwrap();
+}
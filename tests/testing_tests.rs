@@ -0,0 +1,265 @@
+#![cfg(feature = "testing")]
+
+use engine::engine::core::{start_engine, Engine, Message};
+use engine::engine::models::{OrderType, TradingPair};
+use engine::engine::order_book::SimpleOrderBook;
+use engine::engine::testing::market_maker::CLIENT_ID;
+use engine::engine::testing::{btc_usd, OrderBuilder, SimulatedMarketMaker};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio::time::Duration;
+
+#[tokio::test]
+async fn test_run_until_idle_processes_queue_then_returns() {
+    let mut engine = Engine::new(|trading_pair| Box::new(SimpleOrderBook::new(trading_pair)));
+    let (engine_tx, engine_rx) = mpsc::channel(200);
+
+    for id in 1..=100 {
+        let (side, price) = if id % 2 == 0 {
+            (OrderType::Buy, 99.0)
+        } else {
+            (OrderType::Sell, 101.0)
+        };
+        let order = OrderBuilder::new()
+            .id(id)
+            .pair(btc_usd())
+            .side(side)
+            .price(price)
+            .quantity(1.0)
+            .build();
+        engine_tx.send(Message::NewOrder(order)).await.unwrap();
+    }
+    let (book_tx, mut book_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::GetOrderBook(btc_usd(), book_tx))
+        .await
+        .unwrap();
+
+    // `engine_tx` is still open, so only the idle timeout ends the run.
+    let processed = engine
+        .run_until_idle(engine_rx, Duration::from_millis(50))
+        .await;
+    assert_eq!(processed, 101);
+
+    let (bids, asks) = book_rx.recv().await.unwrap();
+    assert_eq!(bids.len(), 1);
+    assert_eq!(asks.len(), 1);
+    assert_eq!((bids[0].price, bids[0].total_quantity), (99.0, 50.0));
+    assert_eq!((asks[0].price, asks[0].total_quantity), (101.0, 50.0));
+    drop(engine_tx);
+}
+
+#[tokio::test]
+async fn test_order_book_handle_reads_engine_book() {
+    let mut engine = Engine::new(|trading_pair| Box::new(SimpleOrderBook::new(trading_pair)));
+    assert!(engine.get_order_book_handle(&btc_usd()).is_none());
+
+    let (engine_tx, engine_rx) = mpsc::channel(10);
+    let order = OrderBuilder::new()
+        .id(1)
+        .pair(btc_usd())
+        .buy_at(99.0)
+        .quantity(2.0)
+        .build();
+    engine_tx.send(Message::NewOrder(order)).await.unwrap();
+    engine
+        .run_until_idle(engine_rx, Duration::from_millis(50))
+        .await;
+
+    let handle = engine.get_order_book_handle(&btc_usd()).unwrap();
+    let (bids, asks) = handle.read().await.get_order_book().await;
+    assert_eq!((bids[0].price, bids[0].total_quantity), (99.0, 2.0));
+    assert!(asks.is_empty());
+    assert!(Arc::ptr_eq(
+        &handle,
+        &engine.get_order_book_handle(&btc_usd()).unwrap()
+    ));
+    drop(engine_tx);
+}
+
+#[tokio::test]
+async fn test_lock_order_book_defers_messages_until_released() {
+    let mut engine = Engine::new(|trading_pair| Box::new(SimpleOrderBook::new(trading_pair)));
+    assert!(engine.lock_order_book(&btc_usd()).await.is_none());
+
+    let (engine_tx, engine_rx) = mpsc::channel(10);
+    let order = |id, price| {
+        OrderBuilder::new()
+            .id(id)
+            .pair(btc_usd())
+            .buy_at(price)
+            .quantity(1.0)
+            .build()
+    };
+    engine_tx
+        .send(Message::NewOrder(order(1, 99.0)))
+        .await
+        .unwrap();
+    engine
+        .run_until_idle(engine_rx, Duration::from_millis(50))
+        .await;
+
+    let (engine_tx, engine_rx) = mpsc::channel(10);
+    let (book_tx, mut book_rx) = mpsc::channel(1);
+    {
+        let guard = engine.lock_order_book(&btc_usd()).await.unwrap();
+        // Replace order 1 in one step; the new order waits for the guard.
+        guard.cancel_order(1).await.unwrap();
+        guard.add_order(order(2, 98.0)).await.unwrap();
+        engine_tx
+            .send(Message::NewOrder(order(3, 97.0)))
+            .await
+            .unwrap();
+        engine_tx
+            .send(Message::GetOrderBook(btc_usd(), book_tx))
+            .await
+            .unwrap();
+        assert!(book_rx.try_recv().is_err());
+    }
+    engine
+        .run_until_idle(engine_rx, Duration::from_millis(50))
+        .await;
+
+    let (bids, _) = book_rx.recv().await.unwrap();
+    let prices: Vec<f64> = bids.iter().map(|level| level.price).collect();
+    assert_eq!(prices, [98.0, 97.0]);
+}
+
+#[tokio::test]
+async fn test_ping_samples_message_queue_depth() {
+    let mut engine = Engine::new(|trading_pair| Box::new(SimpleOrderBook::new(trading_pair)));
+    let (engine_tx, engine_rx) = mpsc::channel(10);
+    let (pong_tx, mut pong_rx) = mpsc::channel(1);
+    engine_tx.send(Message::Ping(pong_tx)).await.unwrap();
+    for id in 1..=3 {
+        let order = OrderBuilder::new()
+            .id(id)
+            .pair(btc_usd())
+            .buy_at(99.0)
+            .quantity(1.0)
+            .build();
+        engine_tx.send(Message::NewOrder(order)).await.unwrap();
+    }
+
+    engine
+        .run_until_idle(engine_rx, Duration::from_millis(50))
+        .await;
+    assert_eq!(pong_rx.recv().await, Some(()));
+    assert_eq!(engine.metrics().snapshot().message_queue_depth, 3);
+}
+
+#[tokio::test]
+async fn test_engine_refuses_orders_at_locked_price_level() {
+    let mut engine = Engine::new(|trading_pair| Box::new(SimpleOrderBook::new(trading_pair)));
+    let order = |id, price| {
+        OrderBuilder::new()
+            .id(id)
+            .pair(btc_usd())
+            .buy_at(price)
+            .quantity(1.0)
+            .build()
+    };
+    let (engine_tx, engine_rx) = mpsc::channel(10);
+    engine_tx
+        .send(Message::NewOrder(order(1, 99.0)))
+        .await
+        .unwrap();
+    engine
+        .run_until_idle(engine_rx, Duration::from_millis(50))
+        .await;
+
+    let handle = engine.get_order_book_handle(&btc_usd()).unwrap();
+    let lock = handle.read().await.lock_price_level(98.0).unwrap();
+    let (engine_tx, engine_rx) = mpsc::channel(10);
+    let (book_tx, mut book_rx) = mpsc::channel(1);
+    for (id, price) in [(2, 98.0), (3, 97.0)] {
+        engine_tx
+            .send(Message::NewOrder(order(id, price)))
+            .await
+            .unwrap();
+    }
+    engine_tx
+        .send(Message::GetOrderBook(btc_usd(), book_tx))
+        .await
+        .unwrap();
+    engine
+        .run_until_idle(engine_rx, Duration::from_millis(50))
+        .await;
+    drop(lock);
+
+    let (bids, _) = book_rx.recv().await.unwrap();
+    let prices: Vec<f64> = bids.iter().map(|level| level.price).collect();
+    assert_eq!(prices, [99.0, 97.0]);
+    assert_eq!(engine.metrics().snapshot().orders_rejected, 1);
+}
+
+#[test]
+fn test_trading_pair_combinations() {
+    let pairs = TradingPair::all_combinations(&["BTC", "ETH", "USDT"]);
+    assert_eq!(pairs.len(), 6);
+    assert!(pairs.iter().all(|pair| pair.base != pair.quote));
+    assert!(pairs.iter().all(|pair| pairs.contains(&pair.inverse())));
+
+    let pairs = TradingPair::spot_matrix(&["BTC", "ETH", "USDT"], &["USDT", "USD"]);
+    let names: Vec<String> = pairs
+        .iter()
+        .map(|pair| format!("{}/{}", pair.base, pair.quote))
+        .collect();
+    assert_eq!(
+        names,
+        ["BTC/USDT", "BTC/USD", "ETH/USDT", "ETH/USD", "USDT/USD"]
+    );
+}
+
+#[tokio::test]
+async fn test_simulated_market_maker_quotes_and_gets_filled() {
+    let engine_tx = start_engine(|trading_pair| Box::new(SimpleOrderBook::new(trading_pair)));
+    let market_maker = SimulatedMarketMaker {
+        pair: btc_usd(),
+        mid_price: 100.0,
+        spread_bps: 10.0,
+        qty_per_level: 1.0,
+        levels: 3,
+        update_interval: Duration::from_millis(20),
+        engine_tx: engine_tx.clone(),
+    };
+
+    // A taker crosses the whole book every few milliseconds.
+    let taker_tx = engine_tx.clone();
+    let taker = tokio::spawn(async move {
+        for id in 1..=20 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            let order = OrderBuilder::new()
+                .id(id)
+                .pair(btc_usd())
+                .buy_at(200.0)
+                .quantity(0.5)
+                .build();
+            taker_tx.send(Message::NewOrder(order)).await.unwrap();
+            let (match_tx, mut match_rx) = mpsc::channel(1);
+            taker_tx
+                .send(Message::MatchOrders(btc_usd(), match_tx))
+                .await
+                .unwrap();
+            match_rx.recv().await.unwrap();
+        }
+    });
+
+    let stats = market_maker.run(Duration::from_millis(150)).await;
+    taker.await.unwrap();
+    assert!(stats.quotes_submitted >= 6);
+    assert_eq!(stats.quotes_submitted % 6, 0);
+    assert!(stats.fills_received > 0);
+    assert!(stats.avg_fill_latency > Duration::ZERO);
+
+    let (orders_tx, mut orders_rx) = mpsc::channel(1);
+    engine_tx
+        .send(Message::GetClientOrders(
+            CLIENT_ID.to_string(),
+            btc_usd(),
+            orders_tx,
+        ))
+        .await
+        .unwrap();
+    assert!(orders_rx.recv().await.unwrap().is_empty());
+}